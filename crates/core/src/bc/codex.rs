@@ -5,6 +5,7 @@
 //!
 use crate::bc::model::*;
 use crate::bc::xml::*;
+use crate::bc_protocol::{PacketDirection, PacketTap};
 use crate::{Credentials, Error, Result};
 use bytes::BytesMut;
 use nom::AsBytes;
@@ -12,6 +13,7 @@ use tokio_util::codec::{Decoder, Encoder};
 
 pub(crate) struct BcCodex {
     context: BcContext,
+    tap: Option<PacketTap>,
 }
 
 impl BcCodex {
@@ -19,13 +21,20 @@ impl BcCodex {
         let mut context = BcContext::new(credentials);
 
         context.debug_on();
-        Self { context }
+        Self { context, tap: None }
     }
     pub(crate) fn new(credentials: Credentials) -> Self {
         Self {
             context: BcContext::new(credentials),
+            tap: None,
         }
     }
+
+    /// Pass a copy of each packet to the tap as it is encoded or decoded
+    pub(crate) fn with_tap(mut self, tap: Option<PacketTap>) -> Self {
+        self.tap = tap;
+        self
+    }
 }
 
 impl Encoder<Bc> for BcCodex {
@@ -46,6 +55,9 @@ impl Encoder<Bc> for BcCodex {
             n => n,
         };
         let buf = item.serialize(buf, enc_protocol)?;
+        if let Some(tap) = self.tap.as_ref() {
+            tap.tap(PacketDirection::Sent, buf.as_slice());
+        }
         dst.extend_from_slice(buf.as_slice());
         Ok(())
     }
@@ -85,10 +97,15 @@ impl Decoder for BcCodex {
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
         // trace!("Decoding: {:X?}", src);
-        let bc = Bc::deserialize(&self.context, src);
+        let bc = Bc::deserialize_raw(&self.context, src);
         // trace!("As: {:?}", bc);
         let bc = match bc {
-            Ok(bc) => bc,
+            Ok((bc, raw)) => {
+                if let Some(tap) = self.tap.as_ref() {
+                    tap.tap(PacketDirection::Received, &raw);
+                }
+                bc
+            }
             Err(Error::NomIncomplete(_)) => return Ok(None),
             Err(e) => return Err(e),
         };
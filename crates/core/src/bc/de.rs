@@ -1,6 +1,6 @@
 use super::model::*;
 use crate::Error;
-use bytes::{Buf, BytesMut};
+use bytes::BytesMut;
 use log::*;
 use nom::{
     bytes::streaming::take, combinator::*, error::context as error_context, number::streaming::*,
@@ -12,15 +12,23 @@ type IResult<I, O, E = nom::error::VerboseError<I>> = Result<(I, O), nom::Err<E>
 impl Bc {
     /// Returns Ok(deserialized data, the amount of data consumed)
     /// Can then use this as the amount that should be remove from a buffer
+    #[cfg(test)]
     pub(crate) fn deserialize(context: &BcContext, buf: &mut BytesMut) -> Result<Bc, Error> {
+        Self::deserialize_raw(context, buf).map(|(result, _)| result)
+    }
+
+    /// Returns the deserialized data and the bytes of the buffer that it was made from
+    pub(crate) fn deserialize_raw(
+        context: &BcContext,
+        buf: &mut BytesMut,
+    ) -> Result<(Bc, BytesMut), Error> {
         let parser = BcParser { context };
         let (result, amount) = match consumed(parser)(buf) {
             Ok((_, (parsed_buff, result))) => Ok((result, parsed_buff.len())),
             Err(e) => Err(Error::from(e)),
         }?;
 
-        buf.advance(amount);
-        Ok(result)
+        Ok((result, buf.split_to(amount)))
     }
 }
 
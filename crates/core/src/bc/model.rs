@@ -4,11 +4,11 @@ pub use super::crypto::EncryptionProtocol;
 pub use super::xml::{BcPayloads, BcXml, Extension};
 use std::collections::HashSet;
 
-pub(super) const MAGIC_HEADER: u32 = 0x0abcdef0;
+pub(crate) const MAGIC_HEADER: u32 = 0x0abcdef0;
 /// Sometimes will get the BE magic header even though all other numbers are LE?
 /// Seems to happens with certain messages like snap that produce jpegs, so perhaps it
 /// it is meant to be a hint as to the endianess of the binary payload
-pub(super) const MAGIC_HEADER_REV: u32 = 0x0fedcba0;
+pub(crate) const MAGIC_HEADER_REV: u32 = 0x0fedcba0;
 
 /// Login messages have this ID
 pub const MSG_ID_LOGIN: u32 = 1;
@@ -36,6 +36,30 @@ pub const MSG_ID_MOTION: u32 = 33;
 pub const MSG_ID_SET_SERVICE_PORTS: u32 = 36;
 /// Get service ports
 pub const MSG_ID_GET_SERVICE_PORTS: u32 = 37;
+/// Get the NTP settings
+pub const MSG_ID_GET_NTP: u32 = 38;
+/// Set the NTP settings
+pub const MSG_ID_SET_NTP: u32 = 39;
+/// Get the email alert settings
+pub const MSG_ID_GET_EMAIL: u32 = 42;
+/// Set the email alert settings
+pub const MSG_ID_SET_EMAIL: u32 = 43;
+/// Get the OSD (on screen display) settings
+pub const MSG_ID_GET_OSD: u32 = 44;
+/// Set the OSD (on screen display) settings
+pub const MSG_ID_SET_OSD: u32 = 45;
+/// Get the motion detection settings
+pub const MSG_ID_GET_MD_ALARM: u32 = 46;
+/// Set the motion detection settings
+pub const MSG_ID_SET_MD_ALARM: u32 = 47;
+/// Get the encoder settings of the streams
+pub const MSG_ID_GET_COMPRESSION: u32 = 56;
+/// Set the encoder settings of the streams
+pub const MSG_ID_SET_COMPRESSION: u32 = 57;
+/// Get the users of the camera
+pub const MSG_ID_GET_USER_LIST: u32 = 58;
+/// Add, remove or modify users of the camera
+pub const MSG_ID_SET_USER_LIST: u32 = 59;
 /// Version messages have this ID
 pub const MSG_ID_VERSION: u32 = 80;
 /// Ping messages have this ID
@@ -50,6 +74,8 @@ pub const MSG_ID_SNAP: u32 = 109;
 pub const MSG_ID_UID: u32 = 114;
 /// Used to pass the token and client ID for push notifications
 pub const MSG_ID_PUSH_INFO: u32 = 124;
+/// Ask the camera to send a test email
+pub const MSG_ID_TEST_EMAIL: u32 = 141;
 /// StreamInfoList messages have this ID
 pub const MSG_ID_STREAM_INFO_LIST: u32 = 146;
 /// Used to get the abilities of a user
@@ -70,6 +96,10 @@ pub const MSG_ID_SET_LED_STATUS: u32 = 209;
 pub const MSG_ID_GET_PIR_ALARM: u32 = 212;
 /// Setting PIR status messages have this ID
 pub const MSG_ID_START_PIR_ALARM: u32 = 213;
+/// Set the schedule of the email alerts
+pub const MSG_ID_SET_EMAIL_TASK: u32 = 216;
+/// Get the schedule of the email alerts
+pub const MSG_ID_GET_EMAIL_TASK: u32 = 217;
 /// UDP Keep alive
 pub const MSG_ID_UDP_KEEP_ALIVE: u32 = 234;
 /// Battery message initiaed by the camera
@@ -203,14 +233,6 @@ pub struct BcMeta {
     pub class: u16,
 }
 
-/// The components of the Baichuan header that must be filled out after the body is serialized, or
-/// is needed for the deserialization of the body (strictly part of the wire format of the message)
-#[derive(Debug, PartialEq, Eq)]
-pub(super) struct BcSendInfo {
-    pub body_len: u32,
-    pub payload_offset: Option<u32>,
-}
-
 #[derive(Debug)]
 pub(crate) struct BcContext {
     pub(crate) credentials: Credentials,
@@ -132,6 +132,30 @@ pub struct BcXml {
     /// For changing rtmp server port
     #[serde(rename = "OnvifPort", skip_serializing_if = "Option::is_none")]
     pub onvif_port: Option<OnvifPort>,
+    /// The OSD settings for the camera name label
+    #[serde(rename = "OsdChannelName", skip_serializing_if = "Option::is_none")]
+    pub osd_channel_name: Option<OsdChannelName>,
+    /// The OSD settings for the date/time label
+    #[serde(rename = "OsdDatetime", skip_serializing_if = "Option::is_none")]
+    pub osd_datetime: Option<OsdDatetime>,
+    /// The encoder settings of the streams
+    #[serde(rename = "Compression", skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+    /// The motion detection settings
+    #[serde(rename = "MD", skip_serializing_if = "Option::is_none")]
+    pub md: Option<Md>,
+    /// The email alert settings
+    #[serde(rename = "Email", skip_serializing_if = "Option::is_none")]
+    pub email: Option<Email>,
+    /// The schedule of the email alerts
+    #[serde(rename = "EmailTask", skip_serializing_if = "Option::is_none")]
+    pub email_task: Option<EmailTask>,
+    /// The users of the camera
+    #[serde(rename = "UserList", skip_serializing_if = "Option::is_none")]
+    pub user_list: Option<UserList>,
+    /// The NTP settings
+    #[serde(rename = "Ntp", skip_serializing_if = "Option::is_none")]
+    pub ntp: Option<Ntp>,
 }
 
 impl BcXml {
@@ -1477,6 +1501,251 @@ pub struct OnvifPort {
     pub enable: Option<u32>,
 }
 
+/// OSD xml for the camera name that is drawn onto the stream
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct OsdChannelName {
+    /// XML Version
+    #[serde(rename = "@version")]
+    pub version: String,
+    /// The channel ID. Usually zero unless from an NVR
+    #[serde(rename = "channelId")]
+    pub channel_id: u8,
+    /// The name to draw
+    pub name: String,
+    /// The enable status known values are `1`, `0`
+    pub enable: u8,
+    /// X coordinate of the top left of the label
+    #[serde(rename = "topLeftX", skip_serializing_if = "Option::is_none")]
+    pub top_left_x: Option<u32>,
+    /// Y coordinate of the top left of the label
+    #[serde(rename = "topLeftY", skip_serializing_if = "Option::is_none")]
+    pub top_left_y: Option<u32>,
+    /// If the reolink watermark is drawn known values are `1`, `0`
+    #[serde(rename = "enWatermark", skip_serializing_if = "Option::is_none")]
+    pub en_watermark: Option<u8>,
+    /// If the label has a background known values are `1`, `0`
+    #[serde(rename = "enBgcolor", skip_serializing_if = "Option::is_none")]
+    pub en_bgcolor: Option<u8>,
+}
+
+/// OSD xml for the date/time that is drawn onto the stream
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct OsdDatetime {
+    /// XML Version
+    #[serde(rename = "@version")]
+    pub version: String,
+    /// The channel ID. Usually zero unless from an NVR
+    #[serde(rename = "channelId")]
+    pub channel_id: u8,
+    /// The enable status known values are `1`, `0`
+    pub enable: u8,
+    /// X coordinate of the top left of the label
+    #[serde(rename = "topLeftX", skip_serializing_if = "Option::is_none")]
+    pub top_left_x: Option<u32>,
+    /// Y coordinate of the top left of the label
+    #[serde(rename = "topLeftY", skip_serializing_if = "Option::is_none")]
+    pub top_left_y: Option<u32>,
+    /// Width of the label
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    /// Height of the label
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Language of the label e.g. `English`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+/// Encoder settings xml for each of the streams
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct Compression {
+    /// XML Version
+    #[serde(rename = "@version")]
+    pub version: String,
+    /// The channel ID. Usually zero unless from an NVR
+    #[serde(rename = "channelId")]
+    pub channel_id: u8,
+    /// Encoder settings of the HD stream
+    #[serde(rename = "mainStream", skip_serializing_if = "Option::is_none")]
+    pub main_stream: Option<StreamCompression>,
+    /// Encoder settings of the SD stream
+    #[serde(rename = "subStream", skip_serializing_if = "Option::is_none")]
+    pub sub_stream: Option<StreamCompression>,
+    /// Encoder settings of the extern stream
+    #[serde(rename = "thirdStream", skip_serializing_if = "Option::is_none")]
+    pub third_stream: Option<StreamCompression>,
+}
+
+/// Encoder settings of a single stream
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct StreamCompression {
+    /// If audio is included known values are `1`, `0`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<u8>,
+    /// Name of the resolution e.g. `2560*1440`
+    #[serde(rename = "resolutionName", skip_serializing_if = "Option::is_none")]
+    pub resolution_name: Option<String>,
+    /// Width of the stream in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    /// Height of the stream in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Encoder e.g. `h264`
+    #[serde(rename = "encoderType", skip_serializing_if = "Option::is_none")]
+    pub encoder_type: Option<String>,
+    /// Frames per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame: Option<u8>,
+    /// Bitrate in kbps
+    #[serde(rename = "bitRate", skip_serializing_if = "Option::is_none")]
+    pub bit_rate: Option<u32>,
+    /// Encoder profile known values are `base`, `main`, `high`
+    #[serde(rename = "encoderProfile", skip_serializing_if = "Option::is_none")]
+    pub encoder_profile: Option<String>,
+    /// Seconds between IFrames
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gop: Option<u32>,
+}
+
+/// Motion detection settings xml
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct Md {
+    /// XML Version
+    #[serde(rename = "@version")]
+    pub version: String,
+    /// The channel ID. Usually zero unless from an NVR
+    #[serde(rename = "channelId")]
+    pub channel_id: u8,
+    /// The enable status known values are `1`, `0`
+    pub enable: u8,
+    /// Sensitivity from `0` to `100`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensitivity: Option<u8>,
+    /// The detection area
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<MdScope>,
+}
+
+/// The detection area of the motion detection
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct MdScope {
+    /// Number of columns in the grid
+    pub cols: u32,
+    /// Number of rows in the grid
+    pub rows: u32,
+    /// The grid row by row as a string of `1` (detect) and `0` (ignore)
+    pub table: String,
+}
+
+/// Email alert settings xml
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct Email {
+    /// XML Version
+    #[serde(rename = "@version")]
+    pub version: String,
+    /// Hostname of the SMTP server
+    #[serde(rename = "smtpServer")]
+    pub smtp_server: String,
+    /// Port of the SMTP server
+    #[serde(rename = "smtpPort")]
+    pub smtp_port: u16,
+    /// The encryption to the SMTP server known values are `0` (none) and `1` (TLS)
+    pub ssl: u8,
+    /// Username to log into the SMTP server
+    #[serde(rename = "userName")]
+    pub user_name: String,
+    /// Password to log into the SMTP server. Not sent by the camera
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Address that the emails are sent from
+    #[serde(rename = "senderAddress", skip_serializing_if = "Option::is_none")]
+    pub sender_address: Option<String>,
+    /// First recipient
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address1: Option<String>,
+    /// Second recipient
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address2: Option<String>,
+    /// Third recipient
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address3: Option<String>,
+}
+
+/// NTP settings xml
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct Ntp {
+    /// XML Version
+    #[serde(rename = "@version")]
+    pub version: String,
+    /// `1` if the camera sets its clock from the server
+    pub enable: u8,
+    /// Hostname of the NTP server
+    pub server: String,
+    /// Port of the NTP server
+    pub port: u16,
+    /// Minutes between each sync
+    pub interval: u32,
+}
+
+/// The schedule of the email alerts
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct EmailTask {
+    /// XML Version
+    #[serde(rename = "@version")]
+    pub version: String,
+    /// The channel ID. Usually zero unless from an NVR
+    #[serde(rename = "channelId")]
+    pub channel_id: u8,
+    /// The enable status known values are `1`, `0`
+    pub enable: u8,
+    /// The hours of the week that alerts are sent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<EmailSchedule>,
+}
+
+/// The hours of the week that email alerts are sent
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct EmailSchedule {
+    /// 168 characters, one per hour from Sunday 00:00, of `1` (send) and `0` (don't send)
+    pub table: String,
+}
+
+/// The users of the camera
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct UserList {
+    /// XML Version
+    #[serde(rename = "@version")]
+    pub version: String,
+    /// The users
+    #[serde(rename = "User", default)]
+    pub user: Vec<User>,
+}
+
+/// A user of the camera
+#[derive(PartialEq, Eq, Default, Debug, Deserialize, Serialize, Clone)]
+pub struct User {
+    /// The name the user logs in with
+    #[serde(rename = "userName")]
+    pub user_name: String,
+    /// The password. Not sent by the camera
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// The id of the user
+    #[serde(rename = "userId", skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<u32>,
+    /// Known values are `1` (admin) and `0` (guest)
+    #[serde(rename = "userLevel", skip_serializing_if = "Option::is_none")]
+    pub user_level: Option<u8>,
+    /// If the user is logged in right now
+    #[serde(rename = "loginState", skip_serializing_if = "Option::is_none")]
+    pub login_state: Option<u8>,
+    /// What to do with the user when setting. Known values are
+    /// `"none"`, `"add"`, `"delete"` and `"modify"`
+    #[serde(rename = "userSetState", skip_serializing_if = "Option::is_none")]
+    pub user_set_state: Option<String>,
+}
+
 /// Convience function to return the xml version used throughout the library
 pub fn xml_ver() -> String {
     "1.1".to_string()
@@ -1827,3 +2096,194 @@ fn test_empty_floodlight_status_list() {
         _ => panic!(),
     }
 }
+
+#[test]
+fn test_osd_deser() {
+    let sample = indoc!(
+        r#"
+        <?xml version="1.0" encoding="UTF-8" ?>
+        <body>
+        <OsdChannelName version="1.1">
+        <channelId>0</channelId>
+        <name>Front Door</name>
+        <enable>1</enable>
+        <topLeftX>0</topLeftX>
+        <topLeftY>0</topLeftY>
+        <enWatermark>1</enWatermark>
+        <enBgcolor>0</enBgcolor>
+        </OsdChannelName>
+        <OsdDatetime version="1.1">
+        <channelId>0</channelId>
+        <enable>1</enable>
+        <topLeftX>1400</topLeftX>
+        <topLeftY>0</topLeftY>
+        <width>520</width>
+        <height>48</height>
+        <language>English</language>
+        </OsdDatetime>
+        </body>"#
+    );
+    let b = BcXml::try_parse(sample.as_bytes()).unwrap();
+    let name = b.osd_channel_name.as_ref().unwrap();
+    assert_eq!(name.name, "Front Door");
+    assert_eq!(name.enable, 1);
+    assert_eq!(name.en_watermark, Some(1));
+
+    let datetime = b.osd_datetime.as_ref().unwrap();
+    assert_eq!(datetime.enable, 1);
+    assert_eq!(datetime.top_left_x, Some(1400));
+    assert_eq!(datetime.language.as_deref(), Some("English"));
+}
+
+#[test]
+fn test_compression_deser() {
+    let sample = indoc!(
+        r#"
+        <?xml version="1.0" encoding="UTF-8" ?>
+        <body>
+        <Compression version="1.1">
+        <channelId>0</channelId>
+        <mainStream>
+        <audio>1</audio>
+        <resolutionName>2560*1440</resolutionName>
+        <width>2560</width>
+        <height>1440</height>
+        <encoderType>h264</encoderType>
+        <frame>25</frame>
+        <bitRate>6144</bitRate>
+        <encoderProfile>high</encoderProfile>
+        <gop>2</gop>
+        </mainStream>
+        <subStream>
+        <audio>1</audio>
+        <resolutionName>640*360</resolutionName>
+        <width>640</width>
+        <height>360</height>
+        <frame>15</frame>
+        <bitRate>256</bitRate>
+        </subStream>
+        </Compression>
+        </body>"#
+    );
+    let b = BcXml::try_parse(sample.as_bytes()).unwrap();
+    let compression = b.compression.as_ref().unwrap();
+    let main = compression.main_stream.as_ref().unwrap();
+    assert_eq!(main.bit_rate, Some(6144));
+    assert_eq!(main.frame, Some(25));
+    assert_eq!(main.encoder_profile.as_deref(), Some("high"));
+
+    let sub = compression.sub_stream.as_ref().unwrap();
+    assert_eq!(sub.width, Some(640));
+    assert_eq!(sub.encoder_type, None);
+    assert!(compression.third_stream.is_none());
+}
+
+#[test]
+fn test_md_deser() {
+    let sample = indoc!(
+        r#"
+        <?xml version="1.0" encoding="UTF-8" ?>
+        <body>
+        <MD version="1.1">
+        <channelId>0</channelId>
+        <enable>1</enable>
+        <sensitivity>50</sensitivity>
+        <scope>
+        <cols>4</cols>
+        <rows>2</rows>
+        <table>11000110</table>
+        </scope>
+        </MD>
+        </body>"#
+    );
+    let b = BcXml::try_parse(sample.as_bytes()).unwrap();
+    let md = b.md.as_ref().unwrap();
+    assert_eq!(md.enable, 1);
+    assert_eq!(md.sensitivity, Some(50));
+
+    let scope = md.scope.as_ref().unwrap();
+    assert_eq!(scope.cols, 4);
+    assert_eq!(scope.rows, 2);
+    assert_eq!(scope.table, "11000110");
+}
+
+#[test]
+fn test_email_deser() {
+    let sample = indoc!(
+        r#"
+        <?xml version="1.0" encoding="UTF-8" ?>
+        <body>
+        <Email version="1.1">
+        <smtpServer>smtp.example.com</smtpServer>
+        <smtpPort>465</smtpPort>
+        <ssl>1</ssl>
+        <userName>camera@example.com</userName>
+        <address1>me@example.com</address1>
+        <address2></address2>
+        </Email>
+        </body>"#
+    );
+    let b = BcXml::try_parse(sample.as_bytes()).unwrap();
+    let email = b.email.as_ref().unwrap();
+    assert_eq!(email.smtp_server, "smtp.example.com");
+    assert_eq!(email.smtp_port, 465);
+    assert_eq!(email.ssl, 1);
+    assert_eq!(email.password, None);
+    assert_eq!(email.address1.as_deref(), Some("me@example.com"));
+    assert_eq!(email.address2.as_deref(), Some(""));
+    assert_eq!(email.address3, None);
+}
+
+#[test]
+fn test_user_list_deser() {
+    let sample = indoc!(
+        r#"
+        <?xml version="1.0" encoding="UTF-8" ?>
+        <body>
+        <UserList version="1.1">
+        <User>
+        <userName>admin</userName>
+        <userId>0</userId>
+        <userLevel>1</userLevel>
+        <loginState>1</loginState>
+        </User>
+        <User>
+        <userName>guest</userName>
+        <userId>1</userId>
+        <userLevel>0</userLevel>
+        <loginState>0</loginState>
+        </User>
+        </UserList>
+        </body>"#
+    );
+    let b = BcXml::try_parse(sample.as_bytes()).unwrap();
+    let users = &b.user_list.as_ref().unwrap().user;
+    assert_eq!(users.len(), 2);
+    assert_eq!(users[0].user_name, "admin");
+    assert_eq!(users[0].user_level, Some(1));
+    assert_eq!(users[0].password, None);
+    assert_eq!(users[1].user_name, "guest");
+    assert_eq!(users[1].user_id, Some(1));
+}
+
+#[test]
+fn test_ntp_deser() {
+    let sample = indoc!(
+        r#"
+        <?xml version="1.0" encoding="UTF-8" ?>
+        <body>
+        <Ntp version="1.1">
+        <enable>1</enable>
+        <server>pool.ntp.org</server>
+        <port>123</port>
+        <interval>1440</interval>
+        </Ntp>
+        </body>"#
+    );
+    let b = BcXml::try_parse(sample.as_bytes()).unwrap();
+    let ntp = b.ntp.as_ref().unwrap();
+    assert_eq!(ntp.enable, 1);
+    assert_eq!(ntp.server, "pool.ntp.org");
+    assert_eq!(ntp.port, 123);
+    assert_eq!(ntp.interval, 1440);
+}
@@ -7,15 +7,20 @@ use std::{
     collections::HashMap,
     sync::atomic::{AtomicBool, AtomicU16, Ordering},
 };
-use tokio::sync::RwLock;
+use tokio::sync::{
+    watch::{channel as watch, Receiver as WatchReceiver},
+    OnceCell, RwLock,
+};
 use tokio_util::sync::CancellationToken;
 
 use Md5Trunc::*;
 
 mod abilityinfo;
 mod battery;
+mod capabilities;
 mod connection;
 mod credentials;
+mod email;
 mod errors;
 mod floodlight;
 mod keepalive;
@@ -24,6 +29,9 @@ mod link;
 mod login;
 mod logout;
 mod motion;
+mod motion_detection;
+mod ntp;
+mod osd;
 mod ping;
 mod pirstate;
 mod ptz;
@@ -35,24 +43,35 @@ mod siren;
 mod snap;
 mod stream;
 mod stream_info;
+mod stream_quality;
 mod support;
 mod talk;
 mod time;
 mod uid;
+mod users;
 mod version;
 
+pub use capabilities::CameraCapabilities;
 pub(crate) use connection::*;
+pub use connection::{ConnectionStats, HttpProxy, PacketDirection, PacketTap, TappedPacket};
 pub use credentials::*;
+pub use email::{EmailConfig, EmailTlsMode, MAX_EMAIL_RECIPIENTS};
 pub use errors::Error;
 pub use ledstate::LightState;
-pub use login::MaxEncryption;
+pub use login::{is_login_challenge_reply, login_challenge_request, MaxEncryption};
 pub use motion::{MotionData, MotionStatus};
+pub use motion_detection::{
+    normalize_regions, regions_from_grid, MotionDetectionConfig, MotionRegion, Rect,
+};
+pub use ntp::NtpConfig;
+pub use osd::{OsdConfig, OsdPosition};
 pub use pirstate::PirState;
-pub use ptz::Direction;
+pub use ptz::{Direction, PtzPresetInfo};
 pub use pushinfo::PhoneType;
 pub use resolution::*;
 use std::sync::Arc;
 pub use stream::{StreamData, StreamKind};
+pub use stream_quality::{round_gop_size, EncodingConfig, QualityPreset, StreamQualityConfig};
 
 pub(crate) type Result<T> = std::result::Result<T, Error>;
 
@@ -69,11 +88,13 @@ enum ReadKind {
 pub struct BcCamera {
     channel_id: u8,
     connection: Arc<BcConnection>,
-    logged_in: AtomicBool,
-    message_num: AtomicU16,
+    logged_in: Arc<AtomicBool>,
+    message_num: Arc<AtomicU16>,
     // Certain commands such as logout require the username/pass in plain text.... why....???
     credentials: Credentials,
-    abilities: RwLock<HashMap<String, ReadKind>>,
+    abilities: Arc<RwLock<HashMap<String, ReadKind>>>,
+    capabilities: Arc<OnceCell<CameraCapabilities>>,
+    connection_stats: WatchReceiver<ConnectionStats>,
     #[allow(dead_code)]
     cancel: CancellationToken,
 }
@@ -102,6 +123,10 @@ pub struct BcCameraOpt {
     pub credentials: Credentials,
     /// Toggle debug print of underlying data
     pub debug: bool,
+    /// Http proxy to tunnel tcp connections through
+    pub proxy: Option<HttpProxy>,
+    /// Receives a copy of every packet sent and received
+    pub packet_tap: Option<PacketTap>,
 }
 
 /// Used to choose the print format of various status messages like battery levels
@@ -159,10 +184,14 @@ impl BcCamera {
                 info!("{}: Trying TCP discovery", options.name);
                 for socket in sockets.drain(..) {
                     let channel_id: u8 = options.channel_id;
-                    if let Ok(addr) = discovery.check_tcp(socket, channel_id).await.map(|_| {
-                        info!("{}: TCP Discovery success at {:?}", options.name, &socket);
-                        socket
-                    }) {
+                    if let Ok(addr) = discovery
+                        .check_tcp(socket, channel_id, options.proxy.as_ref())
+                        .await
+                        .map(|_| {
+                            info!("{}: TCP Discovery success at {:?}", options.name, &socket);
+                            socket
+                        })
+                    {
                         return Ok(CameraLocation::Tcp(addr));
                     }
                 }
@@ -328,13 +357,21 @@ impl BcCamera {
         let username: String = options.credentials.username.clone();
         let passwd: Option<String> = options.credentials.password.clone();
 
-        let (sink, source): (BcConnSink, BcConnSource) = {
+        let (sink, source, connection_stats): (BcConnSink, BcConnSource, _) = {
             match BcCamera::find_camera(options).await? {
                 CameraLocation::Tcp(addr) => {
-                    let (x, r) = TcpSource::new(addr, &username, passwd.as_ref(), options.debug)
-                        .await?
-                        .split();
-                    (Box::new(x), Box::new(r))
+                    let source = TcpSource::new(
+                        addr,
+                        &username,
+                        passwd.as_ref(),
+                        options.proxy.as_ref(),
+                        options.debug,
+                        options.packet_tap.clone(),
+                    )
+                    .await?;
+                    let connection_stats = source.stats();
+                    let (x, r) = source.split();
+                    (Box::new(x), Box::new(r), connection_stats)
                 }
                 CameraLocation::Udp(discovery) => {
                     let (x, r) = UdpSource::new_from_discovery(
@@ -342,10 +379,13 @@ impl BcCamera {
                         &username,
                         passwd.as_ref(),
                         options.debug,
+                        options.packet_tap.clone(),
                     )
                     .await?
                     .split();
-                    (Box::new(x), Box::new(r))
+                    // Udp has no stats so these stay as None
+                    let (_, connection_stats) = watch(ConnectionStats::default());
+                    (Box::new(x), Box::new(r), connection_stats)
                 }
             }
         };
@@ -355,11 +395,13 @@ impl BcCamera {
         trace!("Success");
         let me = Self {
             connection: Arc::new(conn),
-            message_num: AtomicU16::new(0),
+            message_num: Arc::new(AtomicU16::new(0)),
             channel_id: options.channel_id,
-            logged_in: AtomicBool::new(false),
+            logged_in: Arc::new(AtomicBool::new(false)),
             credentials: Credentials::new(username, passwd),
             abilities: Default::default(),
+            capabilities: Default::default(),
+            connection_stats,
             cancel: CancellationToken::new(),
         };
         me.keepalive().await?;
@@ -431,6 +473,34 @@ impl BcCamera {
         self.connection.shutdown().await?;
         Ok(())
     }
+
+    /// The statistics of the tcp connection to the camera
+    ///
+    /// They are sampled every 10s while the connection is receiving. The
+    /// values are `None` for udp connections and on platforms other than linux
+    pub fn connection_stats(&self) -> ConnectionStats {
+        *self.connection_stats.borrow()
+    }
+
+    /// Create a handle on another channel of the same device
+    ///
+    /// The new handle shares this connection, login and message numbers. This is
+    /// used to reach each camera of an NVR without opening a new connection for every
+    /// channel. Calling `logout` or `shutdown` on any of the handles will affect all of them
+    pub fn with_channel(&self, channel_id: u8) -> Self {
+        Self {
+            channel_id,
+            connection: self.connection.clone(),
+            logged_in: self.logged_in.clone(),
+            message_num: self.message_num.clone(),
+            credentials: self.credentials.clone(),
+            abilities: self.abilities.clone(),
+            // Capabilities are per channel
+            capabilities: Default::default(),
+            connection_stats: self.connection_stats.clone(),
+            cancel: self.cancel.clone(),
+        }
+    }
 }
 
 /// The Baichuan library has a very peculiar behavior where it always zeros the last byte.  I
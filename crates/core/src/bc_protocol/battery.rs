@@ -79,6 +79,8 @@ impl BcCamera {
 
     /// Requests the current battery status of the camera
     pub async fn battery_info(&self) -> Result<BatteryInfo> {
+        self.require_capability("battery", |c| c.has_battery)
+            .await?;
         let connection = self.get_connection();
 
         let msg_num = self.new_message_num();
@@ -0,0 +1,152 @@
+use super::{BcCamera, Error, ReadKind, Result};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The features that the camera reports that it supports
+///
+/// Each is `None` when the camera does not say either way
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct CameraCapabilities {
+    /// Can pan, tilt or zoom
+    pub has_ptz: Option<bool>,
+    /// Has IR lights that can be controlled
+    pub has_ir_lights: Option<bool>,
+    /// Has alarm input or output ports
+    pub has_alarm_io: Option<bool>,
+    /// Runs on a battery
+    pub has_battery: Option<bool>,
+    /// Has an SD card or other disk
+    pub has_sd_card: Option<bool>,
+    /// Can detect people, vehicles or animals
+    pub has_ai_detection: Option<bool>,
+    /// Has a speaker for two way audio
+    pub has_talk_back: Option<bool>,
+    /// Has both a main and sub stream
+    pub has_two_streams: Option<bool>,
+    /// Is a doorbell
+    pub has_doorbell: Option<bool>,
+}
+
+/// Combines several reports of the same feature
+///
+/// Any report that it is present wins, otherwise it is absent only if
+/// something reported it as absent
+fn reported(reports: &[Option<bool>]) -> Option<bool> {
+    if reports.contains(&Some(true)) {
+        Some(true)
+    } else if reports.contains(&Some(false)) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+impl BcCamera {
+    /// Get the features that the camera supports
+    ///
+    /// This is made from the [`crate::bc::xml::Support`] xml, the stream info and the
+    /// abilities of the logged in user. It is only queried once, later calls
+    /// return the cached value
+    pub async fn get_capabilities(&self) -> Result<CameraCapabilities> {
+        self.capabilities
+            .get_or_try_init(|| async {
+                let support = self.get_support().await?;
+                let item = support
+                    .items
+                    .iter()
+                    .find(|item| item.chn_id == self.channel_id as u32)
+                    .or_else(|| support.items.first());
+                let is_set = |value: Option<u32>| value.map(|value| value > 0);
+
+                let stream_names = self
+                    .get_stream_info()
+                    .await
+                    .map(|info| {
+                        info.stream_infos
+                            .iter()
+                            .flat_map(|info| info.encode_tables.iter())
+                            .map(|encode| encode.name.clone())
+                            .collect::<HashSet<_>>()
+                    })
+                    .ok();
+
+                let capabilities = CameraCapabilities {
+                    has_ptz: reported(&[
+                        support
+                            .ptz_mode
+                            .as_deref()
+                            .map(|mode| !mode.is_empty() && mode != "none"),
+                        item.and_then(|item| is_set(item.ptz_type)),
+                        item.and_then(|item| is_set(item.ptz_control)),
+                    ]),
+                    has_ir_lights: self.has_any_ability(&["ledState"]).await,
+                    has_alarm_io: reported(&[
+                        is_set(support.io_input_port_num),
+                        is_set(support.io_output_port_num),
+                    ]),
+                    has_battery: item.and_then(|item| is_set(item.battery)),
+                    has_sd_card: is_set(support.disk_num),
+                    has_ai_detection: item.and_then(|item| is_set(item.ai_type)),
+                    has_talk_back: reported(&[
+                        is_set(support.audio_talk),
+                        item.and_then(|item| is_set(item.ipc_audio_talk)),
+                    ]),
+                    has_two_streams: stream_names.map(|names| names.len() >= 2),
+                    has_doorbell: self.has_any_ability(&["doorbell", "visitor"]).await,
+                };
+                debug!("Capabilities: {:?}", capabilities);
+                Ok(capabilities)
+            })
+            .await
+            .copied()
+    }
+
+    /// Error if the camera reports that it does not support a feature
+    ///
+    /// If the camera does not report the feature, or the capabilities cannot
+    /// be read, the command is allowed so that cameras with incomplete
+    /// support xml still work
+    pub(crate) async fn require_capability(
+        &self,
+        feature: &'static str,
+        has: impl Fn(&CameraCapabilities) -> Option<bool>,
+    ) -> Result<()> {
+        match self.get_capabilities().await {
+            Ok(capabilities) if has(&capabilities) == Some(false) => {
+                Err(Error::UnsupportedFeature { feature })
+            }
+            Ok(_) => Ok(()),
+            Err(e) => {
+                debug!("Could not read capabilities, assuming {feature} is supported: {e:?}");
+                Ok(())
+            }
+        }
+    }
+
+    /// None if the abilities of the user are not known
+    async fn has_any_ability(&self, names: &[&str]) -> Option<bool> {
+        if self.abilities.read().await.is_empty() {
+            return None;
+        }
+        for name in names {
+            if !matches!(self.has_ability(*name).await, ReadKind::None) {
+                return Some(true);
+            }
+        }
+        Some(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reported() {
+        assert_eq!(reported(&[]), None);
+        assert_eq!(reported(&[None, None]), None);
+        assert_eq!(reported(&[None, Some(false)]), Some(false));
+        assert_eq!(reported(&[Some(false), None, Some(true)]), Some(true));
+    }
+}
@@ -7,7 +7,10 @@ use log::*;
 use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
 use std::sync::Arc;
-use tokio::sync::mpsc::{channel, Sender};
+use tokio::sync::{
+    mpsc::{channel, error::SendError, Receiver, Sender},
+    Notify,
+};
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::sync::CancellationToken;
 
@@ -26,6 +29,99 @@ struct Subscriber {
     id: BTreeMap<u32, Arc<MsgHandler>>,
 }
 
+/// The order that the queued messages are sent to the camera in
+///
+/// The camera sends the video so the binary data that is sent to it is
+/// mostly the audio of talk. Without this a PTZ command or keepalive could
+/// wait behind seconds of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    /// Commands, replies and keepalives
+    Control,
+    /// The binary data of talk
+    AudioData,
+    /// Any other binary data
+    VideoData,
+}
+
+impl Priority {
+    fn of(bc: &Bc) -> Self {
+        match &bc.body {
+            BcBody::ModernMsg(ModernMsg {
+                payload: Some(BcPayloads::Binary(_)),
+                ..
+            }) if bc.meta.msg_id == MSG_ID_TALK => Priority::AudioData,
+            BcBody::ModernMsg(ModernMsg {
+                payload: Some(BcPayloads::Binary(_)),
+                ..
+            }) => Priority::VideoData,
+            _ => Priority::Control,
+        }
+    }
+}
+
+/// Queues the messages that are sent to the camera by their [`Priority`]
+///
+/// Messages of the same priority are sent in the order they were queued
+#[derive(Clone)]
+struct PrioritySender {
+    control: Sender<Result<Bc>>,
+    audio: Sender<Result<Bc>>,
+    video: Sender<Result<Bc>>,
+}
+
+/// The receiving end of a [`PrioritySender`]
+struct PriorityReceiver {
+    control: Receiver<Result<Bc>>,
+    audio: Receiver<Result<Bc>>,
+    video: Receiver<Result<Bc>>,
+}
+
+fn priority_channel(buffer: usize) -> (PrioritySender, PriorityReceiver) {
+    let (control, control_rx) = channel(buffer);
+    let (audio, audio_rx) = channel(buffer);
+    let (video, video_rx) = channel(buffer);
+    (
+        PrioritySender {
+            control,
+            audio,
+            video,
+        },
+        PriorityReceiver {
+            control: control_rx,
+            audio: audio_rx,
+            video: video_rx,
+        },
+    )
+}
+
+impl PrioritySender {
+    async fn send(&self, packet: Result<Bc>) -> std::result::Result<(), SendError<Result<Bc>>> {
+        let priority = match &packet {
+            Ok(bc) => Priority::of(bc),
+            Err(_) => Priority::Control,
+        };
+        match priority {
+            Priority::Control => self.control.send(packet).await,
+            Priority::AudioData => self.audio.send(packet).await,
+            Priority::VideoData => self.video.send(packet).await,
+        }
+    }
+}
+
+impl PriorityReceiver {
+    /// The next message from the highest priority queue that has one
+    async fn recv(&mut self) -> Option<Result<Bc>> {
+        tokio::select! {
+            biased;
+            Some(packet) = self.control.recv() => Some(packet),
+            Some(packet) = self.audio.recv() => Some(packet),
+            Some(packet) = self.video.recv() => Some(packet),
+            else => None,
+        }
+    }
+}
+
 pub(crate) type BcConnSink = Box<dyn Sink<Bc, Error = Error> + Send + Sync + Unpin>;
 pub(crate) type BcConnSource = Box<dyn Stream<Item = Result<Bc>> + Send + Sync + Unpin>;
 
@@ -35,22 +131,29 @@ pub(crate) type BcConnSource = Box<dyn Stream<Item = Result<Bc>> + Send + Sync +
 ///
 /// There can be only one subscriber per kind of message at a time.
 pub struct BcConnection {
-    sink: Sender<Result<Bc>>,
+    sink: PrioritySender,
     poll_commander: Sender<PollCommand>,
     rx_thread: RwLock<JoinSet<Result<()>>>,
     cancel: CancellationToken,
+    session_expired: Arc<Notify>,
 }
 
+/// The response code the camera sends when a command arrives after the login
+/// session has ended
+const RESPONSE_CODE_UNAUTHORIZED: u16 = 401;
+
 impl BcConnection {
     pub async fn new(mut sink: BcConnSink, mut source: BcConnSource) -> Result<BcConnection> {
-        let (sinker, sinker_rx) = channel::<Result<Bc>>(100);
+        let (sinker, mut sinker_rx) = priority_channel(100);
         let cancel = CancellationToken::new();
 
         let (poll_commander, poll_commanded) = channel(200);
+        let session_expired = Arc::new(Notify::new());
         let mut poller = Poller {
             subscribers: Default::default(),
             sink: sinker.clone(),
             reciever: ReceiverStream::new(poll_commanded),
+            session_expired: session_expired.clone(),
         };
 
         let mut rx_thread = JoinSet::<Result<()>>::new();
@@ -76,8 +179,7 @@ impl BcConnection {
             tokio::select! {
                 _ = thread_cancel.cancelled() => Result::Ok(()),
                 v = async {
-                    let mut stream = ReceiverStream::new(sinker_rx);
-                    while let Some(packet) = stream.next().await {
+                    while let Some(packet) = sinker_rx.recv().await {
                         sink.send(packet?).await?;
                     }
                     Ok(())
@@ -105,9 +207,16 @@ impl BcConnection {
             poll_commander,
             rx_thread: RwLock::new(rx_thread),
             cancel,
+            session_expired,
         })
     }
 
+    /// Completes when the camera rejects a command because the login
+    /// session has expired
+    pub(crate) async fn session_expired(&self) {
+        self.session_expired.notified().await
+    }
+
     pub(super) async fn send(&self, bc: Bc) -> crate::Result<()> {
         self.sink.send(Ok(bc)).await?;
         Ok(())
@@ -224,8 +333,9 @@ impl std::fmt::Debug for PollCommand {
 
 struct Poller {
     subscribers: Subscriber,
-    sink: Sender<Result<Bc>>,
+    sink: PrioritySender,
     reciever: ReceiverStream<PollCommand>,
+    session_expired: Arc<Notify>,
 }
 
 impl Poller {
@@ -248,6 +358,14 @@ impl Poller {
                         Ok(response) => {
                             let msg_id = response.meta.msg_id;
                             let msg_num = response.meta.msg_num;
+                            if response.meta.response_code == RESPONSE_CODE_UNAUTHORIZED
+                                && msg_id != MSG_ID_LOGIN
+                                && msg_id != MSG_ID_LOGOUT
+                            {
+                                debug!("Camera rejected message id {} as unauthorized", msg_id);
+                                // Stores a permit if no one is waiting yet
+                                self.session_expired.notify_one();
+                            }
                             log::trace!(
                                 "Looking for ID: {} with num: {}, in {:?} and {:?}",
                                 msg_id,
@@ -403,3 +521,41 @@ impl Poller {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bc(msg_id: u32, payload: Option<BcPayloads>) -> Bc {
+        Bc::new(
+            BcMeta {
+                msg_id,
+                channel_id: 0,
+                stream_type: 0,
+                response_code: 0,
+                msg_num: 0,
+                class: 0x6414,
+            },
+            None,
+            payload,
+        )
+    }
+
+    #[test]
+    fn test_priority() {
+        assert_eq!(
+            Priority::of(&bc(MSG_ID_PTZ_CONTROL, None)),
+            Priority::Control
+        );
+        assert_eq!(
+            Priority::of(&bc(MSG_ID_TALK, Some(BcPayloads::Binary(vec![0; 10])))),
+            Priority::AudioData
+        );
+        assert_eq!(
+            Priority::of(&bc(MSG_ID_VIDEO, Some(BcPayloads::Binary(vec![0; 10])))),
+            Priority::VideoData
+        );
+        assert!(Priority::Control < Priority::AudioData);
+        assert!(Priority::AudioData < Priority::VideoData);
+    }
+}
@@ -4,7 +4,7 @@
 //!
 use super::DiscoveryResult;
 use crate::bc::model::*;
-use crate::bc_protocol::{md5_string, Md5Trunc, TcpSource};
+use crate::bc_protocol::{md5_string, HttpProxy, Md5Trunc, TcpSource};
 use crate::bcudp::codex::BcUdpCodex;
 use crate::bcudp::model::*;
 use crate::bcudp::xml::*;
@@ -1076,11 +1076,19 @@ impl Discovery {
     // Check if TCP is possible
     //
     // To do this we send a dummy login  and see if it replies with any BC packet
-    pub(crate) async fn check_tcp(&self, addr: SocketAddr, channel_id: u8) -> Result<()> {
+    pub(crate) async fn check_tcp(
+        &self,
+        addr: SocketAddr,
+        channel_id: u8,
+        proxy: Option<&HttpProxy>,
+    ) -> Result<()> {
         let username = "admin";
         let password = Some("123456");
-        let mut tcp_source =
-            timeout(*TCP_WAIT, TcpSource::new(addr, username, password, false)).await??;
+        let mut tcp_source = timeout(
+            *TCP_WAIT,
+            TcpSource::new(addr, username, password, proxy, false, None),
+        )
+        .await??;
 
         let md5_username = md5_string(username, Md5Trunc::ZeroLast);
         let md5_password = password
@@ -0,0 +1,145 @@
+use crate::{Error, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::net::SocketAddr;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// The longest reply header that will be accepted from the proxy
+const MAX_REPLY_LEN: usize = 8192;
+
+/// An http proxy that tcp connections to the camera are tunneled through
+///
+/// The tunnel is made with the `CONNECT` method so the proxy only ever sees
+/// the already encrypted bc traffic. UDP connections cannot use the proxy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpProxy {
+    /// Hostname or IP of the proxy
+    pub host: String,
+    /// Port of the proxy
+    pub port: u16,
+    /// Username for the `Proxy-Authorization` header
+    pub username: Option<String>,
+    /// Password for the `Proxy-Authorization` header
+    pub password: Option<String>,
+    /// Hostname of the camera to ask the proxy for
+    ///
+    /// When `None` the IP that the camera's address resolved to is used
+    pub target_host: Option<String>,
+}
+
+impl HttpProxy {
+    /// Create the proxy from a url such as `http://proxy.example.com:3128`
+    ///
+    /// When there is no port 3128 is used
+    pub fn from_url<T: AsRef<str>>(url: T) -> Result<Self> {
+        let url = url.as_ref();
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or(Error::Other("Proxy url must start with http://"))?;
+        let authority = rest.split('/').next().unwrap_or_default();
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (
+                host,
+                port.parse::<u16>()
+                    .map_err(|_| Error::Other("Proxy url has an invalid port"))?,
+            ),
+            _ => (authority, 3128),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(Error::Other("Proxy url has no host"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            username: None,
+            password: None,
+            target_host: None,
+        })
+    }
+
+    /// Connect to the proxy and ask it to open a tunnel to `addr`
+    ///
+    /// The returned stream is connected to the camera
+    pub(crate) async fn connect(&self, addr: SocketAddr) -> Result<TcpStream> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+
+        let target = self.target(addr);
+        let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+        if let Some(username) = self.username.as_ref() {
+            let token = BASE64.encode(format!(
+                "{}:{}",
+                username,
+                self.password.as_deref().unwrap_or_default()
+            ));
+            request.push_str(&format!("Proxy-Authorization: Basic {token}\r\n"));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+        stream.flush().await?;
+
+        // Read one byte at a time so that nothing after the header is consumed
+        let mut reply = Vec::new();
+        while !reply.ends_with(b"\r\n\r\n") {
+            if reply.len() >= MAX_REPLY_LEN {
+                return Err(Error::ProxyRefused("Reply header too long".to_string()));
+            }
+            let byte = stream.read_u8().await?;
+            reply.push(byte);
+        }
+
+        let reply = String::from_utf8_lossy(&reply);
+        let status_line = reply.lines().next().unwrap_or_default();
+        match status_line.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(stream),
+            _ => Err(Error::ProxyRefused(status_line.to_string())),
+        }
+    }
+
+    /// The `host:port` to put in the `CONNECT` request
+    fn target(&self, addr: SocketAddr) -> String {
+        match self.target_host.as_deref() {
+            Some(host) if host.contains(':') => format!("[{}]:{}", host, addr.port()),
+            Some(host) => format!("{}:{}", host, addr.port()),
+            None => addr.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_url() {
+        let proxy = HttpProxy::from_url("http://proxy.corp.example.com:3128").unwrap();
+        assert_eq!(proxy.host, "proxy.corp.example.com");
+        assert_eq!(proxy.port, 3128);
+
+        let proxy = HttpProxy::from_url("http://[::1]:8080/").unwrap();
+        assert_eq!(proxy.host, "::1");
+        assert_eq!(proxy.port, 8080);
+
+        let proxy = HttpProxy::from_url("http://10.0.0.1").unwrap();
+        assert_eq!(proxy.host, "10.0.0.1");
+        assert_eq!(proxy.port, 3128);
+
+        assert!(HttpProxy::from_url("https://10.0.0.1:3128").is_err());
+        assert!(HttpProxy::from_url("http://10.0.0.1:port").is_err());
+    }
+
+    #[test]
+    fn test_connect_target() {
+        let mut proxy = HttpProxy::from_url("http://proxy.corp.example.com:3128").unwrap();
+        let addr: SocketAddr = "10.20.0.5:9000".parse().unwrap();
+        assert_eq!(proxy.target(addr), "10.20.0.5:9000");
+
+        proxy.target_host = Some("camera.corp.example.com".to_string());
+        assert_eq!(proxy.target(addr), "camera.corp.example.com:9000");
+
+        proxy.target_host = Some("fd00::5".to_string());
+        assert_eq!(proxy.target(addr), "[fd00::5]:9000");
+    }
+}
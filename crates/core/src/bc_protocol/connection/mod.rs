@@ -11,13 +11,19 @@ use tokio::net::UdpSocket;
 mod bcconn;
 mod bcsub;
 mod discovery;
+mod httpproxy;
+mod packettap;
 mod tcpsource;
+mod tcpstats;
 mod udpsource;
 
 pub(crate) use self::{
     bcconn::BcConnection, bcconn::*, bcsub::BcSubscription, discovery::Discovery,
     tcpsource::TcpSource, udpsource::UdpSource,
 };
+pub use httpproxy::HttpProxy;
+pub use packettap::{PacketDirection, PacketTap, TappedPacket};
+pub use tcpstats::ConnectionStats;
 
 pub(crate) struct DiscoveryResult {
     socket: Arc<UdpSocket>,
@@ -0,0 +1,52 @@
+//! Copies the BC packets of a connection as they are sent and received
+//!
+//! The copies are the bytes on the wire so they are still encrypted. They
+//! are meant for capture files that are read by the wireshark dissector
+use std::time::SystemTime;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Which way a [`TappedPacket`] went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+    /// From neolink to the camera
+    Sent,
+    /// From the camera to neolink
+    Received,
+}
+
+/// A copy of one BC packet
+#[derive(Debug, Clone)]
+pub struct TappedPacket {
+    /// Which way the packet went
+    pub direction: PacketDirection,
+    /// When the packet was sent or received
+    pub time: SystemTime,
+    /// The bytes of the packet as they were on the wire
+    pub data: Vec<u8>,
+}
+
+/// Passes a copy of every BC packet of a connection to a receiver
+///
+/// Give it to [`crate::bc_protocol::BcCameraOpt`] to tap the camera's connection
+#[derive(Debug, Clone)]
+pub struct PacketTap {
+    sender: UnboundedSender<TappedPacket>,
+}
+
+impl PacketTap {
+    /// Create a tap and the receiver of its packets
+    ///
+    /// The packets are dropped once the receiver is dropped
+    pub fn new() -> (Self, UnboundedReceiver<TappedPacket>) {
+        let (sender, receiver) = unbounded_channel();
+        (Self { sender }, receiver)
+    }
+
+    pub(crate) fn tap(&self, direction: PacketDirection, data: &[u8]) {
+        let _ = self.sender.send(TappedPacket {
+            direction,
+            time: SystemTime::now(),
+            data: data.to_vec(),
+        });
+    }
+}
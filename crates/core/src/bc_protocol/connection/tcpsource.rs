@@ -1,16 +1,20 @@
 use crate::bc::model::*;
 use crate::Result;
 use crate::{bc::codex::BcCodex, Credentials};
+
+use super::{tcpstats::StatsMonitor, ConnectionStats, HttpProxy, PacketTap};
 use delegate::delegate;
 use futures::{sink::Sink, stream::Stream};
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use tokio::net::{TcpSocket, TcpStream};
+use tokio::sync::watch::Receiver as WatchReceiver;
 use tokio_util::codec::{Decoder, Encoder, Framed};
 
 pub(crate) struct TcpSource {
     inner: Framed<TcpStream, BcCodex>,
+    stats: StatsMonitor,
 }
 
 impl TcpSource {
@@ -18,28 +22,40 @@ impl TcpSource {
         addr: SocketAddr,
         username: T,
         password: Option<U>,
+        proxy: Option<&HttpProxy>,
         debug: bool,
+        packet_tap: Option<PacketTap>,
     ) -> Result<TcpSource> {
-        let stream = connect_to(addr).await?;
+        let stream = match proxy {
+            Some(proxy) => proxy.connect(addr).await?,
+            None => connect_to(addr).await?,
+        };
 
         let codex = if debug {
             BcCodex::new_with_debug(Credentials::new(username, password))
         } else {
             BcCodex::new(Credentials::new(username, password))
-        };
+        }
+        .with_tap(packet_tap);
         Ok(Self {
             inner: Framed::new(stream, codex),
+            stats: StatsMonitor::new(addr),
         })
     }
+
+    /// The statistics of the connection, updated as packets are received
+    pub(crate) fn stats(&self) -> WatchReceiver<ConnectionStats> {
+        self.stats.subscribe()
+    }
 }
 
 impl Stream for TcpSource {
     type Item = std::result::Result<<BcCodex as Decoder>::Item, <BcCodex as Decoder>::Error>;
 
-    delegate! {
-        to Pin::new(&mut self.inner) {
-            fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
-        }
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        this.stats.sample(this.inner.get_ref());
+        Pin::new(&mut this.inner).poll_next(cx)
     }
 
     delegate! {
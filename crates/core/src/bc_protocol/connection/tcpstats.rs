@@ -0,0 +1,202 @@
+//! Reads the kernel's statistics of the tcp connection to the camera
+//!
+//! These show when a poor network is the cause of timeouts that look like
+//! the camera misbehaving
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::watch::{channel as watch, Receiver as WatchReceiver, Sender as WatchSender};
+
+/// Statistics of the tcp connection to the camera
+///
+/// These are only read on linux and only for tcp connections. Otherwise they are `None`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// The smoothed round trip time in microseconds
+    pub tcp_rtt_us: Option<u32>,
+    /// The total number of segments that have been retransmitted
+    pub tcp_retransmits: Option<u32>,
+    /// The space in the receive buffer in bytes
+    pub tcp_rcv_space: Option<u32>,
+    /// The total number of bytes received
+    pub bytes_received: Option<u64>,
+}
+
+/// Samples the [`ConnectionStats`] of a tcp stream as it is polled
+pub(crate) struct StatsMonitor {
+    addr: SocketAddr,
+    last_sample: Option<Instant>,
+    retransmits: VecDeque<(Instant, u32)>,
+    stats: WatchSender<ConnectionStats>,
+}
+
+impl StatsMonitor {
+    const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+    const RETRANSMIT_WINDOW: Duration = Duration::from_secs(60);
+    const RTT_WARN_US: u32 = 200_000;
+    const RETRANSMIT_WARN: u32 = 10;
+
+    pub(crate) fn new(addr: SocketAddr) -> Self {
+        let (stats, _) = watch(ConnectionStats::default());
+        Self {
+            addr,
+            last_sample: None,
+            retransmits: VecDeque::new(),
+            stats,
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> WatchReceiver<ConnectionStats> {
+        self.stats.subscribe()
+    }
+
+    /// Read the stats of the stream if it has been long enough since the last time
+    pub(crate) fn sample(&mut self, stream: &TcpStream) {
+        let now = Instant::now();
+        if self
+            .last_sample
+            .is_some_and(|last| now.duration_since(last) < Self::SAMPLE_INTERVAL)
+        {
+            return;
+        }
+        self.last_sample = Some(now);
+
+        let stats = read_stats(stream);
+        if let Some(rtt) = stats.tcp_rtt_us.filter(|rtt| *rtt > Self::RTT_WARN_US) {
+            log::warn!(
+                "{}: The round trip time to the camera is high: {}ms",
+                self.addr,
+                rtt / 1000
+            );
+        }
+        if let Some(total) = stats.tcp_retransmits {
+            let recent = self.recent_retransmits(now, total);
+            if recent > Self::RETRANSMIT_WARN {
+                log::warn!(
+                    "{}: {} tcp retransmits to the camera in the last minute",
+                    self.addr,
+                    recent
+                );
+            }
+        }
+        self.stats.send_replace(stats);
+    }
+
+    /// Record the total retransmits and return how many happened in the last minute
+    fn recent_retransmits(&mut self, now: Instant, total: u32) -> u32 {
+        self.retransmits.push_back((now, total));
+        while self
+            .retransmits
+            .front()
+            .is_some_and(|(time, _)| now.duration_since(*time) > Self::RETRANSMIT_WINDOW)
+        {
+            self.retransmits.pop_front();
+        }
+        self.retransmits
+            .front()
+            .map(|(_, first)| total.saturating_sub(*first))
+            .unwrap_or(0)
+    }
+}
+
+/// The start of the kernel's `struct tcp_info` up to `tcpi_bytes_received`
+///
+/// The kernel only ever adds to the end of this struct so older kernels
+/// fill less of it
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct TcpInfo {
+    tcpi_state: u8,
+    tcpi_ca_state: u8,
+    tcpi_retransmits: u8,
+    tcpi_probes: u8,
+    tcpi_backoff: u8,
+    tcpi_options: u8,
+    tcpi_wscale: u8,
+    tcpi_delivery_rate_app_limited: u8,
+    tcpi_rto: u32,
+    tcpi_ato: u32,
+    tcpi_snd_mss: u32,
+    tcpi_rcv_mss: u32,
+    tcpi_unacked: u32,
+    tcpi_sacked: u32,
+    tcpi_lost: u32,
+    tcpi_retrans: u32,
+    tcpi_fackets: u32,
+    tcpi_last_data_sent: u32,
+    tcpi_last_ack_sent: u32,
+    tcpi_last_data_recv: u32,
+    tcpi_last_ack_recv: u32,
+    tcpi_pmtu: u32,
+    tcpi_rcv_ssthresh: u32,
+    tcpi_rtt: u32,
+    tcpi_rttvar: u32,
+    tcpi_snd_ssthresh: u32,
+    tcpi_snd_cwnd: u32,
+    tcpi_advmss: u32,
+    tcpi_reordering: u32,
+    tcpi_rcv_rtt: u32,
+    tcpi_rcv_space: u32,
+    tcpi_total_retrans: u32,
+    tcpi_pacing_rate: u64,
+    tcpi_max_pacing_rate: u64,
+    tcpi_bytes_acked: u64,
+    tcpi_bytes_received: u64,
+}
+
+#[cfg(target_os = "linux")]
+fn read_stats(stream: &TcpStream) -> ConnectionStats {
+    use std::os::unix::io::AsRawFd;
+
+    let mut info = TcpInfo::default();
+    let mut len = std::mem::size_of::<TcpInfo>() as libc::socklen_t;
+    // SAFETY: The fd is kept open by the borrow of the stream and the
+    // kernel writes at most `len` bytes into `info`
+    let res = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut TcpInfo as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if res != 0 {
+        return Default::default();
+    }
+    let has_bytes_received = len as usize >= std::mem::size_of::<TcpInfo>();
+    ConnectionStats {
+        tcp_rtt_us: Some(info.tcpi_rtt),
+        tcp_retransmits: Some(info.tcpi_total_retrans),
+        tcp_rcv_space: Some(info.tcpi_rcv_space),
+        bytes_received: has_bytes_received.then_some(info.tcpi_bytes_received),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_stats(_stream: &TcpStream) -> ConnectionStats {
+    Default::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_retransmits() {
+        let mut monitor = StatsMonitor::new("127.0.0.1:9000".parse().unwrap());
+        let start = Instant::now();
+        assert_eq!(monitor.recent_retransmits(start, 5), 0);
+        assert_eq!(
+            monitor.recent_retransmits(start + Duration::from_secs(30), 12),
+            7
+        );
+        // The first sample is now out of the window
+        assert_eq!(
+            monitor.recent_retransmits(start + Duration::from_secs(70), 20),
+            8
+        );
+    }
+}
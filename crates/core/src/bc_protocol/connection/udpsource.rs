@@ -1,4 +1,4 @@
-use super::DiscoveryResult;
+use super::{DiscoveryResult, PacketTap};
 use crate::bc::codex::BcCodex;
 use crate::bc::model::*;
 use crate::bcudp::codex::BcUdpCodex;
@@ -51,11 +51,12 @@ impl UdpSource {
         username: T,
         password: Option<U>,
         debug: bool,
+        packet_tap: Option<PacketTap>,
     ) -> Result<Self> {
         let stream = Arc::new(connect().await?);
 
         Self::new_from_socket(
-            stream, addr, client_id, camera_id, username, password, debug,
+            stream, addr, client_id, camera_id, username, password, debug, packet_tap,
         )
         .await
     }
@@ -64,6 +65,7 @@ impl UdpSource {
         username: T,
         password: Option<U>,
         debug: bool,
+        packet_tap: Option<PacketTap>,
     ) -> Result<Self> {
         // Ensure that the discovery keep alive are all stopped here
         // We now handle all coms in UdpSource
@@ -76,10 +78,12 @@ impl UdpSource {
             username,
             password,
             debug,
+            packet_tap,
         )
         .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn new_from_socket<T: Into<String>, U: Into<String>>(
         stream: Arc<UdpSocket>,
         addr: SocketAddr,
@@ -88,6 +92,7 @@ impl UdpSource {
         username: T,
         password: Option<U>,
         debug: bool,
+        packet_tap: Option<PacketTap>,
     ) -> Result<Self> {
         let bcudp_source = BcUdpSource::new_from_socket(stream, addr).await?;
         let payload_source = bcudp_source.into_payload_source(client_id, camera_id).await;
@@ -96,7 +101,8 @@ impl UdpSource {
             BcCodex::new_with_debug(Credentials::new(username, password))
         } else {
             BcCodex::new(Credentials::new(username, password))
-        };
+        }
+        .with_tap(packet_tap);
         let framed = Framed::new(async_read, codex);
 
         Ok(Self {
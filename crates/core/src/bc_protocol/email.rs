@@ -0,0 +1,399 @@
+use super::{BcCamera, Error, Result};
+use crate::bc::{model::*, xml::*};
+use serde::{Deserialize, Serialize};
+
+/// The encryption used to talk to the SMTP server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmailTlsMode {
+    /// Plain text
+    #[serde(alias = "none")]
+    None,
+    /// SSL/TLS
+    #[serde(alias = "tls", alias = "ssl", alias = "Ssl")]
+    Tls,
+}
+
+/// The most recipients that a camera can send to
+pub const MAX_EMAIL_RECIPIENTS: usize = 3;
+
+/// The email alert settings of the camera
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailConfig {
+    /// Hostname of the SMTP server
+    pub smtp_host: String,
+    /// Port of the SMTP server
+    pub smtp_port: u16,
+    /// Encryption to the SMTP server
+    pub tls_mode: EmailTlsMode,
+    /// Username to log into the SMTP server
+    pub username: String,
+    /// Password to log into the SMTP server
+    ///
+    /// The camera never sends this back so it is always `None` when read.
+    /// When setting, `None` leaves the password unchanged
+    pub password: Option<String>,
+    /// Address that the emails are sent from
+    pub sender: Option<String>,
+    /// Up to [`MAX_EMAIL_RECIPIENTS`] addresses to send to
+    pub recipients: Vec<String>,
+    /// If email alerts are sent at all
+    pub enabled: bool,
+    /// 168 characters, one per hour from Sunday 00:00, of `1` (send) and `0` (don't send)
+    ///
+    /// When setting, `None` leaves the schedule unchanged
+    pub schedule: Option<String>,
+}
+
+impl EmailConfig {
+    /// Check the settings before any are sent to the camera
+    pub fn validate(&self) -> Result<()> {
+        if self.recipients.len() > MAX_EMAIL_RECIPIENTS {
+            return Err(Error::Other(
+                "Cameras only support up to 3 email recipients",
+            ));
+        }
+        if let Some(table) = self.schedule.as_ref() {
+            if table.len() != 168 || !table.chars().all(|c| c == '0' || c == '1') {
+                return Err(Error::Other(
+                    "Email schedule must be 168 characters of 0 or 1",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BcCamera {
+    /// Get the [Email] xml
+    async fn get_email(&self) -> Result<Email> {
+        self.has_ability_ro("email").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_get = connection.subscribe(MSG_ID_GET_EMAIL, msg_num).await?;
+        let get = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_GET_EMAIL,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: None,
+                payload: None,
+            }),
+        };
+
+        sub_get.send(get).await?;
+        let msg = sub_get.recv().await?;
+        if msg.meta.response_code != 200 {
+            return Err(Error::CameraServiceUnavailable {
+                id: msg.meta.msg_id,
+                code: msg.meta.response_code,
+            });
+        }
+
+        if let BcBody::ModernMsg(ModernMsg {
+            payload:
+                Some(BcPayloads::BcXml(BcXml {
+                    email: Some(email), ..
+                })),
+            ..
+        }) = msg.body
+        {
+            Ok(email)
+        } else {
+            Err(Error::UnintelligibleReply {
+                reply: std::sync::Arc::new(Box::new(msg)),
+                why: "Expected Email xml but it was not recieved",
+            })
+        }
+    }
+
+    /// Send an [Email] xml
+    async fn set_email(&self, email: Email) -> Result<()> {
+        self.has_ability_rw("email").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_set = connection.subscribe(MSG_ID_SET_EMAIL, msg_num).await?;
+        let set = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_SET_EMAIL,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: None,
+                payload: Some(BcPayloads::BcXml(BcXml {
+                    email: Some(email),
+                    ..Default::default()
+                })),
+            }),
+        };
+
+        sub_set.send(set).await?;
+        if let Ok(reply) =
+            tokio::time::timeout(tokio::time::Duration::from_millis(500), sub_set.recv()).await
+        {
+            let msg = reply?;
+
+            if let BcMeta {
+                response_code: 200, ..
+            } = msg.meta
+            {
+                Ok(())
+            } else {
+                Err(Error::UnintelligibleReply {
+                    reply: std::sync::Arc::new(Box::new(msg)),
+                    why: "The camera did not except the Email xml",
+                })
+            }
+        } else {
+            // Some cameras seem to just not send a reply on success, so after 500ms we return Ok
+            Ok(())
+        }
+    }
+
+    /// Get the [EmailTask] xml
+    async fn get_email_task(&self) -> Result<EmailTask> {
+        self.has_ability_ro("email").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_get = connection.subscribe(MSG_ID_GET_EMAIL_TASK, msg_num).await?;
+        let get = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_GET_EMAIL_TASK,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: Some(Extension {
+                    channel_id: Some(self.channel_id),
+                    ..Default::default()
+                }),
+                payload: None,
+            }),
+        };
+
+        sub_get.send(get).await?;
+        let msg = sub_get.recv().await?;
+        if msg.meta.response_code != 200 {
+            return Err(Error::CameraServiceUnavailable {
+                id: msg.meta.msg_id,
+                code: msg.meta.response_code,
+            });
+        }
+
+        if let BcBody::ModernMsg(ModernMsg {
+            payload:
+                Some(BcPayloads::BcXml(BcXml {
+                    email_task: Some(email_task),
+                    ..
+                })),
+            ..
+        }) = msg.body
+        {
+            Ok(email_task)
+        } else {
+            Err(Error::UnintelligibleReply {
+                reply: std::sync::Arc::new(Box::new(msg)),
+                why: "Expected EmailTask xml but it was not recieved",
+            })
+        }
+    }
+
+    /// Send an [EmailTask] xml
+    async fn set_email_task(&self, email_task: EmailTask) -> Result<()> {
+        self.has_ability_rw("email").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_set = connection.subscribe(MSG_ID_SET_EMAIL_TASK, msg_num).await?;
+        let set = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_SET_EMAIL_TASK,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: Some(Extension {
+                    channel_id: Some(self.channel_id),
+                    ..Default::default()
+                }),
+                payload: Some(BcPayloads::BcXml(BcXml {
+                    email_task: Some(email_task),
+                    ..Default::default()
+                })),
+            }),
+        };
+
+        sub_set.send(set).await?;
+        if let Ok(reply) =
+            tokio::time::timeout(tokio::time::Duration::from_millis(500), sub_set.recv()).await
+        {
+            let msg = reply?;
+
+            if let BcMeta {
+                response_code: 200, ..
+            } = msg.meta
+            {
+                Ok(())
+            } else {
+                Err(Error::UnintelligibleReply {
+                    reply: std::sync::Arc::new(Box::new(msg)),
+                    why: "The camera did not except the EmailTask xml",
+                })
+            }
+        } else {
+            // Some cameras seem to just not send a reply on success, so after 500ms we return Ok
+            Ok(())
+        }
+    }
+
+    /// Get the email alert settings
+    pub async fn get_email_config(&self) -> Result<EmailConfig> {
+        let email = self.get_email().await?;
+        let email_task = self.get_email_task().await?;
+        Ok(EmailConfig {
+            smtp_host: email.smtp_server,
+            smtp_port: email.smtp_port,
+            tls_mode: if email.ssl == 0 {
+                EmailTlsMode::None
+            } else {
+                EmailTlsMode::Tls
+            },
+            username: email.user_name,
+            password: None,
+            sender: email.sender_address.filter(|sender| !sender.is_empty()),
+            recipients: IntoIterator::into_iter([email.address1, email.address2, email.address3])
+                .flatten()
+                .filter(|address| !address.is_empty())
+                .collect(),
+            enabled: email_task.enable == 1,
+            schedule: email_task.schedule.map(|schedule| schedule.table),
+        })
+    }
+
+    /// Set the email alert settings
+    ///
+    /// The current settings are read first so that the password and schedule
+    /// can be left unchanged. Everything is validated and read before the
+    /// first change is sent so that a bad config changes nothing
+    pub async fn set_email_config(&self, config: &EmailConfig) -> Result<()> {
+        config.validate()?;
+        let mut email = self.get_email().await?;
+        let mut email_task = self.get_email_task().await?;
+
+        email.smtp_server.clone_from(&config.smtp_host);
+        email.smtp_port = config.smtp_port;
+        email.ssl = match config.tls_mode {
+            EmailTlsMode::None => 0,
+            EmailTlsMode::Tls => 1,
+        };
+        email.user_name.clone_from(&config.username);
+        if config.password.is_some() {
+            email.password.clone_from(&config.password);
+        }
+        email.sender_address.clone_from(&config.sender);
+        let mut recipients = config.recipients.iter().cloned();
+        email.address1 = Some(recipients.next().unwrap_or_default());
+        email.address2 = Some(recipients.next().unwrap_or_default());
+        email.address3 = Some(recipients.next().unwrap_or_default());
+
+        email_task.enable = config.enabled as u8;
+        if let Some(table) = config.schedule.as_ref() {
+            email_task.schedule = Some(EmailSchedule {
+                table: table.clone(),
+            });
+        }
+
+        self.set_email(email).await?;
+        self.set_email_task(email_task).await
+    }
+
+    /// Ask the camera to send a test email using its current settings
+    pub async fn test_email(&self) -> Result<()> {
+        let email = self.get_email().await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_test = connection.subscribe(MSG_ID_TEST_EMAIL, msg_num).await?;
+        let test = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_TEST_EMAIL,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: None,
+                payload: Some(BcPayloads::BcXml(BcXml {
+                    email: Some(email),
+                    ..Default::default()
+                })),
+            }),
+        };
+
+        sub_test.send(test).await?;
+        // The camera only replies once it has talked to the SMTP server
+        let msg = tokio::time::timeout(tokio::time::Duration::from_secs(30), sub_test.recv())
+            .await
+            .map_err(|_| Error::Other("Timed out waiting for the test email to send"))??;
+        if msg.meta.response_code != 200 {
+            return Err(Error::CameraServiceUnavailable {
+                id: msg.meta.msg_id,
+                code: msg.meta.response_code,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> EmailConfig {
+        EmailConfig {
+            smtp_host: "smtp.example.com".to_string(),
+            smtp_port: 465,
+            tls_mode: EmailTlsMode::Tls,
+            username: "user".to_string(),
+            password: None,
+            sender: None,
+            recipients: vec!["a@example.com".to_string()],
+            enabled: true,
+            schedule: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_recipients() {
+        let mut config = config();
+        config.recipients = vec!["a@example.com".to_string(); MAX_EMAIL_RECIPIENTS];
+        assert!(config.validate().is_ok());
+        config.recipients.push("d@example.com".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_schedule() {
+        let mut config = config();
+        config.schedule = Some("01".repeat(84));
+        assert!(config.validate().is_ok());
+        config.schedule = Some("1".repeat(167));
+        assert!(config.validate().is_err());
+        config.schedule = Some("2".repeat(168));
+        assert!(config.validate().is_err());
+    }
+}
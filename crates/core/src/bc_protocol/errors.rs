@@ -195,6 +195,26 @@ pub enum Error {
     #[error("Thread panicked")]
     JoinError(#[from] std::sync::Arc<tokio::task::JoinError>),
 
+    /// Raised when the camera does not report that it supports a feature
+    #[error("Camera does not support this feature: {feature}")]
+    UnsupportedFeature {
+        /// Name of the feature
+        feature: &'static str,
+    },
+
+    /// Raised when a stream is set to a resolution the camera does not support
+    #[error("Resolution {}x{} is not supported, supported resolutions are: {}", requested.0, requested.1, supported.iter().map(|(w, h)| format!("{w}x{h}")).collect::<Vec<_>>().join(", "))]
+    UnsupportedResolution {
+        /// The resolution that was asked for
+        requested: (u32, u32),
+        /// The resolutions that the camera reports
+        supported: Vec<(u32, u32)>,
+    },
+
+    /// Raised when the http proxy will not open a tunnel to the camera
+    #[error("Proxy refused the connection: {0}")]
+    ProxyRefused(String),
+
     /// A generic catch all error
     #[error("Other error: {0}")]
     Other(&'static str),
@@ -113,6 +113,8 @@ impl BcCamera {
     /// This is for the RED IR lights that can come on automaitcally
     /// during low light.
     pub async fn irled_light_set(&self, state: LightState) -> Result<()> {
+        self.require_capability("ir lights", |c| c.has_ir_lights)
+            .await?;
         let mut led_state = self.get_ledstate().await?;
         led_state.state = match state {
             LightState::On => "open".to_string(),
@@ -167,4 +167,62 @@ impl BcCamera {
         self.polulate_abilities().await?;
         Ok(device_info)
     }
+
+    /// Completes when the camera starts rejecting commands because the
+    /// login session has expired
+    ///
+    /// Some cameras end the session after a while. Call [`BcCamera::login_with_maxenc`]
+    /// again to continue on the same connection
+    pub async fn session_expired(&self) {
+        self.get_connection().session_expired().await;
+        self.logged_in.store(false, Ordering::Relaxed);
+    }
+}
+
+/// The first message of the login
+///
+/// It asks the camera for the nonce that the credentials are hashed with.
+/// Sent on its own it checks whether a port talks the BC protocol without
+/// logging in
+pub fn login_challenge_request() -> Vec<u8> {
+    let legacy_login = Bc {
+        meta: BcMeta {
+            msg_id: MSG_ID_LOGIN,
+            channel_id: 0,
+            msg_num: 0,
+            stream_type: 0,
+            response_code: 0xdc12,
+            class: 0x6514,
+        },
+        body: BcBody::LegacyMsg(LegacyMsg::LoginUpgrade),
+    };
+    legacy_login
+        .serialize(vec![], &EncryptionProtocol::Unencrypted)
+        .expect("A header only message always serializes")
+}
+
+/// Whether `buf` starts with the header of a reply to [`login_challenge_request`]
+///
+/// Only the first 8 bytes are checked
+pub fn is_login_challenge_reply(buf: &[u8]) -> bool {
+    if buf.len() < 8 {
+        return false;
+    }
+    let magic = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+    let msg_id = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
+    (magic == MAGIC_HEADER || magic == MAGIC_HEADER_REV) && msg_id == MSG_ID_LOGIN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login_challenge_header() {
+        let request = login_challenge_request();
+        assert_eq!(request.len(), 20);
+        assert!(is_login_challenge_reply(&request));
+        assert!(!is_login_challenge_reply(b"HTTP/1.1 400 Bad Request\r\n"));
+        assert!(!is_login_challenge_reply(&request[..4]));
+    }
 }
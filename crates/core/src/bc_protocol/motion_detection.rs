@@ -0,0 +1,362 @@
+use super::{BcCamera, Error, Result};
+use crate::bc::{model::*, xml::*};
+use serde::{Deserialize, Serialize};
+
+/// A rectangle on the motion detection grid, in grid cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Rect {
+    /// Column of the left edge
+    pub x: u32,
+    /// Row of the top edge
+    pub y: u32,
+    /// Number of columns covered
+    pub width: u32,
+    /// Number of rows covered
+    pub height: u32,
+}
+
+/// An area of the motion detection grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MotionRegion {
+    /// The area covered
+    pub rect: Rect,
+    /// If motion is detected in this area. Disabled regions are
+    /// applied after enabled ones so they can cut holes out of them
+    pub enabled: bool,
+}
+
+/// The motion detection settings of the camera
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct MotionDetectionConfig {
+    /// If motion detection is on
+    pub enabled: bool,
+    /// Sensitivity from `0` to `100`
+    pub sensitivity: u8,
+    /// The areas where motion is detected
+    ///
+    /// When reading from the camera these are the enabled areas of the grid.
+    /// When setting, an empty list leaves the areas as they are
+    pub regions: Vec<MotionRegion>,
+}
+
+impl BcCamera {
+    /// Get the [Md] xml
+    async fn get_md(&self) -> Result<Md> {
+        self.has_ability_ro("motion").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_get = connection.subscribe(MSG_ID_GET_MD_ALARM, msg_num).await?;
+        let get = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_GET_MD_ALARM,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: Some(Extension {
+                    channel_id: Some(self.channel_id),
+                    ..Default::default()
+                }),
+                payload: None,
+            }),
+        };
+
+        sub_get.send(get).await?;
+        let msg = sub_get.recv().await?;
+        if msg.meta.response_code != 200 {
+            return Err(Error::CameraServiceUnavailable {
+                id: msg.meta.msg_id,
+                code: msg.meta.response_code,
+            });
+        }
+
+        if let BcBody::ModernMsg(ModernMsg {
+            payload: Some(BcPayloads::BcXml(BcXml { md: Some(md), .. })),
+            ..
+        }) = msg.body
+        {
+            Ok(md)
+        } else {
+            Err(Error::UnintelligibleReply {
+                reply: std::sync::Arc::new(Box::new(msg)),
+                why: "Expected MD xml but it was not recieved",
+            })
+        }
+    }
+
+    /// Send a [Md] xml
+    async fn set_md(&self, md: Md) -> Result<()> {
+        self.has_ability_rw("motion").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_set = connection.subscribe(MSG_ID_SET_MD_ALARM, msg_num).await?;
+        let set = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_SET_MD_ALARM,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: Some(Extension {
+                    channel_id: Some(self.channel_id),
+                    ..Default::default()
+                }),
+                payload: Some(BcPayloads::BcXml(BcXml {
+                    md: Some(md),
+                    ..Default::default()
+                })),
+            }),
+        };
+
+        sub_set.send(set).await?;
+        if let Ok(reply) =
+            tokio::time::timeout(tokio::time::Duration::from_millis(500), sub_set.recv()).await
+        {
+            let msg = reply?;
+
+            if let BcMeta {
+                response_code: 200, ..
+            } = msg.meta
+            {
+                Ok(())
+            } else {
+                Err(Error::UnintelligibleReply {
+                    reply: std::sync::Arc::new(Box::new(msg)),
+                    why: "The camera did not except the MD xml",
+                })
+            }
+        } else {
+            // Some cameras seem to just not send a reply on success, so after 500ms we return Ok
+            Ok(())
+        }
+    }
+
+    /// Get the motion detection settings
+    pub async fn get_motion_detection_config(&self) -> Result<MotionDetectionConfig> {
+        let md = self.get_md().await?;
+        Ok(MotionDetectionConfig {
+            enabled: md.enable == 1,
+            sensitivity: md.sensitivity.unwrap_or_default(),
+            regions: md.scope.as_ref().map(scope_to_regions).unwrap_or_default(),
+        })
+    }
+
+    /// Get the size of the motion detection grid as `(columns, rows)`
+    pub async fn get_motion_grid_size(&self) -> Result<(u32, u32)> {
+        let md = self.get_md().await?;
+        let scope = md
+            .scope
+            .ok_or(Error::Other("Camera did not report its motion grid size"))?;
+        Ok((scope.cols, scope.rows))
+    }
+
+    /// Set the motion detection settings
+    ///
+    /// The current settings are read first so that the grid size is known
+    pub async fn set_motion_detection_config(&self, config: &MotionDetectionConfig) -> Result<()> {
+        let mut md = self.get_md().await?;
+        md.enable = config.enabled as u8;
+        md.sensitivity = Some(config.sensitivity.min(100));
+        if !config.regions.is_empty() {
+            let scope = md
+                .scope
+                .as_mut()
+                .ok_or(Error::Other("Camera did not report its motion grid size"))?;
+            scope.table = regions_to_table(scope.cols, scope.rows, &config.regions);
+        }
+        self.set_md(md).await
+    }
+}
+
+/// Build the grid table from a list of regions
+fn regions_to_table(cols: u32, rows: u32, regions: &[MotionRegion]) -> String {
+    let mut grid = vec![false; (cols * rows) as usize];
+    let enabled = regions.iter().filter(|region| region.enabled);
+    let disabled = regions.iter().filter(|region| !region.enabled);
+    for region in enabled.chain(disabled) {
+        let rect = region.rect;
+        for y in rect.y..(rect.y.saturating_add(rect.height)).min(rows) {
+            for x in rect.x..(rect.x.saturating_add(rect.width)).min(cols) {
+                grid[(y * cols + x) as usize] = region.enabled;
+            }
+        }
+    }
+    grid.iter().map(|&on| if on { '1' } else { '0' }).collect()
+}
+
+/// Put a list of regions into the form the camera reports them in
+///
+/// The camera only keeps the grid, so overlapping and disabled regions are
+/// flattened and then split into enabled rectangles the same way as
+/// [`BcCamera::get_motion_detection_config`] does
+pub fn normalize_regions(cols: u32, rows: u32, regions: &[MotionRegion]) -> Vec<MotionRegion> {
+    let grid = regions_to_table(cols, rows, regions)
+        .chars()
+        .map(|c| c == '1')
+        .collect::<Vec<_>>();
+    regions_from_grid(cols, rows, &grid)
+}
+
+/// Split the enabled cells of the grid into rectangles
+fn scope_to_regions(scope: &MdScope) -> Vec<MotionRegion> {
+    let grid = scope.table.chars().map(|c| c == '1').collect::<Vec<_>>();
+    regions_from_grid(scope.cols, scope.rows, &grid)
+}
+
+/// Split a grid of cells, row by row with `true` for detect, into enabled rectangles
+///
+/// Cells missing from the end of `grid` are taken as `false`
+pub fn regions_from_grid(cols: u32, rows: u32, grid: &[bool]) -> Vec<MotionRegion> {
+    let (cols, rows) = (cols as usize, rows as usize);
+    let mut grid = grid
+        .iter()
+        .copied()
+        .chain(std::iter::repeat(false))
+        .take(cols * rows)
+        .collect::<Vec<_>>();
+
+    let mut regions = vec![];
+    for y in 0..rows {
+        for x in 0..cols {
+            if !grid[y * cols + x] {
+                continue;
+            }
+            // Grow right then down as far as the cells are all enabled
+            let width = (x..cols).take_while(|&x| grid[y * cols + x]).count();
+            let height = (y..rows)
+                .take_while(|&y| (x..x + width).all(|x| grid[y * cols + x]))
+                .count();
+            for cell_y in y..y + height {
+                for cell_x in x..x + width {
+                    grid[cell_y * cols + cell_x] = false;
+                }
+            }
+            regions.push(MotionRegion {
+                rect: Rect {
+                    x: x as u32,
+                    y: y as u32,
+                    width: width as u32,
+                    height: height as u32,
+                },
+                enabled: true,
+            });
+        }
+    }
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_motion_regions_roundtrip() {
+        let regions = vec![
+            MotionRegion {
+                rect: Rect {
+                    x: 0,
+                    y: 0,
+                    width: 4,
+                    height: 3,
+                },
+                enabled: true,
+            },
+            MotionRegion {
+                rect: Rect {
+                    x: 2,
+                    y: 0,
+                    width: 2,
+                    height: 1,
+                },
+                enabled: false,
+            },
+        ];
+        let table = regions_to_table(4, 3, &regions);
+        assert_eq!(table, "110011111111");
+
+        let scope = MdScope {
+            cols: 4,
+            rows: 3,
+            table,
+        };
+        let read = scope_to_regions(&scope);
+        assert_eq!(read.len(), 2);
+        assert_eq!(
+            read[0].rect,
+            Rect {
+                x: 0,
+                y: 0,
+                width: 2,
+                height: 3
+            }
+        );
+        assert_eq!(
+            read[1].rect,
+            Rect {
+                x: 2,
+                y: 1,
+                width: 2,
+                height: 2
+            }
+        );
+        assert_eq!(regions_to_table(4, 3, &read), scope.table);
+    }
+
+    #[test]
+    fn test_normalize_regions() {
+        let regions = vec![
+            MotionRegion {
+                rect: Rect {
+                    x: 0,
+                    y: 0,
+                    width: 4,
+                    height: 3,
+                },
+                enabled: true,
+            },
+            MotionRegion {
+                rect: Rect {
+                    x: 2,
+                    y: 0,
+                    width: 2,
+                    height: 1,
+                },
+                enabled: false,
+            },
+        ];
+        let normalized = normalize_regions(4, 3, &regions);
+        let scope = MdScope {
+            cols: 4,
+            rows: 3,
+            table: regions_to_table(4, 3, &regions),
+        };
+        assert_eq!(normalized, scope_to_regions(&scope));
+        assert_eq!(normalize_regions(4, 3, &normalized), normalized);
+
+        // Regions past the edge of the grid are clipped to it
+        let outside = vec![MotionRegion {
+            rect: Rect {
+                x: 2,
+                y: 2,
+                width: 10,
+                height: 10,
+            },
+            enabled: true,
+        }];
+        assert_eq!(
+            normalize_regions(4, 3, &outside)[0].rect,
+            Rect {
+                x: 2,
+                y: 2,
+                width: 2,
+                height: 1
+            }
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use super::{BcCamera, Error, Result};
+use crate::bc::{model::*, xml::*};
+use serde::{Deserialize, Serialize};
+
+/// The settings the camera uses to sync its clock over NTP
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct NtpConfig {
+    /// If the camera syncs its clock from the server
+    pub enabled: bool,
+    /// Hostname of the NTP server
+    pub server: String,
+    /// Port of the NTP server
+    pub port: u16,
+    /// Minutes between each sync
+    pub interval_mins: u32,
+}
+
+impl BcCamera {
+    /// Get the [Ntp] xml
+    async fn get_ntp(&self) -> Result<Ntp> {
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_get = connection.subscribe(MSG_ID_GET_NTP, msg_num).await?;
+        let get = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_GET_NTP,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: None,
+                payload: None,
+            }),
+        };
+
+        sub_get.send(get).await?;
+        let msg = sub_get.recv().await?;
+        if msg.meta.response_code != 200 {
+            return Err(Error::CameraServiceUnavailable {
+                id: msg.meta.msg_id,
+                code: msg.meta.response_code,
+            });
+        }
+
+        if let BcBody::ModernMsg(ModernMsg {
+            payload: Some(BcPayloads::BcXml(BcXml { ntp: Some(ntp), .. })),
+            ..
+        }) = msg.body
+        {
+            Ok(ntp)
+        } else {
+            Err(Error::UnintelligibleReply {
+                reply: std::sync::Arc::new(Box::new(msg)),
+                why: "Expected Ntp xml but it was not recieved",
+            })
+        }
+    }
+
+    /// Send an [Ntp] xml
+    async fn set_ntp(&self, ntp: Ntp) -> Result<()> {
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_set = connection.subscribe(MSG_ID_SET_NTP, msg_num).await?;
+        let set = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_SET_NTP,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: None,
+                payload: Some(BcPayloads::BcXml(BcXml {
+                    ntp: Some(ntp),
+                    ..Default::default()
+                })),
+            }),
+        };
+
+        sub_set.send(set).await?;
+        if let Ok(reply) =
+            tokio::time::timeout(tokio::time::Duration::from_millis(500), sub_set.recv()).await
+        {
+            let msg = reply?;
+
+            if let BcMeta {
+                response_code: 200, ..
+            } = msg.meta
+            {
+                Ok(())
+            } else {
+                Err(Error::UnintelligibleReply {
+                    reply: std::sync::Arc::new(Box::new(msg)),
+                    why: "The camera did not except the Ntp xml",
+                })
+            }
+        } else {
+            // Some cameras seem to just not send a reply on success, so after 500ms we return Ok
+            Ok(())
+        }
+    }
+
+    /// Get the NTP settings
+    pub async fn get_ntp_config(&self) -> Result<NtpConfig> {
+        let ntp = self.get_ntp().await?;
+        Ok(NtpConfig {
+            enabled: ntp.enable == 1,
+            server: ntp.server,
+            port: ntp.port,
+            interval_mins: ntp.interval,
+        })
+    }
+
+    /// Set the NTP settings
+    pub async fn set_ntp_config(&self, config: &NtpConfig) -> Result<()> {
+        let mut ntp = self.get_ntp().await?;
+        ntp.enable = config.enabled as u8;
+        ntp.server.clone_from(&config.server);
+        ntp.port = config.port;
+        ntp.interval = config.interval_mins;
+        self.set_ntp(ntp).await
+    }
+}
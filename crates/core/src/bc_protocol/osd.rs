@@ -0,0 +1,179 @@
+use super::{BcCamera, Error, Result};
+use crate::bc::{model::*, xml::*};
+use serde::{Deserialize, Serialize};
+
+/// The position of an OSD label in the camera's display coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct OsdPosition {
+    /// X coordinate of the top left of the label
+    pub x: u32,
+    /// Y coordinate of the top left of the label
+    pub y: u32,
+}
+
+/// The on screen display settings that the camera burns into the stream
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct OsdConfig {
+    /// If the date/time is drawn
+    pub timestamp_enabled: bool,
+    /// Order of the date fields. Known values are `"DMY"`, `"MDY"` and `"YMD"`
+    pub timestamp_format: String,
+    /// If the camera name is drawn
+    pub camera_name_enabled: bool,
+    /// The camera name to draw
+    pub camera_name: String,
+    /// Where the camera name is drawn
+    pub position: OsdPosition,
+}
+
+impl BcCamera {
+    /// Get the [OsdChannelName] and [OsdDatetime] xml
+    async fn get_osd(&self) -> Result<(OsdChannelName, OsdDatetime)> {
+        self.has_ability_ro("osd").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_get = connection.subscribe(MSG_ID_GET_OSD, msg_num).await?;
+        let get = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_GET_OSD,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: Some(Extension {
+                    channel_id: Some(self.channel_id),
+                    ..Default::default()
+                }),
+                payload: None,
+            }),
+        };
+
+        sub_get.send(get).await?;
+        let msg = sub_get.recv().await?;
+        if msg.meta.response_code != 200 {
+            return Err(Error::CameraServiceUnavailable {
+                id: msg.meta.msg_id,
+                code: msg.meta.response_code,
+            });
+        }
+
+        if let BcBody::ModernMsg(ModernMsg {
+            payload:
+                Some(BcPayloads::BcXml(BcXml {
+                    osd_channel_name: Some(osd_channel_name),
+                    osd_datetime: Some(osd_datetime),
+                    ..
+                })),
+            ..
+        }) = msg.body
+        {
+            Ok((osd_channel_name, osd_datetime))
+        } else {
+            Err(Error::UnintelligibleReply {
+                reply: std::sync::Arc::new(Box::new(msg)),
+                why: "Expected OsdChannelName and OsdDatetime xml but it was not recieved",
+            })
+        }
+    }
+
+    /// Set the [OsdChannelName] and [OsdDatetime] xml
+    async fn set_osd(
+        &self,
+        osd_channel_name: OsdChannelName,
+        osd_datetime: OsdDatetime,
+    ) -> Result<()> {
+        self.has_ability_rw("osd").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_set = connection.subscribe(MSG_ID_SET_OSD, msg_num).await?;
+        let set = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_SET_OSD,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: Some(Extension {
+                    channel_id: Some(self.channel_id),
+                    ..Default::default()
+                }),
+                payload: Some(BcPayloads::BcXml(BcXml {
+                    osd_channel_name: Some(osd_channel_name),
+                    osd_datetime: Some(osd_datetime),
+                    ..Default::default()
+                })),
+            }),
+        };
+
+        sub_set.send(set).await?;
+        if let Ok(reply) =
+            tokio::time::timeout(tokio::time::Duration::from_millis(500), sub_set.recv()).await
+        {
+            let msg = reply?;
+
+            if let BcMeta {
+                response_code: 200, ..
+            } = msg.meta
+            {
+                Ok(())
+            } else {
+                Err(Error::UnintelligibleReply {
+                    reply: std::sync::Arc::new(Box::new(msg)),
+                    why: "The camera did not except the OSD xml",
+                })
+            }
+        } else {
+            // Some cameras seem to just not send a reply on success, so after 500ms we return Ok
+            Ok(())
+        }
+    }
+
+    /// Get the current on screen display settings
+    pub async fn get_osd_config(&self) -> Result<OsdConfig> {
+        let (osd_channel_name, osd_datetime) = self.get_osd().await?;
+        let general = self.get_general().await?;
+
+        Ok(OsdConfig {
+            timestamp_enabled: osd_datetime.enable == 1,
+            timestamp_format: general.osd_format.unwrap_or_default(),
+            camera_name_enabled: osd_channel_name.enable == 1,
+            camera_name: osd_channel_name.name,
+            position: OsdPosition {
+                x: osd_channel_name.top_left_x.unwrap_or_default(),
+                y: osd_channel_name.top_left_y.unwrap_or_default(),
+            },
+        })
+    }
+
+    /// Set the on screen display settings
+    ///
+    /// The current settings are read first so that values not covered by
+    /// [OsdConfig] (such as the watermark) are preserved
+    pub async fn set_osd_config(&self, config: &OsdConfig) -> Result<()> {
+        let (mut osd_channel_name, mut osd_datetime) = self.get_osd().await?;
+
+        osd_channel_name.name.clone_from(&config.camera_name);
+        osd_channel_name.enable = config.camera_name_enabled as u8;
+        osd_channel_name.top_left_x = Some(config.position.x);
+        osd_channel_name.top_left_y = Some(config.position.y);
+        osd_datetime.enable = config.timestamp_enabled as u8;
+        self.set_osd(osd_channel_name, osd_datetime).await?;
+
+        let general = self.get_general().await?;
+        if general.osd_format.as_deref() != Some(config.timestamp_format.as_str()) {
+            self.set_general(SystemGeneral {
+                version: xml_ver(),
+                osd_format: Some(config.timestamp_format.clone()),
+                ..Default::default()
+            })
+            .await?;
+        }
+        Ok(())
+    }
+}
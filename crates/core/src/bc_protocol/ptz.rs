@@ -16,10 +16,20 @@ pub enum Direction {
     Stop,
 }
 
+/// A preset position that is saved on the camera
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PtzPresetInfo {
+    /// The ID used to move to the preset
+    pub id: u8,
+    /// The name given when the preset was saved
+    pub name: String,
+}
+
 impl BcCamera {
     /// Send a PTZ message to the camera
     pub async fn send_ptz(&self, direction: Direction, amount: f32) -> Result<()> {
         self.has_ability_rw("control").await?;
+        self.require_capability("ptz", |c| c.has_ptz).await?;
         let connection = self.get_connection();
         let msg_num = self.new_message_num();
         let mut sub_set = connection.subscribe(MSG_ID_PTZ_CONTROL, msg_num).await?;
@@ -77,6 +87,7 @@ impl BcCamera {
     /// Get the [PtzPreset] XML which contains the list of the preset positions known to the camera
     pub async fn get_ptz_preset(&self) -> Result<PtzPreset> {
         self.has_ability_rw("control").await?;
+        self.require_capability("ptz", |c| c.has_ptz).await?;
         let connection = self.get_connection();
         let msg_num = self.new_message_num();
         let mut sub_set = connection.subscribe(MSG_ID_GET_PTZ_PRESET, msg_num).await?;
@@ -120,11 +131,29 @@ impl BcCamera {
         }
     }
 
+    /// Get the preset positions that are saved on the camera ordered by their ID
+    pub async fn get_ptz_presets(&self) -> Result<Vec<PtzPresetInfo>> {
+        let mut presets = self
+            .get_ptz_preset()
+            .await?
+            .preset_list
+            .preset
+            .into_iter()
+            .map(|preset| PtzPresetInfo {
+                id: preset.id,
+                name: preset.name.unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        presets.sort_by_key(|preset| preset.id);
+        Ok(presets)
+    }
+
     /// Set a PTZ preset.
     ///
     /// The current position will be saved as a preset with the given [preset_id] and [name]
     pub async fn set_ptz_preset(&self, preset_id: u8, name: String) -> Result<()> {
         self.has_ability_rw("control").await?;
+        self.require_capability("ptz", |c| c.has_ptz).await?;
         let connection = self.get_connection();
         let msg_num = self.new_message_num();
         let mut sub_set = connection
@@ -182,6 +211,7 @@ impl BcCamera {
     /// The camera will attempt to move to the preset with the given ID.
     pub async fn moveto_ptz_preset(&self, preset_id: u8) -> Result<()> {
         self.has_ability_rw("control").await?;
+        self.require_capability("ptz", |c| c.has_ptz).await?;
         let connection = self.get_connection();
         let msg_num = self.new_message_num();
         let mut sub_set = connection
@@ -243,6 +273,7 @@ impl BcCamera {
         let zoom_pos = zoom_pos.clamp(current.zoom.min_pos, current.zoom.max_pos);
 
         self.has_ability_rw("control").await?;
+        self.require_capability("ptz", |c| c.has_ptz).await?;
         let connection = self.get_connection();
         let msg_num = self.new_message_num();
         let mut sub_set = connection.subscribe(MSG_ID_SET_ZOOM_FOCUS, msg_num).await?;
@@ -293,6 +324,7 @@ impl BcCamera {
     /// Get the zoom xml, that has current min and max zoom values
     pub async fn get_zoom(&self) -> Result<PtzZoomFocus> {
         self.has_ability_ro("control").await?;
+        self.require_capability("ptz", |c| c.has_ptz).await?;
         let connection = self.get_connection();
         let msg_num = self.new_message_num();
         let mut sub_get = connection.subscribe(MSG_ID_GET_ZOOM_FOCUS, msg_num).await?;
@@ -93,6 +93,17 @@ impl Drop for StreamData {
 
 impl BcCamera {
     ///
+    /// Starts the video stream of a channel of this connection
+    ///
+    /// NVRs send the stream of every camera over the one connection, each on
+    /// its own channel. This is the same as [`BcCamera::start_video`] with the
+    /// default buffer on a [`BcCamera::with_channel`] handle
+    pub async fn start_stream(&self, channel_id: u8, stream: StreamKind) -> Result<StreamData> {
+        self.with_channel(channel_id)
+            .start_video(stream, 0, false)
+            .await
+    }
+
     /// Starts the video stream
     ///
     /// The returned object manages the data stream, when it is dropped
@@ -0,0 +1,443 @@
+use super::{BcCamera, Error, Result, StreamKind};
+use crate::bc::{model::*, xml::*};
+use crate::bcmedia::model::VideoType;
+use serde::{Deserialize, Serialize};
+
+/// Broad encoder quality levels
+///
+/// These map onto the H264 encoder profile of the camera. `VeryHigh`
+/// also sends an IFrame every second
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QualityPreset {
+    /// Baseline profile
+    #[serde(alias = "low")]
+    Low,
+    /// Main profile
+    #[serde(alias = "medium")]
+    Medium,
+    /// High profile
+    #[serde(alias = "high")]
+    High,
+    /// High profile with an IFrame every second
+    #[serde(alias = "veryhigh", alias = "very_high")]
+    VeryHigh,
+}
+
+/// The IFrame interval, in seconds, that is put back when a stream leaves [`QualityPreset::VeryHigh`]
+const DEFAULT_GOP_SECS: u32 = 2;
+
+impl QualityPreset {
+    /// Set the encoder profile and IFrame interval of the preset
+    ///
+    /// Leaving `VeryHigh` puts the IFrame interval back to
+    /// [`DEFAULT_GOP_SECS`] otherwise the one second interval would still
+    /// read back as `VeryHigh`
+    fn apply(&self, stream_compression: &mut StreamCompression) {
+        stream_compression.encoder_profile = Some(self.encoder_profile().to_string());
+        if *self == QualityPreset::VeryHigh {
+            stream_compression.gop = Some(1);
+        } else if stream_compression.gop == Some(1) {
+            stream_compression.gop = Some(DEFAULT_GOP_SECS);
+        }
+    }
+
+    fn encoder_profile(&self) -> &'static str {
+        match self {
+            QualityPreset::Low => "base",
+            QualityPreset::Medium => "main",
+            QualityPreset::High | QualityPreset::VeryHigh => "high",
+        }
+    }
+
+    fn from_stream_compression(stream_compression: &StreamCompression) -> Option<Self> {
+        match stream_compression.encoder_profile.as_deref()? {
+            "base" | "baseline" => Some(QualityPreset::Low),
+            "main" => Some(QualityPreset::Medium),
+            "high" if stream_compression.gop == Some(1) => Some(QualityPreset::VeryHigh),
+            "high" => Some(QualityPreset::High),
+            _ => None,
+        }
+    }
+}
+
+/// The settings used by the camera's encoder for one stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamQualityConfig {
+    /// Bitrate in kbps
+    pub bitrate_kbps: u32,
+    /// Frames per second
+    pub frame_rate: u8,
+    /// Width and height of the stream. `None` leaves it unchanged
+    pub resolution: Option<(u32, u32)>,
+    /// Encoder quality. `None` leaves it unchanged
+    pub quality_preset: Option<QualityPreset>,
+}
+
+/// The resolution, frame rate, bitrate and codec of one stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodingConfig {
+    /// Width and height of the stream
+    pub resolution: (u32, u32),
+    /// Frames per second
+    pub frame_rate: u8,
+    /// Bitrate in kbps
+    pub bitrate_kbps: u32,
+    /// Video codec
+    pub codec: VideoType,
+}
+
+impl BcCamera {
+    /// Get the [Compression] xml which holds the encoder settings of all streams
+    async fn get_compression(&self) -> Result<Compression> {
+        self.has_ability_ro("compress").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_get = connection
+            .subscribe(MSG_ID_GET_COMPRESSION, msg_num)
+            .await?;
+        let get = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_GET_COMPRESSION,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: Some(Extension {
+                    channel_id: Some(self.channel_id),
+                    ..Default::default()
+                }),
+                payload: None,
+            }),
+        };
+
+        sub_get.send(get).await?;
+        let msg = sub_get.recv().await?;
+        if msg.meta.response_code != 200 {
+            return Err(Error::CameraServiceUnavailable {
+                id: msg.meta.msg_id,
+                code: msg.meta.response_code,
+            });
+        }
+
+        if let BcBody::ModernMsg(ModernMsg {
+            payload:
+                Some(BcPayloads::BcXml(BcXml {
+                    compression: Some(compression),
+                    ..
+                })),
+            ..
+        }) = msg.body
+        {
+            Ok(compression)
+        } else {
+            Err(Error::UnintelligibleReply {
+                reply: std::sync::Arc::new(Box::new(msg)),
+                why: "Expected Compression xml but it was not recieved",
+            })
+        }
+    }
+
+    /// Send a [Compression] xml
+    async fn set_compression(&self, compression: Compression) -> Result<()> {
+        self.has_ability_rw("compress").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_set = connection
+            .subscribe(MSG_ID_SET_COMPRESSION, msg_num)
+            .await?;
+        let set = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_SET_COMPRESSION,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg {
+                extension: Some(Extension {
+                    channel_id: Some(self.channel_id),
+                    ..Default::default()
+                }),
+                payload: Some(BcPayloads::BcXml(BcXml {
+                    compression: Some(compression),
+                    ..Default::default()
+                })),
+            }),
+        };
+
+        sub_set.send(set).await?;
+        if let Ok(reply) =
+            tokio::time::timeout(tokio::time::Duration::from_millis(500), sub_set.recv()).await
+        {
+            let msg = reply?;
+
+            if let BcMeta {
+                response_code: 200, ..
+            } = msg.meta
+            {
+                Ok(())
+            } else {
+                Err(Error::UnintelligibleReply {
+                    reply: std::sync::Arc::new(Box::new(msg)),
+                    why: "The camera did not except the Compression xml",
+                })
+            }
+        } else {
+            // Some cameras seem to just not send a reply on success, so after 500ms we return Ok
+            Ok(())
+        }
+    }
+
+    /// Get the encoder settings of a stream
+    pub async fn get_stream_quality(&self, stream: StreamKind) -> Result<StreamQualityConfig> {
+        let mut compression = self.get_compression().await?;
+        let stream_compression = stream_compression_mut(&mut compression, stream)
+            .take()
+            .ok_or(Error::Other(
+                "Camera did not report settings for this stream",
+            ))?;
+
+        Ok(StreamQualityConfig {
+            bitrate_kbps: stream_compression.bit_rate.unwrap_or_default(),
+            frame_rate: stream_compression.frame.unwrap_or_default(),
+            resolution: stream_compression.width.zip(stream_compression.height),
+            quality_preset: QualityPreset::from_stream_compression(&stream_compression),
+        })
+    }
+
+    /// Set the encoder settings of a stream
+    ///
+    /// The current settings are read first so that the other streams and any
+    /// values not covered by [StreamQualityConfig] are preserved
+    pub async fn set_stream_quality(
+        &self,
+        stream: StreamKind,
+        config: &StreamQualityConfig,
+    ) -> Result<()> {
+        let mut compression = self.get_compression().await?;
+        let stream_compression = stream_compression_mut(&mut compression, stream)
+            .as_mut()
+            .ok_or(Error::Other(
+                "Camera did not report settings for this stream",
+            ))?;
+
+        stream_compression.bit_rate = Some(config.bitrate_kbps);
+        stream_compression.frame = Some(config.frame_rate);
+        if let Some((width, height)) = config.resolution {
+            stream_compression.width = Some(width);
+            stream_compression.height = Some(height);
+            stream_compression.resolution_name = Some(format!("{}*{}", width, height));
+        }
+        if let Some(preset) = config.quality_preset {
+            preset.apply(stream_compression);
+        }
+
+        self.set_compression(compression).await
+    }
+}
+
+impl BcCamera {
+    /// Get the number of frames between IFrames of a stream
+    ///
+    /// The camera keeps this as whole seconds so it is always a multiple of
+    /// the stream's frame rate
+    pub async fn get_gop_size(&self, stream: StreamKind) -> Result<u32> {
+        let mut compression = self.get_compression().await?;
+        let stream_compression = stream_compression_mut(&mut compression, stream)
+            .take()
+            .ok_or(Error::Other(
+                "Camera did not report settings for this stream",
+            ))?;
+
+        let gop = stream_compression
+            .gop
+            .ok_or(Error::Other("Camera did not report the stream GOP size"))?;
+        Ok(gop * stream_compression.frame.unwrap_or(1).max(1) as u32)
+    }
+
+    /// Set the number of frames between IFrames of a stream
+    ///
+    /// The camera keeps this as whole seconds so it is rounded to the nearest
+    /// second at the stream's current frame rate, with a minimum of one second
+    pub async fn set_gop_size(&self, stream: StreamKind, frames: u32) -> Result<()> {
+        let mut compression = self.get_compression().await?;
+        let stream_compression = stream_compression_mut(&mut compression, stream)
+            .as_mut()
+            .ok_or(Error::Other(
+                "Camera did not report settings for this stream",
+            ))?;
+
+        let frame_rate = stream_compression.frame.unwrap_or(1).max(1);
+        stream_compression.gop = Some(round_gop_size(frames, frame_rate) / frame_rate as u32);
+
+        self.set_compression(compression).await
+    }
+}
+
+impl BcCamera {
+    /// Get the resolutions that the camera supports for a stream
+    pub async fn get_supported_resolutions(&self, stream: StreamKind) -> Result<Vec<(u32, u32)>> {
+        let stream_name = stream.to_string();
+        let mut resolutions = self
+            .get_stream_info()
+            .await?
+            .stream_infos
+            .iter()
+            .flat_map(|info| info.encode_tables.iter())
+            .filter(|encode| encode.name == stream_name)
+            .map(|encode| (encode.resolution.width, encode.resolution.height))
+            .collect::<Vec<_>>();
+        resolutions.sort_unstable();
+        resolutions.dedup();
+        Ok(resolutions)
+    }
+
+    /// Get the resolution, frame rate, bitrate and codec of a stream
+    pub async fn get_encoding_config(&self, stream: StreamKind) -> Result<EncodingConfig> {
+        let mut compression = self.get_compression().await?;
+        let stream_compression = stream_compression_mut(&mut compression, stream)
+            .take()
+            .ok_or(Error::Other(
+                "Camera did not report settings for this stream",
+            ))?;
+
+        Ok(EncodingConfig {
+            resolution: stream_compression
+                .width
+                .zip(stream_compression.height)
+                .ok_or(Error::Other("Camera did not report the stream resolution"))?,
+            frame_rate: stream_compression.frame.unwrap_or_default(),
+            bitrate_kbps: stream_compression.bit_rate.unwrap_or_default(),
+            codec: match stream_compression.encoder_type.as_deref() {
+                Some("h265") => VideoType::H265,
+                Some("av1") => VideoType::Av1,
+                _ => VideoType::H264,
+            },
+        })
+    }
+
+    /// Set the resolution, frame rate, bitrate and codec of a stream
+    ///
+    /// The resolution must be one that the camera reports for the stream
+    /// otherwise [`Error::UnsupportedResolution`] is returned
+    pub async fn set_encoding_config(
+        &self,
+        stream: StreamKind,
+        config: &EncodingConfig,
+    ) -> Result<()> {
+        let supported = self.get_supported_resolutions(stream).await?;
+        if !supported.contains(&config.resolution) {
+            return Err(Error::UnsupportedResolution {
+                requested: config.resolution,
+                supported,
+            });
+        }
+
+        let mut compression = self.get_compression().await?;
+        let stream_compression = stream_compression_mut(&mut compression, stream)
+            .as_mut()
+            .ok_or(Error::Other(
+                "Camera did not report settings for this stream",
+            ))?;
+
+        let (width, height) = config.resolution;
+        stream_compression.width = Some(width);
+        stream_compression.height = Some(height);
+        stream_compression.resolution_name = Some(format!("{}*{}", width, height));
+        stream_compression.frame = Some(config.frame_rate);
+        stream_compression.bit_rate = Some(config.bitrate_kbps);
+        stream_compression.encoder_type = Some(
+            match config.codec {
+                VideoType::H264 => "h264",
+                VideoType::H265 => "h265",
+                VideoType::Av1 => "av1",
+            }
+            .to_string(),
+        );
+
+        self.set_compression(compression).await
+    }
+}
+
+/// The number of frames between IFrames that the camera will actually use
+///
+/// The camera keeps the GOP size in whole seconds so `frames` is rounded to the
+/// nearest second at `frame_rate`, with a minimum of one second
+pub fn round_gop_size(frames: u32, frame_rate: u8) -> u32 {
+    let frame_rate = frame_rate.max(1) as u32;
+    ((frames + frame_rate / 2) / frame_rate).max(1) * frame_rate
+}
+
+fn stream_compression_mut(
+    compression: &mut Compression,
+    stream: StreamKind,
+) -> &mut Option<StreamCompression> {
+    match stream {
+        StreamKind::Main => &mut compression.main_stream,
+        StreamKind::Sub => &mut compression.sub_stream,
+        StreamKind::Extern => &mut compression.third_stream,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn high(gop: u32) -> StreamCompression {
+        StreamCompression {
+            encoder_profile: Some("high".to_string()),
+            gop: Some(gop),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_leaving_very_high_resets_gop() {
+        let mut stream = high(1);
+        assert_eq!(
+            QualityPreset::from_stream_compression(&stream),
+            Some(QualityPreset::VeryHigh)
+        );
+        QualityPreset::High.apply(&mut stream);
+        assert_eq!(stream.gop, Some(DEFAULT_GOP_SECS));
+        assert_eq!(
+            QualityPreset::from_stream_compression(&stream),
+            Some(QualityPreset::High)
+        );
+
+        let mut stream = high(1);
+        QualityPreset::Medium.apply(&mut stream);
+        assert_eq!(stream.gop, Some(DEFAULT_GOP_SECS));
+        assert_eq!(
+            QualityPreset::from_stream_compression(&stream),
+            Some(QualityPreset::Medium)
+        );
+    }
+
+    #[test]
+    fn test_round_gop_size() {
+        assert_eq!(round_gop_size(25, 25), 25);
+        assert_eq!(round_gop_size(30, 25), 25);
+        assert_eq!(round_gop_size(38, 25), 50);
+        assert_eq!(round_gop_size(5, 25), 25);
+        assert_eq!(round_gop_size(300, 15), 300);
+        assert_eq!(round_gop_size(10, 0), 10);
+    }
+
+    #[test]
+    fn test_presets_keep_other_gops() {
+        let mut stream = high(4);
+        QualityPreset::High.apply(&mut stream);
+        assert_eq!(stream.gop, Some(4));
+        QualityPreset::VeryHigh.apply(&mut stream);
+        assert_eq!(stream.gop, Some(1));
+        assert_eq!(
+            QualityPreset::from_stream_compression(&stream),
+            Some(QualityPreset::VeryHigh)
+        );
+    }
+}
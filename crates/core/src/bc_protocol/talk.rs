@@ -58,6 +58,7 @@ impl BcCamera {
     /// Requests the [`TalkAbility`] xml
     ///
     pub async fn talk_ability(&self) -> Result<TalkAbility> {
+        self.require_capability("talk", |c| c.has_talk_back).await?;
         let connection = self.get_connection();
         let msg_num = self.new_message_num();
         let mut sub_get = connection.subscribe(MSG_ID_TALKABILITY, msg_num).await?;
@@ -117,6 +118,7 @@ impl BcCamera {
     ///
     ///
     pub async fn talk(&self, adpcm: &[u8], talk_config: TalkConfig) -> Result<()> {
+        self.require_capability("talk", |c| c.has_talk_back).await?;
         let connection = self.get_connection();
 
         let msg_num = self.new_message_num();
@@ -258,6 +260,7 @@ impl BcCamera {
     ///
     ///
     pub async fn talk_stream(&self, rx: Receiver<Vec<u8>>, talk_config: TalkConfig) -> Result<()> {
+        self.require_capability("talk", |c| c.has_talk_back).await?;
         let connection = self.get_connection();
 
         let msg_num = self.new_message_num();
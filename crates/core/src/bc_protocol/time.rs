@@ -14,56 +14,27 @@ impl BcCamera {
     /// returns either an error or an option with the offsetted date time
     ///
     pub async fn get_time(&self) -> Result<Option<OffsetDateTime>> {
-        self.has_ability_ro("general").await?;
-        let connection = self.get_connection();
-        let msg_num = self.new_message_num();
-        let mut sub_get_general = connection.subscribe(MSG_ID_GET_GENERAL, msg_num).await?;
-        let get = Bc {
-            meta: BcMeta {
-                msg_id: MSG_ID_GET_GENERAL,
-                channel_id: self.channel_id,
-                msg_num,
-                response_code: 0,
-                stream_type: 0,
-                class: 0x6414,
-            },
-            body: BcBody::ModernMsg(ModernMsg::default()),
-        };
-
-        sub_get_general.send(get).await?;
-        let msg = sub_get_general.recv().await?;
-        if msg.meta.response_code != 200 {
-            return Err(Error::CameraServiceUnavailable {
-                id: msg.meta.msg_id,
-                code: msg.meta.response_code,
-            });
-        }
-
-        if let BcBody::ModernMsg(ModernMsg {
-            payload:
-                Some(BcPayloads::BcXml(BcXml {
-                    system_general:
-                        Some(SystemGeneral {
-                            time_zone: Some(time_zone),
-                            year: Some(year),
-                            month: Some(month),
-                            day: Some(day),
-                            hour: Some(hour),
-                            minute: Some(minute),
-                            second: Some(second),
-                            ..
-                        }),
-                    ..
-                })),
+        let general = self.get_general().await?;
+        if let SystemGeneral {
+            time_zone: Some(time_zone),
+            year: Some(year),
+            month: Some(month),
+            day: Some(day),
+            hour: Some(hour),
+            minute: Some(minute),
+            second: Some(second),
             ..
-        }) = msg.body
+        } = general
         {
             let datetime =
                 match try_build_timestamp(time_zone, year, month, day, hour, minute, second) {
                     Ok(dt) => dt,
                     Err(_) => {
-                        return Err(Error::UnintelligibleReply {
-                            reply: std::sync::Arc::new(Box::new(msg)),
+                        return Err(Error::UnintelligibleXml {
+                            reply: std::sync::Arc::new(Box::new(BcXml {
+                                system_general: Some(general),
+                                ..Default::default()
+                            })),
                             why: "Could not parse date",
                         })
                     }
@@ -83,8 +54,11 @@ impl BcCamera {
                 Ok(Some(datetime))
             }
         } else {
-            Err(Error::UnintelligibleReply {
-                reply: std::sync::Arc::new(Box::new(msg)),
+            Err(Error::UnintelligibleXml {
+                reply: std::sync::Arc::new(Box::new(BcXml {
+                    system_general: Some(general),
+                    ..Default::default()
+                })),
                 why: "Reply did not contain SystemGeneral with all time fields filled out",
             })
         }
@@ -149,6 +123,121 @@ impl BcCamera {
 
         Ok(())
     }
+
+    ///
+    /// Get the UTC offset the camera shows its time in
+    ///
+    /// The camera only keeps an offset and not the name of a time zone
+    ///
+    /// # Returns
+    ///
+    /// returns the offset in seconds east of UTC so +7:00 is 25200
+    ///
+    pub async fn get_timezone(&self) -> Result<i32> {
+        let general = self.get_general().await?;
+        // Reolink uses positive seconds to indicate a negative UTC offset:
+        general.time_zone.map(|tz| -tz).ok_or(Error::TimeParse)
+    }
+
+    ///
+    /// Sets the UTC offset the camera shows its time in
+    ///
+    /// The camera's clock is kept at the same instant and only the offset
+    /// changes. If the camera has no time set it is set to the time now
+    ///
+    /// # Parameters
+    ///
+    /// * `offset` - The offset in seconds east of UTC so +7:00 is 25200
+    ///
+    pub async fn set_timezone(&self, offset: i32) -> Result<()> {
+        let offset = UtcOffset::from_whole_seconds(offset)?;
+        let now = self
+            .get_time()
+            .await?
+            .unwrap_or_else(OffsetDateTime::now_utc);
+        self.set_time(now.to_offset(offset)).await
+    }
+
+    /// Get the [SystemGeneral] xml which holds the camera time and display settings
+    pub(crate) async fn get_general(&self) -> Result<SystemGeneral> {
+        self.has_ability_ro("general").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_get_general = connection.subscribe(MSG_ID_GET_GENERAL, msg_num).await?;
+        let get = Bc {
+            meta: BcMeta {
+                msg_id: MSG_ID_GET_GENERAL,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            body: BcBody::ModernMsg(ModernMsg::default()),
+        };
+
+        sub_get_general.send(get).await?;
+        let msg = sub_get_general.recv().await?;
+        if msg.meta.response_code != 200 {
+            return Err(Error::CameraServiceUnavailable {
+                id: msg.meta.msg_id,
+                code: msg.meta.response_code,
+            });
+        }
+
+        if let BcBody::ModernMsg(ModernMsg {
+            payload:
+                Some(BcPayloads::BcXml(BcXml {
+                    system_general: Some(system_general),
+                    ..
+                })),
+            ..
+        }) = msg.body
+        {
+            Ok(system_general)
+        } else {
+            Err(Error::UnintelligibleReply {
+                reply: std::sync::Arc::new(Box::new(msg)),
+                why: "Expected SystemGeneral xml but it was not recieved",
+            })
+        }
+    }
+
+    /// Send a [SystemGeneral] xml. Only the fields that are `Some` will be changed
+    pub(crate) async fn set_general(&self, system_general: SystemGeneral) -> Result<()> {
+        self.has_ability_rw("general").await?;
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_set_general = connection.subscribe(MSG_ID_SET_GENERAL, msg_num).await?;
+        let set = Bc::new_from_xml(
+            BcMeta {
+                msg_id: MSG_ID_SET_GENERAL,
+                channel_id: self.channel_id,
+                msg_num,
+                response_code: 0,
+                stream_type: 0,
+                class: 0x6414,
+            },
+            BcXml {
+                system_general: Some(system_general),
+                ..Default::default()
+            },
+        );
+
+        sub_set_general.send(set).await?;
+        let msg = sub_set_general.recv().await?;
+        if let BcMeta {
+            response_code: 200, ..
+        } = msg.meta
+        {
+            Ok(())
+        } else {
+            Err(Error::UnintelligibleReply {
+                reply: std::sync::Arc::new(Box::new(msg)),
+                why: "The camera did not accept the SystemGeneral xml",
+            })
+        }
+    }
 }
 
 fn try_build_timestamp(
@@ -0,0 +1,149 @@
+use super::{BcCamera, Error, Result};
+use crate::bc::{model::*, xml::*};
+
+impl BcCamera {
+    /// Get the [UserList] xml
+    async fn get_user_list(&self) -> Result<UserList> {
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_get = connection.subscribe(MSG_ID_GET_USER_LIST, msg_num).await?;
+        let get = get_user_list_msg(self.channel_id, msg_num);
+
+        sub_get.send(get).await?;
+        let msg = sub_get.recv().await?;
+        if msg.meta.response_code != 200 {
+            return Err(Error::CameraServiceUnavailable {
+                id: msg.meta.msg_id,
+                code: msg.meta.response_code,
+            });
+        }
+
+        if let BcBody::ModernMsg(ModernMsg {
+            payload:
+                Some(BcPayloads::BcXml(BcXml {
+                    user_list: Some(user_list),
+                    ..
+                })),
+            ..
+        }) = msg.body
+        {
+            Ok(user_list)
+        } else {
+            Err(Error::UnintelligibleReply {
+                reply: std::sync::Arc::new(Box::new(msg)),
+                why: "Expected UserList xml but it was not recieved",
+            })
+        }
+    }
+
+    /// Change the password of one of the camera's users
+    ///
+    /// Unlike most set commands this waits for the camera's reply since a
+    /// password that does not meet the camera's rules must be reported
+    pub async fn set_password(&self, username: &str, password: &str) -> Result<()> {
+        let mut user = self
+            .get_user_list()
+            .await?
+            .user
+            .into_iter()
+            .find(|user| user.user_name == username)
+            .ok_or(Error::Other("No user with this name on the camera"))?;
+        user.password = Some(password.to_string());
+        user.login_state = None;
+        user.user_set_state = Some("modify".to_string());
+
+        let connection = self.get_connection();
+        let msg_num = self.new_message_num();
+        let mut sub_set = connection.subscribe(MSG_ID_SET_USER_LIST, msg_num).await?;
+        let set = set_user_msg(self.channel_id, msg_num, user);
+
+        sub_set.send(set).await?;
+        let msg = sub_set.recv().await?;
+        if let BcMeta {
+            response_code: 200, ..
+        } = msg.meta
+        {
+            Ok(())
+        } else {
+            Err(Error::UnintelligibleReply {
+                reply: std::sync::Arc::new(Box::new(msg)),
+                why: "The camera did not accept the new password",
+            })
+        }
+    }
+}
+
+/// The request for the [UserList] of the camera
+fn get_user_list_msg(channel_id: u8, msg_num: u16) -> Bc {
+    Bc {
+        meta: BcMeta {
+            msg_id: MSG_ID_GET_USER_LIST,
+            channel_id,
+            msg_num,
+            response_code: 0,
+            stream_type: 0,
+            class: 0x6414,
+        },
+        body: BcBody::ModernMsg(ModernMsg {
+            extension: None,
+            payload: None,
+        }),
+    }
+}
+
+/// The request that applies the `userSetState` of a single [User]
+fn set_user_msg(channel_id: u8, msg_num: u16, user: User) -> Bc {
+    Bc {
+        meta: BcMeta {
+            msg_id: MSG_ID_SET_USER_LIST,
+            channel_id,
+            msg_num,
+            response_code: 0,
+            stream_type: 0,
+            class: 0x6414,
+        },
+        body: BcBody::ModernMsg(ModernMsg {
+            extension: None,
+            payload: Some(BcPayloads::BcXml(BcXml {
+                user_list: Some(UserList {
+                    version: xml_ver(),
+                    user: vec![user],
+                }),
+                ..Default::default()
+            })),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_password_msg_ids() {
+        // 40/41 are the DDNS settings, the users are read with 58 and written with 59
+        let get = get_user_list_msg(0, 1);
+        assert_eq!(get.meta.msg_id, 58);
+
+        let user = User {
+            user_name: "admin".to_string(),
+            password: Some("secret".to_string()),
+            user_set_state: Some("modify".to_string()),
+            ..Default::default()
+        };
+        let set = set_user_msg(0, 2, user.clone());
+        assert_eq!(set.meta.msg_id, 59);
+        assert_eq!(set.meta.msg_num, 2);
+        match set.body {
+            BcBody::ModernMsg(ModernMsg {
+                payload:
+                    Some(BcPayloads::BcXml(BcXml {
+                        user_list: Some(list),
+                        ..
+                    })),
+                ..
+            }) => assert_eq!(list.user, vec![user]),
+            _ => panic!("Expected a UserList payload"),
+        }
+    }
+}
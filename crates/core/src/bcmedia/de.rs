@@ -165,7 +165,7 @@ fn take4(buf: &[u8]) -> IResult<&[u8], &str> {
 fn bcmedia_iframe(buf: &[u8]) -> IResult<&[u8], BcMediaIframe> {
     let (buf, video_type_str) = context(
         "Video Type is unrecognised in IFrame",
-        verify(take4, |x| matches!(x, "H264" | "H265")),
+        verify(take4, |x| matches!(x, "H264" | "H265" | "AV01")),
     )(buf)?;
     let (buf, payload_size) = le_u32(buf)?;
     let (buf, additional_header_size) = le_u32(buf)?;
@@ -196,6 +196,7 @@ fn bcmedia_iframe(buf: &[u8]) -> IResult<&[u8], BcMediaIframe> {
     let video_type = match video_type_str {
         "H264" => VideoType::H264,
         "H265" => VideoType::H265,
+        "AV01" => VideoType::Av1,
         _ => unreachable!(),
     };
 
@@ -214,7 +215,7 @@ fn bcmedia_iframe(buf: &[u8]) -> IResult<&[u8], BcMediaIframe> {
 fn bcmedia_pframe(buf: &[u8]) -> IResult<&[u8], BcMediaPframe> {
     let (buf, video_type_str) = context(
         "Video Type is unrecognised in PFrame",
-        verify(take4, |x| matches!(x, "H264" | "H265")),
+        verify(take4, |x| matches!(x, "H264" | "H265" | "AV01")),
     )(buf)?;
     let (buf, payload_size) = le_u32(buf)?;
     let (buf, additional_header_size) = le_u32(buf)?;
@@ -232,6 +233,7 @@ fn bcmedia_pframe(buf: &[u8]) -> IResult<&[u8], BcMediaPframe> {
     let video_type = match video_type_str {
         "H264" => VideoType::H264,
         "H265" => VideoType::H265,
+        "AV01" => VideoType::Av1,
         _ => unreachable!(),
     };
 
@@ -530,6 +532,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_av1_iframe() {
+        init();
+
+        let iframe = BcMedia::Iframe(BcMediaIframe {
+            video_type: VideoType::Av1,
+            microseconds: 1000,
+            time: Some(1700000000),
+            data: vec![0x12, 0x00, 0x0A, 0x0B, 0x00, 0x00, 0x00],
+        });
+        let sample = iframe.serialize(vec![]).unwrap();
+        let mut buf = BytesMut::from(&sample[..]);
+
+        let e = BcMedia::deserialize(&mut buf);
+        if let Ok(BcMedia::Iframe(BcMediaIframe {
+            video_type: VideoType::Av1,
+            microseconds: 1000,
+            time: Some(1700000000),
+            data: d,
+        })) = e
+        {
+            assert_eq!(d, vec![0x12, 0x00, 0x0A, 0x0B, 0x00, 0x00, 0x00]);
+        } else {
+            panic!();
+        }
+    }
+
     #[test]
     fn test_adpcm() {
         init();
@@ -3,5 +3,7 @@ pub(crate) mod codex;
 pub mod de;
 /// Structure model for BCMedia
 pub mod model;
+/// RTP packetisation of BCMedia
+pub mod rtp;
 /// Serlizer for BCMedia
 pub mod ser;
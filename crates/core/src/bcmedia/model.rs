@@ -108,18 +108,23 @@ pub(super) const MAGIC_HEADER_BCMEDIA_IFRAME: u32 = 0x63643030;
 pub(super) const MAGIC_HEADER_BCMEDIA_IFRAME_LAST: u32 = 0x63643039;
 
 /// Video Types for I/PFrame
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum VideoType {
     /// H264 video data
     H264,
     /// H265 video data
     H265,
+    /// AV1 video data
+    ///
+    /// No camera sends this yet. It is expected to be marked `AV01` and to
+    /// be a low overhead OBU stream
+    Av1,
 }
 
 /// This is a BcMedia video IFrame.
 #[derive(Clone)]
 pub struct BcMediaIframe {
-    /// "H264", or "H265"
+    /// "H264", "H265" or "AV01"
     pub video_type: VideoType,
     // Size of payload after header in bytes
     // pub payload_size: u32,
@@ -161,7 +166,7 @@ pub(super) const MAGIC_HEADER_BCMEDIA_PFRAME_LAST: u32 = 0x63643139;
 /// This is a BcMedia video PFrame.
 #[derive(Clone)]
 pub struct BcMediaPframe {
-    /// "H264", or "H265"
+    /// "H264", "H265" or "AV01"
     pub video_type: VideoType,
     // Size of payload after header in bytes
     // pub payload_size: u32,
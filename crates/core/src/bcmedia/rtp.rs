@@ -0,0 +1,281 @@
+// Packetisation of BcMedia video into RTP
+//
+// H264 follows RFC 6184 and H265 follows RFC 7798. NAL units that fit
+// within [`RTP_MAX_PAYLOAD`] are sent as single NAL unit packets, larger
+// ones are split into fragmentation units (FU-A for H264, FU for H265)
+use super::model::*;
+use crate::Error;
+
+/// A single serialised RTP packet including its 12 byte header
+pub type RtpPacket = Vec<u8>;
+
+/// The largest RTP payload that will be produced. This keeps each packet
+/// under a typical 1500 byte ethernet MTU once IP/UDP/RTP headers are added
+pub const RTP_MAX_PAYLOAD: usize = 1400;
+
+/// Clock rate of the RTP timestamp for video as required by RFC 6184/7798
+const VIDEO_CLOCK_RATE: u64 = 90_000;
+
+const RTP_HEADER_LEN: usize = 12;
+
+const H264_NAL_FU_A: u8 = 28;
+const H265_NAL_FU: u8 = 49;
+
+impl BcMedia {
+    /// Encode this media packet as a series of RTP packets
+    ///
+    /// `sequence` is the RTP sequence number of the first packet and is
+    /// advanced by one for every packet produced so that it can be passed
+    /// into the next call. The marker bit is set on the last packet of each
+    /// frame.
+    ///
+    /// Info packets carry no media and produce no RTP packets. Audio and
+    /// AV1 are not currently supported and return an error.
+    pub fn encode_as_rtp(
+        &self,
+        sequence: &mut u16,
+        ssrc: u32,
+        payload_type: u8,
+    ) -> Result<Vec<RtpPacket>, Error> {
+        let (video_type, microseconds, data) = match self {
+            BcMedia::Iframe(BcMediaIframe {
+                video_type: VideoType::Av1,
+                ..
+            })
+            | BcMedia::Pframe(BcMediaPframe {
+                video_type: VideoType::Av1,
+                ..
+            }) => return Err(Error::Other("RTP encoding of AV1 is not supported")),
+            BcMedia::InfoV1(_) | BcMedia::InfoV2(_) => return Ok(vec![]),
+            BcMedia::Iframe(BcMediaIframe {
+                video_type,
+                microseconds,
+                data,
+                ..
+            }) => (video_type, *microseconds, data),
+            BcMedia::Pframe(BcMediaPframe {
+                video_type,
+                microseconds,
+                data,
+            }) => (video_type, *microseconds, data),
+            BcMedia::Aac(_) | BcMedia::Adpcm(_) => {
+                return Err(Error::Other("RTP encoding of audio is not supported"))
+            }
+        };
+        if payload_type > 0x7F {
+            return Err(Error::Other("RTP payload type must be less than 128"));
+        }
+
+        // Reolink timestamps are in microseconds, RTP video uses a 90kHz clock
+        let timestamp = (microseconds as u64 * VIDEO_CLOCK_RATE / 1_000_000) as u32;
+
+        let nals: Vec<&[u8]> = split_annex_b(data).collect();
+        let mut payloads = vec![];
+        for nal in nals.iter() {
+            match video_type {
+                VideoType::H264 => packetize_h264(nal, &mut payloads),
+                VideoType::H265 => packetize_h265(nal, &mut payloads)?,
+                VideoType::Av1 => unreachable!(),
+            }
+        }
+
+        let last = payloads.len().saturating_sub(1);
+        Ok(payloads
+            .into_iter()
+            .enumerate()
+            .map(|(i, payload)| {
+                let mut packet = Vec::with_capacity(RTP_HEADER_LEN + payload.len());
+                // Version 2, no padding, no extension, no CSRCs
+                packet.push(0x80);
+                packet.push(if i == last { 0x80 } else { 0x00 } | payload_type);
+                packet.extend_from_slice(&sequence.to_be_bytes());
+                packet.extend_from_slice(&timestamp.to_be_bytes());
+                packet.extend_from_slice(&ssrc.to_be_bytes());
+                packet.extend_from_slice(&payload);
+                *sequence = sequence.wrapping_add(1);
+                packet
+            })
+            .collect())
+    }
+}
+
+/// Split an Annex B byte stream on its `00 00 01` and `00 00 00 01` start codes
+///
+/// If no start code is present the whole buffer is treated as one NAL unit
+fn split_annex_b(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut starts = vec![];
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i] == 0 && data[i + 1] == 0 && data[i + 2] == 1 {
+            starts.push((i, i + 3));
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    if starts.is_empty() {
+        starts.push((0, 0));
+    }
+
+    let mut nals = Vec::with_capacity(starts.len());
+    for (n, &(_, nal_start)) in starts.iter().enumerate() {
+        let mut nal_end = starts
+            .get(n + 1)
+            .map(|&(next_code, _)| next_code)
+            .unwrap_or(data.len());
+        // Trailing zeros belong to the next four byte start code
+        while nal_end > nal_start && data[nal_end - 1] == 0 {
+            nal_end -= 1;
+        }
+        nals.push(&data[nal_start..nal_end]);
+    }
+    nals.into_iter().filter(|nal| !nal.is_empty())
+}
+
+fn packetize_h264(nal: &[u8], payloads: &mut Vec<Vec<u8>>) {
+    if nal.len() <= RTP_MAX_PAYLOAD {
+        payloads.push(nal.to_vec());
+        return;
+    }
+
+    let nal_header = nal[0];
+    let fu_indicator = (nal_header & 0xE0) | H264_NAL_FU_A;
+    let nal_type = nal_header & 0x1F;
+    let chunks: Vec<&[u8]> = nal[1..].chunks(RTP_MAX_PAYLOAD - 2).collect();
+    let last = chunks.len() - 1;
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let mut fu_header = nal_type;
+        if i == 0 {
+            fu_header |= 0x80;
+        }
+        if i == last {
+            fu_header |= 0x40;
+        }
+        let mut payload = Vec::with_capacity(chunk.len() + 2);
+        payload.push(fu_indicator);
+        payload.push(fu_header);
+        payload.extend_from_slice(chunk);
+        payloads.push(payload);
+    }
+}
+
+fn packetize_h265(nal: &[u8], payloads: &mut Vec<Vec<u8>>) -> Result<(), Error> {
+    if nal.len() <= RTP_MAX_PAYLOAD {
+        payloads.push(nal.to_vec());
+        return Ok(());
+    }
+    if nal.len() < 2 {
+        return Err(Error::Other("H265 NAL unit is missing its header"));
+    }
+
+    let nal_type = (nal[0] >> 1) & 0x3F;
+    // Keep the F bit and the layer/tid fields, replace the type with FU
+    let payload_header = [(nal[0] & 0x81) | (H265_NAL_FU << 1), nal[1]];
+    let chunks: Vec<&[u8]> = nal[2..].chunks(RTP_MAX_PAYLOAD - 3).collect();
+    let last = chunks.len() - 1;
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let mut fu_header = nal_type;
+        if i == 0 {
+            fu_header |= 0x80;
+        }
+        if i == last {
+            fu_header |= 0x40;
+        }
+        let mut payload = Vec::with_capacity(chunk.len() + 3);
+        payload.extend_from_slice(&payload_header);
+        payload.push(fu_header);
+        payload.extend_from_slice(chunk);
+        payloads.push(payload);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iframe(video_type: VideoType, data: Vec<u8>) -> BcMedia {
+        BcMedia::Iframe(BcMediaIframe {
+            video_type,
+            microseconds: 1_000_000,
+            time: None,
+            data,
+        })
+    }
+
+    #[test]
+    fn test_h264_single_nal() {
+        let mut data = vec![0, 0, 0, 1, 0x67, 1, 2, 3];
+        data.extend_from_slice(&[0, 0, 1, 0x68, 4, 5]);
+        let media = iframe(VideoType::H264, data);
+
+        let mut sequence = 65535;
+        let packets = media.encode_as_rtp(&mut sequence, 0xDEADBEEF, 96).unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(sequence, 1);
+
+        assert_eq!(packets[0][0], 0x80);
+        assert_eq!(packets[0][1], 96);
+        assert_eq!(&packets[0][2..4], &[0xFF, 0xFF]);
+        assert_eq!(&packets[0][4..8], &90_000u32.to_be_bytes());
+        assert_eq!(&packets[0][8..12], &0xDEADBEEFu32.to_be_bytes());
+        assert_eq!(&packets[0][12..], &[0x67, 1, 2, 3]);
+
+        assert_eq!(packets[1][1], 0x80 | 96);
+        assert_eq!(&packets[1][2..4], &[0, 0]);
+        assert_eq!(&packets[1][12..], &[0x68, 4, 5]);
+    }
+
+    #[test]
+    fn test_h264_fu_a() {
+        let mut nal = vec![0x65];
+        nal.extend((0..3000).map(|i| (i % 251 + 1) as u8));
+        let mut data = vec![0, 0, 0, 1];
+        data.extend_from_slice(&nal);
+        let media = iframe(VideoType::H264, data);
+
+        let mut sequence = 0;
+        let packets = media.encode_as_rtp(&mut sequence, 1, 96).unwrap();
+        assert_eq!(packets.len(), 3);
+        assert!(packets
+            .iter()
+            .all(|p| p.len() <= RTP_HEADER_LEN + RTP_MAX_PAYLOAD));
+
+        let mut rebuilt = vec![];
+        for (i, packet) in packets.iter().enumerate() {
+            assert_eq!(packet[12], 0x60 | H264_NAL_FU_A);
+            let fu_header = packet[13];
+            assert_eq!(fu_header & 0x1F, 5);
+            assert_eq!(fu_header & 0x80 != 0, i == 0);
+            assert_eq!(fu_header & 0x40 != 0, i == packets.len() - 1);
+            assert_eq!(packet[1] & 0x80 != 0, i == packets.len() - 1);
+            rebuilt.extend_from_slice(&packet[14..]);
+        }
+        assert_eq!(rebuilt, nal[1..]);
+    }
+
+    #[test]
+    fn test_h265_fu() {
+        let mut nal = vec![0x26, 0x01];
+        nal.extend((0..2000).map(|i| (i % 251 + 1) as u8));
+        let mut data = vec![0, 0, 0, 1];
+        data.extend_from_slice(&nal);
+        let media = iframe(VideoType::H265, data);
+
+        let mut sequence = 0;
+        let packets = media.encode_as_rtp(&mut sequence, 1, 97).unwrap();
+        assert_eq!(packets.len(), 2);
+
+        let mut rebuilt = vec![];
+        for (i, packet) in packets.iter().enumerate() {
+            assert_eq!(packet[12], H265_NAL_FU << 1);
+            assert_eq!(packet[13], 0x01);
+            let fu_header = packet[14];
+            assert_eq!(fu_header & 0x3F, 19);
+            assert_eq!(fu_header & 0x80 != 0, i == 0);
+            assert_eq!(fu_header & 0x40 != 0, i == packets.len() - 1);
+            rebuilt.extend_from_slice(&packet[15..]);
+        }
+        assert_eq!(rebuilt, nal[2..]);
+    }
+}
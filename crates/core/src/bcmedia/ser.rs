@@ -131,6 +131,7 @@ fn bcmedia_iframe<W: Write>(payload: &BcMediaIframe) -> impl SerializeFn<W> {
     let vid_string = match payload.video_type {
         VideoType::H264 => "H264",
         VideoType::H265 => "H265",
+        VideoType::Av1 => "AV01",
     };
     let (extra_header, extra_header_size) = if let Some(payload_time) = payload.time {
         let extra_header = slice(
@@ -161,6 +162,7 @@ fn bcmedia_pframe<W: Write>(payload: &BcMediaPframe) -> impl SerializeFn<W> {
     let vid_string = match payload.video_type {
         VideoType::H264 => "H264",
         VideoType::H265 => "H265",
+        VideoType::Av1 => "AV01",
     };
     tuple((
         le_u32(MAGIC_HEADER_BCMEDIA_PFRAME),
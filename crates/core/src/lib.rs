@@ -25,6 +25,8 @@
 //!     },
 //!     debug: false,
 //!     max_discovery_retries: 10,
+//!     proxy: None,
+//!     packet_tap: None,
 //! };
 //! let mut camera = BcCamera::new(&options).await.unwrap();
 //! # })
@@ -49,6 +51,8 @@
 //! #    },
 //! #    debug: false,
 //! #    max_discovery_retries: 10,
+//! #    proxy: None,
+//! #    packet_tap: None,
 //! # };
 //! # let mut camera = BcCamera::new(&options).await.unwrap();
 //! camera.login().await;
@@ -94,6 +94,8 @@ impl AddressOrUid {
                 password: camera_config.password.clone(),
             },
             debug: false,
+            proxy: None,
+            packet_tap: None,
         };
 
         trace!("Camera Info: {:?}", options);
@@ -0,0 +1,11 @@
+use clap::Parser;
+
+/// The audio-test command plays the audio of the camera on this machine
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// How long to play the audio for in seconds
+    #[arg(long, default_value_t = 10)]
+    pub duration: u64,
+}
@@ -0,0 +1,210 @@
+///
+/// # Neolink Audio Test
+///
+/// This module plays the audio of a camera on the sound system of this
+/// machine. It is a quick way to check that the microphone works and that
+/// the levels are sensible without setting up an rtsp client
+///
+/// The format of the audio as it was parsed from the camera is printed
+/// first and then the rms and peak level of the audio is printed every
+/// second while it plays
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink audio-test --config=config.toml CameraName --duration=30
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use gstreamer::{
+    glib::ValueArray, parse::launch_full, prelude::*, Bus, MessageView, ParseFlags, Pipeline, State,
+};
+use gstreamer_app::AppSrc;
+use neolink_core::bc_protocol::StreamKind;
+use tokio::{
+    sync::broadcast::error::RecvError,
+    time::{sleep, timeout, Duration, Instant},
+};
+
+mod cmdline;
+
+use crate::common::{AudFormat, NeoReactor};
+pub(crate) use cmdline::Opt;
+
+/// How long to wait for the camera to send some audio
+const AUDIO_TIMEOUT_SECS: u64 = 10;
+
+/// Sample rates of the `sampling_frequency_index` of an adts header
+const AAC_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// Entry point for the audio-test subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    // The audio is the same on every stream so use the lightest
+    let mut stream_instance = camera
+        .stream(StreamKind::Sub)
+        .await
+        .context("Failed to start the stream")?;
+    let aud_format = timeout(
+        Duration::from_secs(AUDIO_TIMEOUT_SECS),
+        stream_instance.config.wait_for(|config| config.aud_ready()),
+    )
+    .await
+    .map_err(|_| anyhow!("The camera did not send any audio, check that its audio is enabled"))??
+    .aud_format;
+
+    let aud = &mut stream_instance.aud;
+    let first = loop {
+        match aud.recv().await {
+            Ok(frame) => break frame,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return Err(anyhow!("The stream has closed")),
+        }
+    };
+
+    let caps = match aud_format {
+        AudFormat::Aac => {
+            match parse_adts(&first.data) {
+                Some((profile, rate, channels)) => println!(
+                    "Audio: AAC, profile {}, {} Hz, {} channel(s)",
+                    profile, rate, channels
+                ),
+                None => println!("Audio: AAC, without an adts header"),
+            }
+            "audio/mpeg,mpegversion=4,stream-format=adts ! aacparse ! decodebin".to_string()
+        }
+        AudFormat::Adpcm(block_size) => {
+            // The block is a 4 byte header with the first sample then two samples per byte
+            println!(
+                "Audio: ADPCM (DVI-4), 8000 Hz, 1 channel(s), block size {} bytes ({} samples)",
+                block_size,
+                block_size * 2 + 1
+            );
+            format!(
+                "audio/x-adpcm,layout=dvi,block_align={},channels=1,rate=8000 ! adpcmdec",
+                first.data.len()
+            )
+        }
+        AudFormat::None => unreachable!(),
+    };
+
+    let pipeline = create_pipeline(&caps)?;
+    let appsrc = pipeline
+        .by_name("thesource")
+        .ok_or_else(|| anyhow!("There shoud be a `thesource`"))?
+        .dynamic_cast::<AppSrc>()
+        .map_err(|_| anyhow!("Cannot find appsource in gstreamer, check your gstreamer plugins"))?;
+    let bus = pipeline
+        .bus()
+        .expect("Pipeline without bus. Shouldn't happen!");
+    pipeline.set_state(State::Playing)?;
+
+    let end = Instant::now() + Duration::from_secs(opt.duration);
+    let mut frames = 0u64;
+    let mut next = Some(first);
+    let res = async {
+        loop {
+            if let Some(frame) = next.take() {
+                appsrc
+                    .push_buffer(gstreamer::Buffer::from_slice(frame.data.as_ref().clone()))
+                    .map_err(|e| anyhow!("Streaming error: {e:?}"))?;
+                frames += 1;
+            }
+            handle_messages(&bus)?;
+
+            next = tokio::select! {
+                _ = sleep(end.saturating_duration_since(Instant::now())) => break,
+                frame = aud.recv() => match frame {
+                    Ok(frame) => Some(frame),
+                    Err(RecvError::Lagged(n)) => {
+                        log::warn!("Dropped {} audio frames", n);
+                        None
+                    }
+                    Err(RecvError::Closed) => return Err(anyhow!("The stream has closed")),
+                },
+            };
+        }
+        Ok(())
+    }
+    .await;
+
+    let _ = appsrc.end_of_stream();
+    pipeline.set_state(State::Null)?;
+    res?;
+    println!("Played {} audio frames", frames);
+    Ok(())
+}
+
+fn create_pipeline(caps: &str) -> Result<Pipeline> {
+    gstreamer::init()
+        .context("Unable to start gstreamer ensure it and all plugins are installed")?;
+    let launch_str = format!(
+        "appsrc name=thesource is-live=true do-timestamp=true format=time caps={caps} \
+        ! queue \
+        ! audioconvert \
+        ! audioresample \
+        ! level post-messages=true \
+        ! autoaudiosink"
+    );
+    log::debug!("{}", launch_str);
+
+    launch_full(&launch_str, None, ParseFlags::empty())
+        .context("Unable to load gstreamer pipeline ensure all gstramer plugins are installed")?
+        .dynamic_cast::<Pipeline>()
+        .map_err(|_| {
+            anyhow!("Unable to create gstreamer pipeline ensure all gstramer plugins are installed")
+        })
+}
+
+/// Print the levels and stop on errors
+fn handle_messages(bus: &Bus) -> Result<()> {
+    while let Some(msg) = bus.pop() {
+        match msg.view() {
+            MessageView::Error(err) => {
+                return Err(anyhow!(
+                    "Error from {:?}: {} ({:?})",
+                    err.src().map(|src| src.path_string()),
+                    err.error(),
+                    err.debug()
+                ));
+            }
+            MessageView::Element(element) => {
+                let Some(structure) = element.structure() else {
+                    continue;
+                };
+                if structure.name() != "level" {
+                    continue;
+                }
+                let loudest = |field| {
+                    structure.get::<ValueArray>(field).ok().and_then(|values| {
+                        values
+                            .iter()
+                            .filter_map(|value| value.get::<f64>().ok())
+                            .reduce(f64::max)
+                    })
+                };
+                if let (Some(rms), Some(peak)) = (loudest("rms"), loudest("peak")) {
+                    println!("Level: {:6.1} dB rms {:6.1} dB peak", rms, peak);
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// The profile, sample rate and channels of the adts header at the start of the data
+fn parse_adts(data: &[u8]) -> Option<(u8, u32, u8)> {
+    if data.len() < 7 || data[0] != 0xFF || data[1] & 0xF0 != 0xF0 {
+        return None;
+    }
+    let profile = (data[2] >> 6) + 1;
+    let rate = *AAC_SAMPLE_RATES.get(((data[2] >> 2) & 0x0F) as usize)?;
+    let channels = ((data[2] & 0x01) << 2) | (data[3] >> 6);
+    Some((profile, rate, channels))
+}
@@ -0,0 +1,30 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+
+/// How the report is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// One block of lines for each finding
+    Text,
+    /// A json array of the findings
+    Json,
+}
+
+fn format_parse(src: &str) -> Result<ReportFormat> {
+    match src {
+        "text" => Ok(ReportFormat::Text),
+        "json" => Ok(ReportFormat::Json),
+        _ => Err(anyhow!(
+            "Could not understand {}, check your input, should be text or json",
+            src
+        )),
+    }
+}
+
+/// The audit command checks the config for common security problems
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// How to print the report: text or json
+    #[arg(long, value_parser = format_parse, default_value = "text")]
+    pub format: ReportFormat,
+}
@@ -0,0 +1,332 @@
+///
+/// # Neolink Audit
+///
+/// This module checks the config for common security problems
+///
+/// - Cameras with no password or a well known default or weak one
+/// - Cameras whose address is on the public internet
+/// - RTSP streams that can be watched without a user. When the rtsp server
+///   is running it is also asked for each stream without any credentials
+/// - RTSP users with weak passwords
+/// - The rtsp server not having a TLS certificate
+///
+/// Each finding is rated CRITICAL, HIGH, MEDIUM or LOW and comes with
+/// how to fix it. The command fails when there is a CRITICAL finding
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink audit --config=config.toml
+/// neolink audit --config=config.toml --format=json
+/// ```
+///
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::str::FromStr;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::{timeout, Duration},
+};
+
+mod cmdline;
+
+use crate::{
+    common::NeoReactor,
+    config::{CameraConfig, Config},
+};
+pub(crate) use cmdline::Opt;
+use cmdline::ReportFormat;
+
+/// Passwords that are the default of a camera or are among the first tried
+const WEAK_PASSWORDS: &[&str] = &[
+    "",
+    "admin",
+    "Admin",
+    "password",
+    "Password",
+    "pass",
+    "reolink",
+    "123456",
+    "1234567",
+    "12345678",
+    "123456789",
+    "1234",
+    "12345",
+    "000000",
+    "111111",
+    "888888",
+    "666666",
+    "654321",
+    "abc123",
+    "qwerty",
+    "letmein",
+    "default",
+    "changeme",
+];
+
+/// How long the rtsp server is given to answer the anonymous request
+const RTSP_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Severity::Low => "LOW",
+            Severity::Medium => "MEDIUM",
+            Severity::High => "HIGH",
+            Severity::Critical => "CRITICAL",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Finding {
+    severity: Severity,
+    /// `None` for findings about neolink itself
+    camera: Option<String>,
+    issue: String,
+    remediation: String,
+}
+
+/// Entry point for the audit subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let config = reactor.config().await?.borrow().clone();
+    let local_only = is_loopback_bind(&config.bind_addr);
+
+    let mut findings = vec![];
+    for camera in config.cameras.iter() {
+        check_camera_password(camera, &mut findings);
+        check_camera_address(camera, &mut findings);
+        check_anonymous_rtsp(&config, camera, local_only, &mut findings).await;
+    }
+    check_rtsp_users(&config, &mut findings);
+    if config.certificate.is_none() {
+        findings.push(Finding {
+            severity: if local_only {
+                Severity::Low
+            } else {
+                Severity::Medium
+            },
+            camera: None,
+            issue: "The rtsp server does not use TLS so the video and rtsp passwords can be read on the network".to_string(),
+            remediation: "Set `certificate` in the config to a pem with the certificate and key".to_string(),
+        });
+    }
+    findings.sort_by(|a, b| b.severity.cmp(&a.severity));
+
+    match opt.format {
+        ReportFormat::Text => print_text(&findings),
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&findings)?),
+    }
+
+    let critical = findings
+        .iter()
+        .filter(|finding| finding.severity == Severity::Critical)
+        .count();
+    if critical > 0 {
+        Err(anyhow!("The audit found {} critical problem(s)", critical))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_camera_password(camera: &CameraConfig, findings: &mut Vec<Finding>) {
+    let password = camera.password.as_deref().unwrap_or_default();
+    if WEAK_PASSWORDS.contains(&password) {
+        findings.push(Finding {
+            severity: Severity::Critical,
+            camera: Some(camera.name.clone()),
+            issue: if password.is_empty() {
+                "The camera has no password".to_string()
+            } else {
+                "The camera's password is a well known default or weak password".to_string()
+            },
+            remediation: "Change the camera's password with `neolink set-password` or the Reolink app and update `password` in the config".to_string(),
+        });
+    }
+}
+
+fn check_camera_address(camera: &CameraConfig, findings: &mut Vec<Finding>) {
+    // Cameras found by UID go through the Reolink relay and have no address
+    let Some(addr) = camera.camera_addr.as_deref() else {
+        return;
+    };
+    let ips = match addr.to_socket_addrs() {
+        Ok(addrs) => addrs.map(|addr| addr.ip()).collect::<Vec<_>>(),
+        Err(_) => IpAddr::from_str(addr).into_iter().collect(),
+    };
+    if let Some(ip) = ips.into_iter().find(is_public) {
+        findings.push(Finding {
+            severity: Severity::High,
+            camera: Some(camera.name.clone()),
+            issue: format!("The camera's address {} is the public ip {}", addr, ip),
+            remediation: "Do not forward the camera's ports from the router. Reach it over a VPN and use its local address".to_string(),
+        });
+    }
+}
+
+async fn check_anonymous_rtsp(
+    config: &Config,
+    camera: &CameraConfig,
+    local_only: bool,
+    findings: &mut Vec<Finding>,
+) {
+    if !camera.enabled {
+        return;
+    }
+    // The same rules as the rtsp server uses to give the stream its users
+    let anonymous = match camera.permitted_users.as_ref() {
+        Some(users) => users.iter().any(|user| user == "anonymous"),
+        None => config.users.is_empty(),
+    };
+    if !anonymous {
+        return;
+    }
+    let confirmed = if config.certificate.is_none() {
+        rtsp_allows_anonymous(config, &camera.name).await
+    } else {
+        false
+    };
+    findings.push(Finding {
+        severity: if local_only {
+            Severity::Low
+        } else {
+            Severity::High
+        },
+        camera: Some(camera.name.clone()),
+        issue: format!(
+            "The rtsp stream can be watched without a user{}",
+            if confirmed {
+                ", the running rtsp server served it without credentials"
+            } else {
+                ""
+            }
+        ),
+        remediation: "Add `[[users]]` to the config and give the camera `permitted_users`"
+            .to_string(),
+    });
+}
+
+fn check_rtsp_users(config: &Config, findings: &mut Vec<Finding>) {
+    for user in config.users.iter() {
+        if WEAK_PASSWORDS.contains(&user.pass.as_str()) || user.pass == user.name {
+            findings.push(Finding {
+                severity: if user.admin {
+                    Severity::Critical
+                } else {
+                    Severity::High
+                },
+                camera: None,
+                issue: format!("The rtsp user {} has a weak password", user.name),
+                remediation: "Change the `pass` of the user in `[[users]]` to a long random one"
+                    .to_string(),
+            });
+        }
+    }
+}
+
+/// Ask the running rtsp server for the stream without credentials
+///
+/// False if it refused or is not running
+async fn rtsp_allows_anonymous(config: &Config, name: &str) -> bool {
+    // Any address binds are checked over the loopback
+    let host = match config.bind_addr.as_str() {
+        "0.0.0.0" => "127.0.0.1",
+        "::" | "[::]" => "::1",
+        addr => addr,
+    };
+    let probe = async {
+        let mut socket = TcpStream::connect((host, config.bind_port)).await?;
+        let url_host = if host.contains(':') {
+            format!("[{}]", host)
+        } else {
+            host.to_string()
+        };
+        let request = format!(
+            "DESCRIBE rtsp://{}:{}/{} RTSP/1.0\r\nCSeq: 1\r\nAccept: application/sdp\r\n\r\n",
+            url_host, config.bind_port, name
+        );
+        socket.write_all(request.as_bytes()).await?;
+        let mut status_line = String::new();
+        BufReader::new(socket).read_line(&mut status_line).await?;
+        Result::<bool>::Ok(status_line.split_whitespace().nth(1) == Some("200"))
+    };
+    matches!(timeout(RTSP_PROBE_TIMEOUT, probe).await, Ok(Ok(true)))
+}
+
+fn is_loopback_bind(bind_addr: &str) -> bool {
+    bind_addr == "localhost"
+        || IpAddr::from_str(bind_addr.trim_matches(|c| c == '[' || c == ']'))
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false)
+}
+
+/// If the ip can be reached from the internet
+fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_unspecified()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // Carrier grade NAT
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local
+                || (first & 0xfe00) == 0xfc00
+                // Link local
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+fn print_text(findings: &[Finding]) {
+    if findings.is_empty() {
+        println!("No problems found");
+        return;
+    }
+    for finding in findings.iter() {
+        match finding.camera.as_ref() {
+            Some(camera) => println!("[{}] {}: {}", finding.severity, camera, finding.issue),
+            None => println!("[{}] {}", finding.severity, finding.issue),
+        }
+        println!("    Fix: {}", finding.remediation);
+    }
+    for severity in [
+        Severity::Critical,
+        Severity::High,
+        Severity::Medium,
+        Severity::Low,
+    ] {
+        let count = findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count();
+        if count > 0 {
+            println!("{}: {}", severity, count);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use crate::common::stream_parse;
+use clap::Parser;
+use neolink_core::bc_protocol::StreamKind;
+
+/// The benchmark command will measure how fast the stream can be received and processed
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The stream to benchmark: main, sub or extern
+    #[arg(long, value_parser = stream_parse, default_value = "main")]
+    pub stream: StreamKind,
+    /// How long to run the benchmark for in seconds
+    #[arg(long, default_value_t = 30)]
+    pub duration: u64,
+    /// Also print the metrics every this many seconds
+    #[arg(long)]
+    pub report_interval: Option<u64>,
+}
@@ -0,0 +1,292 @@
+///
+/// # Neolink Benchmark
+///
+/// This module measures how well the camera and this machine keep up
+/// with a stream
+///
+/// The stream is pushed into a gstreamer pipeline that parses it and
+/// then throws it away. The frame rate, data rate, frame size, time taken
+/// to push each frame into gstreamer, the fill of the pipeline's queue,
+/// the cpu used by neolink and the round trip time and retransmits of the
+/// tcp connection to the camera are reported
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink benchmark --config=config.toml CameraName --duration=60 --report-interval=5
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use gstreamer::{parse::launch_full, prelude::*, ClockTime, Element, ParseFlags, Pipeline, State};
+use gstreamer_app::AppSrc;
+use neolink_core::bc_protocol::ConnectionStats;
+use tokio::{
+    sync::broadcast::error::RecvError,
+    time::{interval_at, sleep, Duration, Instant},
+};
+
+mod cmdline;
+
+use crate::common::{NeoInstance, NeoReactor, VidFormat};
+pub(crate) use cmdline::Opt;
+
+/// Metrics collected over a period of the benchmark
+#[derive(Default)]
+struct Metrics {
+    frames: u64,
+    keyframes: u64,
+    dropped: u64,
+    bytes: u64,
+    push_time: Duration,
+    max_push_time: Duration,
+    max_queue_fill: f64,
+}
+
+impl Metrics {
+    fn report(
+        &self,
+        title: &str,
+        elapsed: Duration,
+        cpu: Option<Duration>,
+        queue_fill: f64,
+        connection: ConnectionStats,
+    ) {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        let frames = self.frames.max(1);
+        println!("{title} ({:.1}s)", secs);
+        println!("  frames/s:            {:.2}", self.frames as f64 / secs);
+        println!(
+            "  kbytes/s:            {:.1}",
+            self.bytes as f64 / 1000.0 / secs
+        );
+        println!("  avg frame size:      {} bytes", self.bytes / frames);
+        println!("  keyframes:           {}", self.keyframes);
+        println!("  dropped frames:      {}", self.dropped);
+        println!(
+            "  avg push latency:    {:?}",
+            self.push_time / frames as u32
+        );
+        println!("  max push latency:    {:?}", self.max_push_time);
+        println!("  queue fill:          {:.0}%", queue_fill * 100.0);
+        println!("  max queue fill:      {:.0}%", self.max_queue_fill * 100.0);
+        match cpu {
+            Some(cpu) => println!(
+                "  cpu:                 {:.1}%",
+                cpu.as_secs_f64() / secs * 100.0
+            ),
+            None => println!("  cpu:                 unknown"),
+        }
+        match connection.tcp_rtt_us {
+            Some(rtt) => println!("  tcp rtt:             {:.1}ms", rtt as f64 / 1000.0),
+            None => println!("  tcp rtt:             unknown"),
+        }
+        match connection.tcp_retransmits {
+            Some(retransmits) => println!("  tcp retransmits:     {}", retransmits),
+            None => println!("  tcp retransmits:     unknown"),
+        }
+    }
+}
+
+/// Entry point for the benchmark subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let mut stream_instance = camera
+        .stream(opt.stream)
+        .await
+        .context("Failed to start video")?;
+    let vid_format = stream_instance
+        .config
+        .wait_for(|config| config.vid_ready())
+        .await?
+        .vid_format;
+
+    let pipeline = create_pipeline(vid_format)?;
+    let appsrc = pipeline
+        .by_name("thesource")
+        .ok_or_else(|| anyhow!("There shoud be a `thesource`"))?
+        .dynamic_cast::<AppSrc>()
+        .map_err(|_| anyhow!("Cannot find appsource in gstreamer, check your gstreamer plugins"))?;
+    let queue = pipeline
+        .by_name("thequeue")
+        .ok_or_else(|| anyhow!("There shoud be a `thequeue`"))?;
+    pipeline.set_state(State::Playing)?;
+
+    let duration = Duration::from_secs(opt.duration);
+    let report_interval = opt
+        .report_interval
+        .map(Duration::from_secs)
+        .filter(|interval| !interval.is_zero());
+    let start = Instant::now();
+    let end = start + duration;
+    let mut reports = interval_at(
+        start + report_interval.unwrap_or(duration),
+        report_interval.unwrap_or(duration),
+    );
+    let start_cpu = cpu_time();
+    let mut interval_start = (start, start_cpu);
+
+    let mut total = Metrics::default();
+    let mut current = Metrics::default();
+    let mut ts_0 = None;
+    let vid = &mut stream_instance.vid;
+    let res = async {
+        loop {
+            let frame = tokio::select! {
+                _ = sleep(end.saturating_duration_since(Instant::now())) => break,
+                _ = reports.tick(), if report_interval.is_some() => {
+                    let now = Instant::now();
+                    let cpu = cpu_time()
+                        .zip(interval_start.1)
+                        .map(|(now, before)| now.saturating_sub(before));
+                    let connection = connection_stats(&camera).await;
+                    current.report("Interval", now - interval_start.0, cpu, queue_fill(&queue), connection);
+                    current = Metrics::default();
+                    interval_start = (now, cpu_time());
+                    continue;
+                },
+                frame = vid.recv() => frame,
+            };
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(RecvError::Lagged(n)) => {
+                    total.dropped += n;
+                    current.dropped += n;
+                    continue;
+                }
+                Err(RecvError::Closed) => return Err(anyhow!("The stream has closed")),
+            };
+            if ts_0.is_none() && !frame.keyframe {
+                continue;
+            }
+            let ts_0 = *ts_0.get_or_insert(frame.ts);
+
+            let push_start = Instant::now();
+            let time = ClockTime::from_useconds(frame.ts.saturating_sub(ts_0).as_micros() as u64);
+            let mut buf = gstreamer::Buffer::from_slice(frame.data.as_ref().clone());
+            {
+                let buf = buf.get_mut().unwrap();
+                buf.set_dts(time);
+                buf.set_pts(time);
+            }
+            appsrc
+                .push_buffer(buf)
+                .map_err(|e| anyhow!("Streaming error: {e:?}"))?;
+            let push_time = push_start.elapsed();
+            let fill = queue_fill(&queue);
+
+            for metrics in [&mut total, &mut current] {
+                metrics.frames += 1;
+                metrics.keyframes += frame.keyframe as u64;
+                metrics.bytes += frame.data.len() as u64;
+                metrics.push_time += push_time;
+                metrics.max_push_time = metrics.max_push_time.max(push_time);
+                metrics.max_queue_fill = metrics.max_queue_fill.max(fill);
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    let _ = appsrc.end_of_stream();
+    pipeline.set_state(State::Null)?;
+    res?;
+
+    let cpu = cpu_time()
+        .zip(start_cpu)
+        .map(|(now, before)| now.saturating_sub(before));
+    let connection = connection_stats(&camera).await;
+    total.report(
+        "Summary",
+        start.elapsed(),
+        cpu,
+        queue_fill(&queue),
+        connection,
+    );
+    Ok(())
+}
+
+fn create_pipeline(format: VidFormat) -> Result<Pipeline> {
+    gstreamer::init()
+        .context("Unable to start gstreamer ensure it and all plugins are installed")?;
+    let (caps, parser) = match format {
+        VidFormat::H264 => ("video/x-h264,stream-format=byte-stream", "h264parse"),
+        VidFormat::H265 => ("video/x-h265,stream-format=byte-stream", "h265parse"),
+        VidFormat::Av1 => ("video/x-av1,stream-format=obu-stream", "av1parse"),
+        VidFormat::None => unreachable!(),
+    };
+    let launch_str = format!(
+        "appsrc name=thesource is-live=true format=time caps={caps} \
+        ! {parser} \
+        ! queue name=thequeue \
+        ! fakesink sync=false"
+    );
+    log::debug!("{}", launch_str);
+
+    launch_full(&launch_str, None, ParseFlags::empty())
+        .context("Unable to load gstreamer pipeline ensure all gstramer plugins are installed")?
+        .dynamic_cast::<Pipeline>()
+        .map_err(|_| {
+            anyhow!("Unable to create gstreamer pipeline ensure all gstramer plugins are installed")
+        })
+}
+
+/// How full the queue is from `0.0` to `1.0` by whichever limit is closest
+fn queue_fill(queue: &Element) -> f64 {
+    [
+        ("current-level-buffers", "max-size-buffers"),
+        ("current-level-bytes", "max-size-bytes"),
+    ]
+    .iter()
+    .map(|(current, max)| {
+        let max = queue.property::<u32>(max);
+        if max == 0 {
+            0.0
+        } else {
+            queue.property::<u32>(current) as f64 / max as f64
+        }
+    })
+    .chain(std::iter::once({
+        let max = queue.property::<u64>("max-size-time");
+        if max == 0 {
+            0.0
+        } else {
+            queue.property::<u64>("current-level-time") as f64 / max as f64
+        }
+    }))
+    .fold(0.0, f64::max)
+}
+
+/// The stats of the camera's tcp connection
+///
+/// These are all `None` when they cannot be read
+async fn connection_stats(camera: &NeoInstance) -> ConnectionStats {
+    camera
+        .run_passive_task(|cam| Box::pin(async move { Ok(cam.connection_stats()) }))
+        .await
+        .unwrap_or_default()
+}
+
+/// Time that all the threads of this process have spent on the cpu
+///
+/// Both user and system time are counted. Only available on unix
+#[cfg(unix)]
+fn cpu_time() -> Option<Duration> {
+    // SAFETY: rusage is plain data that getrusage fills in
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    // SAFETY: usage is a valid rusage for getrusage to write to
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    let timeval = |tv: libc::timeval| {
+        Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+    };
+    Some(timeval(usage.ru_utime) + timeval(usage.ru_stime))
+}
+
+#[cfg(not(unix))]
+fn cpu_time() -> Option<Duration> {
+    None
+}
@@ -28,4 +28,41 @@ pub enum Command {
     Image(super::image::Opt),
     Battery(super::battery::Opt),
     Services(super::services::Opt),
+    Osd(super::osd::Opt),
+    PushConfig(super::push_config::Opt),
+    ImportCert(super::import_cert::Opt),
+    MotionConfig(super::motion_config::Opt),
+    StreamInfo(super::stream_info::Opt),
+    EmailConfig(super::email_config::Opt),
+    Benchmark(super::benchmark::Opt),
+    SetResolution(super::set_resolution::Opt),
+    SetPassword(super::set_password::Opt),
+    GenerateMask(super::generate_mask::Opt),
+    ExportConfig(super::export_config::Opt),
+    TestMotion(super::test_motion::Opt),
+    LogEvents(super::log_events::Opt),
+    ListCameras(super::list_cameras::Opt),
+    HealthCheck(super::health_check::Opt),
+    ScanPorts(super::scan_ports::Opt),
+    MotionAlert(super::motion_alert::Opt),
+    SimulateStream(super::simulate_stream::Opt),
+    DumpBc(super::dump_bc::Opt),
+    Convert(super::convert::Opt),
+    StreamCheck(super::stream_check::Opt),
+    AudioTest(super::audio_test::Opt),
+    GenerateConfig(super::generate_config::Opt),
+    ExportStream(super::export_stream::Opt),
+    ConfigDiff(super::config_diff::Opt),
+    Mirror(super::mirror::Opt),
+    MqttBridge(super::mqtt_bridge::Opt),
+    Watchdog(super::watchdog::Opt),
+    HttpMjpeg(super::http_mjpeg::Opt),
+    SetOsdName(super::set_osd_name::Opt),
+    Audit(super::audit::Opt),
+    RecordEvent(super::record_event::Opt),
+    StreamForward(super::stream_forward::Opt),
+    SetTimezone(super::set_timezone::Opt),
+    SnapshotDiff(super::snapshot_diff::Opt),
+    StressTest(super::stress_test::Opt),
+    ComplianceReport(super::compliance_report::Opt),
 }
@@ -1,12 +1,23 @@
-use std::sync::{Arc, Weak};
+use std::sync::{Arc, Mutex, Weak};
 use tokio::{
     sync::watch::{Receiver as WatchReceiver, Sender as WatchSender},
     time::{interval, sleep, timeout, Duration, Instant},
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::{config::CameraConfig, utils::connect_and_login, AnyResult};
-use neolink_core::bc_protocol::BcCamera;
+use super::NeoConnections;
+use crate::{
+    config::{
+        CameraConfig, EmailConfig, MotionDetectionConfig, NtpConfig, OsdConfig, StreamQualityConfig,
+    },
+    utils::{connect, login},
+    AnyResult,
+};
+use anyhow::Context;
+use neolink_core::bc_protocol::{normalize_regions, round_gop_size, BcCamera, QualityPreset};
+
+/// How long the camera is given to accept the login again when its session expires
+const REAUTH_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Eq, PartialEq, Copy, Clone)]
 pub(crate) enum NeoCamThreadState {
@@ -14,11 +25,50 @@ pub(crate) enum NeoCamThreadState {
     Disconnected,
 }
 
+/// Where the camera is in its connection lifecycle
+///
+/// Unlike [`NeoCamThreadState`], which is what we want the camera to be,
+/// this is what the camera thread is actually doing
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub(crate) enum CameraConnectionState {
+    Disconnected,
+    Connecting,
+    Authenticating,
+    StartingStream,
+    Streaming,
+    Reconnecting { attempt: u32, next_retry: Instant },
+    ShuttingDown,
+}
+
+impl std::fmt::Display for CameraConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CameraConnectionState::Disconnected => write!(f, "Disconnected"),
+            CameraConnectionState::Connecting => write!(f, "Connecting"),
+            CameraConnectionState::Authenticating => write!(f, "Authenticating"),
+            CameraConnectionState::StartingStream => write!(f, "StartingStream"),
+            CameraConnectionState::Streaming => write!(f, "Streaming"),
+            CameraConnectionState::Reconnecting {
+                attempt,
+                next_retry,
+            } => write!(
+                f,
+                "Reconnecting (attempt {} in {:?})",
+                attempt,
+                next_retry.saturating_duration_since(Instant::now())
+            ),
+            CameraConnectionState::ShuttingDown => write!(f, "ShuttingDown"),
+        }
+    }
+}
+
 pub(crate) struct NeoCamThread {
     state: WatchReceiver<NeoCamThreadState>,
     config: WatchReceiver<CameraConfig>,
     cancel: CancellationToken,
     camera_watch: WatchSender<Weak<BcCamera>>,
+    connection_state: Arc<Mutex<CameraConnectionState>>,
+    connections: NeoConnections,
 }
 
 impl NeoCamThread {
@@ -26,6 +76,8 @@ impl NeoCamThread {
         watch_state_rx: WatchReceiver<NeoCamThreadState>,
         watch_config_rx: WatchReceiver<CameraConfig>,
         camera_watch_tx: WatchSender<Weak<BcCamera>>,
+        connection_state: Arc<Mutex<CameraConnectionState>>,
+        connections: NeoConnections,
         cancel: CancellationToken,
     ) -> Self {
         Self {
@@ -33,21 +85,70 @@ impl NeoCamThread {
             config: watch_config_rx,
             cancel,
             camera_watch: camera_watch_tx,
+            connection_state,
+            connections,
+        }
+    }
+
+    fn transition(&self, name: &str, new_state: CameraConnectionState) {
+        let mut state = self.connection_state.lock().unwrap();
+        if *state != new_state {
+            log::info!("{name}: {} -> {}", *state, new_state);
+            *state = new_state;
         }
     }
+
     async fn run_camera(&mut self, config: &CameraConfig) -> AnyResult<()> {
         let name = config.name.clone();
-        let camera = Arc::new(connect_and_login(config).await?);
+        self.transition(&name, CameraConnectionState::Connecting);
+        let shared = self
+            .connections
+            .get(config, async {
+                let camera = connect(config).await?;
+                self.transition(&name, CameraConnectionState::Authenticating);
+                login(config, &camera).await?;
+                Ok(camera)
+            })
+            .await?;
+        let camera = shared.camera().clone();
 
+        self.transition(&name, CameraConnectionState::StartingStream);
         sleep(Duration::from_secs(2)).await; // Delay a little since some calls will error if camera is waking up
         update_camera_time(&camera, &name, config.update_time).await?;
+        if let Some(osd) = config.osd.as_ref() {
+            if let Err(e) = update_camera_osd(&camera, &name, osd).await {
+                log::warn!("{name}: Could not apply the OSD settings: {:?}", e);
+            }
+        }
+        if let Some(quality) = config.stream_quality.as_ref() {
+            if let Err(e) = update_camera_stream_quality(&camera, &name, quality).await {
+                log::warn!(
+                    "{name}: Could not apply the stream quality settings: {:?}",
+                    e
+                );
+            }
+        }
+        if let Some(motion_detection) = config.motion_detection.as_ref() {
+            if let Err(e) = update_camera_motion_detection(&camera, &name, motion_detection).await {
+                log::warn!(
+                    "{name}: Could not apply the motion detection settings: {:?}",
+                    e
+                );
+            }
+        }
+        if let Some(email) = config.email.as_ref() {
+            if let Err(e) = update_camera_email(&camera, &name, email).await {
+                log::warn!("{name}: Could not apply the email settings: {:?}", e);
+            }
+        }
         sleep(Duration::from_secs(2)).await; // Delay a little since some calls will error if camera is waking up
 
         self.camera_watch.send_replace(Arc::downgrade(&camera));
+        self.transition(&name, CameraConnectionState::Streaming);
 
         let cancel_check = self.cancel.clone();
         // Now we wait for a disconnect
-        tokio::select! {
+        let result = tokio::select! {
             _ = cancel_check.cancelled() => {
                 AnyResult::Ok(())
             }
@@ -55,6 +156,19 @@ impl NeoCamThread {
                 v?;
                 Ok(())
             },
+            v = async {
+                loop {
+                    camera.session_expired().await;
+                    // Log in again on the same connection so the streams carry on.
+                    // If that fails the error drops us into the reconnect
+                    log::info!("{name}: Login session expired. Logging in again");
+                    self.transition(&name, CameraConnectionState::Authenticating);
+                    timeout(REAUTH_TIMEOUT, login(config, &camera))
+                        .await
+                        .with_context(|| format!("{name}: Timed out logging in again"))??;
+                    self.transition(&name, CameraConnectionState::Streaming);
+                }
+            } => v,
             v = async {
                 let mut interval = interval(Duration::from_secs(5));
                 let mut missed_pings = 0;
@@ -85,12 +199,14 @@ impl NeoCamThread {
                     }
                 }
             } => v,
-        }?;
-
-        let _ = camera.logout().await;
-        let _ = camera.shutdown().await;
-
-        Ok(())
+        };
+        if result.is_err() {
+            // The other channels will find out on their own but must not be
+            // given this connection again
+            shared.invalidate().await;
+        }
+        // The connection is closed once every channel has let go of it
+        result
     }
 
     // Will run and attempt to maintain the connection
@@ -102,6 +218,7 @@ impl NeoCamThread {
         const MIN_BACKOFF: Duration = Duration::from_millis(50);
 
         let mut backoff = MIN_BACKOFF;
+        let mut attempt = 0;
 
         loop {
             self.state
@@ -133,6 +250,7 @@ impl NeoCamThread {
                 // If None go back and reload NOW
                 //
                 // This occurs if there was a config change
+                self.transition(&name, CameraConnectionState::Disconnected);
                 continue;
             }
 
@@ -142,6 +260,7 @@ impl NeoCamThread {
             if now.elapsed() > Duration::from_secs(60) {
                 // Command ran long enough to be considered a success
                 backoff = MIN_BACKOFF;
+                attempt = 0;
             }
             if backoff > MAX_BACKOFF {
                 backoff = MAX_BACKOFF;
@@ -150,6 +269,7 @@ impl NeoCamThread {
             match result {
                 Ok(()) => {
                     // Normal shutdown
+                    self.transition(&name, CameraConnectionState::ShuttingDown);
                     self.cancel.cancel();
                     return Ok(());
                 }
@@ -161,13 +281,21 @@ impl NeoCamThread {
                         Some(neolink_core::Error::CameraLoginFail) => {
                             // Fatal
                             log::error!("{name}: Login credentials were not accepted");
+                            self.transition(&name, CameraConnectionState::Disconnected);
                             self.cancel.cancel();
                             return Err(e);
                         }
                         _ => {
                             // Non fatal
                             log::warn!("{name}: Connection Lost: {:?}", e);
-                            log::info!("{name}: Attempt reconnect in {:?}", backoff);
+                            attempt += 1;
+                            self.transition(
+                                &name,
+                                CameraConnectionState::Reconnecting {
+                                    attempt,
+                                    next_retry: Instant::now() + backoff,
+                                },
+                            );
                             sleep(backoff).await;
                             backoff *= 2;
                         }
@@ -219,3 +347,233 @@ async fn update_camera_time(camera: &BcCamera, name: &str, update_time: bool) ->
     }
     Ok(())
 }
+
+/// Apply the OSD settings leaving anything not given as it is
+pub(crate) async fn update_camera_osd(
+    camera: &BcCamera,
+    name: &str,
+    osd: &OsdConfig,
+) -> AnyResult<()> {
+    let current = camera.get_osd_config().await?;
+    let mut wanted = current.clone();
+    if let Some(timestamp_enabled) = osd.timestamp_enabled {
+        wanted.timestamp_enabled = timestamp_enabled;
+    }
+    if let Some(timestamp_format) = osd.timestamp_format.as_ref() {
+        wanted.timestamp_format.clone_from(timestamp_format);
+    }
+    if let Some(camera_name_enabled) = osd.camera_name_enabled {
+        wanted.camera_name_enabled = camera_name_enabled;
+    }
+    if let Some(camera_name) = osd.camera_name.as_ref() {
+        wanted.camera_name.clone_from(camera_name);
+    }
+    if let Some(position) = osd.position {
+        wanted.position = position;
+    }
+
+    if wanted != current {
+        log::info!("{}: Updating OSD settings to {:?}", name, wanted);
+        camera.set_osd_config(&wanted).await?;
+    } else {
+        log::debug!("{}: OSD settings are already set", name);
+    }
+    Ok(())
+}
+
+/// Apply the stream quality settings leaving anything not given as it is
+pub(crate) async fn update_camera_stream_quality(
+    camera: &BcCamera,
+    name: &str,
+    quality: &StreamQualityConfig,
+) -> AnyResult<()> {
+    let stream = quality.stream_kind();
+    let current = camera.get_stream_quality(stream).await?;
+    log::info!(
+        "{}: Current {} stream quality settings are {:?}",
+        name,
+        stream,
+        current
+    );
+    let mut wanted = current.clone();
+    if let Some(bitrate_kbps) = quality.bitrate_kbps {
+        wanted.bitrate_kbps = bitrate_kbps;
+    }
+    if let Some(frame_rate) = quality.frame_rate {
+        wanted.frame_rate = frame_rate;
+    }
+    if let Some(resolution) = quality.resolution {
+        wanted.resolution = Some(resolution);
+    }
+    if let Some(quality_preset) = quality.quality_preset {
+        wanted.quality_preset = Some(quality_preset);
+    }
+    // A GOP of one second reads back as VeryHigh, which is the same as High
+    // with a `gop_size_frames` of one second, so don't reapply it on every connect
+    if quality.gop_size_frames.is_some()
+        && wanted.quality_preset == Some(QualityPreset::High)
+        && current.quality_preset == Some(QualityPreset::VeryHigh)
+    {
+        wanted.quality_preset = current.quality_preset;
+    }
+
+    if wanted != current {
+        log::info!(
+            "{}: Updating {} stream quality settings to {:?}",
+            name,
+            stream,
+            wanted
+        );
+        camera.set_stream_quality(stream, &wanted).await?;
+    } else {
+        log::debug!("{}: Stream quality settings are already set", name);
+    }
+
+    if let Some(gop_size_frames) = quality.gop_size_frames {
+        let rounded = round_gop_size(gop_size_frames, wanted.frame_rate);
+        if rounded != gop_size_frames {
+            log::warn!(
+                "{}: The camera keeps the GOP size in whole seconds so {} frames at {} fps will be {} frames",
+                name,
+                gop_size_frames,
+                wanted.frame_rate,
+                rounded
+            );
+        }
+        let gop_size_frames = rounded;
+        let current = camera.get_gop_size(stream).await?;
+        log::info!(
+            "{}: Current {} stream GOP size is {} frames",
+            name,
+            stream,
+            current
+        );
+        if gop_size_frames != current {
+            log::info!(
+                "{}: Updating {} stream GOP size from {} to {} frames",
+                name,
+                stream,
+                current,
+                gop_size_frames
+            );
+            camera.set_gop_size(stream, gop_size_frames).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Apply the motion detection settings leaving anything not given as it is
+pub(crate) async fn update_camera_motion_detection(
+    camera: &BcCamera,
+    name: &str,
+    motion_detection: &MotionDetectionConfig,
+) -> AnyResult<()> {
+    let current = camera.get_motion_detection_config().await?;
+    let mut wanted = current.clone();
+    if let Some(enabled) = motion_detection.enabled {
+        wanted.enabled = enabled;
+    }
+    if let Some(sensitivity) = motion_detection.sensitivity {
+        wanted.sensitivity = sensitivity;
+    }
+    if let Some(regions) = motion_detection.regions.as_ref() {
+        // The camera reports its grid split into enabled rectangles so
+        // compare against the configured regions in that same form
+        let (cols, rows) = camera.get_motion_grid_size().await?;
+        wanted.regions = normalize_regions(cols, rows, regions);
+    }
+
+    if wanted != current {
+        log::info!(
+            "{}: Updating motion detection settings to {:?}",
+            name,
+            wanted
+        );
+        // Send the regions as configured so that a list of only disabled
+        // regions still clears the grid
+        let mut to_set = wanted;
+        if let Some(regions) = motion_detection.regions.as_ref() {
+            to_set.regions.clone_from(regions);
+        }
+        camera.set_motion_detection_config(&to_set).await?;
+    } else {
+        log::debug!("{}: Motion detection settings are already set", name);
+    }
+    Ok(())
+}
+
+/// Apply the email settings leaving anything not given as it is
+pub(crate) async fn update_camera_email(
+    camera: &BcCamera,
+    name: &str,
+    email: &EmailConfig,
+) -> AnyResult<()> {
+    let current = camera.get_email_config().await?;
+    let mut wanted = current.clone();
+    if let Some(enabled) = email.enabled {
+        wanted.enabled = enabled;
+    }
+    if let Some(smtp_host) = email.smtp_host.as_ref() {
+        wanted.smtp_host.clone_from(smtp_host);
+    }
+    if let Some(smtp_port) = email.smtp_port {
+        wanted.smtp_port = smtp_port;
+    }
+    if let Some(tls_mode) = email.tls_mode {
+        wanted.tls_mode = tls_mode;
+    }
+    if let Some(username) = email.username.as_ref() {
+        wanted.username.clone_from(username);
+    }
+    if let Some(sender) = email.sender.as_ref() {
+        wanted.sender = Some(sender.clone());
+    }
+    if let Some(recipients) = email.recipients.as_ref() {
+        wanted.recipients.clone_from(recipients);
+    }
+    if let Some(schedule) = email.schedule.as_ref() {
+        wanted.schedule = Some(schedule.clone());
+    }
+
+    if wanted != current {
+        // Password is left out so it does not end up in the logs
+        log::info!("{}: Updating email settings to {:?}", name, wanted);
+    } else if email.password.is_none() {
+        log::debug!("{}: Email settings are already set", name);
+        return Ok(());
+    }
+    // The camera never sends the password back so it is always sent when configured
+    wanted.password.clone_from(&email.password);
+    camera.set_email_config(&wanted).await?;
+    Ok(())
+}
+
+/// Apply the NTP settings leaving anything not given as it is
+pub(crate) async fn update_camera_ntp(
+    camera: &BcCamera,
+    name: &str,
+    ntp: &NtpConfig,
+) -> AnyResult<()> {
+    let current = camera.get_ntp_config().await?;
+    let mut wanted = current.clone();
+    if let Some(enabled) = ntp.enabled {
+        wanted.enabled = enabled;
+    }
+    if let Some(server) = ntp.server.as_ref() {
+        wanted.server.clone_from(server);
+    }
+    if let Some(port) = ntp.port {
+        wanted.port = port;
+    }
+    if let Some(interval_mins) = ntp.interval_mins {
+        wanted.interval_mins = interval_mins;
+    }
+
+    if wanted != current {
+        log::info!("{}: Updating NTP settings to {:?}", name, wanted);
+        camera.set_ntp_config(&wanted).await?;
+    } else {
+        log::debug!("{}: NTP settings are already set", name);
+    }
+    Ok(())
+}
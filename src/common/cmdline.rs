@@ -0,0 +1,41 @@
+//! Value parsers that are shared by the command line options of the subcommands
+use anyhow::{anyhow, Result};
+use neolink_core::{bc_protocol::StreamKind, bcmedia::model::VideoType};
+
+/// Parses `main`, `sub` or `extern` into a [`StreamKind`]
+pub(crate) fn stream_parse(src: &str) -> Result<StreamKind> {
+    match src {
+        "main" | "mainStream" => Ok(StreamKind::Main),
+        "sub" | "subStream" => Ok(StreamKind::Sub),
+        "extern" | "externStream" => Ok(StreamKind::Extern),
+        _ => Err(anyhow!(
+            "Could not understand {}, check your input, should be main, sub or extern",
+            src
+        )),
+    }
+}
+
+/// Parses `h264` or `h265`, the codecs that the cameras encode with
+pub(crate) fn codec_parse(src: &str) -> Result<VideoType> {
+    match src {
+        "h264" | "H264" => Ok(VideoType::H264),
+        "h265" | "H265" | "hevc" => Ok(VideoType::H265),
+        _ => Err(anyhow!(
+            "Could not understand {}, check your input, should be h264 or h265",
+            src
+        )),
+    }
+}
+
+/// Like [`codec_parse`] but also accepts `av1`
+pub(crate) fn codec_parse_with_av1(src: &str) -> Result<VideoType> {
+    match src {
+        "av1" | "AV1" => Ok(VideoType::Av1),
+        _ => codec_parse(src).map_err(|_| {
+            anyhow!(
+                "Could not understand {}, check your input, should be h264, h265 or av1",
+                src
+            )
+        }),
+    }
+}
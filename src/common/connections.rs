@@ -0,0 +1,131 @@
+//! The connections that the reactor holds to the cameras
+//!
+//! An NVR sends every one of its cameras over a single connection on
+//! different channels. The cameras made from the `channels` of a config all
+//! ask for the connection to the same address and get a handle on their own
+//! channel of it, whichever of them connects first
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::{config::CameraConfig, AnyResult};
+use neolink_core::bc_protocol::BcCamera;
+
+type Slot = Arc<AsyncMutex<Option<Arc<BcCamera>>>>;
+
+/// One connection per camera address
+#[derive(Clone, Default)]
+pub(crate) struct NeoConnections {
+    slots: Arc<Mutex<HashMap<String, Slot>>>,
+}
+
+impl NeoConnections {
+    /// Get a handle on the channel of the camera
+    ///
+    /// If there is no connection for the camera yet then `connect` is used to
+    /// make one. Anyone else that asks for the same connection in the meantime
+    /// waits for it rather than opening their own
+    pub(crate) async fn get<F>(&self, config: &CameraConfig, connect: F) -> AnyResult<SharedCamera>
+    where
+        F: Future<Output = AnyResult<BcCamera>>,
+    {
+        let slot = self
+            .slots
+            .lock()
+            .unwrap()
+            .entry(connection_key(config))
+            .or_default()
+            .clone();
+
+        let mut connection = slot.lock().await;
+        let base = match connection.as_ref() {
+            Some(base) => {
+                log::info!(
+                    "{}: Using the open connection for channel {}",
+                    config.name,
+                    config.channel_id
+                );
+                base.clone()
+            }
+            None => {
+                let base = Arc::new(connect.await?);
+                *connection = Some(base.clone());
+                base
+            }
+        };
+        drop(connection);
+
+        Ok(SharedCamera {
+            camera: Arc::new(base.with_channel(config.channel_id)),
+            base: Some(base),
+            slot,
+        })
+    }
+}
+
+/// The connections are shared by the camera that lists the `channels` and
+/// the cameras made from them. Every other camera has its own
+fn connection_key(config: &CameraConfig) -> String {
+    match config.shared_connection.as_ref() {
+        Some(owner) => owner.clone(),
+        None => config.name.clone(),
+    }
+}
+
+/// A camera on one channel of a connection from [`NeoConnections`]
+///
+/// The connection is logged out and closed when the last handle on it is dropped
+pub(crate) struct SharedCamera {
+    camera: Arc<BcCamera>,
+    base: Option<Arc<BcCamera>>,
+    slot: Slot,
+}
+
+impl SharedCamera {
+    pub(crate) fn camera(&self) -> &Arc<BcCamera> {
+        &self.camera
+    }
+
+    /// Stop handing out this connection because it has failed
+    ///
+    /// The next camera to ask will make a new one
+    pub(crate) async fn invalidate(&self) {
+        let mut connection = self.slot.lock().await;
+        if let (Some(current), Some(base)) = (connection.as_ref(), self.base.as_ref()) {
+            if Arc::ptr_eq(current, base) {
+                *connection = None;
+            }
+        }
+    }
+}
+
+impl Drop for SharedCamera {
+    fn drop(&mut self) {
+        let Some(base) = self.base.take() else {
+            return;
+        };
+        let slot = self.slot.clone();
+        tokio::task::spawn(async move {
+            let mut connection = slot.lock().await;
+            let in_slot = connection
+                .as_ref()
+                .map(|current| Arc::ptr_eq(current, &base))
+                .unwrap_or(false);
+            // Every handle clones the connection while holding the slot so
+            // this count cannot go up while we hold it
+            if Arc::strong_count(&base) > 1 + in_slot as usize {
+                drop(base);
+                return;
+            }
+            if in_slot {
+                *connection = None;
+            }
+            drop(connection);
+            let _ = base.logout().await;
+            let _ = base.shutdown().await;
+        });
+    }
+}
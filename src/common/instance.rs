@@ -6,7 +6,7 @@
 //! whenever the camera is lost/updated
 use anyhow::{anyhow, Context};
 use futures::TryFutureExt;
-use std::sync::{Arc, Weak};
+use std::sync::{Arc, Mutex, Weak};
 use tokio::{
     sync::{
         mpsc::Sender as MpscSender, oneshot::channel as oneshot, watch::channel as watch,
@@ -16,7 +16,10 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 
-use super::{MdState, NeoCamCommand, NeoCamThreadState, Permit, PushNoti, StreamInstance};
+use super::{
+    CameraConnectionState, MdState, NeoCamCommand, NeoCamThreadState, Permit, PushNoti,
+    StreamInstance,
+};
 use crate::{config::CameraConfig, AnyResult, Result};
 use neolink_core::bc_protocol::{BcCamera, StreamKind};
 
@@ -310,6 +313,46 @@ impl NeoInstance {
         Ok(instance_rx.await?)
     }
 
+    /// Send a motion start that did not come from the camera followed
+    /// by a motion stop after `duration`
+    pub(crate) async fn simulate_motion(&self, duration: Duration) -> Result<()> {
+        self.camera_control
+            .send(NeoCamCommand::SimulateMotion(duration))
+            .await?;
+        Ok(())
+    }
+
+    /// Move through the PTZ presets waiting `dwell` at each
+    ///
+    /// With `repeat` it goes round until cancelled otherwise it stops
+    /// after the last preset
+    pub(crate) async fn ptz_tour(
+        &self,
+        presets: &[u8],
+        dwell: Duration,
+        repeat: bool,
+    ) -> Result<()> {
+        let name = self.config().await?.borrow().name.clone();
+        loop {
+            for &preset_id in presets.iter() {
+                log::info!("{}: PTZ tour moving to preset {}", name, preset_id);
+                self.run_task(|cam| {
+                    Box::pin(async move {
+                        cam.moveto_ptz_preset(preset_id)
+                            .await
+                            .context("Unable to move to PTZ preset")?;
+                        Ok(())
+                    })
+                })
+                .await?;
+                sleep(dwell).await;
+            }
+            if !repeat {
+                return Ok(());
+            }
+        }
+    }
+
     pub(crate) async fn config(&self) -> Result<WatchReceiver<CameraConfig>> {
         let (instance_tx, instance_rx) = oneshot();
         self.camera_control
@@ -347,6 +390,15 @@ impl NeoInstance {
         Ok(instance_rx.await?)
     }
 
+    /// The current state of the camera's connection
+    pub(crate) async fn connection_state(&self) -> Result<Arc<Mutex<CameraConnectionState>>> {
+        let (instance_tx, instance_rx) = oneshot();
+        self.camera_control
+            .send(NeoCamCommand::ConnectionState(instance_tx))
+            .await?;
+        Ok(instance_rx.await?)
+    }
+
     pub(crate) async fn permit(&self) -> Result<Permit> {
         let (instance_tx, instance_rx) = oneshot();
         self.camera_control
@@ -62,6 +62,17 @@ impl NeoCamMdThread {
                         } => {
                           let _ = sender.send(self.md_watcher.subscribe());
                         },
+                        MdRequest::Simulate {
+                            duration
+                        } => {
+                            log::info!("Simulating motion for {:?}", duration);
+                            self.md_watcher.send_replace(MdState::Start(Instant::now()));
+                            let watcher = self.md_watcher.clone();
+                            tokio::task::spawn(async move {
+                                sleep(duration).await;
+                                watcher.send_replace(MdState::Stop(Instant::now()));
+                            });
+                        },
                     }
                 }
                 Ok(())
@@ -112,4 +123,6 @@ pub(crate) enum MdRequest {
     Get {
         sender: OneshotSender<WatchReceiver<MdState>>,
     },
+    /// Start a motion event that is not from the camera and stop it after `duration`
+    Simulate { duration: Duration },
 }
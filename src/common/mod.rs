@@ -1,17 +1,23 @@
 mod camthread;
+mod cmdline;
+mod connections;
 mod instance;
 mod mdthread;
 mod neocam;
 mod pushnoti;
 mod reactor;
+mod snapshots;
 mod streamthread;
 mod usecounter;
 
 pub(crate) use camthread::*;
+pub(crate) use cmdline::*;
+pub(crate) use connections::*;
 pub(crate) use instance::*;
 pub(crate) use mdthread::*;
 pub(crate) use neocam::*;
 pub(crate) use pushnoti::*;
 pub(crate) use reactor::*;
+pub(crate) use snapshots::*;
 pub(crate) use streamthread::*;
 pub(crate) use usecounter::*;
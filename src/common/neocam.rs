@@ -6,7 +6,7 @@
 //!    Clonable interface to share amongst threadsanyhow::anyhow;
 use anyhow::Context;
 use futures::{stream::StreamExt, TryFutureExt};
-use std::sync::Weak;
+use std::sync::{Arc, Mutex, Weak};
 use tokio::{
     sync::{
         mpsc::{channel as mpsc, Sender as MpscSender},
@@ -20,8 +20,9 @@ use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::sync::CancellationToken;
 
 use super::{
-    MdRequest, MdState, NeoCamMdThread, NeoCamStreamThread, NeoCamThread, NeoCamThreadState,
-    NeoInstance, Permit, PnRequest, PushNoti, StreamInstance, StreamRequest, UseCounter,
+    scheduled_snapshots, CameraConnectionState, MdRequest, MdState, NeoCamMdThread,
+    NeoCamStreamThread, NeoCamThread, NeoCamThreadState, NeoConnections, NeoInstance, Permit,
+    PnRequest, PushNoti, StreamInstance, StreamRequest, UseCounter,
 };
 use crate::{config::CameraConfig, AnyResult, Result};
 use neolink_core::bc_protocol::{BcCamera, StreamKind};
@@ -35,6 +36,7 @@ pub(crate) enum NeoCamCommand {
     LowStream(OneshotSender<Option<StreamInstance>>),
     Streams(OneshotSender<Vec<StreamInstance>>),
     Motion(OneshotSender<WatchReceiver<MdState>>),
+    SimulateMotion(Duration),
     Config(OneshotSender<WatchReceiver<CameraConfig>>),
     Disconnect(OneshotSender<()>),
     Connect(OneshotSender<()>),
@@ -42,6 +44,7 @@ pub(crate) enum NeoCamCommand {
     GetPermit(OneshotSender<Permit>),
     PushNoti(OneshotSender<WatchReceiver<Option<PushNoti>>>),
     GetUid(OneshotSender<String>),
+    ConnectionState(OneshotSender<Arc<Mutex<CameraConnectionState>>>),
 }
 /// The underlying camera binding
 pub(crate) struct NeoCam {
@@ -56,6 +59,7 @@ impl NeoCam {
     pub(crate) async fn new(
         config: CameraConfig,
         pn_request_tx: MpscSender<PnRequest>,
+        connections: NeoConnections,
     ) -> Result<NeoCam> {
         let (commander_tx, commander_rx) = mpsc(100);
         let (watch_config_tx, watch_config_rx) = watch(config.clone());
@@ -64,6 +68,7 @@ impl NeoCam {
         let (md_request_tx, md_request_rx) = mpsc(100);
         let (state_tx, state_rx) = watch(NeoCamThreadState::Connected);
         let (uid_tx, uid_rx) = watch(config.camera_uid.clone());
+        let connection_state = Arc::new(Mutex::new(CameraConnectionState::Disconnected));
 
         let set = JoinSet::new();
         let users = UseCounter::new().await;
@@ -87,6 +92,7 @@ impl NeoCam {
         let thread_commander_tx = commander_tx.clone();
         let thread_watch_config_rx = watch_config_rx.clone();
         let thread_pn_request_tx = pn_request_tx.clone();
+        let thread_connection_state = connection_state.clone();
         me.set.spawn(async move {
             let thread_cancel = sender_cancel.clone();
             let res = tokio::select! {
@@ -145,6 +151,13 @@ impl NeoCam {
                                     }
                                 ).await?;
                             },
+                            NeoCamCommand::SimulateMotion(duration) => {
+                                md_request_tx.send(
+                                    MdRequest::Simulate {
+                                        duration,
+                                    }
+                                ).await?;
+                            },
                             NeoCamCommand::Config(sender) => {
                                 let _ = sender.send(thread_watch_config_rx.clone());
                             },
@@ -173,6 +186,9 @@ impl NeoCam {
                                     }
                                 ).await?;
                             },
+                            NeoCamCommand::ConnectionState(sender) => {
+                                let _ = sender.send(thread_connection_state.clone());
+                            }
                             NeoCamCommand::GetUid(sender) => {
                                 let mut uid_rx = uid_rx.clone();
                                 tokio::task::spawn(async move {
@@ -206,6 +222,8 @@ impl NeoCam {
             state_rx,
             thread_watch_config_rx,
             camera_watch_tx,
+            connection_state,
+            connections,
             me.cancel.clone(),
         )
         .await;
@@ -369,6 +387,75 @@ impl NeoCam {
             }
         });
 
+        // Cycles the PTZ presets of the ptz_tour config
+        let tour_instance = instance.subscribe().await?;
+        let tour_cancel = me.cancel.clone();
+        me.set.spawn(async move {
+            tokio::select!{
+                _ = tour_cancel.cancelled() => {
+                    AnyResult::Ok(())
+                },
+                v = async {
+                    let mut config_rx = tour_instance.config().await?;
+                    loop {
+                        // Wait for a tour
+                        let tour = config_rx.wait_for(|config| config.ptz_tour.is_some()).await?.ptz_tour.clone();
+                        let Some(tour) = tour else { continue; };
+
+                        tokio::select!{
+                            // Restart on any change of the tour
+                            v = config_rx.wait_for(|config| config.ptz_tour.as_ref() != Some(&tour)) => {
+                                v?;
+                            }
+                            v = async {
+                                if let Err(e) = tour_instance.ptz_tour(&tour.presets, Duration::from_secs(tour.dwell_secs), true).await {
+                                    log::warn!("PTZ tour stopped: {:?}", e);
+                                }
+                                // Wait for the config to change before trying again
+                                futures::future::pending::<()>().await;
+                            } => v,
+                        };
+                    }
+                } => {
+                    v
+                },
+            }
+        });
+
+        // Takes the snapshots of the scheduled_snapshots config
+        let snapshot_instance = instance.subscribe().await?;
+        let snapshot_cancel = me.cancel.clone();
+        me.set.spawn(async move {
+            tokio::select!{
+                _ = snapshot_cancel.cancelled() => {
+                    AnyResult::Ok(())
+                },
+                v = async {
+                    let snapshot_instance = &snapshot_instance;
+                    let mut config_rx = snapshot_instance.config().await?;
+                    loop {
+                        let schedules = config_rx.borrow_and_update().scheduled_snapshots.clone();
+                        tokio::select!{
+                            // Restart on any change of the schedules
+                            v = config_rx.wait_for(|config| config.scheduled_snapshots != schedules).map_ok(|_| ()) => {
+                                v?;
+                            }
+                            _ = futures::future::join_all(schedules.iter().map(|schedule| async move {
+                                if let Err(e) = scheduled_snapshots(snapshot_instance, schedule).await {
+                                    log::warn!("Scheduled snapshots stopped: {:?}", e);
+                                }
+                            })) => {
+                                // Wait for the config to change before trying again
+                                futures::future::pending::<()>().await;
+                            }
+                        };
+                    }
+                } => {
+                    v
+                },
+            }
+        });
+
         // This thread will apply battery saving by disconnecting the camera when there are no
         // active permits.
         //
@@ -14,12 +14,15 @@ use tokio::{
         watch::{channel as watch, Receiver as WatchReceiver, Sender as WatchSender},
         RwLock,
     },
-    time::{sleep, timeout, Duration},
+    time::{interval_at, sleep, timeout, Duration, Instant},
 };
 
 use super::NeoInstance;
 use crate::AnyResult;
 
+/// The cameras forget the push registration after a while so it is sent again this often
+const REGISTRATION_RENEWAL: Duration = Duration::from_secs(12 * 60 * 60);
+
 pub(crate) struct PushNotiThread {
     pn_watcher: Arc<WatchSender<Option<PushNoti>>>,
     registed_cameras: HashMap<String, NeoInstance>,
@@ -148,27 +151,11 @@ impl PushNotiThread {
             let thread_pn_watcher = self.pn_watcher.clone();
 
             for (_, instance) in self.registed_cameras.iter() {
-                let uid = uid.clone();
-                let fcm_token = fcm_token.clone();
-                let instance = instance.clone();
-                tokio::task::spawn(async move {
-                    let _ = instance
-                        .run_task(|camera| {
-                            let fcm_token = fcm_token.clone();
-                            let uid = uid.clone();
-                            Box::pin(async move {
-                                let r = camera.send_pushinfo_android(&fcm_token, &uid).await;
-                                log::debug!(
-                                    "Registered {} for push notifications: {:?}",
-                                    camera.uid().await?,
-                                    r
-                                );
-                                r?;
-                                AnyResult::Ok(())
-                            })
-                        })
-                        .await;
-                });
+                tokio::task::spawn(register_camera(
+                    instance.clone(),
+                    fcm_token.clone(),
+                    uid.clone(),
+                ));
             }
 
             let received_ids = self.received_ids.clone();
@@ -225,7 +212,25 @@ impl PushNotiThread {
                     }
                 } => v,
                 v = async {
-                    while let Some(msg) = pn_request_rx.recv().await {
+                    let mut renewal = interval_at(Instant::now() + REGISTRATION_RENEWAL, REGISTRATION_RENEWAL);
+                    loop {
+                        let msg = tokio::select! {
+                            msg = pn_request_rx.recv() => msg,
+                            _ = renewal.tick() => {
+                                log::debug!("Renewing push notification registrations");
+                                for (_, instance) in self.registed_cameras.iter() {
+                                    tokio::task::spawn(register_camera(
+                                        instance.clone(),
+                                        fcm_token.clone(),
+                                        uid.clone(),
+                                    ));
+                                }
+                                continue;
+                            }
+                        };
+                        let Some(msg) = msg else {
+                            break;
+                        };
                         match msg {
                             PnRequest::Get{sender} => {
                                 let _ = sender.send(self.pn_watcher.subscribe());
@@ -235,20 +240,7 @@ impl PushNotiThread {
                                 let fcm_token = fcm_token.clone();
                                 self.registed_cameras.insert(uid.clone(), instance.clone());
                                 tokio::task::spawn(async move {
-                                    let r = instance.run_task(|camera| {
-                                        let fcm_token = fcm_token.clone();
-                                        let uid = uid.clone();
-                                        Box::pin(async move {
-                                            let r = camera.send_pushinfo_android(&fcm_token, &uid).await;
-                                            log::debug!(
-                                                "Registered {} for push notifications: {:?}",
-                                                camera.uid().await?,
-                                                r
-                                            );
-                                            r?;
-                                            AnyResult::Ok(())
-                                        })
-                                    }).await;
+                                    let r = register_camera(instance, fcm_token, uid).await;
                                     let _ = sender.send(r);
                                 });
                             }
@@ -267,3 +259,23 @@ impl PushNotiThread {
         }
     }
 }
+
+/// Send the push notification token to a camera so that it will send us its events
+async fn register_camera(instance: NeoInstance, fcm_token: String, uid: String) -> AnyResult<()> {
+    instance
+        .run_task(|camera| {
+            let fcm_token = fcm_token.clone();
+            let uid = uid.clone();
+            Box::pin(async move {
+                let r = camera.send_pushinfo_android(&fcm_token, &uid).await;
+                log::debug!(
+                    "Registered {} for push notifications: {:?}",
+                    camera.uid().await?,
+                    r
+                );
+                r?;
+                AnyResult::Ok(())
+            })
+        })
+        .await
+}
@@ -16,7 +16,7 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 
-use super::{NeoCam, NeoInstance};
+use super::{NeoCam, NeoConnections, NeoInstance};
 use crate::{common::PushNotiThread, config::Config, AnyResult, Result};
 
 #[allow(clippy::large_enum_variant)]
@@ -44,6 +44,9 @@ impl NeoReactor {
         let (config_tx, _) = watch(config);
         let mut set = JoinSet::new();
         let config_tx = Arc::new(config_tx);
+        // The reactor owns the connections so that the channels of an NVR
+        // can use the same one whichever of them connects first
+        let connections = NeoConnections::default();
 
         let cancel1 = cancel.clone();
         let cancel2 = cancel.clone();
@@ -73,7 +76,7 @@ impl NeoReactor {
                                     Entry::Vacant(vac) => {
                                         let current_config: Config = (*thread_config_tx.borrow()).clone();
                                         if let Some(config) = current_config.cameras.iter().find(|cam| cam.name == name).cloned() {
-                                            let cam = NeoCam::new(config, push_noti.clone()).await?;
+                                            let cam = NeoCam::new(config, push_noti.clone(), connections.clone()).await?;
                                             Result::Ok(Some(
                                                 vac.insert(
                                                     cam,
@@ -0,0 +1,519 @@
+//! Snapshots that are taken on a cron like schedule
+//!
+//! The schedule has six fields `sec min hour day month weekday` and is in
+//! local time. Each field can be `*`, a value, a range `a-b`, a step `*/n`
+//! or `a-b/n`, or a comma separated list of those. Weekdays are 0-6 from
+//! Sunday and 7 is also Sunday. Both the day and the weekday must match
+use anyhow::{anyhow, Context, Result};
+use chrono::{
+    format::{Item, Pad, StrftimeItems},
+    DateTime, Datelike, Duration as ChronoDuration, Local, NaiveDate, NaiveDateTime, Timelike,
+};
+use regex::Regex;
+use std::fmt::Write;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tokio::time::{sleep, Duration};
+
+use super::NeoInstance;
+use crate::config::ScheduledSnapshotConfig;
+
+/// Format of `{timestamp}` in the output pattern
+const TIMESTAMP_FORMAT: &str = "%Y%m%d_%H%M%S";
+
+/// A parsed cron schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CronSchedule {
+    seconds: Vec<u32>,
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>,
+}
+
+impl CronSchedule {
+    pub(crate) fn parse(schedule: &str) -> Result<Self> {
+        let fields = schedule.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 6 {
+            return Err(anyhow!(
+                "Expected six fields `sec min hour day month weekday` but got {}",
+                fields.len()
+            ));
+        }
+        let mut weekdays = parse_field(fields[5], 0, 7).context("Invalid weekday")?;
+        // 7 is also Sunday
+        if weekdays.contains(&7) {
+            weekdays.retain(|&day| day != 7);
+            if !weekdays.contains(&0) {
+                weekdays.insert(0, 0);
+            }
+        }
+        Ok(Self {
+            seconds: parse_field(fields[0], 0, 59).context("Invalid second")?,
+            minutes: parse_field(fields[1], 0, 59).context("Invalid minute")?,
+            hours: parse_field(fields[2], 0, 23).context("Invalid hour")?,
+            days: parse_field(fields[3], 1, 31).context("Invalid day")?,
+            months: parse_field(fields[4], 1, 12).context("Invalid month")?,
+            weekdays,
+        })
+    }
+
+    /// The first time that matches the schedule strictly after `after`
+    ///
+    /// None if nothing matches in the next few years, such as the 31st of
+    /// February
+    pub(crate) fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = after.with_nanosecond(0)? + ChronoDuration::seconds(1);
+        let give_up = time + ChronoDuration::days(366 * 5);
+        while time < give_up {
+            if !self.months.contains(&time.month()) {
+                // Start of the next month
+                let (year, month) = match time.month() {
+                    12 => (time.year() + 1, 1),
+                    month => (time.year(), month + 1),
+                };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.days.contains(&time.day())
+                || !self
+                    .weekdays
+                    .contains(&time.weekday().num_days_from_sunday())
+            {
+                time = (time.date() + ChronoDuration::days(1)).and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.hours.contains(&time.hour()) {
+                time = time.date().and_hms_opt(time.hour(), 0, 0)? + ChronoDuration::hours(1);
+                continue;
+            }
+            if !self.minutes.contains(&time.minute()) {
+                time = time.date().and_hms_opt(time.hour(), time.minute(), 0)?
+                    + ChronoDuration::minutes(1);
+                continue;
+            }
+            if !self.seconds.contains(&time.second()) {
+                time += ChronoDuration::seconds(1);
+                continue;
+            }
+            return Some(time);
+        }
+        None
+    }
+}
+
+/// Parses one field of the schedule into its sorted values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>> {
+    let mut values = vec![];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(anyhow!("Step cannot be 0"));
+        }
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (start.parse::<u32>()?, end.parse::<u32>()?),
+                // A single value with a step runs to the end like `5/10`
+                None if step > 1 => (range.parse::<u32>()?, max),
+                None => {
+                    let value = range.parse::<u32>()?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(anyhow!("{} is outside of {}-{}", part, min, max));
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+/// Checks that the output pattern only has valid strftime specifiers
+pub(crate) fn validate_output_pattern(pattern: &str) -> Result<()> {
+    if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+        Err(anyhow!("Invalid strftime specifier in {}", pattern))
+    } else {
+        Ok(())
+    }
+}
+
+/// Replaces `{camera}` and `{timestamp}` leaving only strftime fields
+fn expand_placeholders(pattern: &str, camera: &str) -> String {
+    pattern
+        .replace("{camera}", &camera.replace('%', "%%"))
+        .replace("{timestamp}", TIMESTAMP_FORMAT)
+}
+
+/// The path of a snapshot taken now
+fn snapshot_path(pattern: &str, camera: &str, now: &DateTime<Local>) -> Result<PathBuf> {
+    let pattern = expand_placeholders(pattern, camera);
+    let mut path = String::new();
+    write!(path, "{}", now.format(&pattern))
+        .map_err(|_| anyhow!("Invalid strftime specifier in {}", pattern))?;
+    Ok(PathBuf::from(path))
+}
+
+/// Takes the snapshots of one schedule until an error
+pub(crate) async fn scheduled_snapshots(
+    instance: &NeoInstance,
+    config: &ScheduledSnapshotConfig,
+) -> Result<()> {
+    let schedule = CronSchedule::parse(&config.schedule)?;
+    loop {
+        let now = Local::now();
+        let next = schedule
+            .next_after(now.naive_local())
+            .with_context(|| format!("Schedule {} never fires", config.schedule))?;
+        let wait = (next - now.naive_local()).to_std().unwrap_or_default();
+        sleep(wait).await;
+
+        let name = instance.config().await?.borrow().name.clone();
+        match take_snapshot(instance, config, &name).await {
+            Ok(path) => log::debug!("{}: Saved scheduled snapshot to {:?}", name, path),
+            Err(e) => log::warn!("{}: Could not take scheduled snapshot: {:?}", name, e),
+        }
+        if let Some(max_age_hours) = config.max_age_hours {
+            if let Err(e) = remove_old_snapshots(&config.output_pattern, &name, max_age_hours).await
+            {
+                log::warn!("{}: Could not remove old snapshots: {:?}", name, e);
+            }
+        }
+        // Do not fire twice in the same second
+        sleep(Duration::from_millis(500)).await;
+    }
+}
+
+async fn take_snapshot(
+    instance: &NeoInstance,
+    config: &ScheduledSnapshotConfig,
+    name: &str,
+) -> Result<PathBuf> {
+    let jpeg_data = instance
+        .run_task(|camera| Box::pin(async move { Ok(camera.get_snapshot().await?) }))
+        .await?;
+    let path = snapshot_path(&config.output_pattern, name, &Local::now())?;
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::write(&path, jpeg_data).await?;
+    Ok(path)
+}
+
+/// Matches the paths that an output pattern can produce
+///
+/// The leading directories without any fields are kept as they are and each
+/// remaining path component becomes a regex so that `{camera}` and strftime
+/// fields in directory names are matched as well as in the file name
+struct SnapshotMatcher {
+    root: PathBuf,
+    components: Vec<Regex>,
+}
+
+impl SnapshotMatcher {
+    fn new(pattern: &str, camera: &str) -> Result<Self> {
+        let pattern = expand_placeholders(pattern, camera);
+        let parts = Path::new(&pattern).components().collect::<Vec<_>>();
+        let mut root = PathBuf::new();
+        let mut components = vec![];
+        for (i, part) in parts.iter().enumerate() {
+            let text = part
+                .as_os_str()
+                .to_str()
+                .context("Invalid output pattern")?;
+            // The file name is always matched even when it has no fields
+            if components.is_empty() && !text.contains('%') && i + 1 < parts.len() {
+                root.push(part);
+            } else {
+                components.push(component_regex(text)?);
+            }
+        }
+        if components.is_empty() {
+            return Err(anyhow!("Output pattern has no file name"));
+        }
+        Ok(Self { root, components })
+    }
+}
+
+/// A regex that matches everything that strftime can write for one component
+fn component_regex(component: &str) -> Result<Regex> {
+    let mut regex = String::from("^");
+    for item in StrftimeItems::new(component) {
+        match item {
+            Item::Literal(text) | Item::Space(text) => regex.push_str(&regex::escape(text)),
+            Item::OwnedLiteral(text) | Item::OwnedSpace(text) => {
+                regex.push_str(&regex::escape(&text))
+            }
+            Item::Numeric(_, Pad::Space) => regex.push_str(r" *-?\d+"),
+            Item::Numeric(..) => regex.push_str(r"-?\d+"),
+            Item::Fixed(_) => regex.push_str(".+?"),
+            Item::Error => return Err(anyhow!("Invalid strftime specifier in {}", component)),
+        }
+    }
+    regex.push('$');
+    Ok(Regex::new(&regex)?)
+}
+
+/// Removes the snapshots of this pattern that are older than `max_age_hours`
+///
+/// Only files whose whole path matches the expanded pattern are removed so
+/// that other files next to the snapshots are left alone
+async fn remove_old_snapshots(pattern: &str, camera: &str, max_age_hours: u32) -> Result<()> {
+    let matcher = SnapshotMatcher::new(pattern, camera)?;
+    let max_age = Duration::from_secs(max_age_hours as u64 * 60 * 60);
+
+    let mut dirs = vec![(matcher.root.clone(), 0)];
+    while let Some((dir, depth)) = dirs.pop() {
+        let read_from = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir.as_path()
+        };
+        let mut entries = match tokio::fs::read_dir(read_from).await {
+            Ok(entries) => entries,
+            // Nothing has been written here yet
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let is_last = depth + 1 == matcher.components.len();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let matches = name
+                .to_str()
+                .map(|name| matcher.components[depth].is_match(name))
+                .unwrap_or(false);
+            if !matches {
+                continue;
+            }
+            let path = dir.join(&name);
+            let metadata = entry.metadata().await?;
+            if !is_last {
+                if metadata.is_dir() {
+                    dirs.push((path, depth + 1));
+                }
+                continue;
+            }
+            let age = metadata.modified()?.elapsed().unwrap_or_default();
+            if metadata.is_file() && age > max_age {
+                log::debug!("Removing old snapshot {:?}", path);
+                tokio::fs::remove_file(&path).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, min, sec)
+            .unwrap()
+    }
+
+    /// Whether the matcher accepts every component of `path`
+    fn accepts(matcher: &SnapshotMatcher, path: &Path) -> bool {
+        let rest = match path.strip_prefix(&matcher.root) {
+            Ok(rest) => rest,
+            Err(_) => return false,
+        };
+        let parts = rest.components().collect::<Vec<_>>();
+        parts.len() == matcher.components.len()
+            && parts.iter().zip(&matcher.components).all(|(part, regex)| {
+                part.as_os_str()
+                    .to_str()
+                    .map(|part| regex.is_match(part))
+                    .unwrap_or(false)
+            })
+    }
+
+    #[test]
+    fn test_parse_field() {
+        assert_eq!(parse_field("*", 0, 3).unwrap(), vec![0, 1, 2, 3]);
+        assert_eq!(parse_field("5", 0, 59).unwrap(), vec![5]);
+        assert_eq!(parse_field("1-3", 0, 59).unwrap(), vec![1, 2, 3]);
+        assert_eq!(parse_field("*/20", 0, 59).unwrap(), vec![0, 20, 40]);
+        assert_eq!(parse_field("10-20/5", 0, 59).unwrap(), vec![10, 15, 20]);
+        assert_eq!(parse_field("50/5", 0, 59).unwrap(), vec![50, 55]);
+        assert_eq!(parse_field("3,1,2,1", 0, 59).unwrap(), vec![1, 2, 3]);
+
+        assert!(parse_field("60", 0, 59).is_err());
+        assert!(parse_field("0", 1, 31).is_err());
+        assert!(parse_field("5-1", 0, 59).is_err());
+        assert!(parse_field("*/0", 0, 59).is_err());
+        assert!(parse_field("a", 0, 59).is_err());
+        assert!(parse_field("", 0, 59).is_err());
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let schedule = CronSchedule::parse("0 */15 8-17 * * 1-5").unwrap();
+        assert_eq!(schedule.seconds, vec![0]);
+        assert_eq!(schedule.minutes, vec![0, 15, 30, 45]);
+        assert_eq!(schedule.hours, (8..=17).collect::<Vec<_>>());
+        assert_eq!(schedule.days, (1..=31).collect::<Vec<_>>());
+        assert_eq!(schedule.months, (1..=12).collect::<Vec<_>>());
+        assert_eq!(schedule.weekdays, vec![1, 2, 3, 4, 5]);
+
+        // 7 is Sunday too
+        assert_eq!(
+            CronSchedule::parse("0 0 0 * * 7").unwrap().weekdays,
+            vec![0]
+        );
+        assert_eq!(
+            CronSchedule::parse("0 0 0 * * 0,6,7").unwrap().weekdays,
+            vec![0, 6]
+        );
+
+        assert!(CronSchedule::parse("0 0 * * *").is_err());
+        assert!(CronSchedule::parse("0 0 0 * * * *").is_err());
+        assert!(CronSchedule::parse("0 0 24 * * *").is_err());
+        assert!(CronSchedule::parse("0 0 0 * 13 *").is_err());
+    }
+
+    #[test]
+    fn test_next_after() {
+        let every_minute = CronSchedule::parse("0 * * * * *").unwrap();
+        assert_eq!(
+            every_minute.next_after(at(2023, 5, 1, 10, 30, 0)),
+            Some(at(2023, 5, 1, 10, 31, 0))
+        );
+        // Strictly after even with a fraction of a second
+        assert_eq!(
+            every_minute.next_after(at(2023, 5, 1, 10, 30, 0) + ChronoDuration::milliseconds(500)),
+            Some(at(2023, 5, 1, 10, 31, 0))
+        );
+        assert_eq!(
+            every_minute.next_after(at(2023, 12, 31, 23, 59, 30)),
+            Some(at(2024, 1, 1, 0, 0, 0))
+        );
+
+        // 2023-05-06 is a Saturday
+        let weekdays = CronSchedule::parse("30 0 9 * * 1-5").unwrap();
+        assert_eq!(
+            weekdays.next_after(at(2023, 5, 6, 12, 0, 0)),
+            Some(at(2023, 5, 8, 9, 0, 30))
+        );
+
+        let leap_day = CronSchedule::parse("0 0 0 29 2 *").unwrap();
+        assert_eq!(
+            leap_day.next_after(at(2023, 1, 1, 0, 0, 0)),
+            Some(at(2024, 2, 29, 0, 0, 0))
+        );
+
+        let never = CronSchedule::parse("0 0 0 31 2 *").unwrap();
+        assert_eq!(never.next_after(at(2023, 1, 1, 0, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_matcher_matches_written_paths() {
+        let now = Local.with_ymd_and_hms(2023, 5, 1, 8, 5, 9).unwrap();
+        for pattern in [
+            "{timestamp}.jpg",
+            "snapshots/{camera}_{timestamp}.jpg",
+            "/var/snapshots/{camera}/%Y-%m-%d/%H%M%S.jpg",
+            "snapshots/%b %e/{camera} %I%p.jpg",
+        ] {
+            let matcher = SnapshotMatcher::new(pattern, "Garage").unwrap();
+            let path = snapshot_path(pattern, "Garage", &now).unwrap();
+            assert!(
+                accepts(&matcher, &path),
+                "{} did not match {:?}",
+                pattern,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_matcher_expands_directories() {
+        let matcher =
+            SnapshotMatcher::new("/var/snapshots/{camera}/%Y-%m-%d/%H%M%S.jpg", "Garage").unwrap();
+        assert_eq!(matcher.root, PathBuf::from("/var/snapshots/Garage"));
+        assert_eq!(matcher.components.len(), 2);
+
+        let matcher = SnapshotMatcher::new("snapshots/%Y/{camera}/latest.jpg", "Garage").unwrap();
+        assert_eq!(matcher.root, PathBuf::from("snapshots"));
+        assert_eq!(matcher.components.len(), 3);
+        assert!(accepts(
+            &matcher,
+            Path::new("snapshots/2023/Garage/latest.jpg")
+        ));
+        assert!(!accepts(
+            &matcher,
+            Path::new("snapshots/2023/Porch/latest.jpg")
+        ));
+    }
+
+    #[test]
+    fn test_matcher_ignores_other_files() {
+        let matcher = SnapshotMatcher::new("{timestamp}.jpg", "Garage").unwrap();
+        assert_eq!(matcher.root, PathBuf::new());
+        assert!(accepts(&matcher, Path::new("20230501_080509.jpg")));
+        assert!(!accepts(&matcher, Path::new("holiday.jpg")));
+        assert!(!accepts(&matcher, Path::new("20230501_080509.png")));
+        assert!(!accepts(&matcher, Path::new("20230501_080509.jpg.bak")));
+
+        let matcher = SnapshotMatcher::new("snaps/{camera}_{timestamp}.jpg", "Garage").unwrap();
+        assert!(accepts(
+            &matcher,
+            Path::new("snaps/Garage_20230501_080509.jpg")
+        ));
+        assert!(!accepts(
+            &matcher,
+            Path::new("snaps/Porch_20230501_080509.jpg")
+        ));
+        assert!(!accepts(
+            &matcher,
+            Path::new("snaps/Garage_20230501_080509.jpg/x.jpg")
+        ));
+
+        // Camera names are literal even with regex or strftime characters
+        let matcher = SnapshotMatcher::new("{camera}_{timestamp}.jpg", "a.b%Y").unwrap();
+        assert!(accepts(&matcher, Path::new("a.b%Y_20230501_080509.jpg")));
+        assert!(!accepts(&matcher, Path::new("axb2023_20230501_080509.jpg")));
+    }
+
+    #[tokio::test]
+    async fn test_remove_old_snapshots() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("neolink_snapshots_{}", std::process::id()));
+        let day = dir.join("Garage").join("2023-05-01");
+        std::fs::create_dir_all(&day)?;
+        let old = std::time::SystemTime::now() - Duration::from_secs(3 * 60 * 60);
+        let files = [
+            (day.join("080509.jpg"), old, false),
+            (day.join("notes.jpg"), old, true),
+            (day.join("080510.jpg"), std::time::SystemTime::now(), true),
+            (dir.join("Garage").join("080509.jpg"), old, true),
+        ];
+        for (path, modified, _) in files.iter() {
+            std::fs::File::create(path)?.set_modified(*modified)?;
+        }
+
+        let pattern = format!("{}/{{camera}}/%Y-%m-%d/%H%M%S.jpg", dir.display());
+        let result = remove_old_snapshots(&pattern, "Garage", 1).await;
+        let kept = files
+            .iter()
+            .map(|(path, _, _)| path.exists())
+            .collect::<Vec<_>>();
+        std::fs::remove_dir_all(&dir)?;
+
+        result?;
+        assert_eq!(
+            kept,
+            files.iter().map(|(_, _, keep)| *keep).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+}
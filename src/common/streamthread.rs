@@ -242,6 +242,7 @@ pub(crate) enum VidFormat {
     None,
     H264,
     H265,
+    Av1,
 }
 #[derive(Eq, PartialEq, Clone, Debug, Copy)]
 pub(crate) enum AudFormat {
@@ -280,7 +281,6 @@ pub(crate) struct StampedData {
 }
 
 pub(crate) struct StreamInstance {
-    #[allow(dead_code)]
     pub(crate) name: StreamKind,
     pub(crate) vid: BroadcastReceiver<StampedData>,
     pub(crate) vid_history: WatchReceiver<VecDeque<StampedData>>,
@@ -318,6 +318,7 @@ impl StreamData {
     async fn new(name: StreamKind, instance: NeoInstance, strict: bool) -> Result<Self> {
         let buffer_duration =
             Duration::from_millis(instance.config().await?.borrow().buffer_duration);
+        let min_iframe_bytes = instance.config().await?.borrow().min_iframe_bytes;
         log::trace!("New StreamData::{name:?}");
         // At 30fps for 15s with audio is is about 900 frames
         let buffer_size: usize = 30usize * buffer_duration.as_millis() as usize / 1000usize;
@@ -482,6 +483,7 @@ impl StreamData {
                                     let fps_table = fps_table.clone();
                                     let master_ts = master_ts.clone();
                                     let fps_delta = fps_delta.clone();
+                                    let print_name = print_name.clone();
 
                                     Box::pin(async move {
                                         log::trace!("Starting streamthread TASK");
@@ -489,6 +491,7 @@ impl StreamData {
                                         // let mut file = std::fs::File::create("reference.h264")?;
                                         let mut recieved_iframe = false;
                                         let mut aud_keyframe = false;
+                                        let mut corrupt_frames_skipped = 0u64;
 
                                         let res = async {
                                             let mut stream_data = camera.start_video(name, 0, strict).await?;
@@ -535,6 +538,7 @@ impl StreamData {
                                                             let expected = match frame.video_type {
                                                                 VideoType::H264 => VidFormat::H264,
                                                                 VideoType::H265 => VidFormat::H265,
+                                                                VideoType::Av1 => VidFormat::Av1,
                                                             };
                                                             if state.vid_format != expected {
                                                                 state.vid_format = expected;
@@ -550,6 +554,7 @@ impl StreamData {
                                                             let expected = match frame.video_type {
                                                                 VideoType::H264 => VidFormat::H264,
                                                                 VideoType::H265 => VidFormat::H265,
+                                                                VideoType::Av1 => VidFormat::Av1,
                                                             };
                                                             if state.vid_format != expected {
                                                                 state.vid_format = expected;
@@ -584,6 +589,18 @@ impl StreamData {
                                                 }
 
                                                 match data {
+                                                    BcMedia::Iframe(BcMediaIframe{data, ..}) if data.len() < min_iframe_bytes => {
+                                                        // Too small to be a real keyframe. It is not
+                                                        // passed on or treated as a keyframe so that the
+                                                        // decoder keeps going from the last good one
+                                                        corrupt_frames_skipped += 1;
+                                                        log::warn!(
+                                                            "{print_name}: Skipped a keyframe of {} bytes as it is under {} bytes ({} skipped)",
+                                                            data.len(),
+                                                            min_iframe_bytes,
+                                                            corrupt_frames_skipped
+                                                        );
+                                                    }
                                                     BcMedia::Iframe(BcMediaIframe{data, ..}) => {
                                                         let d = StampedData{
                                                                 keyframe: true,
@@ -0,0 +1,10 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// The compliance-report command documents how the cameras' video is handled
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The markdown file to write the report to. Printed if not given
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
@@ -0,0 +1,267 @@
+///
+/// # Neolink Compliance Report
+///
+/// This module writes a markdown report of what the config does with the
+/// cameras' video for privacy audits such as for GDPR. It lists
+///
+/// - Each camera with its address and rtsp paths
+/// - Who can watch each stream and whether the rtsp server uses TLS
+/// - Where snapshots and DASH segments are stored and for how long
+/// - Where motion events are sent, over MQTT or by email
+///
+/// Only the config is read, the cameras are not contacted. Passwords are
+/// never written to the report
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink compliance-report --config=config.toml --output=report.md
+/// ```
+///
+use anyhow::{Context, Result};
+use std::fmt::Write;
+
+mod cmdline;
+
+use crate::{
+    common::NeoReactor,
+    config::{CameraConfig, Config},
+    utils::redact_url,
+};
+pub(crate) use cmdline::Opt;
+
+/// Entry point for the compliance-report subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let config = reactor.config().await?.borrow().clone();
+    let report = build_report(&config)?;
+    match opt.output.as_ref() {
+        Some(path) => {
+            tokio::fs::write(path, report)
+                .await
+                .with_context(|| format!("Unable to write {:?}", path))?;
+            println!("Wrote the compliance report to {:?}", path);
+        }
+        None => print!("{}", report),
+    }
+    Ok(())
+}
+
+fn build_report(config: &Config) -> Result<String> {
+    let mut md = String::new();
+    writeln!(md, "# Neolink Compliance Report")?;
+    writeln!(md)?;
+    writeln!(
+        md,
+        "- Generated: {}",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S %Z")
+    )?;
+    writeln!(md, "- Neolink version: {}", env!("NEOLINK_VERSION"))?;
+    writeln!(md, "- Cameras: {}", config.cameras.len())?;
+    writeln!(md)?;
+
+    writeln!(md, "## RTSP Server")?;
+    writeln!(md)?;
+    writeln!(
+        md,
+        "- Listening on: `{}:{}`",
+        config.bind_addr, config.bind_port
+    )?;
+    match config.certificate.as_ref() {
+        Some(certificate) => writeln!(
+            md,
+            "- TLS: enabled with `{}`, client certificates `{}`",
+            certificate, config.tls_client_auth
+        )?,
+        None => writeln!(md, "- TLS: **disabled**, video is sent unencrypted")?,
+    }
+    if config.users.is_empty() {
+        writeln!(
+            md,
+            "- Users: none, streams without `permitted_users` are public"
+        )?;
+    } else {
+        let users = config
+            .users
+            .iter()
+            .map(|user| {
+                if user.admin {
+                    format!("`{}` (admin)", user.name)
+                } else {
+                    format!("`{}`", user.name)
+                }
+            })
+            .collect::<Vec<_>>();
+        writeln!(md, "- Users: {}", users.join(", "))?;
+    }
+    writeln!(md)?;
+
+    writeln!(md, "## Cameras")?;
+    writeln!(md)?;
+    writeln!(
+        md,
+        "| Camera | Enabled | Address | RTSP paths | Who can watch |"
+    )?;
+    writeln!(md, "| --- | --- | --- | --- | --- |")?;
+    for camera in config.cameras.iter() {
+        writeln!(
+            md,
+            "| {} | {} | {} | {} | {} |",
+            cell(&camera.name),
+            if camera.enabled { "yes" } else { "no" },
+            cell(&address(camera)),
+            cell(&rtsp_paths(camera).join(", ")),
+            cell(&viewers(config, camera)),
+        )?;
+    }
+    writeln!(md)?;
+
+    writeln!(md, "## Storage and Retention")?;
+    writeln!(md)?;
+    writeln!(
+        md,
+        "Neolink does not record continuously. Video is only stored by the outputs below"
+    )?;
+    writeln!(md)?;
+    let mut stored = false;
+    for camera in config.cameras.iter() {
+        for snapshots in camera.scheduled_snapshots.iter() {
+            stored = true;
+            let retention = match snapshots.max_age_hours {
+                Some(hours) => format!("deleted after {} hours", hours),
+                None => "**kept until deleted by hand**".to_string(),
+            };
+            writeln!(
+                md,
+                "- {}: snapshots on `{}` to `{}`, {}",
+                camera.name, snapshots.schedule, snapshots.output_pattern, retention
+            )?;
+        }
+        if let Some(dash) = camera.dash.as_ref() {
+            stored = true;
+            writeln!(
+                md,
+                "- {}: MPEG-DASH segments in `{}` served on `{}:{}`, **not deleted by neolink**",
+                camera.name,
+                dash.output_dir.display(),
+                dash.bind_addr,
+                dash.bind_port
+            )?;
+        }
+    }
+    if !stored {
+        writeln!(md, "- No camera stores video or snapshots")?;
+    }
+    writeln!(md)?;
+
+    writeln!(md, "## Event Forwarding")?;
+    writeln!(md)?;
+    let mut forwarded = false;
+    for camera in config.cameras.iter() {
+        if let Some(mqtt) = config.mqtt.as_ref() {
+            let mut events = vec![];
+            if camera.mqtt.enable_motion {
+                events.push("motion");
+            }
+            if camera.mqtt.enable_preview {
+                events.push("preview images");
+            }
+            if !events.is_empty() {
+                forwarded = true;
+                writeln!(
+                    md,
+                    "- {}: {} to the MQTT broker `{}:{}`{}",
+                    camera.name,
+                    events.join(" and "),
+                    mqtt.broker_addr,
+                    mqtt.port,
+                    if mqtt.ca.is_some() { " over TLS" } else { "" }
+                )?;
+            }
+        }
+        if let Some(email) = camera.email.as_ref() {
+            if email.enabled == Some(true) {
+                forwarded = true;
+                let recipients = email.recipients.as_ref().map(Vec::len).unwrap_or(0);
+                writeln!(
+                    md,
+                    "- {}: motion emails sent by the camera through `{}` to {} recipient(s)",
+                    camera.name,
+                    email
+                        .smtp_host
+                        .as_deref()
+                        .unwrap_or("the camera's smtp server"),
+                    recipients
+                )?;
+            }
+        }
+    }
+    if !forwarded {
+        writeln!(md, "- No camera forwards its events")?;
+    }
+    Ok(md)
+}
+
+/// How neolink reaches the camera
+fn address(camera: &CameraConfig) -> String {
+    if camera.is_external_rtsp() {
+        let url = camera.rtsp_url.as_deref().unwrap_or_default();
+        return format!("relays {}", redact_url(url));
+    }
+    match (camera.camera_addr.as_ref(), camera.camera_uid.as_ref()) {
+        (Some(addr), _) => addr.clone(),
+        (None, Some(uid)) => format!("UID {} through the Reolink relay", uid),
+        (None, None) => "unknown".to_string(),
+    }
+}
+
+/// The main path of each stream and the aliases
+fn rtsp_paths(camera: &CameraConfig) -> Vec<String> {
+    if !camera.enabled {
+        return vec![];
+    }
+    let name = &camera.name;
+    let mut paths = vec![format!("/{}", name)];
+    if camera.is_external_rtsp() {
+        paths.push(format!("/{}/main", name));
+    } else {
+        for kind in camera.stream.as_stream_kinds() {
+            let kind = format!("{:?}", kind).to_lowercase();
+            paths.push(format!("/{}/{}", name, kind));
+        }
+    }
+    paths.extend(camera.path_aliases.iter().cloned());
+    paths
+}
+
+/// The same rules as the rtsp server uses to give the stream its users
+fn viewers(config: &Config, camera: &CameraConfig) -> String {
+    let all_users = || {
+        config
+            .users
+            .iter()
+            .map(|user| user.name.clone())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match camera.permitted_users.as_ref() {
+        Some(users) if users.iter().any(|user| user == "anonymous") => {
+            "**anyone, no authentication**".to_string()
+        }
+        Some(users) if users.iter().any(|user| user == "anyone") => all_users(),
+        Some(users) => users.join(", "),
+        None if config.users.is_empty() => "**anyone, no authentication**".to_string(),
+        None => all_users(),
+    }
+}
+
+/// Escape the text for a markdown table
+fn cell(text: &str) -> String {
+    if text.is_empty() {
+        "-".to_string()
+    } else {
+        text.replace('|', "\\|")
+    }
+}
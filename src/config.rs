@@ -1,5 +1,8 @@
 use crate::mqtt::Discoveries;
-use neolink_core::bc_protocol::{DiscoveryMethods, PrintFormat, StreamKind};
+use neolink_core::bc_protocol::{
+    DiscoveryMethods, EmailTlsMode, MotionRegion, OsdPosition, PrintFormat, QualityPreset,
+    StreamKind,
+};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -11,11 +14,18 @@ use validator_derive::Validate;
 static RE_TLS_CLIENT_AUTH: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^(none|request|require)$").unwrap());
 static RE_PAUSE_MODE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(black|still|test|none)$").unwrap());
+static RE_OSD_FORMAT: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(DMY|MDY|YMD)$").unwrap());
+static RE_SOURCE_TYPE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(bc_protocol|rtsp_url)$").unwrap());
+static RE_STREAM_KIND: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(main|sub|extern)$").unwrap());
+static RE_PROXY_URL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^http://(\[[0-9a-fA-F:.]+\]|[^/:\[\]]+)(:[0-9]{1,5})?/?$").unwrap());
+static RE_EMAIL_SCHEDULE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[01]{168}$").unwrap());
 static RE_MAXENC_SRC: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^([nN]one|[Aa][Ee][Ss]|[Bb][Cc][Ee][Nn][Cc][Rr][Yy][Pp][Tt])$").unwrap()
 });
 
 #[derive(Debug, Deserialize, Serialize, Validate, Clone, PartialEq)]
+#[validate(schema(function = "validate_config"))]
 pub(crate) struct Config {
     #[validate]
     pub(crate) cameras: Vec<CameraConfig>,
@@ -27,6 +37,62 @@ pub(crate) struct Config {
     #[serde(default = "default_bind_port")]
     pub(crate) bind_port: u16,
 
+    /// Latency of the RTP jitter buffer in ms
+    #[validate(range(
+        max = 10000,
+        message = "Invalid jitter buffer latency (it's in ms)",
+        code = "rtpjitterbuffer_latency_ms"
+    ))]
+    #[serde(
+        default = "default_rtpjitterbuffer_latency_ms",
+        alias = "rtsp_latency",
+        alias = "latency"
+    )]
+    pub(crate) rtpjitterbuffer_latency_ms: u32,
+
+    /// Maximum number of threads used to handle rtsp sessions
+    #[validate(range(
+        min = 1,
+        max = 1024,
+        message = "Invalid rtsp thread count",
+        code = "rtsp_threads"
+    ))]
+    #[serde(default = "default_rtsp_threads")]
+    pub(crate) rtsp_threads: u32,
+
+    /// The TCP listen backlog of the rtsp server
+    ///
+    /// Large enough for every NVR channel to reconnect at once after a restart
+    #[validate(range(
+        min = 1,
+        max = 65535,
+        message = "Invalid rtsp listen backlog",
+        code = "rtsp_max_backlog"
+    ))]
+    #[serde(default = "default_rtsp_max_backlog", alias = "rtsp_backlog")]
+    pub(crate) rtsp_max_backlog: u32,
+
+    /// Match rtsp paths regardless of case
+    #[serde(default = "default_false")]
+    pub(crate) rtsp_case_insensitive_paths: bool,
+
+    /// Browser origins that may open the rtsp over http tunnel
+    #[validate(custom(function = "validate_cors_origins"))]
+    #[serde(default)]
+    pub(crate) rtsp_cors_origins: Vec<String>,
+
+    /// The clock that all the rtsp media are timed with
+    #[serde(default = "default_shared_clock")]
+    pub(crate) shared_clock: SharedClock,
+
+    /// Port of the http health endpoint, it is not served when unset
+    #[serde(default, alias = "health_port")]
+    pub(crate) health_bind_port: Option<u16>,
+
+    /// Label the per client rtsp metrics with the ip address of the client
+    #[serde(default = "default_false")]
+    pub(crate) detailed_metrics: bool,
+
     #[serde(default = "default_tokio_console")]
     pub(crate) tokio_console: bool,
 
@@ -67,6 +133,124 @@ pub(crate) struct MqttServerConfig {
     pub(crate) client_auth: Option<(std::path::PathBuf, std::path::PathBuf)>,
 }
 
+impl CameraConfig {
+    /// If the video is relayed from an RTSP url rather than the BC protocol
+    pub(crate) fn is_external_rtsp(&self) -> bool {
+        self.source_type == "rtsp_url"
+    }
+}
+
+impl Config {
+    /// Parses the config applying the `[[camera_groups]]` to their cameras
+    ///
+    /// A group has the same fields as a camera plus the `cameras` it is
+    /// for. Its fields are the defaults of those cameras and anything set on
+    /// the camera itself is kept. Tables like `[camera_groups.pause]` are
+    /// merged key by key
+    pub(crate) fn from_toml(config: &str) -> anyhow::Result<Self> {
+        let mut table = toml::from_str::<toml::Table>(config)?;
+        apply_camera_groups(&mut table)?;
+        Ok(toml::Value::Table(table).try_into::<Config>()?)
+    }
+
+    /// Adds a camera for each of the extra `channels` of a camera
+    ///
+    /// They are named `{name}_ch{channel}` and share the connection
+    /// of the camera that listed them
+    pub(crate) fn expand_channels(mut self) -> Self {
+        let mut extra = vec![];
+        for camera in self.cameras.iter() {
+            for &channel_id in camera.channels.iter() {
+                if channel_id == camera.channel_id {
+                    continue;
+                }
+                let name = format!("{}_ch{}", camera.name, channel_id);
+                if self.cameras.iter().any(|cam| cam.name == name) {
+                    continue;
+                }
+                extra.push(CameraConfig {
+                    name,
+                    channel_id,
+                    channels: vec![],
+                    // The aliases are for the camera they are set on only
+                    path_aliases: vec![],
+                    shared_connection: Some(camera.name.clone()),
+                    ..camera.clone()
+                });
+            }
+        }
+        self.cameras.extend(extra);
+        self
+    }
+}
+
+/// Merges each camera group into the cameras it lists and removes the groups
+fn apply_camera_groups(table: &mut toml::Table) -> anyhow::Result<()> {
+    let groups = match table
+        .remove("camera_groups")
+        .or_else(|| table.remove("camera_group"))
+    {
+        Some(toml::Value::Array(groups)) => groups,
+        Some(_) => return Err(anyhow::anyhow!("camera_groups should be [[camera_groups]]")),
+        None => return Ok(()),
+    };
+    let mut grouped = HashSet::new();
+    for group in groups {
+        let toml::Value::Table(mut group) = group else {
+            return Err(anyhow::anyhow!("camera_groups should be [[camera_groups]]"));
+        };
+        if group.contains_key("name") {
+            return Err(anyhow::anyhow!(
+                "A camera group cannot set name, list the cameras instead"
+            ));
+        }
+        let names = match group.remove("cameras") {
+            Some(toml::Value::Array(names)) => names
+                .into_iter()
+                .map(|name| match name {
+                    toml::Value::String(name) => Ok(name),
+                    _ => Err(anyhow::anyhow!("The cameras of a group should be names")),
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            _ => return Err(anyhow::anyhow!("A camera group needs a list of cameras")),
+        };
+        for name in names {
+            if !grouped.insert(name.clone()) {
+                return Err(anyhow::anyhow!("{name} is in more than one camera group"));
+            }
+            let camera = table
+                .get_mut("cameras")
+                .and_then(|cameras| cameras.as_array_mut())
+                .and_then(|cameras| {
+                    cameras.iter_mut().find(|camera| {
+                        camera.get("name").and_then(|name| name.as_str()) == Some(name.as_str())
+                    })
+                })
+                .and_then(|camera| camera.as_table_mut())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("The camera group lists {name} which is not a camera")
+                })?;
+            merge_defaults(camera, &group);
+        }
+    }
+    Ok(())
+}
+
+/// Adds the keys of `defaults` that `table` does not have, merging sub tables
+fn merge_defaults(table: &mut toml::Table, defaults: &toml::Table) {
+    for (key, default) in defaults.iter() {
+        match (table.get_mut(key), default) {
+            (None, default) => {
+                table.insert(key.clone(), default.clone());
+            }
+            (Some(toml::Value::Table(table)), toml::Value::Table(default)) => {
+                merge_defaults(table, default);
+            }
+            (Some(_), _) => {}
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, Eq, PartialEq)]
 pub(crate) enum StreamConfig {
     #[serde(alias = "none")]
@@ -144,6 +328,11 @@ pub(crate) struct CameraConfig {
 
     pub(crate) permitted_users: Option<Vec<String>>,
 
+    /// More rtsp paths that serve the same stream as `/{name}`
+    #[validate(custom(function = "validate_path_aliases"))]
+    #[serde(default, alias = "aliases")]
+    pub(crate) path_aliases: Vec<String>,
+
     #[validate(range(min = 0, max = 31, message = "Invalid channel", code = "channel_id"))]
     #[serde(default = "default_channel_id", alias = "channel")]
     pub(crate) channel_id: u8,
@@ -191,18 +380,62 @@ pub(crate) struct CameraConfig {
     )]
     pub(crate) buffer_duration: u64,
 
+    /// Number of frames that can wait to go into gstreamer before the oldest
+    /// non keyframes are dropped
+    #[validate(range(
+        min = 1,
+        max = 100000,
+        message = "Invalid frame buffer size",
+        code = "frame_buffer_size"
+    ))]
+    #[serde(default = "default_frame_buffer_size")]
+    pub(crate) frame_buffer_size: usize,
+
+    /// Keyframes smaller than this many bytes are assumed to be corrupt
+    /// and are not passed on
+    #[serde(default = "default_min_iframe_bytes")]
+    pub(crate) min_iframe_bytes: usize,
+
+    /// Audio frames are dropped or repeated to bring the audio back in line
+    /// with the video when they drift apart by more than this many ms. `0`
+    /// disables the correction
+    #[validate(range(
+        max = 10000,
+        message = "Invalid A/V sync threshold (it's in ms)",
+        code = "av_sync_threshold_ms"
+    ))]
+    #[serde(default = "default_av_sync_threshold_ms")]
+    pub(crate) av_sync_threshold_ms: u64,
+
+    /// Take the audio of the rtsp streams from somewhere other than this camera
+    #[serde(default)]
+    pub(crate) external_audio: Option<ExternalAudioSource>,
+
     #[serde(default = "default_true", alias = "enable")]
     pub(crate) enabled: bool,
 
     #[serde(default = "default_false", alias = "verbose")]
     pub(crate) debug: bool,
 
+    /// If false the rtsp streams are served without an audio track
+    #[serde(default = "default_true", alias = "enable_audio")]
+    pub(crate) audio: bool,
+
     #[serde(default = "default_true", alias = "splash")]
     pub(crate) use_splash: bool,
 
     #[serde(default = "default_splash", alias = "pattern")]
     pub(crate) splash_pattern: SplashPattern,
 
+    /// How the frames given to gstreamer are timestamped
+    #[serde(default = "default_frame_timestamps", alias = "timestamp_mode")]
+    pub(crate) frame_timestamps: FrameTimestampMode,
+
+    /// Seconds to wait for the video before the rtsp paths show the splash
+    /// pattern instead. 0 waits forever
+    #[serde(default = "default_format_detect_timeout_secs")]
+    pub(crate) format_detect_timeout_secs: u32,
+
     #[serde(
         default = "default_max_discovery_retries",
         alias = "retries",
@@ -210,11 +443,76 @@ pub(crate) struct CameraConfig {
     )]
     pub(crate) max_discovery_retries: usize,
 
+    /// Seconds to wait for the connection to an `address` and for the login
+    #[validate(range(
+        min = 1,
+        max = 300,
+        message = "Invalid connect timeout should be 1-300 seconds",
+        code = "connect_timeout_secs"
+    ))]
+    #[serde(default = "default_connect_timeout_secs", alias = "connect_timeout")]
+    pub(crate) connect_timeout_secs: u32,
+
     #[serde(default = "default_true", alias = "push", alias = "push_noti")]
     pub(crate) push_notifications: bool,
 
     #[serde(default = "default_false", alias = "idle", alias = "idle_disc")]
     pub(crate) idle_disconnect: bool,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) osd: Option<OsdConfig>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) stream_quality: Option<StreamQualityConfig>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) motion_detection: Option<MotionDetectionConfig>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) dash: Option<DashConfig>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) ptz_tour: Option<PtzTourConfig>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) scheduled_snapshots: Vec<ScheduledSnapshotConfig>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) email: Option<EmailConfig>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) proxy: Option<ProxyConfig>,
+
+    /// Where the video comes from `"bc_protocol"` or `"rtsp_url"`
+    #[validate(regex(
+        path = *RE_SOURCE_TYPE,
+        message = "Incorrect source type should be bc_protocol or rtsp_url",
+        code = "source_type"
+    ))]
+    #[serde(default = "default_source_type", alias = "source")]
+    pub(crate) source_type: String,
+
+    /// The RTSP url to relay when `source_type` is `"rtsp_url"`
+    #[serde(default)]
+    pub(crate) rtsp_url: Option<String>,
+
+    /// Extra NVR channels to serve over this camera's connection
+    #[validate(custom(function = "validate_channels"))]
+    #[serde(default)]
+    pub(crate) channels: Vec<u8>,
+
+    /// Name of the camera whose connection is shared. This is set
+    /// on the cameras created from `channels`
+    #[serde(skip)]
+    pub(crate) shared_connection: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Validate, Clone, PartialEq, Eq, Hash)]
@@ -225,6 +523,10 @@ pub(crate) struct UserConfig {
 
     #[serde(alias = "password")]
     pub(crate) pass: String,
+
+    /// May change the stream with rtsp SET_PARAMETER
+    #[serde(default)]
+    pub(crate) admin: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Validate, PartialEq, Eq)]
@@ -315,6 +617,22 @@ fn default_print() -> PrintFormat {
     PrintFormat::None
 }
 
+fn default_frame_buffer_size() -> usize {
+    2000
+}
+
+fn default_min_iframe_bytes() -> usize {
+    100
+}
+
+fn default_av_sync_threshold_ms() -> u64 {
+    40
+}
+
+fn default_format_detect_timeout_secs() -> u32 {
+    10
+}
+
 fn default_discovery() -> DiscoveryMethods {
     DiscoveryMethods::Relay
 }
@@ -343,6 +661,322 @@ pub(crate) struct PauseConfig {
     pub(crate) mode: String,
 }
 
+/// OSD settings to apply to the camera on connect
+///
+/// Any value that is not given is left as it is on the camera
+#[derive(Debug, Deserialize, Serialize, Validate, Clone, PartialEq, Eq)]
+pub(crate) struct OsdConfig {
+    #[serde(default, alias = "timestamp")]
+    pub(crate) timestamp_enabled: Option<bool>,
+
+    #[validate(regex(
+        path = *RE_OSD_FORMAT,
+        message = "Incorrect timestamp format should be DMY, MDY or YMD",
+        code = "timestamp_format"
+    ))]
+    #[serde(default)]
+    pub(crate) timestamp_format: Option<String>,
+
+    #[serde(default, alias = "name_enabled")]
+    pub(crate) camera_name_enabled: Option<bool>,
+
+    #[serde(default, alias = "name")]
+    pub(crate) camera_name: Option<String>,
+
+    #[serde(default)]
+    pub(crate) position: Option<OsdPosition>,
+}
+
+/// Encoder settings to apply to the camera on connect
+///
+/// Any value that is not given is left as it is on the camera
+#[derive(Debug, Deserialize, Serialize, Validate, Clone, PartialEq, Eq)]
+#[validate(schema(function = "validate_stream_quality"))]
+pub(crate) struct StreamQualityConfig {
+    #[validate(regex(
+        path = *RE_STREAM_KIND,
+        message = "Incorrect stream should be main, sub or extern",
+        code = "stream"
+    ))]
+    #[serde(default = "default_quality_stream")]
+    pub(crate) stream: String,
+
+    #[validate(range(
+        min = 32,
+        max = 16384,
+        message = "Invalid bitrate (it's in kbps)",
+        code = "bitrate_kbps"
+    ))]
+    #[serde(default, alias = "bitrate")]
+    pub(crate) bitrate_kbps: Option<u32>,
+
+    #[validate(range(min = 1, max = 60, message = "Invalid frame rate", code = "frame_rate"))]
+    #[serde(default, alias = "fps")]
+    pub(crate) frame_rate: Option<u8>,
+
+    #[serde(default)]
+    pub(crate) resolution: Option<(u32, u32)>,
+
+    #[serde(default, alias = "quality", alias = "preset")]
+    pub(crate) quality_preset: Option<QualityPreset>,
+
+    #[validate(range(
+        min = 1,
+        max = 300,
+        message = "Invalid GOP size should be 1-300 frames",
+        code = "gop_size_frames"
+    ))]
+    #[serde(default, alias = "gop", alias = "gop_size")]
+    pub(crate) gop_size_frames: Option<u32>,
+}
+
+impl StreamQualityConfig {
+    pub(crate) fn stream_kind(&self) -> StreamKind {
+        match self.stream.as_str() {
+            "sub" => StreamKind::Sub,
+            "extern" => StreamKind::Extern,
+            _ => StreamKind::Main,
+        }
+    }
+}
+
+fn validate_stream_quality(config: &StreamQualityConfig) -> Result<(), ValidationError> {
+    if config.quality_preset == Some(QualityPreset::VeryHigh) && config.gop_size_frames.is_some() {
+        Err(ValidationError::new(
+            "Cannot have both gop_size_frames and the VeryHigh quality_preset set, VeryHigh always uses a one second GOP. Use High with gop_size_frames instead",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Motion detection settings to apply to the camera on connect
+///
+/// Any value that is not given is left as it is on the camera
+#[derive(Debug, Deserialize, Serialize, Validate, Clone, PartialEq, Eq)]
+pub(crate) struct MotionDetectionConfig {
+    #[serde(default, alias = "enable")]
+    pub(crate) enabled: Option<bool>,
+
+    #[validate(range(
+        max = 100,
+        message = "Invalid sensitivity should be 0-100",
+        code = "sensitivity"
+    ))]
+    #[serde(default)]
+    pub(crate) sensitivity: Option<u8>,
+
+    #[serde(default)]
+    pub(crate) regions: Option<Vec<MotionRegion>>,
+}
+
+/// Email alert settings to apply to the camera on connect
+///
+/// Any value that is not given is left as it is on the camera
+#[derive(Debug, Deserialize, Serialize, Validate, Clone, PartialEq, Eq)]
+pub(crate) struct EmailConfig {
+    #[serde(default, alias = "enable")]
+    pub(crate) enabled: Option<bool>,
+
+    #[serde(default, alias = "smtp_server")]
+    pub(crate) smtp_host: Option<String>,
+
+    #[serde(default)]
+    pub(crate) smtp_port: Option<u16>,
+
+    #[serde(default, alias = "tls")]
+    pub(crate) tls_mode: Option<EmailTlsMode>,
+
+    #[serde(default, alias = "smtp_user")]
+    pub(crate) username: Option<String>,
+
+    #[serde(default, alias = "smtp_pass")]
+    pub(crate) password: Option<String>,
+
+    #[serde(default)]
+    pub(crate) sender: Option<String>,
+
+    #[validate(length(
+        max = 3,
+        message = "Cameras support at most 3 email recipients",
+        code = "recipients"
+    ))]
+    #[serde(default)]
+    pub(crate) recipients: Option<Vec<String>>,
+
+    /// 168 `0` or `1`, one per hour of the week from Sunday 00:00
+    #[validate(regex(
+        path = *RE_EMAIL_SCHEDULE,
+        message = "Email schedule must be 168 characters of 0 or 1",
+        code = "schedule"
+    ))]
+    #[serde(default)]
+    pub(crate) schedule: Option<String>,
+}
+
+/// NTP settings to apply to the camera
+///
+/// Any value that is not given is left as it is on the camera
+#[derive(Debug, Deserialize, Serialize, Validate, Clone, PartialEq, Eq)]
+pub(crate) struct NtpConfig {
+    #[serde(default, alias = "enable")]
+    pub(crate) enabled: Option<bool>,
+
+    #[serde(default, alias = "ntp_server")]
+    pub(crate) server: Option<String>,
+
+    #[serde(default)]
+    pub(crate) port: Option<u16>,
+
+    #[validate(range(
+        min = 60,
+        max = 65535,
+        message = "Invalid NTP interval should be 60-65535 minutes",
+        code = "interval_mins"
+    ))]
+    #[serde(default, alias = "interval")]
+    pub(crate) interval_mins: Option<u32>,
+}
+
+/// Http proxy that tcp connections to the camera are tunneled through
+#[derive(Debug, Deserialize, Serialize, Validate, Clone, PartialEq, Eq)]
+pub(crate) struct ProxyConfig {
+    /// Url of the proxy such as `http://proxy.example.com:3128`
+    #[validate(regex(
+        path = *RE_PROXY_URL,
+        message = "Proxy url must be of the form http://host:port",
+        code = "proxy_url"
+    ))]
+    #[serde(alias = "url")]
+    pub(crate) proxy_url: String,
+
+    #[serde(default)]
+    pub(crate) username: Option<String>,
+
+    #[serde(default)]
+    pub(crate) password: Option<String>,
+}
+
+/// MPEG-DASH output of the main stream
+///
+/// Segments are written into `output_dir` which is also served over http
+#[derive(Debug, Deserialize, Serialize, Validate, Clone, PartialEq, Eq)]
+pub(crate) struct DashConfig {
+    pub(crate) output_dir: std::path::PathBuf,
+
+    #[validate(range(
+        min = 1,
+        max = 60,
+        message = "Invalid segment duration should be 1-60s",
+        code = "segment_duration_secs"
+    ))]
+    #[serde(default = "default_segment_duration", alias = "segment_duration")]
+    pub(crate) segment_duration_secs: u32,
+
+    /// Prefix of the segment urls written into the mpd
+    #[serde(default)]
+    pub(crate) base_url: Option<String>,
+
+    #[serde(default = "default_mpd_filename", alias = "mpd")]
+    pub(crate) mpd_filename: String,
+
+    #[serde(default = "default_bind_addr")]
+    pub(crate) bind_addr: String,
+
+    #[serde(default = "default_dash_port", alias = "port")]
+    pub(crate) bind_port: u16,
+}
+
+fn default_segment_duration() -> u32 {
+    4
+}
+
+fn default_mpd_filename() -> String {
+    "stream.mpd".to_string()
+}
+
+fn default_dash_port() -> u16 {
+    8080
+}
+
+fn default_quality_stream() -> String {
+    "main".to_string()
+}
+
+/// PTZ presets that the camera cycles through for as long as it is connected
+#[derive(Debug, Deserialize, Serialize, Validate, Clone, PartialEq, Eq)]
+pub(crate) struct PtzTourConfig {
+    #[validate(length(min = 1, message = "A ptz tour needs at least one preset"))]
+    pub(crate) presets: Vec<u8>,
+
+    #[validate(range(
+        min = 1,
+        max = 86400,
+        message = "Invalid dwell time should be 1-86400s",
+        code = "dwell_secs"
+    ))]
+    #[serde(default = "default_dwell_secs", alias = "dwell")]
+    pub(crate) dwell_secs: u64,
+}
+
+fn default_dwell_secs() -> u64 {
+    10
+}
+
+/// A snapshot that is saved on a cron like schedule
+#[derive(Debug, Deserialize, Serialize, Validate, Clone, PartialEq, Eq)]
+pub(crate) struct ScheduledSnapshotConfig {
+    #[validate(custom(function = "validate_schedule"))]
+    #[serde(alias = "cron")]
+    pub(crate) schedule: String,
+
+    #[validate(custom(function = "validate_output_pattern"))]
+    #[serde(alias = "output", alias = "path")]
+    pub(crate) output_pattern: String,
+
+    #[serde(default)]
+    pub(crate) max_age_hours: Option<u32>,
+}
+
+/// Where the audio comes from when it is not the camera's own
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub(crate) enum ExternalAudioSource {
+    /// The audio of another camera in the config
+    #[serde(alias = "camera", alias = "bc_stream")]
+    BcStream(String),
+    /// The audio of an rtsp url
+    #[serde(alias = "rtsp_url", alias = "url")]
+    RtspUrl(String),
+}
+
+/// The clock shared by all the rtsp media
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub(crate) enum SharedClock {
+    /// Each media picks its own clock
+    #[serde(alias = "none")]
+    None,
+    /// The system clock of this machine
+    #[serde(alias = "system")]
+    System,
+    /// A clock kept in sync with an NTP server given as `address` or `address:port`
+    #[serde(alias = "ntp")]
+    Ntp(String),
+}
+
+/// How the frames given to gstreamer are timestamped
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum FrameTimestampMode {
+    /// Stamped with the time they reached neolink
+    #[serde(alias = "local_clock", alias = "local")]
+    LocalClock,
+    /// Stamped with the time the camera put in the frame
+    #[serde(alias = "camera_provided", alias = "camera")]
+    CameraProvided,
+    /// Not stamped and given as a stream of bytes
+    #[serde(alias = "byte_stream", alias = "bytes")]
+    ByteStream,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, Eq, PartialEq)]
 pub(crate) enum SplashPattern {
     #[serde(alias = "smpte")]
@@ -442,6 +1076,28 @@ fn default_bind_port() -> u16 {
     8554
 }
 
+fn default_rtpjitterbuffer_latency_ms() -> u32 {
+    200
+}
+
+fn default_rtsp_threads() -> u32 {
+    std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(1)
+}
+
+fn default_shared_clock() -> SharedClock {
+    SharedClock::System
+}
+
+fn default_rtsp_max_backlog() -> u32 {
+    128
+}
+
+fn default_source_type() -> String {
+    "bc_protocol".to_string()
+}
+
 fn default_stream() -> StreamConfig {
     StreamConfig::All
 }
@@ -503,6 +1159,10 @@ fn default_max_discovery_retries() -> usize {
     10
 }
 
+fn default_connect_timeout_secs() -> u32 {
+    10
+}
+
 fn default_2000() -> u64 {
     2000
 }
@@ -511,6 +1171,10 @@ fn default_splash() -> SplashPattern {
     SplashPattern::Snow
 }
 
+fn default_frame_timestamps() -> FrameTimestampMode {
+    FrameTimestampMode::CameraProvided
+}
+
 pub(crate) static RESERVED_NAMES: &[&str] = &["anyone", "anonymous"];
 fn validate_username(name: &str) -> Result<(), ValidationError> {
     if name.trim().is_empty() {
@@ -522,7 +1186,93 @@ fn validate_username(name: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
+fn validate_channels(channels: &[u8]) -> Result<(), ValidationError> {
+    if channels.iter().any(|&channel| channel > 31) {
+        Err(ValidationError::new("Invalid channel"))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_cors_origins(origins: &[String]) -> Result<(), ValidationError> {
+    // Each origin is matched exactly so a wildcard would never match
+    if origins
+        .iter()
+        .any(|origin| origin.contains('*') || origin.ends_with('/'))
+    {
+        Err(ValidationError::new(
+            "Invalid cors origin should be like https://example.com",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_path_aliases(aliases: &[String]) -> Result<(), ValidationError> {
+    if aliases
+        .iter()
+        .any(|alias| !alias.starts_with('/') || alias.trim_end_matches('/').is_empty())
+    {
+        Err(ValidationError::new(
+            "Invalid path alias should be like /frontdoor",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_schedule(schedule: &str) -> Result<(), ValidationError> {
+    crate::common::CronSchedule::parse(schedule)
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("Invalid schedule should be like 0 */5 * * * *"))
+}
+
+fn validate_output_pattern(pattern: &str) -> Result<(), ValidationError> {
+    crate::common::validate_output_pattern(pattern)
+        .map_err(|_| ValidationError::new("Invalid strftime specifier in output_pattern"))
+}
+
+fn validate_config(config: &Config) -> Result<(), ValidationError> {
+    for camera_config in config.cameras.iter() {
+        if let Some(ExternalAudioSource::BcStream(other)) = &camera_config.external_audio {
+            match config
+                .cameras
+                .iter()
+                .find(|cam_config| &cam_config.name == other)
+            {
+                None => {
+                    return Err(ValidationError::new(
+                        "external_audio must be the name of another camera in the config",
+                    ))
+                }
+                Some(other) if other.is_external_rtsp() => {
+                    return Err(ValidationError::new(
+                        "external_audio cannot be a camera with source_type rtsp_url",
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
 fn validate_camera_config(camera_config: &CameraConfig) -> Result<(), ValidationError> {
+    if let Some(ExternalAudioSource::BcStream(other)) = &camera_config.external_audio {
+        if other == &camera_config.name {
+            return Err(ValidationError::new(
+                "external_audio cannot be the camera itself",
+            ));
+        }
+    }
+    if camera_config.is_external_rtsp() {
+        return match camera_config.rtsp_url {
+            None => Err(ValidationError::new(
+                "rtsp_url must be given when source_type is rtsp_url",
+            )),
+            Some(_) => Ok(()),
+        };
+    }
     match (&camera_config.camera_addr, &camera_config.camera_uid) {
         (None, None) => Err(ValidationError::new(
             "Either camera address or uid must be given",
@@ -530,3 +1280,176 @@ fn validate_camera_config(camera_config: &CameraConfig) -> Result<(), Validation
         _ => Ok(()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use validator::Validate;
+
+    fn camera<'a>(config: &'a Config, name: &str) -> &'a CameraConfig {
+        config
+            .cameras
+            .iter()
+            .find(|camera| camera.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn camera_groups_precedence() {
+        let config = Config::from_toml(
+            r#"
+            [[camera_groups]]
+            cameras = ["grouped", "overridden"]
+            username = "group_user"
+            channel_id = 1
+
+            [[cameras]]
+            name = "grouped"
+            address = "192.168.1.10"
+
+            [[cameras]]
+            name = "overridden"
+            username = "camera_user"
+            address = "192.168.1.11"
+
+            [[cameras]]
+            name = "ungrouped"
+            username = "admin"
+            address = "192.168.1.12"
+            "#,
+        )
+        .unwrap();
+
+        // The group fills in what the camera does not set
+        let grouped = camera(&config, "grouped");
+        assert_eq!(grouped.username, "group_user");
+        assert_eq!(grouped.channel_id, 1);
+        // The camera wins over the group
+        let overridden = camera(&config, "overridden");
+        assert_eq!(overridden.username, "camera_user");
+        assert_eq!(overridden.channel_id, 1);
+        // The built in defaults are used when neither sets it
+        let ungrouped = camera(&config, "ungrouped");
+        assert_eq!(ungrouped.channel_id, default_channel_id());
+        assert_eq!(grouped.pause.mode, default_pause_mode());
+    }
+
+    #[test]
+    fn camera_groups_merge_nested_tables() {
+        let config = Config::from_toml(
+            r#"
+            [[camera_groups]]
+            cameras = ["cam"]
+            username = "admin"
+            pause = { on_motion = true, motion_timeout = 5.0 }
+
+            [[cameras]]
+            name = "cam"
+            address = "192.168.1.10"
+            pause = { motion_timeout = 2.0 }
+            "#,
+        )
+        .unwrap();
+        let pause = &camera(&config, "cam").pause;
+        assert!(pause.on_motion);
+        assert_eq!(pause.motion_timeout, 2.0);
+        assert_eq!(pause.on_disconnect, default_on_disconnect());
+    }
+
+    #[test]
+    fn camera_groups_reject_unknown_camera() {
+        let err = Config::from_toml(
+            r#"
+            [[camera_groups]]
+            cameras = ["missing"]
+            username = "admin"
+
+            [[cameras]]
+            name = "cam"
+            username = "admin"
+            address = "192.168.1.10"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("missing"), "{}", err);
+    }
+
+    #[test]
+    fn camera_groups_reject_camera_in_two_groups() {
+        let result = Config::from_toml(
+            r#"
+            [[camera_groups]]
+            cameras = ["cam"]
+
+            [[camera_groups]]
+            cameras = ["cam"]
+
+            [[cameras]]
+            name = "cam"
+            username = "admin"
+            address = "192.168.1.10"
+            "#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn merge_defaults_keeps_existing_keys() {
+        let mut table = toml::from_str::<toml::Table>(
+            r#"
+            a = 1
+            [nested]
+            b = 2
+            "#,
+        )
+        .unwrap();
+        let defaults = toml::from_str::<toml::Table>(
+            r#"
+            a = 10
+            c = 30
+            nested = { b = 20, d = 40 }
+            "#,
+        )
+        .unwrap();
+        merge_defaults(&mut table, &defaults);
+        let expected = toml::from_str::<toml::Table>(
+            r#"
+            a = 1
+            c = 30
+            nested = { b = 2, d = 40 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn external_audio_must_be_a_camera() {
+        let config = |external_audio: &str| {
+            Config::from_toml(&format!(
+                r#"
+                [[cameras]]
+                name = "cam"
+                username = "admin"
+                address = "192.168.1.10"
+                external_audio = {{ {external_audio} }}
+
+                [[cameras]]
+                name = "doorbell"
+                username = "admin"
+                address = "192.168.1.11"
+
+                [[cameras]]
+                name = "relay"
+                username = "admin"
+                source_type = "rtsp_url"
+                rtsp_url = "rtsp://192.168.1.12:554/main"
+                "#
+            ))
+            .unwrap()
+        };
+        assert!(config(r#"camera = "doorbell""#).validate().is_ok());
+        assert!(config(r#"camera = "missing""#).validate().is_err());
+        assert!(config(r#"camera = "relay""#).validate().is_err());
+    }
+}
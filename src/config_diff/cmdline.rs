@@ -0,0 +1,37 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// How the differences are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    /// Lines of `- reference` and `+ camera` like `diff`
+    Diff,
+    /// A RFC 6902 json patch that turns the reference into the camera's settings
+    JsonPatch,
+}
+
+fn format_parse(src: &str) -> Result<DiffFormat> {
+    match src {
+        "diff" => Ok(DiffFormat::Diff),
+        "json-patch" | "json_patch" => Ok(DiffFormat::JsonPatch),
+        _ => Err(anyhow!(
+            "Could not understand {}, check your input, should be diff or json-patch",
+            src
+        )),
+    }
+}
+
+/// The config-diff command compares the camera's settings with a saved export-config
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// A toml settings file of the camera such as one from export-config
+    #[arg(long, value_parser = PathBuf::from_str)]
+    pub reference: PathBuf,
+    /// How to print the differences: diff or json-patch
+    #[arg(long, value_parser = format_parse, default_value = "diff")]
+    pub format: DiffFormat,
+}
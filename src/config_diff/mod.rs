@@ -0,0 +1,163 @@
+///
+/// # Neolink Config Diff
+///
+/// This module reads the settings of a camera and compares them with a
+/// settings file saved earlier with `neolink export-config`. Run from
+/// cron it catches cameras that have been changed from their web page or
+/// app, whether by mistake or by someone who should not have
+///
+/// The `osd`, `stream_quality`, `motion_detection`, `email` and `ntp`
+/// sections are compared. Only the settings that are in the reference are
+/// checked so a hand written file can check just the settings that matter
+///
+/// The command exits with 1 when there is a difference and 2 when the
+/// camera or the reference cannot be read
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink export-config --config=config.toml CameraName > camera.toml
+/// # Later
+/// neolink config-diff --config=config.toml CameraName --reference=camera.toml
+/// neolink config-diff --config=config.toml CameraName --reference=camera.toml --format=json-patch
+/// ```
+///
+use anyhow::{Context, Result};
+use serde_json::json;
+use toml::{Table, Value};
+
+mod cmdline;
+
+use crate::{common::NeoReactor, export_config::read_camera};
+use cmdline::DiffFormat;
+pub(crate) use cmdline::Opt;
+
+/// The sections of export-config that are compared
+const SECTIONS: &[&str] = &["osd", "stream_quality", "motion_detection", "email", "ntp"];
+
+/// A setting of the reference that the camera does not match
+struct Difference {
+    path: Vec<String>,
+    reference: Value,
+    /// `None` when the camera does not have the setting
+    camera: Option<Value>,
+}
+
+/// Entry point for the config-diff subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let reference = std::fs::read_to_string(&opt.reference)
+        .with_context(|| format!("Failed to read {:?}", opt.reference))?;
+    let reference = toml::from_str::<Table>(&reference)
+        .with_context(|| format!("Failed to parse {:?}", opt.reference))?;
+
+    let camera = reactor.get(&opt.camera).await?;
+    let current = match Value::try_from(read_camera(&camera).await?)? {
+        Value::Table(table) => table,
+        _ => unreachable!(),
+    };
+
+    let mut differences = vec![];
+    for section in SECTIONS.iter() {
+        if let Some(reference) = reference.get(*section) {
+            compare(
+                &mut vec![section.to_string()],
+                reference,
+                current.get(*section),
+                &mut differences,
+            );
+        }
+    }
+
+    match opt.format {
+        DiffFormat::Diff => print_diff(&opt, &differences),
+        DiffFormat::JsonPatch => print_json_patch(&differences)?,
+    }
+
+    if !differences.is_empty() {
+        log::warn!(
+            "{} has {} setting(s) that differ from {:?}",
+            opt.camera,
+            differences.len(),
+            opt.reference
+        );
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Compare every setting of the reference with the same one of the camera
+///
+/// Tables are compared setting by setting and anything else as a whole
+fn compare(
+    path: &mut Vec<String>,
+    reference: &Value,
+    camera: Option<&Value>,
+    differences: &mut Vec<Difference>,
+) {
+    match reference {
+        Value::Table(table) => {
+            for (key, reference) in table.iter() {
+                path.push(key.clone());
+                compare(
+                    path,
+                    reference,
+                    camera.and_then(|camera| camera.get(key)),
+                    differences,
+                );
+                path.pop();
+            }
+        }
+        _ if camera != Some(reference) => differences.push(Difference {
+            path: path.clone(),
+            reference: reference.clone(),
+            camera: camera.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+fn print_diff(opt: &Opt, differences: &[Difference]) {
+    if differences.is_empty() {
+        println!("{} matches {:?}", opt.camera, opt.reference);
+        return;
+    }
+    println!("--- {}", opt.reference.display());
+    println!("+++ {}", opt.camera);
+    for difference in differences.iter() {
+        let path = difference.path.join(".");
+        println!("- {} = {}", path, difference.reference);
+        if let Some(camera) = difference.camera.as_ref() {
+            println!("+ {} = {}", path, camera);
+        }
+    }
+}
+
+/// Print the differences as a json patch that turns the reference into the camera's settings
+fn print_json_patch(differences: &[Difference]) -> Result<()> {
+    let patch = differences
+        .iter()
+        .map(|difference| {
+            let path = difference
+                .path
+                .iter()
+                .flat_map(|key| ["/".to_string(), key.replace('~', "~0").replace('/', "~1")])
+                .collect::<String>();
+            Ok(match difference.camera.as_ref() {
+                Some(camera) => json!({
+                    "op": "replace",
+                    "path": path,
+                    "value": serde_json::to_value(camera)?,
+                }),
+                None => json!({
+                    "op": "remove",
+                    "path": path,
+                }),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    println!("{}", serde_json::to_string_pretty(&patch)?);
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use crate::common::codec_parse_with_av1;
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use neolink_core::bcmedia::model::VideoType;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+fn preset_parse(src: &str) -> Result<String> {
+    if super::PRESETS.contains(&src) {
+        Ok(src.to_string())
+    } else {
+        Err(anyhow!(
+            "Could not understand {}, check your input, should be one of {}",
+            src,
+            super::PRESETS.join(", ")
+        ))
+    }
+}
+
+/// The convert command transcodes an mp4 file into one that more players can play
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The mp4 file to convert
+    #[arg(long, value_parser = PathBuf::from_str)]
+    pub input: PathBuf,
+    /// The mp4 file to write
+    #[arg(long, value_parser = PathBuf::from_str)]
+    pub output: PathBuf,
+    /// Video codec of the output: h264, h265 or av1
+    #[arg(long, value_parser = codec_parse_with_av1, default_value = "h264")]
+    pub codec: VideoType,
+    /// Quality of the output, lower is better and larger
+    #[arg(long, default_value_t = 23, value_parser = clap::value_parser!(u32).range(0..=51))]
+    pub crf: u32,
+    /// How much time to spend on the encoding from ultrafast to veryslow
+    #[arg(long, value_parser = preset_parse, default_value = "medium")]
+    pub preset: String,
+}
@@ -0,0 +1,182 @@
+///
+/// # Neolink Convert
+///
+/// This module transcodes an mp4 file, such as the h265 video of a
+/// camera, into one that more players can play. It is not a replacement
+/// for ffmpeg but is handy when neolink and its gstreamer are installed
+/// but ffmpeg is not
+///
+/// The video is decoded and encoded again with the codec, quality and
+/// preset that are given. The audio is copied as it is
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink convert --input=camera.mp4 --output=camera_h264.mp4
+/// # Smaller but slower
+/// neolink convert --input=camera.mp4 --output=camera_h265.mp4 --codec=h265 --crf=28 --preset=slow
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use gstreamer::{
+    format::Bytes, parse::launch_full, prelude::*, ClockTime, MessageView, ParseFlags, Pipeline,
+    State,
+};
+use neolink_core::bcmedia::model::VideoType;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+mod cmdline;
+
+pub(crate) use cmdline::Opt;
+
+/// The speed presets from fastest to slowest
+pub(super) const PRESETS: &[&str] = &[
+    "ultrafast",
+    "superfast",
+    "veryfast",
+    "faster",
+    "fast",
+    "medium",
+    "slow",
+    "slower",
+    "veryslow",
+];
+
+/// How often the progress is printed
+const PROGRESS_INTERVAL_MS: u64 = 500;
+
+/// Entry point for the convert subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt) -> Result<()> {
+    gstreamer::init()
+        .context("Unable to start gstreamer ensure it and all plugins are installed")?;
+    let size = tokio::fs::metadata(&opt.input)
+        .await
+        .with_context(|| format!("Could not read {:?}", opt.input))?
+        .len();
+    let pipeline = create_pipeline(&opt)?;
+
+    tokio::task::spawn_blocking(move || run_pipeline(pipeline, size)).await??;
+    println!("Converted {:?} to {:?}", opt.input, opt.output);
+    Ok(())
+}
+
+fn create_pipeline(opt: &Opt) -> Result<Pipeline> {
+    // The pads of the mux are named so that the video is always the first
+    // track which some players, such as QuickTime, insist on
+    let launch_str = format!(
+        "filesrc name=thesource location={} \
+        ! qtdemux name=demux \
+        mp4mux name=mux \
+        ! filesink location={} \
+        demux.video_0 ! queue ! decodebin ! videoconvert \
+        ! {} \
+        ! mux.video_0 \
+        demux.audio_0 ! queue ! mux.audio_0",
+        quote(&opt.input),
+        quote(&opt.output),
+        encoder(&opt.codec, opt.crf, &opt.preset),
+    );
+    log::debug!("{}", launch_str);
+
+    let pipeline = launch_full(&launch_str, None, ParseFlags::empty())
+        .context("Unable to load gstreamer pipeline ensure all gstramer plugins are installed")?;
+    pipeline.dynamic_cast::<Pipeline>().map_err(|_| {
+        anyhow!("Unable to create gstreamer pipeline ensure all gstramer plugins are installed")
+    })
+}
+
+/// The encoder and parser of the codec
+///
+/// `crf` is the constant quality of x264 and x265. For av1 it is used as the
+/// `cq-level` of aom and the preset is turned into its `cpu-used`
+fn encoder(codec: &VideoType, crf: u32, preset: &str) -> String {
+    match codec {
+        VideoType::H264 => {
+            format!("x264enc pass=qual quantizer={crf} speed-preset={preset} ! h264parse")
+        }
+        VideoType::H265 => {
+            format!("x265enc option-string=crf={crf} speed-preset={preset} ! h265parse")
+        }
+        VideoType::Av1 => {
+            let slowness = PRESETS.iter().position(|p| *p == preset).unwrap_or(5);
+            let cpu_used = PRESETS.len() - 1 - slowness;
+            format!("av1enc end-usage=q cq-level={crf} cpu-used={cpu_used} ! av1parse")
+        }
+    }
+}
+
+/// Quote a path for the launch string
+fn quote(path: &Path) -> String {
+    format!(
+        "\"{}\"",
+        path.display()
+            .to_string()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+    )
+}
+
+/// Run the pipeline to the end printing how much of the input has been read
+fn run_pipeline(pipeline: Pipeline, size: u64) -> Result<()> {
+    let source = pipeline
+        .by_name("thesource")
+        .ok_or_else(|| anyhow!("There shoud be a `thesource`"))?;
+    let bus = pipeline
+        .bus()
+        .expect("Pipeline without bus. Shouldn't happen!");
+    pipeline.set_state(State::Playing)?;
+
+    let interval = Duration::from_millis(PROGRESS_INTERVAL_MS);
+    let mut last_progress = Instant::now();
+    let res = loop {
+        if let Some(msg) = bus.timed_pop(ClockTime::from_mseconds(PROGRESS_INTERVAL_MS)) {
+            match msg.view() {
+                MessageView::Eos(..) => break Ok(()),
+                MessageView::Error(err) => {
+                    break Err(anyhow!(
+                        "Error from {:?}: {} ({:?})",
+                        err.src().map(|src| src.path_string()),
+                        err.error(),
+                        err.debug()
+                    ))
+                }
+                _ => (),
+            }
+        }
+        if last_progress.elapsed() >= interval {
+            last_progress = Instant::now();
+            if let Some(done) = source.query_position::<Bytes>() {
+                print_progress(*done, size);
+            }
+        }
+    };
+    if res.is_ok() {
+        print_progress(size, size);
+    }
+    eprintln!();
+
+    pipeline
+        .set_state(State::Null)
+        .context("Error in gstreamer when setting state to Null")?;
+    res
+}
+
+fn print_progress(done: u64, size: u64) {
+    let percent = if size > 0 {
+        done.min(size) * 100 / size
+    } else {
+        100
+    };
+    eprint!(
+        "\r{:3}% {:.1}/{:.1} MB",
+        percent,
+        done.min(size) as f64 / 1_000_000.0,
+        size as f64 / 1_000_000.0
+    );
+    let _ = std::io::stderr().flush();
+}
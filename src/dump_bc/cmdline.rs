@@ -0,0 +1,21 @@
+use crate::common::stream_parse;
+use clap::Parser;
+use neolink_core::bc_protocol::StreamKind;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The dump-bc command captures the BC packets of a camera to a pcap file
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The pcap file to write
+    #[arg(long, value_parser = PathBuf::from_str)]
+    pub output: PathBuf,
+    /// Stop after this many seconds, 0 runs until stopped with ctrl-c
+    #[arg(long, default_value_t = 0)]
+    pub duration: u64,
+    /// Also start a stream so that its packets are captured: main, sub or extern
+    #[arg(long, value_parser = stream_parse)]
+    pub stream: Option<StreamKind>,
+}
@@ -0,0 +1,99 @@
+///
+/// # Neolink Dump BC
+///
+/// This module connects to a camera and writes every BC packet that is sent
+/// and received to a pcap file. Open the file in wireshark with the
+/// baichuan dissector in `dissector/` to look at the messages without
+/// capturing the traffic live
+///
+/// The packets are written as they were on the wire so they are still
+/// encrypted. Each one is wrapped in made up ethernet, ip and tcp headers
+/// with neolink at 10.0.0.1 and the camera at 10.0.0.2 on port 9000. This
+/// is the same for cameras that are connected to over udp
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink dump-bc --config=config.toml CameraName --output=camera.pcap
+/// # Capture the packets of the main stream for 30 seconds
+/// neolink dump-bc --config=config.toml CameraName --output=camera.pcap --stream=main --duration=30
+/// ```
+///
+use anyhow::{Context, Result};
+use neolink_core::bc_protocol::PacketTap;
+use std::fs::File;
+use std::io::BufWriter;
+use tokio::time::{sleep, Duration};
+
+mod cmdline;
+mod pcap;
+
+use crate::{common::NeoReactor, utils};
+pub(crate) use cmdline::Opt;
+use pcap::PcapWriter;
+
+/// Entry point for the dump-bc subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera_config = reactor
+        .config()
+        .await?
+        .borrow()
+        .cameras
+        .iter()
+        .find(|camera_config| camera_config.name == opt.camera)
+        .cloned()
+        .with_context(|| format!("Camera {} is not in the config", opt.camera))?;
+
+    let file =
+        File::create(&opt.output).with_context(|| format!("Could not create {:?}", opt.output))?;
+    let mut writer = PcapWriter::new(BufWriter::new(file))?;
+    let (tap, mut packets) = PacketTap::new();
+
+    let capture = async {
+        let camera = utils::connect_with_tap(&camera_config, Some(tap)).await?;
+        utils::login(&camera_config, &camera).await?;
+        println!(
+            "Capturing the packets of {} to {:?}",
+            opt.camera, opt.output
+        );
+        match opt.stream {
+            Some(stream) => {
+                let mut stream_data = camera
+                    .start_video(stream, 0, camera_config.strict)
+                    .await
+                    .context("Failed to start video")?;
+                // The frames are dropped, only their packets are wanted
+                loop {
+                    stream_data.get_data().await??;
+                }
+            }
+            None => futures::future::pending::<Result<()>>().await,
+        }
+    };
+    let stop = async {
+        if opt.duration > 0 {
+            sleep(Duration::from_secs(opt.duration)).await;
+            Ok(())
+        } else {
+            tokio::signal::ctrl_c().await
+        }
+    };
+    tokio::pin!(capture, stop);
+    let result = loop {
+        tokio::select! {
+            v = &mut capture => break v,
+            v = &mut stop => break v.map_err(Into::into),
+            Some(packet) = packets.recv() => writer.write_packet(&packet)?,
+        }
+    };
+    // Write whatever is still waiting before the file is closed
+    while let Ok(packet) = packets.try_recv() {
+        writer.write_packet(&packet)?;
+    }
+    writer.flush()?;
+    println!("Wrote the packets to {:?}", opt.output);
+    result
+}
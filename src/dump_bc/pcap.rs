@@ -0,0 +1,221 @@
+//! A minimal writer of pcap files
+//!
+//! Each BC packet is written as a tcp segment between made up addresses
+//! with the camera on port 9000 so that wireshark hands it to the
+//! baichuan dissector in `dissector/`
+use anyhow::Result;
+use neolink_core::bc_protocol::{PacketDirection, TappedPacket};
+use std::io::Write;
+use std::time::UNIX_EPOCH;
+
+/// `LINKTYPE_ETHERNET`
+const LINKTYPE_ETHERNET: u32 = 1;
+/// Largest packet that is recorded
+const SNAPLEN: u32 = 262144;
+/// Largest tcp payload that fits in an ipv4 packet
+const MAX_SEGMENT: usize = 65535 - IPV4_HEADER_LEN - TCP_HEADER_LEN;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const IPV4_HEADER_LEN: usize = 20;
+const TCP_HEADER_LEN: usize = 20;
+
+/// One end of the made up tcp connection
+struct Endpoint {
+    mac: [u8; 6],
+    ip: [u8; 4],
+    port: u16,
+    /// The sequence number of the next byte sent from here
+    seq: u32,
+}
+
+/// Writes the [`TappedPacket`]s as a pcap file
+pub(super) struct PcapWriter<W: Write> {
+    out: W,
+    neolink: Endpoint,
+    camera: Endpoint,
+    ip_id: u16,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Starts the file by writing the pcap header
+    pub(super) fn new(mut out: W) -> Result<Self> {
+        out.write_all(&0xa1b2c3d4u32.to_le_bytes())?;
+        out.write_all(&2u16.to_le_bytes())?;
+        out.write_all(&4u16.to_le_bytes())?;
+        // Timezone and accuracy of the timestamps
+        out.write_all(&0i32.to_le_bytes())?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(&SNAPLEN.to_le_bytes())?;
+        out.write_all(&LINKTYPE_ETHERNET.to_le_bytes())?;
+        Ok(Self {
+            out,
+            neolink: Endpoint {
+                mac: [0x02, 0, 0, 0, 0, 0x01],
+                ip: [10, 0, 0, 1],
+                port: 50000,
+                seq: 1,
+            },
+            camera: Endpoint {
+                mac: [0x02, 0, 0, 0, 0, 0x02],
+                ip: [10, 0, 0, 2],
+                port: 9000,
+                seq: 1,
+            },
+            ip_id: 0,
+        })
+    }
+
+    /// Writes the packet as one or more tcp segments
+    pub(super) fn write_packet(&mut self, packet: &TappedPacket) -> Result<()> {
+        let since_epoch = packet.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        for segment in packet.data.chunks(MAX_SEGMENT) {
+            let frame = self.frame(packet.direction, segment);
+            self.out
+                .write_all(&(since_epoch.as_secs() as u32).to_le_bytes())?;
+            self.out
+                .write_all(&since_epoch.subsec_micros().to_le_bytes())?;
+            self.out.write_all(&(frame.len() as u32).to_le_bytes())?;
+            self.out.write_all(&(frame.len() as u32).to_le_bytes())?;
+            self.out.write_all(&frame)?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn flush(&mut self) -> Result<()> {
+        Ok(self.out.flush()?)
+    }
+
+    /// Wraps the payload in ethernet, ipv4 and tcp headers
+    fn frame(&mut self, direction: PacketDirection, payload: &[u8]) -> Vec<u8> {
+        let (from, to) = match direction {
+            PacketDirection::Sent => (&self.neolink, &self.camera),
+            PacketDirection::Received => (&self.camera, &self.neolink),
+        };
+        let mut frame = Vec::with_capacity(
+            ETHERNET_HEADER_LEN + IPV4_HEADER_LEN + TCP_HEADER_LEN + payload.len(),
+        );
+
+        frame.extend_from_slice(&to.mac);
+        frame.extend_from_slice(&from.mac);
+        frame.extend_from_slice(&0x0800u16.to_be_bytes());
+
+        let ip_start = frame.len();
+        let total_len = (IPV4_HEADER_LEN + TCP_HEADER_LEN + payload.len()) as u16;
+        frame.push(0x45);
+        frame.push(0);
+        frame.extend_from_slice(&total_len.to_be_bytes());
+        frame.extend_from_slice(&self.ip_id.to_be_bytes());
+        // Don't fragment
+        frame.extend_from_slice(&0x4000u16.to_be_bytes());
+        frame.push(64);
+        frame.push(6);
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&from.ip);
+        frame.extend_from_slice(&to.ip);
+        let checksum = ipv4_checksum(&frame[ip_start..]);
+        frame[ip_start + 10..ip_start + 12].copy_from_slice(&checksum.to_be_bytes());
+
+        frame.extend_from_slice(&from.port.to_be_bytes());
+        frame.extend_from_slice(&to.port.to_be_bytes());
+        frame.extend_from_slice(&from.seq.to_be_bytes());
+        frame.extend_from_slice(&to.seq.to_be_bytes());
+        // Header length of five words and the PSH and ACK flags
+        frame.push(5 << 4);
+        frame.push(0x18);
+        frame.extend_from_slice(&u16::MAX.to_be_bytes());
+        // The tcp checksum is left as 0 which wireshark does not check by default
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&[0, 0]);
+
+        frame.extend_from_slice(payload);
+
+        self.ip_id = self.ip_id.wrapping_add(1);
+        let from = match direction {
+            PacketDirection::Sent => &mut self.neolink,
+            PacketDirection::Received => &mut self.camera,
+        };
+        from.seq = from.seq.wrapping_add(payload.len() as u32);
+        frame
+    }
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn global_header() {
+        let writer = PcapWriter::new(vec![]).unwrap();
+        assert_eq!(
+            writer.out,
+            [
+                0xd4, 0xc3, 0xb2, 0xa1, // Magic
+                0x02, 0x00, 0x04, 0x00, // Version 2.4
+                0x00, 0x00, 0x00, 0x00, // Timezone
+                0x00, 0x00, 0x00, 0x00, // Accuracy
+                0x00, 0x00, 0x04, 0x00, // Snaplen
+                0x01, 0x00, 0x00, 0x00, // Ethernet
+            ]
+        );
+    }
+
+    #[test]
+    fn one_record() {
+        let mut writer = PcapWriter::new(vec![]).unwrap();
+        writer.out.clear();
+        writer
+            .write_packet(&TappedPacket {
+                direction: PacketDirection::Sent,
+                time: UNIX_EPOCH + Duration::from_micros(1_500_000),
+                data: vec![0xf0, 0xde, 0xbc, 0x0a],
+            })
+            .unwrap();
+        // 1.5s, 58 bytes captured of 58
+        let record = [
+            0x01, 0x00, 0x00, 0x00, 0x20, 0xa1, 0x07, 0x00, 0x3a, 0x00, 0x00, 0x00, 0x3a, 0x00,
+            0x00, 0x00,
+        ];
+        // To the camera from neolink
+        let ethernet = [
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00,
+        ];
+        // From 10.0.0.1 to 10.0.0.2
+        let ipv4 = [
+            0x45, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x40, 0x00, 0x40, 0x06, 0x26, 0xca, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+        ];
+        // From 50000 to 9000
+        let tcp = [
+            0xc3, 0x50, 0x23, 0x28, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x50, 0x18,
+            0xff, 0xff, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let payload = [0xf0, 0xde, 0xbc, 0x0a];
+        assert_eq!(
+            writer.out,
+            [&record[..], &ethernet, &ipv4, &tcp, &payload].concat()
+        );
+    }
+
+    #[test]
+    fn sequence_numbers_follow_payload() {
+        let mut writer = PcapWriter::new(vec![]).unwrap();
+        writer.frame(PacketDirection::Sent, &[0; 10]);
+        let reply = writer.frame(PacketDirection::Received, &[0; 4]);
+        let tcp = &reply[ETHERNET_HEADER_LEN + IPV4_HEADER_LEN..];
+        // Seq of the camera then ack of what neolink sent
+        assert_eq!(tcp[4..8], 1u32.to_be_bytes());
+        assert_eq!(tcp[8..12], 11u32.to_be_bytes());
+    }
+}
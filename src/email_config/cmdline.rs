@@ -0,0 +1,29 @@
+use clap::Parser;
+
+/// The email-config command will get or set the email alerts of the camera
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// Hostname of the SMTP server
+    #[arg(long)]
+    pub smtp_host: Option<String>,
+    /// Port of the SMTP server
+    #[arg(long)]
+    pub smtp_port: Option<u16>,
+    /// Username to log into the SMTP server
+    #[arg(long)]
+    pub smtp_user: Option<String>,
+    /// Password to log into the SMTP server
+    #[arg(long)]
+    pub smtp_pass: Option<String>,
+    /// Address that the emails are sent from
+    #[arg(long)]
+    pub sender: Option<String>,
+    /// Address to send to. Can be given up to 3 times
+    #[arg(long)]
+    pub recipient: Vec<String>,
+    /// Send a test email after any changes are made
+    #[arg(long)]
+    pub test: bool,
+}
@@ -0,0 +1,108 @@
+///
+/// # Neolink Email Config
+///
+/// This module handles the email alert settings of the camera
+///
+///
+/// # Usage
+///
+/// ```bash
+/// # To print the current email settings
+/// neolink email-config --config=config.toml CameraName
+/// # To change the SMTP server and recipient then send a test email
+/// neolink email-config --config=config.toml CameraName --smtp-host=smtp.example.com --smtp-user=me@example.com --smtp-pass=password --recipient=me@example.com --test
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use neolink_core::bc_protocol::MAX_EMAIL_RECIPIENTS;
+
+mod cmdline;
+
+use crate::common::NeoReactor;
+pub(crate) use cmdline::Opt;
+
+/// Entry point for the email-config subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    if opt.recipient.len() > MAX_EMAIL_RECIPIENTS {
+        return Err(anyhow!(
+            "Cameras support at most {} recipients",
+            MAX_EMAIL_RECIPIENTS
+        ));
+    }
+
+    let changed = opt.smtp_host.is_some()
+        || opt.smtp_port.is_some()
+        || opt.smtp_user.is_some()
+        || opt.smtp_pass.is_some()
+        || opt.sender.is_some()
+        || !opt.recipient.is_empty();
+    if changed {
+        let opt = &opt;
+        camera
+            .run_task(|cam| {
+                let smtp_host = opt.smtp_host.clone();
+                let smtp_port = opt.smtp_port;
+                let smtp_user = opt.smtp_user.clone();
+                let smtp_pass = opt.smtp_pass.clone();
+                let sender = opt.sender.clone();
+                let recipients = opt.recipient.clone();
+                Box::pin(async move {
+                    let mut email = cam
+                        .get_email_config()
+                        .await
+                        .context("Unable to get camera email settings")?;
+                    if let Some(smtp_host) = smtp_host {
+                        email.smtp_host = smtp_host;
+                    }
+                    if let Some(smtp_port) = smtp_port {
+                        email.smtp_port = smtp_port;
+                    }
+                    if let Some(smtp_user) = smtp_user {
+                        email.username = smtp_user;
+                    }
+                    email.password = smtp_pass;
+                    if sender.is_some() {
+                        email.sender = sender;
+                    }
+                    if !recipients.is_empty() {
+                        email.recipients = recipients;
+                    }
+                    // Leave the schedule as it is
+                    email.schedule = None;
+                    cam.set_email_config(&email)
+                        .await
+                        .context("Unable to set camera email settings")
+                })
+            })
+            .await?;
+    }
+
+    if opt.test {
+        camera
+            .run_task(|cam| {
+                Box::pin(async move {
+                    cam.test_email()
+                        .await
+                        .context("The camera could not send the test email")
+                })
+            })
+            .await?;
+        println!("Test email sent");
+    } else if !changed {
+        let email = camera
+            .run_task(|cam| {
+                Box::pin(async move {
+                    cam.get_email_config()
+                        .await
+                        .context("Unable to get camera email settings")
+                })
+            })
+            .await?;
+        println!("{}", toml::to_string(&email)?);
+    }
+
+    Ok(())
+}
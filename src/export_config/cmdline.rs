@@ -0,0 +1,8 @@
+use clap::Parser;
+
+/// The export-config command reads the camera's settings and prints them as neolink config
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+}
@@ -0,0 +1,208 @@
+///
+/// # Neolink Export Config
+///
+/// This module reads the settings of a camera and prints them as a
+/// push-config settings file
+///
+/// The file can be kept as a backup and given to `neolink push-config` to
+/// restore the camera. The `osd`, `stream_quality`, `motion_detection` and
+/// `email` sections are the same as those of `[[cameras]]` so they can also
+/// be copied into the config. Any setting that the camera does not support is
+/// left out
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink export-config --config=config.toml CameraName > camera.toml
+/// # Later
+/// neolink push-config --config=config.toml CameraName --settings=camera.toml
+/// ```
+///
+use anyhow::Result;
+use neolink_core::bc_protocol::StreamKind;
+use serde::Serialize;
+use std::future::Future;
+
+mod cmdline;
+
+use crate::{
+    common::{NeoInstance, NeoReactor},
+    config::{EmailConfig, MotionDetectionConfig, NtpConfig, OsdConfig, StreamQualityConfig},
+};
+pub(crate) use cmdline::Opt;
+
+/// The settings read from the camera in the shape of the push-config settings file
+#[derive(Serialize)]
+pub(crate) struct ExportedSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    osd: Option<OsdConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_quality: Option<StreamQualityConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    motion_detection: Option<MotionDetectionConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<EmailConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ntp: Option<NtpConfig>,
+}
+
+/// Entry point for the export-config subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let settings = read_camera(&camera).await?;
+    println!("# Settings of {} for neolink push-config", opt.camera);
+    println!("# The passwords are not exported");
+    println!("{}", toml::to_string(&settings)?);
+
+    Ok(())
+}
+
+/// Read the settings of the camera
+///
+/// Any section that the camera cannot give is left out
+pub(crate) async fn read_camera(camera: &NeoInstance) -> Result<ExportedSettings> {
+    let name = camera.config().await?.borrow().name.clone();
+    let (osd, stream_quality, motion_detection, email, ntp) = camera
+        .run_task(|cam| {
+            let name = name.clone();
+            Box::pin(async move {
+                let osd = read_section(&name, "OSD", cam.get_osd_config()).await;
+                let stream_quality = read_section(
+                    &name,
+                    "stream quality",
+                    cam.get_stream_quality(StreamKind::Main),
+                )
+                .await;
+                let motion_detection =
+                    read_section(&name, "motion detection", cam.get_motion_detection_config())
+                        .await;
+                let email = read_section(&name, "email", cam.get_email_config()).await;
+                let ntp = read_section(&name, "NTP", cam.get_ntp_config()).await;
+                Ok((osd, stream_quality, motion_detection, email, ntp))
+            })
+        })
+        .await?;
+
+    Ok(ExportedSettings {
+        osd: osd.map(|osd| OsdConfig {
+            timestamp_enabled: Some(osd.timestamp_enabled),
+            timestamp_format: Some(osd.timestamp_format),
+            camera_name_enabled: Some(osd.camera_name_enabled),
+            camera_name: Some(osd.camera_name),
+            position: Some(osd.position),
+        }),
+        stream_quality: stream_quality.map(|quality| StreamQualityConfig {
+            stream: "main".to_string(),
+            bitrate_kbps: Some(quality.bitrate_kbps),
+            frame_rate: Some(quality.frame_rate),
+            resolution: quality.resolution,
+            quality_preset: quality.quality_preset,
+            gop_size_frames: None,
+        }),
+        motion_detection: motion_detection.map(|motion| MotionDetectionConfig {
+            enabled: Some(motion.enabled),
+            sensitivity: Some(motion.sensitivity),
+            regions: Some(motion.regions),
+        }),
+        email: email.map(|email| EmailConfig {
+            enabled: Some(email.enabled),
+            smtp_host: Some(email.smtp_host),
+            smtp_port: Some(email.smtp_port),
+            tls_mode: Some(email.tls_mode),
+            username: Some(email.username),
+            // Never sent by the camera
+            password: None,
+            sender: email.sender,
+            recipients: Some(email.recipients),
+            schedule: email.schedule,
+        }),
+        ntp: ntp.map(|ntp| NtpConfig {
+            enabled: Some(ntp.enabled),
+            server: Some(ntp.server),
+            port: Some(ntp.port),
+            interval_mins: Some(ntp.interval_mins),
+        }),
+    })
+}
+
+/// Read one of the settings, logging and skipping it if the camera cannot give it
+async fn read_section<T, F>(name: &str, section: &str, read: F) -> Option<T>
+where
+    F: Future<Output = Result<T, neolink_core::Error>>,
+{
+    match read.await {
+        Ok(value) => Some(value),
+        Err(e) => {
+            log::warn!("{}: Unable to read the {} settings: {:?}", name, section, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use neolink_core::bc_protocol::{EmailTlsMode, MotionRegion, OsdPosition, QualityPreset, Rect};
+
+    #[test]
+    fn test_export_can_be_pushed() {
+        let settings = ExportedSettings {
+            osd: Some(OsdConfig {
+                timestamp_enabled: Some(true),
+                timestamp_format: Some("DMY".to_string()),
+                camera_name_enabled: Some(true),
+                camera_name: Some("Front Door".to_string()),
+                position: Some(OsdPosition { x: 10, y: 20 }),
+            }),
+            stream_quality: Some(StreamQualityConfig {
+                stream: "main".to_string(),
+                bitrate_kbps: Some(2048),
+                frame_rate: Some(15),
+                resolution: Some((2560, 1440)),
+                quality_preset: Some(QualityPreset::High),
+                gop_size_frames: None,
+            }),
+            motion_detection: Some(MotionDetectionConfig {
+                enabled: Some(true),
+                sensitivity: Some(50),
+                regions: Some(vec![MotionRegion {
+                    rect: Rect {
+                        x: 0,
+                        y: 0,
+                        width: 4,
+                        height: 2,
+                    },
+                    enabled: true,
+                }]),
+            }),
+            email: Some(EmailConfig {
+                enabled: Some(false),
+                smtp_host: Some("smtp.example.com".to_string()),
+                smtp_port: Some(465),
+                tls_mode: Some(EmailTlsMode::Tls),
+                username: Some("camera".to_string()),
+                password: None,
+                sender: None,
+                recipients: Some(vec!["me@example.com".to_string()]),
+                schedule: Some("1".repeat(168)),
+            }),
+            ntp: Some(NtpConfig {
+                enabled: Some(true),
+                server: Some("pool.ntp.org".to_string()),
+                port: Some(123),
+                interval_mins: Some(1440),
+            }),
+        };
+
+        let text = toml::to_string(&settings).unwrap();
+        let pushed = crate::push_config::parse_settings(&text).unwrap();
+        assert_eq!(pushed.osd, settings.osd);
+        assert_eq!(pushed.stream_quality, settings.stream_quality);
+        assert_eq!(pushed.motion_detection, settings.motion_detection);
+        assert_eq!(pushed.email, settings.email);
+        assert_eq!(pushed.ntp, settings.ntp);
+    }
+}
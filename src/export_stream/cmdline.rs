@@ -0,0 +1,21 @@
+use crate::common::stream_parse;
+use clap::Parser;
+use neolink_core::bc_protocol::StreamKind;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The export-stream command writes the raw video of a camera to a file
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The stream to export: main, sub or extern
+    #[arg(long, value_parser = stream_parse, default_value = "main")]
+    pub stream: StreamKind,
+    /// The file to write such as camera.h264 or camera.h265
+    #[arg(long, value_parser = PathBuf::from_str)]
+    pub output: PathBuf,
+    /// Stop after this many seconds, 0 runs until stopped with ctrl-c
+    #[arg(long, default_value_t = 0)]
+    pub duration: u64,
+}
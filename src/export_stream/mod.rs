@@ -0,0 +1,115 @@
+///
+/// # Neolink Export Stream
+///
+/// This module writes the video of a camera to a file as the raw
+/// bitstream without any container. For h264 and h265 this is the Annex B
+/// byte stream that `ffplay -f h264`, `mediainfo` or a stream analyser can
+/// read directly. For av1 it is the low overhead obu stream
+///
+/// The file starts at the first keyframe. The audio is not exported
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink export-stream --config=config.toml CameraName --output=camera.h264
+/// # Thirty seconds of the sub stream
+/// neolink export-stream --config=config.toml CameraName --stream=sub --output=camera.h264 --duration=30
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use tokio::{
+    sync::broadcast::error::RecvError,
+    time::{sleep, Duration},
+};
+
+mod cmdline;
+
+use crate::common::{NeoReactor, VidFormat};
+pub(crate) use cmdline::Opt;
+
+/// The Annex B start code that is put before a NAL unit that has none
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// Entry point for the export-stream subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let mut stream_instance = camera
+        .stream(opt.stream)
+        .await
+        .context("Failed to start video")?;
+    let vid_format = stream_instance
+        .config
+        .wait_for(|config| config.vid_ready())
+        .await?
+        .vid_format;
+    let add_start_codes = match vid_format {
+        VidFormat::H264 | VidFormat::H265 => true,
+        VidFormat::Av1 => false,
+        VidFormat::None => unreachable!(),
+    };
+
+    let file =
+        File::create(&opt.output).with_context(|| format!("Could not create {:?}", opt.output))?;
+    let mut writer = BufWriter::new(file);
+    println!(
+        "Writing the {:?} video of {} to {:?}",
+        vid_format, opt.camera, opt.output
+    );
+
+    let stop = async {
+        if opt.duration > 0 {
+            sleep(Duration::from_secs(opt.duration)).await;
+            Ok(())
+        } else {
+            tokio::signal::ctrl_c().await
+        }
+    };
+    tokio::pin!(stop);
+    let mut started = false;
+    let mut frames = 0u64;
+    let mut bytes = 0u64;
+    let vid = &mut stream_instance.vid;
+    let result = loop {
+        let frame = tokio::select! {
+            v = &mut stop => break v.map_err(Into::into),
+            frame = vid.recv() => frame,
+        };
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(RecvError::Lagged(n)) => {
+                // A gap would break the following frames until the next keyframe
+                log::warn!("Dropped {} frames, waiting for the next keyframe", n);
+                started = false;
+                continue;
+            }
+            Err(RecvError::Closed) => break Err(anyhow!("The stream has closed")),
+        };
+        started |= frame.keyframe;
+        if !started {
+            continue;
+        }
+        if add_start_codes && !has_start_code(&frame.data) {
+            writer.write_all(&START_CODE)?;
+            bytes += START_CODE.len() as u64;
+        }
+        writer.write_all(&frame.data)?;
+        bytes += frame.data.len() as u64;
+        frames += 1;
+    };
+    writer.flush()?;
+    println!(
+        "Wrote {} frames ({} bytes) to {:?}",
+        frames, bytes, opt.output
+    );
+    result
+}
+
+/// If the data starts with a three or four byte Annex B start code
+fn has_start_code(data: &[u8]) -> bool {
+    data.starts_with(&[0, 0, 1]) || data.starts_with(&[0, 0, 0, 1])
+}
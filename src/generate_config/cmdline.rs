@@ -0,0 +1,11 @@
+use clap::Parser;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The generate-config command asks some questions and writes a config from the answers
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The config file to write
+    #[arg(long, value_parser = PathBuf::from_str, default_value = "neolink.toml")]
+    pub output: PathBuf,
+}
@@ -0,0 +1,274 @@
+///
+/// # Neolink Generate Config
+///
+/// This module writes a first config by asking for the cameras and the
+/// rtsp users on the terminal, so that a new user does not have to start
+/// from an empty toml file
+///
+/// After each camera is entered neolink can log into it straight away so
+/// that a wrong address or password is caught before the config is saved
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink generate-config --output=neolink.toml
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::io::{BufRead, Write};
+use validator::Validate;
+
+mod cmdline;
+
+use crate::{
+    config::{CameraConfig, Config, UserConfig},
+    utils::{connect, login},
+};
+pub(crate) use cmdline::Opt;
+
+/// The port the camera listens on when none is given
+const DEFAULT_CAMERA_PORT: u16 = 9000;
+
+#[derive(Serialize)]
+struct Generated {
+    bind_port: u16,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    users: Vec<UserConfig>,
+    cameras: Vec<CameraSnippet>,
+}
+
+#[derive(Serialize, Clone)]
+struct CameraSnippet {
+    name: String,
+    username: String,
+    password: String,
+    address: String,
+    stream: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    path_aliases: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    permitted_users: Option<Vec<String>>,
+}
+
+/// Entry point for the generate-config subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt) -> Result<()> {
+    if opt.output.exists()
+        && !ask_yes_no(
+            &format!("{:?} already exists, overwrite it?", opt.output),
+            false,
+        )
+        .await?
+    {
+        return Err(anyhow!("Not overwriting {:?}", opt.output));
+    }
+
+    let mut cameras: Vec<CameraSnippet> = vec![];
+    loop {
+        let mut camera = ask_blocking(ask_camera).await?;
+        while ask_yes_no("Test the connection to the camera now?", true).await? {
+            match test_camera(&camera).await {
+                Ok(()) => {
+                    println!("Logged into {} successfully", camera.name);
+                    break;
+                }
+                Err(e) => {
+                    println!("Could not log into {}: {:#}", camera.name, e);
+                    if !ask_yes_no("Enter the camera again?", true).await? {
+                        break;
+                    }
+                    camera = ask_blocking(ask_camera).await?;
+                }
+            }
+        }
+        cameras.push(camera);
+        if !ask_yes_no("Add another camera?", false).await? {
+            break;
+        }
+    }
+
+    let bind_port = ask_blocking(|| ask_parsed("Port of the rtsp server", Some(8554))).await?;
+    let mut users = vec![];
+    if ask_yes_no(
+        "Require a username and password to watch the rtsp streams?",
+        true,
+    )
+    .await?
+    {
+        loop {
+            users.push(ask_blocking(ask_user).await?);
+            if !ask_yes_no("Add another rtsp user?", false).await? {
+                break;
+            }
+        }
+        if users.len() > 1 {
+            let names = users
+                .iter()
+                .map(|user| user.name.clone())
+                .collect::<Vec<_>>();
+            for camera in cameras.iter_mut() {
+                let allowed = ask_blocking({
+                    let question = format!(
+                        "Users allowed to watch {} separated by commas (blank for anyone)",
+                        camera.name
+                    );
+                    move || ask(&question, Some(""))
+                })
+                .await?;
+                let allowed = allowed
+                    .split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect::<Vec<_>>();
+                if let Some(unknown) = allowed.iter().find(|name| !names.contains(name)) {
+                    println!(
+                        "{} is not an rtsp user and will not be able to log in",
+                        unknown
+                    );
+                }
+                if !allowed.is_empty() {
+                    camera.permitted_users = Some(allowed);
+                }
+            }
+        }
+    }
+
+    let generated = toml::to_string(&Generated {
+        bind_port,
+        users,
+        cameras,
+    })?;
+    // Check that neolink will accept what was written
+    Config::from_toml(&generated)?
+        .expand_channels()
+        .validate()
+        .context("The answers do not make a valid config")?;
+    std::fs::write(&opt.output, generated)
+        .with_context(|| format!("Could not write {:?}", opt.output))?;
+    println!(
+        "Written to {:?}. Start neolink with: neolink rtsp --config={:?}",
+        opt.output, opt.output
+    );
+    Ok(())
+}
+
+/// Log into the camera with the answers
+async fn test_camera(camera: &CameraSnippet) -> Result<()> {
+    let config: CameraConfig = toml::from_str(&toml::to_string(camera)?)?;
+    let bc_camera = connect(&config).await?;
+    login(&config, &bc_camera).await?;
+    let _ = bc_camera.logout().await;
+    Ok(())
+}
+
+fn ask_camera() -> Result<CameraSnippet> {
+    println!();
+    let name = ask("Name of the camera", None)?;
+    let ip = ask("IP address or hostname of the camera", None)?;
+    let port: u16 = ask_parsed("Port of the camera", Some(DEFAULT_CAMERA_PORT))?;
+    let username = ask("Username on the camera", Some("admin"))?;
+    let password = rpassword::prompt_password("Password on the camera: ")?;
+    let stream = loop {
+        let stream = ask("Streams to serve: main, sub, both or all", Some("all"))?.to_lowercase();
+        if ["main", "sub", "both", "all"].contains(&stream.as_str()) {
+            break stream;
+        }
+        println!("Should be main, sub, both or all");
+    };
+    let path = ask(
+        &format!("Another rtsp path besides /{} (blank for none)", name),
+        Some(""),
+    )?;
+    Ok(CameraSnippet {
+        address: format!("{}:{}", ip, port),
+        name,
+        username,
+        password,
+        stream,
+        path_aliases: if path.is_empty() {
+            vec![]
+        } else {
+            vec![format!("/{}", path.trim_start_matches('/'))]
+        },
+        permitted_users: None,
+    })
+}
+
+fn ask_user() -> Result<UserConfig> {
+    let name = ask("Username for the rtsp streams", None)?;
+    let pass = loop {
+        let pass = rpassword::prompt_password("Password for the rtsp streams: ")?;
+        if pass.is_empty() {
+            println!("The password cannot be empty");
+            continue;
+        }
+        if pass == rpassword::prompt_password("Confirm password: ")? {
+            break pass;
+        }
+        println!("The passwords did not match");
+    };
+    Ok(UserConfig {
+        name,
+        pass,
+        admin: false,
+    })
+}
+
+/// Run the questions off the async runtime as they block on the terminal
+async fn ask_blocking<T, F>(questions: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(questions).await?
+}
+
+async fn ask_yes_no(question: &str, default: bool) -> Result<bool> {
+    let question = format!("{} [{}]", question, if default { "Y/n" } else { "y/N" });
+    ask_blocking(move || loop {
+        match ask(&question, Some(""))?.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Answer yes or no"),
+        }
+    })
+    .await
+}
+
+fn ask_parsed<T>(question: &str, default: Option<T>) -> Result<T>
+where
+    T: std::str::FromStr + std::fmt::Display,
+{
+    let default = default.map(|default| default.to_string());
+    loop {
+        match ask(question, default.as_deref())?.parse() {
+            Ok(value) => return Ok(value),
+            Err(_) => println!("That is not a valid value"),
+        }
+    }
+}
+
+/// Ask until there is an answer, an empty answer gives the default
+fn ask(question: &str, default: Option<&str>) -> Result<String> {
+    loop {
+        match default {
+            Some(default) if !default.is_empty() => print!("{} [{}]: ", question, default),
+            _ => print!("{}: ", question),
+        }
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().lock().read_line(&mut answer)? == 0 {
+            return Err(anyhow!("No more input"));
+        }
+        let answer = answer.trim();
+        match (answer.is_empty(), default) {
+            (false, _) => return Ok(answer.to_string()),
+            (true, Some(default)) => return Ok(default.to_string()),
+            (true, None) => println!("An answer is needed"),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// The generate-mask command turns an image into motion detection regions
+///
+/// White areas of the image detect motion and black areas do not
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// PNG image of the mask. It is stretched over the camera's motion grid
+    #[arg(long)]
+    pub input: PathBuf,
+    /// Write the config to this file instead of printing it
+    #[arg(long)]
+    pub output_config: Option<PathBuf>,
+    /// Also send the regions to the camera
+    #[arg(long)]
+    pub apply: bool,
+}
@@ -0,0 +1,177 @@
+///
+/// # Neolink Generate Mask
+///
+/// This module turns an image into the motion detection regions of a camera
+///
+/// The image is stretched over the camera's motion grid. Each cell of the
+/// grid where more than half of the pixels are white detects motion. The
+/// regions are written as a config snippet that can be added to the camera
+/// and can also be sent to the camera straight away
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink generate-mask --config=config.toml CameraName --input=mask.png --output-config=mask.toml
+/// # To also send it to the camera
+/// neolink generate-mask --config=config.toml CameraName --input=mask.png --apply
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use neolink_core::bc_protocol::{regions_from_grid, MotionRegion, Rect};
+use serde::Serialize;
+use std::{fs::File, path::Path};
+
+mod cmdline;
+
+use crate::{common::NeoReactor, config::MotionDetectionConfig};
+pub(crate) use cmdline::Opt;
+
+/// The part of the config that is written out
+#[derive(Serialize)]
+struct Snippet {
+    cameras: Vec<CameraSnippet>,
+}
+
+#[derive(Serialize)]
+struct CameraSnippet {
+    name: String,
+    motion_detection: MotionDetectionConfig,
+}
+
+/// Entry point for the generate-mask subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let (width, height, luma) = read_mask(&opt.input)
+        .with_context(|| format!("Unable to read the mask {:?}", opt.input))?;
+
+    let (cols, rows) = camera
+        .run_task(|cam| {
+            Box::pin(async move {
+                cam.get_motion_grid_size()
+                    .await
+                    .context("Unable to get the camera's motion grid")
+            })
+        })
+        .await?;
+    if width < cols || height < rows {
+        return Err(anyhow!(
+            "The mask is {width}x{height} but it must be at least as large as the {cols}x{rows} motion grid"
+        ));
+    }
+
+    let grid = mask_to_grid(&luma, width, height, cols, rows);
+    let mut regions = regions_from_grid(cols, rows, &grid);
+    if regions.is_empty() {
+        // An empty list would leave the camera's regions as they are
+        regions.push(MotionRegion {
+            rect: Rect {
+                x: 0,
+                y: 0,
+                width: cols,
+                height: rows,
+            },
+            enabled: false,
+        });
+    }
+    log::info!(
+        "{}: Mask covers {} of the {}x{} motion grid cells",
+        opt.camera,
+        grid.iter().filter(|&&on| on).count(),
+        cols,
+        rows
+    );
+
+    let snippet = toml::to_string(&Snippet {
+        cameras: vec![CameraSnippet {
+            name: opt.camera.clone(),
+            motion_detection: MotionDetectionConfig {
+                enabled: None,
+                sensitivity: None,
+                regions: Some(regions.clone()),
+            },
+        }],
+    })?;
+    match opt.output_config.as_ref() {
+        Some(path) => std::fs::write(path, snippet)
+            .with_context(|| format!("Unable to write the config to {:?}", path))?,
+        None => println!("{}", snippet),
+    }
+
+    if opt.apply {
+        camera
+            .run_task(|cam| {
+                let regions = regions.clone();
+                Box::pin(async move {
+                    let mut motion_detection = cam
+                        .get_motion_detection_config()
+                        .await
+                        .context("Unable to get camera motion detection settings")?;
+                    motion_detection.regions = regions;
+                    cam.set_motion_detection_config(&motion_detection)
+                        .await
+                        .context("Unable to set camera motion detection settings")
+                })
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Read a png as one brightness byte per pixel
+///
+/// Transparent pixels count as black
+fn read_mask(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    let (color_type, _) = reader.output_color_type();
+    let samples = color_type.samples();
+
+    let luma = buf[..info.buffer_size()]
+        .chunks_exact(samples)
+        .map(|pixel| {
+            let (value, alpha) = match pixel {
+                [v] => (*v as u32, 255),
+                [v, a] => (*v as u32, *a as u32),
+                [r, g, b] => (
+                    (*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000,
+                    255,
+                ),
+                [r, g, b, a] => (
+                    (*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000,
+                    *a as u32,
+                ),
+                _ => (0, 0),
+            };
+            (value * alpha / 255) as u8
+        })
+        .collect();
+    Ok((info.width, info.height, luma))
+}
+
+/// Divide the image into the grid and return which cells are mostly white
+fn mask_to_grid(luma: &[u8], width: u32, height: u32, cols: u32, rows: u32) -> Vec<bool> {
+    let mut grid = Vec::with_capacity((cols * rows) as usize);
+    for row in 0..rows {
+        let (y0, y1) = (row * height / rows, (row + 1) * height / rows);
+        for col in 0..cols {
+            let (x0, x1) = (col * width / cols, (col + 1) * width / cols);
+            let mut white = 0;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    if luma[(y * width + x) as usize] >= 128 {
+                        white += 1;
+                    }
+                }
+            }
+            grid.push(white * 2 > (x1 - x0) * (y1 - y0));
+        }
+    }
+    grid
+}
@@ -0,0 +1,12 @@
+use clap::Parser;
+
+/// The health-check command queries the health endpoint of a running `neolink rtsp`
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The host that neolink is running on
+    #[arg(long, default_value = "127.0.0.1")]
+    pub host: String,
+    /// The port of the health endpoint. Defaults to `health_bind_port` of the config
+    #[arg(long)]
+    pub port: Option<u16>,
+}
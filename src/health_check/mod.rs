@@ -0,0 +1,96 @@
+///
+/// # Neolink Health Check
+///
+/// This module queries the `/healthz` endpoint of a running `neolink rtsp`
+/// so that it can be used as a docker or kubernetes probe
+///
+/// The endpoint is only served when `health_bind_port` is set in the
+/// config. The json body of the response is printed and the exit code is 0
+/// when healthy and 1 when it is not or cannot be reached
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink health-check --config=config.toml
+/// # Another host or port than the config
+/// neolink health-check --config=config.toml --host=192.168.1.10 --port=8555
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::{timeout, Duration},
+};
+
+mod cmdline;
+
+use crate::common::NeoReactor;
+pub(crate) use cmdline::Opt;
+
+/// How long the whole request is given
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Entry point for the health-check subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let port = match opt.port {
+        Some(port) => port,
+        None => reactor
+            .config()
+            .await?
+            .borrow()
+            .health_bind_port
+            .context("No --port given and health_bind_port is not set in the config")?,
+    };
+
+    match timeout(REQUEST_TIMEOUT, get_health(&opt.host, port))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|v| v)
+    {
+        Ok((200, body)) => {
+            println!("{}", body);
+            Ok(())
+        }
+        Ok((_, body)) => {
+            println!("{}", body);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "healthy": false,
+                    "error": format!("Could not reach {}:{}: {:#}", opt.host, port, e),
+                })
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Requests `/healthz` returning the status code and body
+async fn get_health(host: &str, port: u16) -> Result<(u16, String)> {
+    let mut socket = TcpStream::connect((host, port)).await?;
+    socket
+        .write_all(
+            format!("GET /healthz HTTP/1.1\r\nHost: {host}:{port}\r\nConnection: close\r\n\r\n")
+                .as_bytes(),
+        )
+        .await?;
+    let mut response = String::new();
+    socket.read_to_string(&mut response).await?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow!("Invalid http response"))?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("Invalid http status line"))?;
+    Ok((status, body.to_string()))
+}
@@ -0,0 +1,22 @@
+use crate::common::stream_parse;
+use clap::Parser;
+use neolink_core::bc_protocol::StreamKind;
+
+/// The http-mjpeg command serves a camera as an mjpeg stream over http
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The address to listen on
+    #[arg(long, default_value = "0.0.0.0")]
+    pub bind: String,
+    /// The port to listen on
+    #[arg(long, default_value_t = 8080)]
+    pub port: u16,
+    /// The quality of the jpegs from 1 to 100
+    #[arg(long, default_value_t = 85, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub quality: u8,
+    /// The stream to serve: main, sub or extern
+    #[arg(long, value_parser = stream_parse, default_value = "main")]
+    pub stream: StreamKind,
+}
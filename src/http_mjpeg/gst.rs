@@ -0,0 +1,117 @@
+use anyhow::{anyhow, Context, Result};
+use gstreamer::{
+    parse::launch_full, prelude::*, ClockTime, FlowError, FlowSuccess, MessageView, ParseFlags,
+    Pipeline, State,
+};
+use gstreamer_app::{AppSink, AppSinkCallbacks, AppSrc};
+use std::sync::Arc;
+use tokio::sync::watch::Sender as WatchSender;
+
+use crate::common::VidFormat;
+
+/// Decodes the keyframes pushed into the appsrc and sends each as a jpeg
+pub(super) struct JpegEncoder {
+    pipeline: Pipeline,
+    source: AppSrc,
+}
+
+impl JpegEncoder {
+    pub(super) fn new(
+        format: VidFormat,
+        quality: u8,
+        jpegs: WatchSender<Option<Arc<Vec<u8>>>>,
+    ) -> Result<Self> {
+        let pipeline = create_pipeline(format, quality)?;
+        let source = pipeline
+            .by_name("thesource")
+            .expect("There shoud be a `thesource`")
+            .dynamic_cast::<AppSrc>()
+            .map_err(|_| {
+                anyhow!("Cannot find appsource in gstreamer, check your gstreamer plugins")
+            })?;
+        let sink = pipeline
+            .by_name("thesink")
+            .expect("There shoud be a `thesink`")
+            .dynamic_cast::<AppSink>()
+            .map_err(|_| {
+                anyhow!("Cannot find appsink in gstreamer, check your gstreamer plugins")
+            })?;
+        sink.set_callbacks(
+            AppSinkCallbacks::builder()
+                .new_sample(move |sink| {
+                    let sample = sink.pull_sample().map_err(|_| FlowError::Eos)?;
+                    let buffer = sample.buffer().ok_or(FlowError::Error)?;
+                    let map = buffer.map_readable().map_err(|_| FlowError::Error)?;
+                    jpegs.send_replace(Some(Arc::new(map.as_slice().to_vec())));
+                    Ok(FlowSuccess::Ok)
+                })
+                .build(),
+        );
+        Ok(Self { pipeline, source })
+    }
+
+    pub(super) fn push(&self, frame: &[u8]) -> Result<()> {
+        self.source
+            .push_buffer(gstreamer::Buffer::from_slice(frame.to_vec()))
+            .map_err(|e| anyhow!("Streaming error: {e:?}"))?;
+        Ok(())
+    }
+
+    /// Play the pipeline until it ends or fails
+    ///
+    /// This blocks so should be run with `spawn_blocking`
+    pub(super) fn run(&self) -> Result<()> {
+        self.pipeline.set_state(State::Playing)?;
+        let bus = self
+            .pipeline
+            .bus()
+            .expect("Pipeline without bus. Shouldn't happen!");
+        let result = bus
+            .iter_timed(ClockTime::NONE)
+            .find_map(|msg| match msg.view() {
+                MessageView::Eos(..) => Some(Ok(())),
+                MessageView::Error(err) => Some(Err(anyhow!(
+                    "Error from gstreamer while making jpegs: {}",
+                    err.error()
+                ))),
+                _ => None,
+            })
+            .unwrap_or(Ok(()));
+        self.pipeline
+            .set_state(State::Null)
+            .context("Error in gstreamer when setting state to Null")?;
+        result
+    }
+
+    pub(super) fn stop(&self) {
+        let _ = self.source.end_of_stream();
+    }
+}
+
+fn create_pipeline(format: VidFormat, quality: u8) -> Result<Pipeline> {
+    gstreamer::init()
+        .context("Unable to start gstreamer ensure it and all plugins are installed")?;
+
+    let (caps, parse) = match format {
+        VidFormat::H264 => ("", "h264parse"),
+        VidFormat::H265 => ("", "h265parse"),
+        VidFormat::Av1 => ("caps=video/x-av1,stream-format=obu-stream", "av1parse"),
+        VidFormat::None => unreachable!(),
+    };
+    let launch_str = format!(
+        "appsrc name=thesource is-live=true do-timestamp=true format=time {} \
+        ! {} \
+        ! decodebin \
+        ! videoconvert \
+        ! jpegenc quality={} \
+        ! appsink name=thesink sync=false",
+        caps, parse, quality
+    );
+    log::info!("{}", launch_str);
+
+    let pipeline = launch_full(&launch_str, None, ParseFlags::empty())
+        .context("Unable to load gstreamer pipeline ensure all gstramer plugins are installed")?;
+    pipeline.dynamic_cast::<Pipeline>().map_err(|_| {
+        anyhow!("Unable to create gstreamer pipeline ensure all gstramer plugins are installed")
+    })
+}
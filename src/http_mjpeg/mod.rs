@@ -0,0 +1,169 @@
+///
+/// # Neolink HTTP MJPEG
+///
+/// This module serves a camera as an mjpeg stream over http so that it can
+/// be watched in any browser or dashboard that shows an `<img>`, such as
+/// the picture cards of Home Assistant or openHAB
+///
+/// Each keyframe of the camera is decoded and sent as a jpeg so the frame
+/// rate is the keyframe rate of the camera. Every client gets the newest
+/// jpeg and skips any it was too slow for
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink http-mjpeg --config=config.toml CameraName --port=8080 --quality=80
+/// # Then open http://localhost:8080/stream
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{broadcast::error::RecvError, watch},
+};
+
+mod cmdline;
+mod gst;
+
+use crate::common::NeoReactor;
+pub(crate) use cmdline::Opt;
+use gst::JpegEncoder;
+
+/// The jpeg that was made last
+type LatestJpeg = watch::Receiver<Option<Arc<Vec<u8>>>>;
+
+/// Entry point for the http-mjpeg subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let mut stream_instance = camera
+        .stream(opt.stream)
+        .await
+        .context("Failed to start video")?;
+    let vid_format = stream_instance
+        .config
+        .wait_for(|config| config.vid_ready())
+        .await?
+        .vid_format;
+
+    let (jpeg_tx, jpeg_rx) = watch::channel(None);
+    let encoder = Arc::new(JpegEncoder::new(vid_format, opt.quality, jpeg_tx)?);
+    let thread_encoder = encoder.clone();
+    let encode = tokio::task::spawn_blocking(move || thread_encoder.run());
+
+    let listener = TcpListener::bind((opt.bind.as_str(), opt.port))
+        .await
+        .with_context(|| format!("Could not listen on {}:{}", opt.bind, opt.port))?;
+    log::info!(
+        "{}: MJPEG stream at http://{}:{}/stream",
+        opt.camera,
+        opt.bind,
+        opt.port
+    );
+
+    let vid = &mut stream_instance.vid;
+    let feed = async {
+        loop {
+            match vid.recv().await {
+                // A keyframe decodes on its own so the other frames are not needed
+                Ok(frame) if frame.keyframe => encoder.push(&frame.data)?,
+                Ok(_) => {}
+                Err(RecvError::Lagged(n)) => log::debug!("Dropped {} frames", n),
+                Err(RecvError::Closed) => {
+                    return Result::<()>::Err(anyhow!("The stream has closed"))
+                }
+            }
+        }
+    };
+    let serve = async {
+        loop {
+            let (socket, addr) = listener.accept().await?;
+            let jpeg_rx = jpeg_rx.clone();
+            tokio::task::spawn(async move {
+                if let Err(e) = handle_request(socket, jpeg_rx).await {
+                    log::debug!("MJPEG client {addr} ended: {e:?}");
+                }
+            });
+        }
+    };
+
+    let result = tokio::select! {
+        v = feed => v,
+        v = serve => v,
+        v = encode => v?.context("Failed to make the jpegs"),
+        v = tokio::signal::ctrl_c() => v.map_err(Into::into),
+    };
+    encoder.stop();
+    result
+}
+
+async fn handle_request(mut socket: TcpStream, mut jpeg_rx: LatestJpeg) -> Result<()> {
+    let (read, mut write) = socket.split();
+    let mut reader = BufReader::new(read);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    // Headers are not needed but must be read
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+    let status = match (method, path) {
+        ("GET", "/stream") => None,
+        ("GET", _) => Some("404 Not Found"),
+        _ => Some("405 Method Not Allowed"),
+    };
+    if let Some(status) = status {
+        let header = format!(
+            "HTTP/1.1 {status}\r\n\
+            Connection: close\r\n\
+            Content-Length: 0\r\n\r\n"
+        );
+        write.write_all(header.as_bytes()).await?;
+        write.flush().await?;
+        return Ok(());
+    }
+
+    write
+        .write_all(
+            b"HTTP/1.1 200 OK\r\n\
+            Cache-Control: no-cache\r\n\
+            Connection: close\r\n\
+            Content-Type: multipart/x-mixed-replace;boundary=frame\r\n\r\n",
+        )
+        .await?;
+    loop {
+        // Cloned so that the watch is not held over the writes
+        let jpeg = jpeg_rx.borrow_and_update().clone();
+        if let Some(jpeg) = jpeg {
+            let part = format!(
+                "--frame\r\n\
+                Content-Type: image/jpeg\r\n\
+                Content-Length: {}\r\n\r\n",
+                jpeg.len()
+            );
+            write.write_all(part.as_bytes()).await?;
+            write.write_all(&jpeg).await?;
+            write.write_all(b"\r\n").await?;
+            write.flush().await?;
+        }
+        if jpeg_rx.changed().await.is_err() {
+            // No more jpegs will be made
+            return Ok(());
+        }
+    }
+}
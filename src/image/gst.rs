@@ -197,6 +197,16 @@ fn create_pipeline(format: VidFormat, file_path: &Path) -> Result<Pipeline> {
                 file_path.display()
             )
         }
+        VidFormat::Av1 => {
+            format!(
+                "appsrc name=thesource caps=video/x-av1,stream-format=obu-stream \
+                ! av1parse \
+                ! decodebin \
+                ! jpegenc snapshot=TRUE
+                ! filesink location={}",
+                file_path.display()
+            )
+        }
         VidFormat::None => unreachable!(),
     };
 
@@ -0,0 +1,17 @@
+use clap::Parser;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The import-cert command checks a TLS certificate before it is used for the rtsp server
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The PEM file with the certificate (and usually the private key)
+    #[arg(long, value_parser = PathBuf::from_str)]
+    pub cert: PathBuf,
+    /// A PEM file with the private key if it is not in the certificate file
+    #[arg(long, value_parser = PathBuf::from_str)]
+    pub key: Option<PathBuf>,
+    /// Warn if the certificate is not valid for this hostname
+    #[arg(long)]
+    pub validate_cn: Option<String>,
+}
@@ -0,0 +1,167 @@
+///
+/// # Neolink Import Cert
+///
+/// This module checks a TLS certificate before it is given to
+/// the rtsp server with `certificate = "..."` in the config
+///
+/// It prints the subject, issuer, validity and SHA-256 fingerprint
+/// of the certificate and warns about common problems
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink import-cert --cert=/path/to/cert.pem --key=/path/to/key.pem --validate-cn=my.host.name
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use gstreamer::glib::{Checksum, ChecksumType};
+use gstreamer_rtsp_server::gio::TlsCertificate;
+use log::*;
+use std::{convert::TryFrom, fs};
+use x509_parser::{extensions::GeneralName, pem::Pem};
+
+mod cmdline;
+
+pub(crate) use cmdline::Opt;
+
+/// Certificates expiring within this many days will be warned about
+const EXPIRY_WARNING_DAYS: i64 = 30;
+
+/// Entry point for the import-cert subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt) -> Result<()> {
+    let mut contents =
+        fs::read_to_string(&opt.cert).with_context(|| format!("Failed to read {:?}", opt.cert))?;
+    if let Some(key_path) = opt.key.as_ref() {
+        let key = fs::read_to_string(key_path)
+            .with_context(|| format!("Failed to read {:?}", key_path))?;
+        contents.push('\n');
+        contents.push_str(&key);
+    }
+
+    // This is the same call the rtsp server uses, if it passes here it will pass there
+    TlsCertificate::from_pem(&contents).with_context(|| {
+        format!(
+            "{:?} is not a valid PEM certificate that the rtsp server can use",
+            opt.cert
+        )
+    })?;
+    let pems = Pem::iter_from_buffer(contents.as_bytes())
+        .filter_map(|pem| pem.ok())
+        .collect::<Vec<_>>();
+    if !pems.iter().any(|pem| pem.label.ends_with("PRIVATE KEY")) {
+        warn!("No private key was found. The rtsp server needs the private key in the same PEM as the certificate");
+    }
+
+    let pem = pems
+        .iter()
+        .find(|pem| pem.label == "CERTIFICATE")
+        .ok_or_else(|| anyhow!("No CERTIFICATE block in {:?}", opt.cert))?;
+    let x509 = pem
+        .parse_x509()
+        .with_context(|| format!("Could not parse the certificate in {:?}", opt.cert))?;
+
+    let mut checksum =
+        Checksum::new(ChecksumType::Sha256).ok_or_else(|| anyhow!("SHA-256 is not supported"))?;
+    checksum.update(&pem.contents);
+    let fingerprint = checksum
+        .string()
+        .ok_or_else(|| anyhow!("Could not compute the fingerprint"))?
+        .to_uppercase()
+        .as_bytes()
+        .chunks(2)
+        .map(|c| String::from_utf8_lossy(c).to_string())
+        .collect::<Vec<_>>()
+        .join(":");
+
+    let validity = x509.validity();
+    println!("Subject:     {}", x509.subject());
+    println!("Issuer:      {}", x509.issuer());
+    println!("Not Before:  {}", validity.not_before);
+    println!("Not After:   {}", validity.not_after);
+    println!("SHA-256:     {}", fingerprint);
+
+    match validity.time_to_expiration() {
+        None => warn!("The certificate is not currently valid"),
+        Some(remaining) if remaining.whole_days() < EXPIRY_WARNING_DAYS => {
+            warn!("The certificate expires in {} days", remaining.whole_days())
+        }
+        Some(_) => {}
+    }
+
+    let common_names = x509
+        .subject()
+        .iter_common_name()
+        .filter_map(|cn| cn.as_str().ok())
+        .map(|cn| cn.to_string())
+        .collect::<Vec<_>>();
+    let alt_names = x509
+        .subject_alternative_name()
+        .ok()
+        .flatten()
+        .map(|san| {
+            san.value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(dns) => Some(dns.to_string()),
+                    GeneralName::IPAddress(ip) => match ip.len() {
+                        4 => Some(
+                            std::net::Ipv4Addr::from(<[u8; 4]>::try_from(*ip).ok()?).to_string(),
+                        ),
+                        16 => Some(
+                            std::net::Ipv6Addr::from(<[u8; 16]>::try_from(*ip).ok()?).to_string(),
+                        ),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if !alt_names.is_empty() {
+        println!("Alt Names:   {}", alt_names.join(", "));
+    }
+
+    if let Some(hostname) = opt.validate_cn.as_ref() {
+        // Clients check the SAN if there is one and only fall back to the CN without it
+        let names = if alt_names.is_empty() {
+            &common_names
+        } else {
+            &alt_names
+        };
+        if !names.iter().any(|name| hostname_matches(name, hostname)) {
+            warn!(
+                "The certificate is not valid for {}. It is valid for: {}",
+                hostname,
+                names.join(", ")
+            );
+        }
+    }
+
+    println!(
+        "Certificate OK. Add the following to your config:\ncertificate = {:?}",
+        opt.cert
+    );
+    if opt.key.is_some() {
+        println!("The certificate and key must first be combined into one file e.g.\ncat cert.pem key.pem > neolink.pem");
+    }
+
+    Ok(())
+}
+
+/// Check a certificate name against a hostname allowing for a single `*.` wildcard label
+fn hostname_matches(name: &str, hostname: &str) -> bool {
+    let name = name.to_lowercase();
+    let hostname = hostname.to_lowercase();
+    if let Some(suffix) = name.strip_prefix("*.") {
+        hostname
+            .split_once('.')
+            .map(|(_, rest)| rest == suffix)
+            .unwrap_or(false)
+    } else {
+        name == hostname
+    }
+}
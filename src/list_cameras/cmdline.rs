@@ -0,0 +1,15 @@
+use clap::{Parser, ValueEnum};
+
+/// The list-cameras command checks every camera in the config and prints their status
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// How to print the results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser, Debug, Clone, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
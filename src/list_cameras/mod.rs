@@ -0,0 +1,202 @@
+///
+/// # Neolink List Cameras
+///
+/// This module checks all the cameras of the config at once and prints
+/// whether they can be reached and logged into along with their model and
+/// firmware
+///
+/// Cameras with an `address` are first checked with a plain tcp connection
+/// so that unreachable cameras are reported quickly. Cameras with only a
+/// `uid` go straight to discovery
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink list-cameras --config=config.toml
+/// # As a json array
+/// neolink list-cameras --config=config.toml --format=json
+/// ```
+///
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+use tokio::{
+    net::TcpStream,
+    task::JoinSet,
+    time::{timeout, Duration},
+};
+
+mod cmdline;
+
+use crate::{
+    common::NeoReactor,
+    config::CameraConfig,
+    utils::{connect, login},
+};
+pub(crate) use cmdline::{Opt, OutputFormat};
+
+/// How long the plain tcp connection is given
+const REACHABLE_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long a camera is given to be found, logged into and queried
+const CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Reachable,
+    Unreachable,
+    AuthError,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            Status::Reachable => "reachable",
+            Status::Unreachable => "unreachable",
+            Status::AuthError => "auth_error",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CameraStatus {
+    name: String,
+    address: String,
+    model: Option<String>,
+    firmware: Option<String>,
+    status: Status,
+}
+
+/// Entry point for the list-cameras subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let config = reactor.config().await?.borrow().clone();
+
+    let mut set = JoinSet::new();
+    for (idx, camera_config) in config.cameras.into_iter().enumerate() {
+        set.spawn(async move { (idx, check_camera(camera_config).await) });
+    }
+    let mut statuses = vec![];
+    while let Some(res) = set.join_next().await {
+        statuses.push(res?);
+    }
+    // Keep the order of the config
+    statuses.sort_by_key(|(idx, _)| *idx);
+    let statuses = statuses
+        .into_iter()
+        .map(|(_, status)| status)
+        .collect::<Vec<_>>();
+
+    match opt.format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&statuses)?),
+        OutputFormat::Table => print_table(&statuses),
+    }
+    Ok(())
+}
+
+async fn check_camera(config: CameraConfig) -> CameraStatus {
+    let mut status = CameraStatus {
+        name: config.name.clone(),
+        address: config
+            .camera_addr
+            .clone()
+            .or_else(|| config.camera_uid.as_ref().map(|uid| format!("uid:{uid}")))
+            .unwrap_or_default(),
+        model: None,
+        firmware: None,
+        status: Status::Unreachable,
+    };
+
+    if let Some(addr) = config.camera_addr.as_ref() {
+        if let Err(e) = check_reachable(addr).await {
+            log::debug!("{}: Not reachable: {:?}", config.name, e);
+            return status;
+        }
+    }
+
+    let res = timeout(CHECK_TIMEOUT, async {
+        let camera = connect(&config).await?;
+        let logged_in = login(&config, &camera).await;
+        let version = match &logged_in {
+            Ok(()) => camera.version().await.map_err(anyhow::Error::from),
+            Err(_) => Err(anyhow!("Not logged in")),
+        };
+        let _ = camera.logout().await;
+        let _ = camera.shutdown().await;
+        logged_in?;
+        version
+    })
+    .await;
+
+    match res {
+        Ok(Ok(version)) => {
+            status.status = Status::Reachable;
+            status.model = version.model.or(Some(version.name));
+            status.firmware = Some(version.firmwareVersion);
+        }
+        Ok(Err(e)) if is_auth_error(&e) => status.status = Status::AuthError,
+        Ok(Err(e)) => {
+            log::debug!("{}: Check failed: {:?}", config.name, e);
+            // It answered the tcp connection but not the rest
+            if config.camera_addr.is_some() {
+                status.status = Status::Reachable;
+            }
+        }
+        Err(_) => log::debug!("{}: Timed out", config.name),
+    }
+    status
+}
+
+/// Open and close a tcp connection to the address, port 9000 if none is given
+async fn check_reachable(addr: &str) -> Result<()> {
+    let addrs = match addr.to_socket_addrs() {
+        Ok(addrs) => addrs.collect::<Vec<_>>(),
+        Err(_) => vec![SocketAddr::new(IpAddr::from_str(addr)?, 9000)],
+    };
+    timeout(REACHABLE_TIMEOUT, TcpStream::connect(addrs.as_slice())).await??;
+    Ok(())
+}
+
+fn is_auth_error(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<neolink_core::Error>(),
+            Some(neolink_core::Error::AuthFailed)
+        )
+    })
+}
+
+fn print_table(statuses: &[CameraStatus]) {
+    let rows = statuses
+        .iter()
+        .map(|status| {
+            [
+                status.name.clone(),
+                status.address.clone(),
+                status.model.clone().unwrap_or_else(|| "-".to_string()),
+                status.firmware.clone().unwrap_or_else(|| "-".to_string()),
+                status.status.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = ["Name", "Address", "Model", "Firmware", "Status"].map(str::to_string);
+    let mut widths = header.clone().map(|h| h.len());
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let line = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
@@ -0,0 +1,35 @@
+use clap::{Parser, ValueEnum};
+
+/// The log-events command prints the events of a camera as they happen
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The kinds of events to print, comma separated
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "motion,disconnect,stream-format"
+    )]
+    pub events: Vec<EventKind>,
+    /// How to print the events
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EventKind {
+    /// motion_start and motion_stop
+    Motion,
+    /// camera_online, camera_offline and stream_reconnect
+    Disconnect,
+    /// format_change of the main stream
+    StreamFormat,
+}
+
+#[derive(Parser, Debug, Clone, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
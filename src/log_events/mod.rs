@@ -0,0 +1,157 @@
+///
+/// # Neolink Log Events
+///
+/// This module connects to a camera and prints its events as they happen
+/// until it is stopped with Ctrl-C
+///
+/// The events are:
+///
+/// - `motion_start` and `motion_stop`
+/// - `camera_online` and `camera_offline` when the connection comes and goes
+/// - `stream_reconnect` for each attempt to connect again
+/// - `format_change` when the video codec of the main stream changes
+///
+/// Text prints one event per line as `timestamp camera event [detail]`.
+/// Json prints one object per line
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink log-events --config=config.toml CameraName --events=motion --format=json
+/// ```
+///
+use anyhow::Result;
+use neolink_core::bc_protocol::StreamKind;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::{interval, Duration};
+
+mod cmdline;
+
+use crate::common::{CameraConnectionState, MdState, NeoReactor, VidFormat};
+pub(crate) use cmdline::{EventKind, Opt, OutputFormat};
+
+/// How often the connection state is checked for reconnects
+const STATE_POLL: Duration = Duration::from_millis(500);
+
+#[derive(Serialize)]
+struct Event<'a> {
+    /// Seconds since the unix epoch
+    timestamp: f64,
+    camera: &'a str,
+    event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl<'a> Event<'a> {
+    fn print(&self, format: &OutputFormat) -> Result<()> {
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string(self)?),
+            OutputFormat::Text => match self.detail.as_ref() {
+                Some(detail) => println!(
+                    "{:.3} {} {} {}",
+                    self.timestamp, self.camera, self.event, detail
+                ),
+                None => println!("{:.3} {} {}", self.timestamp, self.camera, self.event),
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Entry point for the log-events subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let wants_motion = opt.events.contains(&EventKind::Motion);
+    let wants_disconnect = opt.events.contains(&EventKind::Disconnect);
+
+    let mut motion = camera.motion().await?;
+    let mut camera_watch = camera.camera();
+    let connection_state = camera.connection_state().await?;
+    // The format is only known while the stream is running
+    let mut stream = if opt.events.contains(&EventKind::StreamFormat) {
+        let mut stream = camera.stream(StreamKind::Main).await?;
+        stream.activate().await?;
+        Some(stream)
+    } else {
+        None
+    };
+
+    let print = |event: &'static str, detail: Option<String>| {
+        Event {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            camera: &opt.camera,
+            event,
+            detail,
+        }
+        .print(&opt.format)
+    };
+
+    let mut online = camera_watch.borrow_and_update().upgrade().is_some();
+    let mut last_attempt = None;
+    let mut last_format = stream
+        .as_mut()
+        .map(|stream| stream.config.borrow_and_update().vid_format);
+    let mut state_check = interval(STATE_POLL);
+    loop {
+        tokio::select! {
+            v = tokio::signal::ctrl_c() => {
+                v?;
+                break;
+            }
+            v = motion.changed(), if wants_motion => {
+                v?;
+                match *motion.borrow() {
+                    MdState::Start(_) => print("motion_start", None)?,
+                    MdState::Stop(_) => print("motion_stop", None)?,
+                    MdState::Unknown => {}
+                }
+            }
+            v = camera_watch.changed(), if wants_disconnect => {
+                v?;
+                let now_online = camera_watch.borrow().upgrade().is_some();
+                if now_online != online {
+                    online = now_online;
+                    print(if online { "camera_online" } else { "camera_offline" }, None)?;
+                }
+            }
+            _ = state_check.tick(), if wants_disconnect => {
+                let attempt = match *connection_state.lock().unwrap() {
+                    CameraConnectionState::Reconnecting { attempt, .. } => Some(attempt),
+                    _ => None,
+                };
+                if attempt.is_some() && attempt != last_attempt {
+                    print("stream_reconnect", attempt.map(|attempt| format!("attempt {attempt}")))?;
+                }
+                last_attempt = attempt;
+            }
+            v = async {
+                match stream.as_mut() {
+                    Some(stream) => stream.config.changed().await,
+                    None => futures::future::pending().await,
+                }
+            } => {
+                v?;
+                let format = stream
+                    .as_ref()
+                    .map(|stream| stream.config.borrow().vid_format);
+                // The format goes to None while the stream restarts
+                if format != last_format && !matches!(format, Some(VidFormat::None)) {
+                    print(
+                        "format_change",
+                        format.map(|format| format!("{:?}", format).to_lowercase()),
+                    )?;
+                    last_format = format;
+                }
+            }
+        }
+    }
+    Ok(())
+}
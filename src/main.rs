@@ -36,20 +36,57 @@ use log::*;
 use std::fs;
 use validator::Validate;
 
+mod audio_test;
+mod audit;
 mod battery;
+mod benchmark;
 mod cmdline;
 mod common;
+mod compliance_report;
 mod config;
+mod config_diff;
+mod convert;
+mod dump_bc;
+mod email_config;
+mod export_config;
+mod export_stream;
+mod generate_config;
+mod generate_mask;
+mod health_check;
+mod http_mjpeg;
 mod image;
+mod import_cert;
+mod list_cameras;
+mod log_events;
+mod mirror;
+mod motion_alert;
+mod motion_config;
 mod mqtt;
+mod mqtt_bridge;
+mod osd;
 mod pir;
 mod ptz;
+mod push_config;
 mod reboot;
+mod record_event;
 mod rtsp;
+mod scan_ports;
 mod services;
+mod set_osd_name;
+mod set_password;
+mod set_resolution;
+mod set_timezone;
+mod simulate_stream;
+mod snapshot_diff;
 mod statusled;
+mod stream_check;
+mod stream_forward;
+mod stream_info;
+mod stress_test;
 mod talk;
+mod test_motion;
 mod utils;
+mod watchdog;
 
 use cmdline::{Command, Opt};
 use common::NeoReactor;
@@ -69,12 +106,48 @@ async fn main() -> Result<()> {
 
     let opt = Opt::parse();
 
+    if matches!(opt.cmd, Some(Command::ConfigDiff(_))) {
+        // config-diff exits with 1 for a difference so errors need another code
+        if let Err(e) = run(opt).await {
+            error!("{:?}", e);
+            std::process::exit(2);
+        }
+        return Ok(());
+    }
+    run(opt).await
+}
+
+async fn run(opt: Opt) -> Result<()> {
+    // These do not need a config so they are run before one is loaded
+    if let Some(Command::ImportCert(opts)) = opt.cmd {
+        return import_cert::main(opts).await;
+    }
+    if let Some(Command::ScanPorts(opts)) = opt.cmd {
+        return scan_ports::main(opts).await;
+    }
+    if let Some(Command::Convert(opts)) = opt.cmd {
+        return convert::main(opts).await;
+    }
+    if let Some(Command::StreamCheck(opts)) = opt.cmd {
+        return stream_check::main(opts).await;
+    }
+    if let Some(Command::GenerateConfig(opts)) = opt.cmd {
+        return generate_config::main(opts).await;
+    }
+    if let Some(Command::Watchdog(opts)) = opt.cmd {
+        return watchdog::main(opts).await;
+    }
+    if let Some(Command::StressTest(opts)) = opt.cmd {
+        return stress_test::main(opts).await;
+    }
+
     let conf_path = opt.config.context("Must supply --config file")?;
-    let config: Config = toml::from_str(
+    let config: Config = Config::from_toml(
         &fs::read_to_string(&conf_path)
             .with_context(|| format!("Failed to read {:?}", conf_path))?,
     )
-    .with_context(|| format!("Failed to parse the {:?} config file", conf_path))?;
+    .with_context(|| format!("Failed to parse the {:?} config file", conf_path))?
+    .expand_channels();
 
     config
         .validate()
@@ -126,6 +199,105 @@ async fn main() -> Result<()> {
         Some(Command::Services(opts)) => {
             services::main(opts, neo_reactor.clone()).await?;
         }
+        Some(Command::Osd(opts)) => {
+            osd::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::PushConfig(opts)) => {
+            push_config::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::MotionConfig(opts)) => {
+            motion_config::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::StreamInfo(opts)) => {
+            stream_info::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::EmailConfig(opts)) => {
+            email_config::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::Benchmark(opts)) => {
+            benchmark::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::SetResolution(opts)) => {
+            set_resolution::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::SetPassword(opts)) => {
+            set_password::main(opts, neo_reactor.clone(), &conf_path).await?;
+        }
+        Some(Command::GenerateMask(opts)) => {
+            generate_mask::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::ExportConfig(opts)) => {
+            export_config::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::TestMotion(opts)) => {
+            test_motion::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::LogEvents(opts)) => {
+            log_events::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::ListCameras(opts)) => {
+            list_cameras::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::HealthCheck(opts)) => {
+            health_check::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::MotionAlert(opts)) => {
+            motion_alert::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::SimulateStream(opts)) => {
+            simulate_stream::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::DumpBc(opts)) => {
+            dump_bc::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::AudioTest(opts)) => {
+            audio_test::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::ExportStream(opts)) => {
+            export_stream::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::ConfigDiff(opts)) => {
+            config_diff::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::Mirror(opts)) => {
+            mirror::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::MqttBridge(opts)) => {
+            mqtt_bridge::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::HttpMjpeg(opts)) => {
+            http_mjpeg::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::SetOsdName(opts)) => {
+            set_osd_name::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::Audit(opts)) => {
+            audit::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::RecordEvent(opts)) => {
+            record_event::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::StreamForward(opts)) => {
+            stream_forward::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::SetTimezone(opts)) => {
+            set_timezone::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::SnapshotDiff(opts)) => {
+            snapshot_diff::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::ComplianceReport(opts)) => {
+            compliance_report::main(opts, neo_reactor.clone()).await?;
+        }
+        Some(Command::ImportCert(_))
+        | Some(Command::ScanPorts(_))
+        | Some(Command::Convert(_))
+        | Some(Command::StreamCheck(_))
+        | Some(Command::GenerateConfig(_))
+        | Some(Command::Watchdog(_))
+        | Some(Command::StressTest(_)) => {
+            unreachable!()
+        }
     }
 
     Ok(())
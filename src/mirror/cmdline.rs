@@ -0,0 +1,15 @@
+use clap::Parser;
+
+/// The mirror command plays the audio of one camera through the speaker of another
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The camera whose microphone is listened to. Must be a name in the config
+    #[arg(long)]
+    pub source_camera: String,
+    /// The camera whose speaker plays the audio. Must be a name in the config
+    #[arg(long)]
+    pub dest_camera: String,
+    /// Use to change the volume of the audio
+    #[arg(short, long, default_value = "1.0")]
+    pub volume: f32,
+}
@@ -0,0 +1,117 @@
+///
+/// # Neolink Mirror
+///
+/// This module plays the audio from the microphone of one camera through
+/// the speaker of another as it arrives. Run it twice the other way round
+/// to make an intercom of two cameras
+///
+/// The audio of the source camera is decoded and encoded again as the
+/// adpcm that the talk of the destination camera takes
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink mirror --config=config.toml --source-camera=Garden --dest-camera=Door
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use neolink_core::bc_protocol::StreamKind;
+use tokio::{
+    sync::broadcast::error::RecvError,
+    time::{timeout, Duration},
+};
+
+mod cmdline;
+
+use crate::{
+    common::{AudFormat, NeoReactor},
+    talk,
+};
+pub(crate) use cmdline::Opt;
+
+/// How long to wait for the source camera to send some audio
+const AUDIO_TIMEOUT_SECS: u64 = 10;
+
+/// Entry point for the mirror subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    if opt.source_camera == opt.dest_camera {
+        return Err(anyhow!("The source and dest camera must be different"));
+    }
+    let source = reactor.get(&opt.source_camera).await?;
+    let dest = reactor.get(&opt.dest_camera).await?;
+    let (talk_config, block_size, sample_rate) = talk::talk_config(&dest).await?;
+
+    // The audio is the same on every stream so use the lightest
+    let mut stream_instance = source
+        .stream(StreamKind::Sub)
+        .await
+        .context("Failed to start the stream")?;
+    let aud_format = timeout(
+        Duration::from_secs(AUDIO_TIMEOUT_SECS),
+        stream_instance.config.wait_for(|config| config.aud_ready()),
+    )
+    .await
+    .map_err(|_| {
+        anyhow!(
+            "Camera {} did not send any audio, check that its audio is enabled",
+            opt.source_camera
+        )
+    })??
+    .aud_format;
+    let caps = match aud_format {
+        AudFormat::Aac => "audio/mpeg,mpegversion=4,stream-format=adts".to_string(),
+        // The block has a 4 byte header before the samples
+        AudFormat::Adpcm(block_size) => format!(
+            "audio/x-adpcm,layout=dvi,block_align={},channels=1,rate=8000",
+            block_size + 4
+        ),
+        AudFormat::None => unreachable!(),
+    };
+
+    let (mut set, rx, appsrc) = talk::from_appsrc(&caps, opt.volume, block_size, sample_rate)
+        .context("Failed to setup gst for the audio")?;
+    log::info!(
+        "Playing the audio of {} on {}",
+        opt.source_camera,
+        opt.dest_camera
+    );
+
+    let aud = &mut stream_instance.aud;
+    let forward = async {
+        loop {
+            match aud.recv().await {
+                Ok(frame) => appsrc
+                    .push_buffer(gstreamer::Buffer::from_slice(frame.data.as_ref().clone()))
+                    .map_err(|e| anyhow!("Streaming error: {e:?}"))?,
+                Err(RecvError::Lagged(n)) => {
+                    log::debug!("Dropped {} audio frames", n);
+                    continue;
+                }
+                Err(RecvError::Closed) => {
+                    return Result::<()>::Err(anyhow!("The stream has closed"))
+                }
+            };
+        }
+    };
+    let play = dest.run_task(|cam| {
+        let rx = rx.clone();
+        let talk_config = talk_config.clone();
+        Box::pin(async move {
+            cam.talk_stream(rx, talk_config).await?;
+            Ok(())
+        })
+    });
+    let result = tokio::select! {
+        v = forward => v,
+        v = play => v.context("Talk stream ended early"),
+        v = tokio::signal::ctrl_c() => v.map_err(Into::into),
+    };
+
+    let _ = appsrc.end_of_stream();
+    drop(rx);
+    while set.join_next().await.is_some() {}
+    result
+}
@@ -0,0 +1,19 @@
+use clap::Parser;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The motion-alert command alerts on the motion of a camera until stopped
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// Also send a desktop notification with notify-send (Linux) or osascript (macOS)
+    #[arg(long)]
+    pub notify_send: bool,
+    /// Where the snapshots are saved. Defaults to ~/Pictures/neolink
+    #[arg(long, value_parser = PathBuf::from_str)]
+    pub snapshot_dir: Option<PathBuf>,
+    /// Do not ring the terminal bell
+    #[arg(long)]
+    pub no_bell: bool,
+}
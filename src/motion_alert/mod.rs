@@ -0,0 +1,136 @@
+///
+/// # Neolink Motion Alert
+///
+/// This module alerts on the motion of a camera from a terminal. It runs
+/// until stopped with ctrl-c
+///
+/// When motion starts a snapshot is saved, the terminal bell is rung and,
+/// if asked for, a desktop notification is sent. When motion stops a line
+/// is printed without the bell
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink motion-alert --config=config.toml CameraName
+/// # With desktop notifications and snapshots in another directory
+/// neolink motion-alert --config=config.toml CameraName --notify-send --snapshot-dir=/tmp/motion
+/// ```
+///
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod cmdline;
+
+use crate::common::{MdState, NeoInstance, NeoReactor};
+pub(crate) use cmdline::Opt;
+
+/// Entry point for the motion-alert subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let snapshot_dir = match opt.snapshot_dir.clone() {
+        Some(dir) => dir,
+        None => dirs::picture_dir()
+            .or_else(dirs::home_dir)
+            .context("Could not find the pictures directory, use --snapshot-dir")?
+            .join("neolink"),
+    };
+    tokio::fs::create_dir_all(&snapshot_dir)
+        .await
+        .with_context(|| format!("Could not create {:?}", snapshot_dir))?;
+
+    let camera = reactor.get(&opt.camera).await?;
+    let mut motion = camera.motion().await?;
+    println!(
+        "Watching {} for motion, snapshots are saved to {:?}",
+        opt.camera, snapshot_dir
+    );
+
+    loop {
+        tokio::select! {
+            v = tokio::signal::ctrl_c() => {
+                v?;
+                break;
+            }
+            v = motion.changed() => {
+                v?;
+                let started = match *motion.borrow() {
+                    MdState::Start(_) => Some(true),
+                    MdState::Stop(_) => Some(false),
+                    MdState::Unknown => None,
+                };
+                match started {
+                    Some(true) => {
+                        let snapshot = match save_snapshot(&camera, &opt.camera, &snapshot_dir).await {
+                            Ok(path) => Some(path),
+                            Err(e) => {
+                                log::warn!("Could not save a snapshot: {:?}", e);
+                                None
+                            }
+                        };
+                        alert(&opt, "Motion started", snapshot.as_deref());
+                    }
+                    Some(false) => {
+                        println!("{}: Motion stopped", opt.camera);
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Saves a snapshot from the camera's snap command returning its path
+async fn save_snapshot(camera: &NeoInstance, name: &str, dir: &Path) -> Result<PathBuf> {
+    let jpeg_data = camera
+        .run_task(|camera| Box::pin(async move { Ok(camera.get_snapshot().await?) }))
+        .await?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = dir.join(format!("{}_{}.jpeg", name, timestamp));
+    tokio::fs::write(&path, jpeg_data).await?;
+    Ok(path)
+}
+
+/// Prints the alert, rings the bell and sends the desktop notification
+fn alert(opt: &Opt, message: &str, snapshot: Option<&Path>) {
+    let body = match snapshot {
+        Some(path) => format!("{} ({})", message, path.display()),
+        None => message.to_string(),
+    };
+    println!("{}: {}", opt.camera, body);
+    if !opt.no_bell {
+        print!("\x07");
+        let _ = std::io::stdout().flush();
+    }
+    if opt.notify_send {
+        let title = format!("Neolink: {}", opt.camera);
+        let mut command = if cfg!(target_os = "macos") {
+            let mut command = std::process::Command::new("osascript");
+            command.arg("-e").arg(format!(
+                "display notification {:?} with title {:?}",
+                body, title
+            ));
+            command
+        } else {
+            let mut command = std::process::Command::new("notify-send");
+            if let Some(path) = snapshot {
+                command.arg("--icon").arg(path);
+            }
+            command.arg(&title).arg(&body);
+            command
+        };
+        // Waited on so that it does not linger as a zombie
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = command.status() {
+                log::warn!("Could not send the desktop notification: {:?}", e);
+            }
+        });
+    }
+}
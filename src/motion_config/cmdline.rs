@@ -0,0 +1,11 @@
+use clap::Parser;
+
+/// The motion-config command will get or set the motion detection of the camera
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// Change the motion detection sensitivity (0-100)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub sensitivity: Option<u8>,
+}
@@ -0,0 +1,60 @@
+///
+/// # Neolink Motion Config
+///
+/// This module handles the motion detection settings of the camera
+///
+///
+/// # Usage
+///
+/// ```bash
+/// # To print the current motion detection settings
+/// neolink motion-config --config=config.toml CameraName
+/// # To change the sensitivity
+/// neolink motion-config --config=config.toml CameraName --sensitivity 60
+/// ```
+///
+use anyhow::{Context, Result};
+
+mod cmdline;
+
+use crate::common::NeoReactor;
+pub(crate) use cmdline::Opt;
+
+/// Entry point for the motion-config subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+
+    if let Some(sensitivity) = opt.sensitivity {
+        camera
+            .run_task(|cam| {
+                Box::pin(async move {
+                    let mut motion_detection = cam
+                        .get_motion_detection_config()
+                        .await
+                        .context("Unable to get camera motion detection settings")?;
+                    motion_detection.sensitivity = sensitivity;
+                    // Leave the areas as they are
+                    motion_detection.regions.clear();
+                    cam.set_motion_detection_config(&motion_detection)
+                        .await
+                        .context("Unable to set camera motion detection settings")
+                })
+            })
+            .await?;
+    } else {
+        let motion_detection = camera
+            .run_task(|cam| {
+                Box::pin(async move {
+                    cam.get_motion_detection_config()
+                        .await
+                        .context("Unable to get camera motion detection settings")
+                })
+            })
+            .await?;
+        println!("{}", toml::to_string(&motion_detection)?);
+    }
+
+    Ok(())
+}
@@ -17,6 +17,8 @@
 //! - `/control/ptz` [up|down|left|right|in|out] (amount) Control the PTZ movements, amount defaults to 32.0
 //! - `/control/ptz/preset` [id] Move the camera to a known preset
 //! - `/control/ptz/assign` [id] [name] Assign the current ptz position to an ID and name
+//! - `/control/test_motion` (seconds) Send a motion on to `/status/motion` followed by a motion off
+//!    after the seconds (default 10). Used to test anything that listens for motion
 //!
 //! Status Messages:
 //!
@@ -85,6 +87,7 @@ use anyhow::{anyhow, Context, Result};
 pub(crate) use cmdline::Opt;
 pub(crate) use discovery::Discoveries;
 use log::*;
+pub(crate) use mqttc::mqtt_options;
 use mqttc::{Mqtt, MqttReplyRef};
 
 use self::{
@@ -92,6 +95,9 @@ use self::{
     mqttc::{MqttInstance, MqttReply},
 };
 
+/// How long a `control/test_motion` lasts when no time is given
+pub(crate) const DEFAULT_TEST_MOTION: Duration = Duration::from_secs(10);
+
 /// Entry point for the mqtt subcommand
 ///
 /// Opt is the command line options
@@ -202,7 +208,7 @@ pub(crate) async fn main(_: Opt, reactor: NeoReactor) -> Result<()> {
             v = async {
                 while let Ok(msg) = thread_instance.recv().await {
                     if msg.topic == "config" {
-                        let config: Result<Config> = toml::from_str(&msg.message).with_context(|| {
+                        let config: Result<Config> = Config::from_toml(&msg.message).with_context(|| {
                             format!("Failed to parse the MQTT {:?} config file", msg.topic)
                         });
                         if let Err(e) = config {
@@ -211,7 +217,7 @@ pub(crate) async fn main(_: Opt, reactor: NeoReactor) -> Result<()> {
                                 .await?;
                             continue;
                         }
-                        let config = config?;
+                        let config = config?.expand_channels();
 
                         let validate = config.validate().with_context(|| {
                             format!("Failed to validate the MQTT {:?} config file", msg.topic)
@@ -1123,6 +1129,33 @@ async fn handle_mqtt_message(
                 .await
                 .with_context(|| "Failed to publish siren")?;
         }
+        MqttReplyRef {
+            topic: "control/test_motion",
+            message,
+        } => {
+            let duration = match message {
+                "" | "on" => Ok(DEFAULT_TEST_MOTION),
+                secs => secs.parse::<u64>().map(Duration::from_secs),
+            };
+            let reply = match duration {
+                Ok(duration) => {
+                    if let Err(e) = camera.simulate_motion(duration).await {
+                        error!("Failed to simulate motion: {:?}", e);
+                        format!("FAIL: {e:?}")
+                    } else {
+                        "OK".to_string()
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to parse seconds: {:?}", e);
+                    format!("FAIL: '{message}' => {e:?}")
+                }
+            };
+
+            mqtt.send_message("control/test_motion", &reply, false)
+                .await
+                .with_context(|| "Failed to publish test_motion")?;
+        }
         MqttReplyRef {
             topic: "query/battery",
             ..
@@ -125,47 +125,7 @@ struct MqttBackend<'a> {
 impl<'a> MqttBackend<'a> {
     async fn run(&mut self) -> AnyResult<()> {
         log::trace!("Run MQTT Server");
-        let mut mqttoptions = MqttOptions::new(
-            format!("Neolink{}", Uuid::new_v4()),
-            &self.config.broker_addr,
-            self.config.port,
-        );
-        let max_size = 100 * (1024 * 1024);
-        mqttoptions.set_max_packet_size(max_size, max_size);
-
-        // Use TLS if ca path is set
-        if let Some(ca_path) = &self.config.ca {
-            if let Ok(ca) = std::fs::read(ca_path) {
-                // Use client_auth if they have cert and key
-                let client_auth = if let Some((cert_path, key_path)) = &self.config.client_auth {
-                    if let (Ok(cert_buf), Ok(key_buf)) =
-                        (std::fs::read(cert_path), std::fs::read(key_path))
-                    {
-                        Some((cert_buf, key_buf))
-                    } else {
-                        error!("Failed to set client tls");
-                        None
-                    }
-                } else {
-                    None
-                };
-
-                let transport = Transport::Tls(TlsConfiguration::Simple {
-                    ca,
-                    alpn: None,
-                    client_auth,
-                });
-                mqttoptions.set_transport(transport);
-            } else {
-                error!("Failed to set CA");
-            }
-        };
-
-        if let Some((username, password)) = &self.config.credentials {
-            mqttoptions.set_credentials(username, password);
-        }
-
-        mqttoptions.set_keep_alive(Duration::from_secs(5));
+        let mut mqttoptions = mqtt_options(self.config);
 
         // On unclean disconnect send this
         mqttoptions.set_last_will(LastWill::new(
@@ -596,3 +556,52 @@ impl Drop for LastWillMqtt {
         self.cancel.cancel();
     }
 }
+
+/// The options to connect to the mqtt server with
+///
+/// This sets the tls and credentials but not the last will
+pub(crate) fn mqtt_options(config: &MqttServerConfig) -> MqttOptions {
+    let mut mqttoptions = MqttOptions::new(
+        format!("Neolink{}", Uuid::new_v4()),
+        &config.broker_addr,
+        config.port,
+    );
+    let max_size = 100 * (1024 * 1024);
+    mqttoptions.set_max_packet_size(max_size, max_size);
+
+    // Use TLS if ca path is set
+    if let Some(ca_path) = &config.ca {
+        if let Ok(ca) = std::fs::read(ca_path) {
+            // Use client_auth if they have cert and key
+            let client_auth = if let Some((cert_path, key_path)) = &config.client_auth {
+                if let (Ok(cert_buf), Ok(key_buf)) =
+                    (std::fs::read(cert_path), std::fs::read(key_path))
+                {
+                    Some((cert_buf, key_buf))
+                } else {
+                    error!("Failed to set client tls");
+                    None
+                }
+            } else {
+                None
+            };
+
+            let transport = Transport::Tls(TlsConfiguration::Simple {
+                ca,
+                alpn: None,
+                client_auth,
+            });
+            mqttoptions.set_transport(transport);
+        } else {
+            error!("Failed to set CA");
+        }
+    };
+
+    if let Some((username, password)) = &config.credentials {
+        mqttoptions.set_credentials(username, password);
+    }
+
+    mqttoptions.set_keep_alive(Duration::from_secs(5));
+
+    mqttoptions
+}
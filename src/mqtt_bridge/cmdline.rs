@@ -0,0 +1,22 @@
+use crate::common::stream_parse;
+use clap::Parser;
+use neolink_core::bc_protocol::StreamKind;
+
+/// The mqtt-bridge command publishes the video and audio frames of a camera to mqtt
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The broker like mqtt://host:1883. Defaults to the [mqtt] server of the config
+    #[arg(long)]
+    pub broker: Option<String>,
+    /// The start of the topics
+    #[arg(long, default_value = "neolink")]
+    pub topic_prefix: String,
+    /// The stream to publish: main, sub or extern
+    #[arg(long, value_parser = stream_parse, default_value = "main")]
+    pub stream: StreamKind,
+    /// Largest message in bytes including the header. Larger frames are split
+    #[arg(long, default_value_t = 256 * 1024, value_parser = clap::value_parser!(u32).range(64..))]
+    pub max_message_size: u32,
+}
@@ -0,0 +1,185 @@
+///
+/// # Neolink MQTT Bridge
+///
+/// This module publishes the frames of a camera to mqtt so that they can
+/// be used without rtsp. It is separate from `neolink mqtt` which only
+/// publishes the camera's status and events
+///
+/// The messages are sent to
+/// - `{prefix}/{CAMERANAME}/video/iframe`: The keyframes
+/// - `{prefix}/{CAMERANAME}/video/pframe`: The other video frames
+/// - `{prefix}/{CAMERANAME}/audio`: The aac or adpcm audio
+///
+/// A frame that is too large for one message is split over several. Each
+/// message starts with an 8 byte big endian header of the frame's number
+/// (u32), the part's index (u16) and the number of parts (u16) so that the
+/// frame can be put back together
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink mqtt-bridge --config=config.toml CameraName --broker=mqtt://192.168.1.2:1883
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use rumqttc::{AsyncClient, QoS};
+use std::convert::TryFrom;
+use tokio::sync::broadcast::error::RecvError;
+
+mod cmdline;
+
+use crate::{
+    common::{NeoReactor, StampedData},
+    config::MqttServerConfig,
+    mqtt::mqtt_options,
+};
+pub(crate) use cmdline::Opt;
+
+/// Size of the header at the start of every message
+const HEADER_LEN: usize = 8;
+
+/// Entry point for the mqtt-bridge subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let server = match opt.broker.as_deref() {
+        Some(broker) => parse_broker(broker)?,
+        None => reactor
+            .config()
+            .await?
+            .borrow()
+            .mqtt
+            .clone()
+            .context("Give a --broker or add a [mqtt] server to the config")?,
+    };
+    let camera = reactor.get(&opt.camera).await?;
+    let mut stream_instance = camera
+        .stream(opt.stream)
+        .await
+        .context("Failed to start video")?;
+
+    let (client, mut connection) = AsyncClient::new(mqtt_options(&server), 100);
+    let poll = async {
+        loop {
+            // rumqttc reconnects on the next poll after an error
+            if let Err(e) = connection.poll().await {
+                log::warn!("MQTT bridge connection error: {:?}", e);
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            }
+        }
+    };
+    log::info!(
+        "{}: Publishing frames to {}:{} under {}/{}",
+        opt.camera,
+        server.broker_addr,
+        server.port,
+        opt.topic_prefix,
+        opt.camera
+    );
+
+    let topic = format!("{}/{}", opt.topic_prefix, opt.camera);
+    let max_chunk = opt.max_message_size as usize - HEADER_LEN;
+    let mut number = 0u32;
+    let vid = &mut stream_instance.vid;
+    let aud = &mut stream_instance.aud;
+    let publish = async {
+        loop {
+            let (frame, subtopic) = tokio::select! {
+                frame = vid.recv() => match frame {
+                    Ok(frame) => {
+                        let subtopic = if frame.keyframe { "video/iframe" } else { "video/pframe" };
+                        (frame, subtopic)
+                    }
+                    Err(RecvError::Lagged(n)) => {
+                        log::debug!("Dropped {} video frames", n);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return Result::<()>::Err(anyhow!("The stream has closed")),
+                },
+                frame = aud.recv() => match frame {
+                    Ok(frame) => (frame, "audio"),
+                    Err(RecvError::Lagged(n)) => {
+                        log::debug!("Dropped {} audio frames", n);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return Result::<()>::Err(anyhow!("The stream has closed")),
+                },
+            };
+            publish_frame(
+                &client,
+                &format!("{}/{}", topic, subtopic),
+                number,
+                &frame,
+                max_chunk,
+            )
+            .await?;
+            number = number.wrapping_add(1);
+        }
+    };
+
+    let result = tokio::select! {
+        v = publish => v,
+        _ = poll => unreachable!(),
+        v = tokio::signal::ctrl_c() => v.map_err(Into::into),
+    };
+    let _ = client.disconnect().await;
+    result
+}
+
+/// Publish the frame in as many messages as it needs
+async fn publish_frame(
+    client: &AsyncClient,
+    topic: &str,
+    number: u32,
+    frame: &StampedData,
+    max_chunk: usize,
+) -> Result<()> {
+    // An empty frame is still sent as one message so the numbers have no gaps
+    let chunks = if frame.data.is_empty() {
+        vec![&[][..]]
+    } else {
+        frame.data.chunks(max_chunk).collect::<Vec<_>>()
+    };
+    let count = u16::try_from(chunks.len())
+        .map_err(|_| anyhow!("A frame of {} bytes is too large", frame.data.len()))?;
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let mut payload = Vec::with_capacity(HEADER_LEN + chunk.len());
+        payload.extend_from_slice(&number.to_be_bytes());
+        payload.extend_from_slice(&(index as u16).to_be_bytes());
+        payload.extend_from_slice(&count.to_be_bytes());
+        payload.extend_from_slice(chunk);
+        // Media is only useful live so it is not resent or retained
+        client
+            .publish(topic, QoS::AtMostOnce, false, payload)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Read a broker like `mqtt://host:1883`, `host:1883` or `host`
+fn parse_broker(broker: &str) -> Result<MqttServerConfig> {
+    let address = broker
+        .strip_prefix("mqtt://")
+        .or_else(|| broker.strip_prefix("tcp://"))
+        .unwrap_or(broker)
+        .trim_end_matches('/');
+    let (broker_addr, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (
+            host.to_string(),
+            port.parse()
+                .with_context(|| format!("Invalid port in the broker {}", broker))?,
+        ),
+        None => (address.to_string(), 1883),
+    };
+    if broker_addr.is_empty() {
+        return Err(anyhow!("Invalid broker {}", broker));
+    }
+    Ok(MqttServerConfig {
+        broker_addr,
+        port,
+        credentials: None,
+        ca: None,
+        client_auth: None,
+    })
+}
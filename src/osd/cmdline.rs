@@ -0,0 +1,11 @@
+use clap::Parser;
+
+/// The osd command will get or set the on screen display of the camera
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// Change the camera name that is drawn on the stream
+    #[arg(long)]
+    pub set_name: Option<String>,
+}
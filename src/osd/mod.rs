@@ -0,0 +1,60 @@
+///
+/// # Neolink OSD
+///
+/// This module handles the on screen display that the camera
+/// draws into the video stream
+///
+///
+/// # Usage
+///
+/// ```bash
+/// # To print the current OSD settings
+/// neolink osd --config=config.toml CameraName
+/// # To change the name drawn on screen
+/// neolink osd --config=config.toml CameraName --set-name "Front Door"
+/// ```
+///
+use anyhow::{Context, Result};
+
+mod cmdline;
+
+use crate::common::NeoReactor;
+pub(crate) use cmdline::Opt;
+
+/// Entry point for the osd subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+
+    if let Some(name) = opt.set_name {
+        camera
+            .run_task(|cam| {
+                let name = name.clone();
+                Box::pin(async move {
+                    let mut osd = cam
+                        .get_osd_config()
+                        .await
+                        .context("Unable to get camera OSD settings")?;
+                    osd.camera_name = name;
+                    cam.set_osd_config(&osd)
+                        .await
+                        .context("Unable to set camera OSD settings")
+                })
+            })
+            .await?;
+    } else {
+        let osd = camera
+            .run_task(|cam| {
+                Box::pin(async move {
+                    cam.get_osd_config()
+                        .await
+                        .context("Unable to get camera OSD settings")
+                })
+            })
+            .await?;
+        println!("{}", toml::to_string(&osd)?);
+    }
+
+    Ok(())
+}
@@ -25,6 +25,17 @@ pub enum PtzCommand {
     Preset { preset_id: Option<u8> },
     /// Assign the current position to a preset with a given name
     Assign { preset_id: u8, name: String },
+    /// Print the ID and name of the presets saved on the camera
+    ListPresets,
+    /// Save the current position as a preset
+    SavePreset {
+        /// The ID of the preset. An existing preset with this ID is replaced
+        #[arg(long)]
+        id: u8,
+        /// The name of the preset like "Driveway"
+        #[arg(long)]
+        name: String,
+    },
     /// Performs a movement in the given direction
     Control {
         /// The amount to move
@@ -39,4 +50,16 @@ pub enum PtzCommand {
         /// The amount to zoom to
         amount: f32,
     },
+    /// Cycles through the presets waiting at each
+    Tour {
+        /// The preset IDs to visit in order separated by commas
+        #[arg(long, value_delimiter = ',', required = true)]
+        presets: Vec<u8>,
+        /// How long to stay at each preset in seconds
+        #[arg(long, default_value_t = 10)]
+        dwell_secs: u64,
+        /// Go round the presets until stopped with ctrl-c
+        #[arg(long)]
+        repeat: bool,
+    },
 }
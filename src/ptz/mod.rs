@@ -16,6 +16,11 @@
 /// neolink ptz --config=config.toml CameraName preset 0
 /// # Save the current position as preset ID 0 with name PresetName
 /// neolink ptz --config=config.toml CameraName assign 0 PresetName
+/// # The same with named options
+/// neolink ptz --config=config.toml CameraName list-presets
+/// neolink ptz --config=config.toml CameraName save-preset --id=0 --name=PresetName
+/// # Visit presets 0, 2 and 1 staying 30s at each until stopped
+/// neolink ptz --config=config.toml CameraName tour --presets=0,2,1 --dwell-secs=30 --repeat
 /// ```
 ///
 use anyhow::{Context, Result};
@@ -23,7 +28,7 @@ use tokio::time::{sleep, Duration};
 
 mod cmdline;
 
-use crate::common::NeoReactor;
+use crate::common::{NeoInstance, NeoReactor};
 use crate::ptz::cmdline::CmdDirection;
 use crate::ptz::cmdline::PtzCommand;
 pub(crate) use cmdline::Opt;
@@ -49,30 +54,20 @@ pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
                     })
                     .await?;
             } else {
-                let preset_list = camera
-                    .run_task(|cam| {
-                        Box::pin(async move {
-                            let preset_list = cam
-                                .get_ptz_preset()
-                                .await
-                                .context("Unable to get PTZ presets")?;
-                            Ok(preset_list)
-                        })
-                    })
-                    .await?;
-
-                println!("Available presets:\nID Name");
-                for preset in preset_list.preset_list.preset {
-                    println!("{:<2} {:?}", preset.id, preset.name);
-                }
+                print_presets(&camera).await?;
             }
         }
-        PtzCommand::Assign { preset_id, name } => {
+        PtzCommand::ListPresets => print_presets(&camera).await?,
+        PtzCommand::Assign {
+            preset_id: id,
+            name,
+        }
+        | PtzCommand::SavePreset { id, name } => {
             camera
                 .run_task(|cam| {
                     let name = name.clone();
                     Box::pin(async move {
-                        cam.set_ptz_preset(preset_id, name)
+                        cam.set_ptz_preset(id, name)
                             .await
                             .context("Unable to set PTZ preset")?;
                         Ok(())
@@ -131,7 +126,35 @@ pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
                 .await?;
             sleep(Duration::from_secs(1)).await;
         }
+        PtzCommand::Tour {
+            presets,
+            dwell_secs,
+            repeat,
+        } => {
+            tokio::select! {
+                v = tokio::signal::ctrl_c() => v?,
+                v = camera.ptz_tour(&presets, Duration::from_secs(dwell_secs), repeat) => v?,
+            }
+        }
     };
 
     Ok(())
 }
+
+async fn print_presets(camera: &NeoInstance) -> Result<()> {
+    let presets = camera
+        .run_task(|cam| {
+            Box::pin(async move {
+                cam.get_ptz_presets()
+                    .await
+                    .context("Unable to get PTZ presets")
+            })
+        })
+        .await?;
+
+    println!("Available presets:\nID  Name");
+    for preset in presets.iter() {
+        println!("{:<3} {:?}", preset.id, preset.name);
+    }
+    Ok(())
+}
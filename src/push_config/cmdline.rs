@@ -0,0 +1,12 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// The push-config command will apply a toml file of settings to the camera
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera to change the settings of. Must be a name in the config
+    pub camera: String,
+    /// The toml file with the settings to apply
+    #[arg(long)]
+    pub settings: PathBuf,
+}
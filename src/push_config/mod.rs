@@ -0,0 +1,259 @@
+///
+/// # Neolink Push Config
+///
+/// This module applies a toml file of settings to the camera so that the
+/// same "golden" settings can be put on every camera, for example after a
+/// firmware update has reset them
+///
+/// Every setting is optional and anything that is not in the file is left
+/// as it is on the camera. Each one is applied on its own and whether it
+/// succeeded is printed
+///
+/// ```toml
+/// update_time = true  # Set the camera's clock to this computer's
+/// status_led = true   # The blue status light
+/// ir_lights = "auto"  # The IR lights: on, off or auto
+/// pir = true          # The PIR sensor on battery cameras
+///
+/// [osd]               # The same as [cameras.osd]
+/// camera_name = "Front Door"
+/// timestamp_format = "DMY"
+///
+/// [stream_quality]    # The same as [cameras.stream_quality]
+/// stream = "main"
+/// bitrate_kbps = 2048
+///
+/// [motion_detection]  # The same as [cameras.motion_detection]
+/// sensitivity = 50
+///
+/// [email]             # The same as [cameras.email]
+/// smtp_host = "smtp.example.com"
+/// recipients = ["me@example.com"]
+///
+/// [ntp]               # The NTP server the camera syncs its clock from
+/// server = "pool.ntp.org"
+/// port = 123
+/// interval_mins = 1440
+/// ```
+///
+/// The file is checked against the JSON Schema in `settings.schema.json`,
+/// which is built into the binary, before anything is sent to the camera
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink push-config --config=config.toml CameraName --settings=golden.toml
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use neolink_core::bc_protocol::LightState;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+use validator::Validate;
+use validator_derive::Validate;
+
+mod cmdline;
+mod schema;
+
+use crate::{
+    common::{
+        update_camera_email, update_camera_motion_detection, update_camera_ntp, update_camera_osd,
+        update_camera_stream_quality, NeoReactor,
+    },
+    config::{EmailConfig, MotionDetectionConfig, NtpConfig, OsdConfig, StreamQualityConfig},
+};
+pub(crate) use cmdline::Opt;
+
+static RE_IR_LIGHTS: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(on|off|auto)$").unwrap());
+
+/// The settings file
+#[derive(Debug, Deserialize, Validate, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Settings {
+    #[serde(default)]
+    pub(crate) update_time: Option<bool>,
+
+    #[serde(default, alias = "led")]
+    pub(crate) status_led: Option<bool>,
+
+    #[validate(regex(
+        path = *RE_IR_LIGHTS,
+        message = "Incorrect ir_lights should be on, off or auto",
+        code = "ir_lights"
+    ))]
+    #[serde(default, alias = "ir")]
+    pub(crate) ir_lights: Option<String>,
+
+    #[serde(default)]
+    pub(crate) pir: Option<bool>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) osd: Option<OsdConfig>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) stream_quality: Option<StreamQualityConfig>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) motion_detection: Option<MotionDetectionConfig>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) email: Option<EmailConfig>,
+
+    #[validate]
+    #[serde(default)]
+    pub(crate) ntp: Option<NtpConfig>,
+}
+
+/// Parse a settings file and check it against the schema and the config rules
+pub(crate) fn parse_settings(text: &str) -> Result<Settings> {
+    let settings: toml::Value = toml::from_str(text).context("Failed to parse the settings")?;
+    schema::validate_settings(&settings).context("The settings do not match the schema")?;
+    let settings: Settings = settings
+        .try_into()
+        .context("Failed to parse the settings")?;
+    settings
+        .validate()
+        .context("Failed to validate the settings")?;
+    Ok(settings)
+}
+
+/// Entry point for the push-config subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let settings = parse_settings(
+        &tokio::fs::read_to_string(&opt.settings)
+            .await
+            .with_context(|| format!("Failed to read {:?}", opt.settings))?,
+    )
+    .with_context(|| format!("Invalid {:?} settings file", opt.settings))?;
+
+    let camera = reactor.get(&opt.camera).await?;
+    let name = opt.camera.clone();
+    let mut failed = 0;
+    let mut report = |setting: &str, result: Result<()>| match result {
+        Ok(()) => println!("{}: {}: OK", name, setting),
+        Err(e) => {
+            failed += 1;
+            println!("{}: {}: FAILED: {:#}", name, setting, e);
+        }
+    };
+
+    if settings.update_time == Some(true) {
+        let result = camera
+            .run_task(|cam| {
+                Box::pin(async move {
+                    cam.set_time(std::time::SystemTime::now().into())
+                        .await
+                        .context("Unable to set camera time")
+                })
+            })
+            .await;
+        report("update_time", result);
+    }
+    if let Some(on) = settings.status_led {
+        let result = camera
+            .run_task(|cam| {
+                Box::pin(async move {
+                    cam.led_light_set(on)
+                        .await
+                        .context("Unable to set camera light state")
+                })
+            })
+            .await;
+        report("status_led", result);
+    }
+    if let Some(ir_lights) = settings.ir_lights.as_deref() {
+        let result = camera
+            .run_task(|cam| {
+                let state = match ir_lights {
+                    "on" => LightState::On,
+                    "off" => LightState::Off,
+                    _ => LightState::Auto,
+                };
+                Box::pin(async move {
+                    cam.irled_light_set(state)
+                        .await
+                        .context("Unable to set camera IR light state")
+                })
+            })
+            .await;
+        report("ir_lights", result);
+    }
+    if let Some(on) = settings.pir {
+        let result = camera
+            .run_task(|cam| {
+                Box::pin(async move {
+                    cam.pir_set(on)
+                        .await
+                        .context("Unable to set camera PIR state")
+                })
+            })
+            .await;
+        report("pir", result);
+    }
+    if let Some(osd) = settings.osd.as_ref() {
+        let result = camera
+            .run_task(|cam| {
+                let osd = osd.clone();
+                let name = opt.camera.clone();
+                Box::pin(async move { update_camera_osd(cam, &name, &osd).await })
+            })
+            .await;
+        report("osd", result);
+    }
+    if let Some(quality) = settings.stream_quality.as_ref() {
+        let result = camera
+            .run_task(|cam| {
+                let quality = quality.clone();
+                let name = opt.camera.clone();
+                Box::pin(async move { update_camera_stream_quality(cam, &name, &quality).await })
+            })
+            .await;
+        report("stream_quality", result);
+    }
+    if let Some(motion_detection) = settings.motion_detection.as_ref() {
+        let result = camera
+            .run_task(|cam| {
+                let motion_detection = motion_detection.clone();
+                let name = opt.camera.clone();
+                Box::pin(async move {
+                    update_camera_motion_detection(cam, &name, &motion_detection).await
+                })
+            })
+            .await;
+        report("motion_detection", result);
+    }
+    if let Some(email) = settings.email.as_ref() {
+        let result = camera
+            .run_task(|cam| {
+                let email = email.clone();
+                let name = opt.camera.clone();
+                Box::pin(async move { update_camera_email(cam, &name, &email).await })
+            })
+            .await;
+        report("email", result);
+    }
+    if let Some(ntp) = settings.ntp.as_ref() {
+        let result = camera
+            .run_task(|cam| {
+                let ntp = ntp.clone();
+                let name = opt.camera.clone();
+                Box::pin(async move { update_camera_ntp(cam, &name, &ntp).await })
+            })
+            .await;
+        report("ntp", result);
+    }
+
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!("{} setting(s) could not be applied", failed))
+    }
+}
@@ -0,0 +1,185 @@
+//! Checks a settings file against the JSON Schema that is embedded in the binary
+//!
+//! Only the keywords used by `settings.schema.json` are understood: `type`,
+//! `enum`, `properties`, `additionalProperties`, `required`, `items`,
+//! `minItems`, `maxItems`, `minimum`, `maximum` and `pattern`
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde_json::Value;
+
+/// The JSON Schema of the settings file
+pub(crate) const SETTINGS_SCHEMA: &str = include_str!("settings.schema.json");
+
+/// Check the parsed toml against [`SETTINGS_SCHEMA`]
+///
+/// All problems are reported together so that a file can be fixed in one go
+pub(crate) fn validate_settings(settings: &toml::Value) -> Result<()> {
+    let schema: Value =
+        serde_json::from_str(SETTINGS_SCHEMA).context("The embedded settings schema is invalid")?;
+    let settings = serde_json::to_value(settings)?;
+    let mut errors = vec![];
+    check(&schema, &settings, "", &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("{}", errors.join("\n")))
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let at = if path.is_empty() { "settings" } else { path };
+
+    if let Some(kind) = schema.get("type").and_then(Value::as_str) {
+        let ok = match kind {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "boolean" => value.is_boolean(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            _ => true,
+        };
+        if !ok {
+            errors.push(format!("{}: should be a {}", at, kind));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            errors.push(format!("{}: should be one of {}", at, allowed.join(", ")));
+        }
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+            if number < minimum {
+                errors.push(format!("{}: should be at least {}", at, minimum));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+            if number > maximum {
+                errors.push(format!("{}: should be at most {}", at, maximum));
+            }
+        }
+    }
+
+    if let (Some(text), Some(pattern)) = (
+        value.as_str(),
+        schema.get("pattern").and_then(Value::as_str),
+    ) {
+        match Regex::new(pattern) {
+            Ok(re) if !re.is_match(text) => {
+                errors.push(format!("{}: should match {}", at, pattern));
+            }
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}: bad pattern in schema: {}", at, e)),
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if (items.len() as u64) < min {
+                errors.push(format!("{}: should have at least {} items", at, min));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if (items.len() as u64) > max {
+                errors.push(format!("{}: should have at most {} items", at, max));
+            }
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                check(item_schema, item, &format!("{}[{}]", at, i), errors);
+            }
+        }
+    }
+
+    if let Some(fields) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !fields.contains_key(name) {
+                    errors.push(format!("{}: {} is required", at, name));
+                }
+            }
+        }
+        let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+        for (name, field) in fields {
+            let field_path = if path.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", path, name)
+            };
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(field_schema) => check(field_schema, field, &field_path, errors),
+                None if closed => errors.push(format!("{}: unknown setting", field_path)),
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(settings: &str) -> Result<()> {
+        validate_settings(&toml::from_str(settings).unwrap())
+    }
+
+    #[test]
+    fn test_schema_accepts_all_sections() {
+        validate(
+            r#"
+            update_time = true
+            ir_lights = "auto"
+
+            [osd]
+            camera_name = "Front Door"
+            timestamp_format = "DMY"
+
+            [stream_quality]
+            stream = "main"
+            bitrate_kbps = 2048
+
+            [motion_detection]
+            sensitivity = 50
+            regions = [{ rect = { x = 0, y = 0, width = 10, height = 5 }, enabled = true }]
+
+            [email]
+            smtp_host = "smtp.example.com"
+            recipients = ["a@example.com"]
+
+            [ntp]
+            server = "pool.ntp.org"
+            interval_mins = 1440
+            "#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_schema_rejects_bad_values() {
+        assert!(validate("ir_lights = \"dim\"").is_err());
+        assert!(validate("colour = true").is_err());
+        assert!(validate("[stream_quality]\nbitrate_kbps = 1").is_err());
+        assert!(validate("[motion_detection]\nsensitivity = 101").is_err());
+        assert!(validate("[email]\nrecipients = [\"a\", \"b\", \"c\", \"d\"]").is_err());
+        assert!(validate("[email]\nschedule = \"101\"").is_err());
+        assert!(validate("[ntp]\nport = \"123\"").is_err());
+    }
+
+    #[test]
+    fn test_schema_reports_every_problem() {
+        let err = validate("pir = 1\n[ntp]\ninterval_mins = 1").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("pir: should be a boolean"), "{}", message);
+        assert!(
+            message.contains("ntp.interval_mins: should be at least 60"),
+            "{}",
+            message
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use crate::common::stream_parse;
+use clap::Parser;
+use neolink_core::bc_protocol::StreamKind;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The record-event command saves a clip from before to after a motion event or trigger
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The mp4 file to write
+    #[arg(long, value_parser = PathBuf::from_str)]
+    pub output: PathBuf,
+    /// Seconds of video to keep from before the event
+    #[arg(long, default_value_t = 5)]
+    pub pre_secs: u64,
+    /// Seconds of video to record after the event
+    #[arg(long, default_value_t = 10)]
+    pub post_secs: u64,
+    /// Start the event when a line is read from stdin instead of on motion
+    #[arg(long)]
+    pub trigger_stdin: bool,
+    /// The stream to record: main, sub or extern
+    #[arg(long, value_parser = stream_parse, default_value = "main")]
+    pub stream: StreamKind,
+}
@@ -0,0 +1,128 @@
+use anyhow::{anyhow, Context, Result};
+use gstreamer::{
+    parse::launch_full, prelude::*, ClockTime, MessageView, ParseFlags, Pipeline, State,
+};
+use gstreamer_app::AppSrc;
+use std::path::Path;
+
+use super::Frame;
+use crate::common::{StampedData, VidFormat};
+
+/// Mux the frames into an mp4 at `path`
+///
+/// This blocks until the file is written so should be run with `spawn_blocking`
+pub(super) fn write_mp4(
+    format: VidFormat,
+    with_audio: bool,
+    frames: Vec<Frame>,
+    path: &Path,
+) -> Result<()> {
+    gstreamer::init()
+        .context("Unable to start gstreamer ensure it and all plugins are installed")?;
+    let pipeline = create_pipeline(format, with_audio)?;
+    pipeline
+        .by_name("thesink")
+        .ok_or_else(|| anyhow!("There shoud be a `thesink`"))?
+        .set_property("location", path.to_string_lossy().as_ref());
+    let vid_src = get_source(&pipeline, "vidsrc")?;
+    let aud_src = if with_audio {
+        Some(get_source(&pipeline, "audsrc")?)
+    } else {
+        None
+    };
+
+    let bus = pipeline
+        .bus()
+        .expect("Pipeline without bus. Shouldn't happen!");
+    pipeline.set_state(State::Playing)?;
+
+    // The clip starts at zero from its first frame
+    let ts_0 = frames
+        .iter()
+        .map(|frame| match frame {
+            Frame::Video(data) | Frame::Audio(data) => data.ts,
+        })
+        .min()
+        .unwrap_or_default();
+    for frame in frames.iter() {
+        let (source, data) = match (frame, aud_src.as_ref()) {
+            (Frame::Video(data), _) => (&vid_src, data),
+            (Frame::Audio(data), Some(aud_src)) => (aud_src, data),
+            (Frame::Audio(_), None) => continue,
+        };
+        source
+            .push_buffer(to_buffer(data, ts_0))
+            .map_err(|e| anyhow!("Streaming error: {e:?}"))?;
+    }
+    for source in std::iter::once(&vid_src).chain(aud_src.as_ref()) {
+        source
+            .end_of_stream()
+            .map_err(|e| anyhow!("Streaming error: {e:?}"))?;
+    }
+
+    // mp4mux writes the index when it gets the end of stream
+    let result = bus
+        .iter_timed(ClockTime::NONE)
+        .find_map(|msg| match msg.view() {
+            MessageView::Eos(..) => Some(Ok(())),
+            MessageView::Error(err) => Some(Err(anyhow!(
+                "Error from gstreamer while writing the clip: {} ({:?})",
+                err.error(),
+                err.debug()
+            ))),
+            _ => None,
+        })
+        .unwrap_or(Ok(()));
+    pipeline
+        .set_state(State::Null)
+        .context("Error in gstreamer when setting state to Null")?;
+    result
+}
+
+fn to_buffer(data: &StampedData, ts_0: std::time::Duration) -> gstreamer::Buffer {
+    let time = ClockTime::from_useconds(data.ts.saturating_sub(ts_0).as_micros() as u64);
+    let mut buf = gstreamer::Buffer::from_slice(data.data.as_ref().clone());
+    {
+        let buf = buf.get_mut().unwrap();
+        buf.set_dts(time);
+        buf.set_pts(time);
+    }
+    buf
+}
+
+fn get_source(pipeline: &Pipeline, name: &str) -> Result<AppSrc> {
+    pipeline
+        .by_name(name)
+        .ok_or_else(|| anyhow!("There shoud be a `{}`", name))?
+        .dynamic_cast::<AppSrc>()
+        .map_err(|_| anyhow!("Cannot find appsource in gstreamer, check your gstreamer plugins"))
+}
+
+fn create_pipeline(format: VidFormat, with_audio: bool) -> Result<Pipeline> {
+    let (caps, parser) = match format {
+        VidFormat::H264 => ("video/x-h264,stream-format=byte-stream", "h264parse"),
+        VidFormat::H265 => ("video/x-h265,stream-format=byte-stream", "h265parse"),
+        VidFormat::Av1 => ("video/x-av1,stream-format=obu-stream", "av1parse"),
+        VidFormat::None => unreachable!(),
+    };
+    // The pads of the mux are named so that the video is always the first track
+    let mut launch_str = format!(
+        "mp4mux name=mux ! filesink name=thesink \
+        appsrc name=vidsrc format=time caps={caps} \
+        ! {parser} ! queue ! mux.video_0"
+    );
+    if with_audio {
+        launch_str.push_str(
+            " appsrc name=audsrc format=time caps=audio/mpeg,mpegversion=4,stream-format=adts \
+            ! aacparse ! queue ! mux.audio_0",
+        );
+    }
+    log::debug!("{}", launch_str);
+
+    launch_full(&launch_str, None, ParseFlags::empty())
+        .context("Unable to load gstreamer pipeline ensure all gstramer plugins are installed")?
+        .dynamic_cast::<Pipeline>()
+        .map_err(|_| {
+            anyhow!("Unable to create gstreamer pipeline ensure all gstramer plugins are installed")
+        })
+}
@@ -0,0 +1,229 @@
+///
+/// # Neolink Record Event
+///
+/// This module saves one clip of a camera around an event and then exits.
+/// While it waits for the event it keeps the last `--pre-secs` of the
+/// stream so that the clip shows what led up to it
+///
+/// The event is the camera detecting motion or, with `--trigger-stdin`, a
+/// line on stdin so that home automation can start it with its own
+/// triggers
+///
+/// The clip starts at a keyframe so it may have a little more than
+/// `--pre-secs` before the event. AAC audio is included, adpcm audio is not
+/// supported by mp4 and is left out
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink record-event --config=config.toml CameraName --output=event.mp4 --pre-secs=5 --post-secs=20
+/// # Triggered by something else
+/// echo | neolink record-event --config=config.toml CameraName --output=event.mp4 --trigger-stdin
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use std::collections::VecDeque;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    sync::broadcast::error::RecvError,
+    time::Duration,
+};
+
+mod cmdline;
+mod gst;
+
+use crate::common::{AudFormat, MdState, NeoReactor, StampedData};
+pub(crate) use cmdline::Opt;
+
+/// A frame of the clip
+enum Frame {
+    Video(StampedData),
+    Audio(StampedData),
+}
+
+/// The frames since the first keyframe that is at least `pre` old
+///
+/// Each group starts at a keyframe so the clip can always be decoded
+struct RingBuffer {
+    groups: VecDeque<Vec<Frame>>,
+    pre: Duration,
+}
+
+impl RingBuffer {
+    fn new(pre: Duration) -> Self {
+        Self {
+            groups: VecDeque::new(),
+            pre,
+        }
+    }
+
+    fn push(&mut self, frame: Frame, trim: bool) {
+        match &frame {
+            Frame::Video(video) if video.keyframe => self.groups.push_back(vec![]),
+            // Anything before the first keyframe cannot be used
+            _ if self.groups.is_empty() => return,
+            _ => {}
+        }
+        if let Frame::Video(video) = &frame {
+            let latest = video.ts;
+            while trim
+                && self.groups.len() > 1
+                && self.groups[1]
+                    .first()
+                    .map(|start| frame_ts(start) + self.pre <= latest)
+                    .unwrap_or(false)
+            {
+                self.groups.pop_front();
+            }
+        }
+        self.groups
+            .back_mut()
+            .expect("There is always a group after a keyframe")
+            .push(frame);
+    }
+
+    /// The time of the latest video frame
+    fn latest_ts(&self) -> Option<Duration> {
+        self.groups
+            .iter()
+            .rev()
+            .flat_map(|group| group.iter().rev())
+            .find_map(|frame| match frame {
+                Frame::Video(video) => Some(video.ts),
+                Frame::Audio(_) => None,
+            })
+    }
+}
+
+fn frame_ts(frame: &Frame) -> Duration {
+    match frame {
+        Frame::Video(data) | Frame::Audio(data) => data.ts,
+    }
+}
+
+/// Entry point for the record-event subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let mut motion = if opt.trigger_stdin {
+        None
+    } else {
+        Some(camera.motion().await?)
+    };
+    let mut stream_instance = camera
+        .stream(opt.stream)
+        .await
+        .context("Failed to start video")?;
+    let vid_format = stream_instance
+        .config
+        .wait_for(|config| config.vid_ready())
+        .await?
+        .vid_format;
+
+    let mut buffer = RingBuffer::new(Duration::from_secs(opt.pre_secs));
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    let post = Duration::from_secs(opt.post_secs);
+    let mut triggered = false;
+    // Not known until there has been some video
+    let mut end_ts: Option<Duration> = None;
+    println!(
+        "{}: Waiting for {}",
+        opt.camera,
+        if opt.trigger_stdin {
+            "a line on stdin"
+        } else {
+            "motion"
+        }
+    );
+
+    let vid = &mut stream_instance.vid;
+    let aud = &mut stream_instance.aud;
+    loop {
+        let waiting = !triggered;
+        let frame = tokio::select! {
+            v = tokio::signal::ctrl_c() => {
+                v?;
+                return Err(anyhow!("Stopped before the clip was recorded"));
+            }
+            v = wait_for_trigger(&mut motion, &mut stdin), if waiting => {
+                v?;
+                triggered = true;
+                end_ts = buffer.latest_ts().map(|now| now + post);
+                println!("{}: Event, recording {}s more", opt.camera, opt.post_secs);
+                continue;
+            }
+            frame = vid.recv() => match frame {
+                Ok(frame) => Frame::Video(frame),
+                Err(RecvError::Lagged(n)) => {
+                    log::warn!("Dropped {} video frames, the clip will have a gap", n);
+                    continue;
+                }
+                Err(RecvError::Closed) => return Err(anyhow!("The stream has closed")),
+            },
+            frame = aud.recv() => match frame {
+                Ok(frame) => Frame::Audio(frame),
+                Err(RecvError::Lagged(n)) => {
+                    log::debug!("Dropped {} audio frames", n);
+                    continue;
+                }
+                Err(RecvError::Closed) => return Err(anyhow!("The stream has closed")),
+            },
+        };
+        let done = match &frame {
+            Frame::Video(video) if triggered => video.ts >= *end_ts.get_or_insert(video.ts + post),
+            _ => false,
+        };
+        // Once the event has started everything is kept
+        buffer.push(frame, !triggered);
+        if done {
+            break;
+        }
+    }
+
+    let aud_format = stream_instance.config.borrow().aud_format;
+    let with_audio = match aud_format {
+        AudFormat::Aac => true,
+        AudFormat::Adpcm(_) => {
+            log::warn!("The camera's adpcm audio cannot go in an mp4 so it is left out");
+            false
+        }
+        AudFormat::None => false,
+    };
+    let frames = buffer
+        .groups
+        .into_iter()
+        .flatten()
+        .filter(|frame| with_audio || matches!(frame, Frame::Video(_)))
+        .collect::<Vec<_>>();
+    let output = opt.output.clone();
+    let count = frames.len();
+    tokio::task::spawn_blocking(move || gst::write_mp4(vid_format, with_audio, frames, &output))
+        .await??;
+    println!("{}: Wrote {} frames to {:?}", opt.camera, count, opt.output);
+    Ok(())
+}
+
+/// Wait for motion to start or for a line on stdin
+async fn wait_for_trigger(
+    motion: &mut Option<tokio::sync::watch::Receiver<MdState>>,
+    stdin: &mut tokio::io::Lines<BufReader<tokio::io::Stdin>>,
+) -> Result<()> {
+    match motion {
+        // Motion that is already going when started is not an event
+        Some(motion) => loop {
+            motion.changed().await?;
+            if matches!(*motion.borrow(), MdState::Start(_)) {
+                break;
+            }
+        },
+        None => {
+            stdin
+                .next_line()
+                .await?
+                .ok_or_else(|| anyhow!("stdin closed before the trigger"))?;
+        }
+    }
+    Ok(())
+}
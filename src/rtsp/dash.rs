@@ -0,0 +1,272 @@
+//! MPEG-DASH output of the main stream
+//!
+//! This runs alongside the rtsp server. The main stream is fed into
+//! its own pipeline ending in a `dashsink` which writes an mpd and
+//! fragmented mp4 segments into the `output_dir`. A small http server
+//! then serves that directory so browsers can play it with a Media
+//! Source Extensions player such as dash.js
+//!
+//! `dashsink` is part of gst-plugins-bad and requires GStreamer 1.18 or later
+use anyhow::{anyhow, Context, Result};
+use gstreamer::{
+    parse::launch_full, prelude::*, ClockTime, ElementFactory, MessageView, ParseFlags, Pipeline,
+    State,
+};
+use gstreamer_app::AppSrc;
+use neolink_core::bc_protocol::StreamKind;
+use std::path::{Path, PathBuf};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    common::{NeoInstance, VidFormat},
+    config::DashConfig,
+    AnyResult,
+};
+
+/// Runs the DASH output restarting it whenever `[cameras.dash]` changes
+pub(super) async fn dash_main(camera: NeoInstance) -> Result<()> {
+    let mut camera_config = camera.config().await?.clone();
+    loop {
+        let dash_config = camera_config.borrow_and_update().dash.clone();
+        break tokio::select! {
+            v = camera_config.wait_for(|config| config.dash != dash_config) => {
+                if let Err(e) = v {
+                    AnyResult::Err(e.into())
+                } else {
+                    // config.dash changed restart
+                    continue;
+                }
+            },
+            v = async {
+                match dash_config.as_ref() {
+                    Some(dash_config) => dash_run(&camera, dash_config).await,
+                    None => futures::future::pending().await,
+                }
+            } => v,
+        };
+    }
+}
+
+async fn dash_run(camera: &NeoInstance, config: &DashConfig) -> Result<()> {
+    let name = camera.config().await?.borrow().name.clone();
+    tokio::fs::create_dir_all(&config.output_dir)
+        .await
+        .with_context(|| format!("Could not create {:?}", config.output_dir))?;
+
+    let stream_instance = camera.stream(StreamKind::Main).await?;
+    let vid_format = stream_instance
+        .config
+        .clone()
+        .wait_for(|config| config.vid_ready())
+        .await?
+        .vid_format;
+    let pipeline = create_pipeline(vid_format, config)?;
+    let appsrc = pipeline
+        .by_name("thesource")
+        .ok_or_else(|| anyhow!("There shoud be a `thesource`"))?
+        .dynamic_cast::<AppSrc>()
+        .map_err(|_| anyhow!("Cannot find appsource in gstreamer, check your gstreamer plugins"))?;
+
+    let cancel = CancellationToken::new();
+    let _drop_guard = cancel.clone().drop_guard();
+    let thread_cancel = cancel.clone();
+
+    log::info!(
+        "{}: Serving DASH at http://{}:{}/{}",
+        name,
+        config.bind_addr,
+        config.bind_port,
+        config.mpd_filename
+    );
+    let frames =
+        BroadcastStream::new(stream_instance.vid.resubscribe()).filter_map(|frame| frame.ok()); // Ignore lagged
+    tokio::select! {
+        v = tokio::task::spawn_blocking(move || run_pipeline(pipeline, thread_cancel)) => v?,
+        v = serve(config.output_dir.clone(), &config.bind_addr, config.bind_port) => v,
+        v = async {
+            let mut frames = Box::pin(frames);
+            let mut ts_0 = None;
+            while let Some(frame) = frames.next().await {
+                // Segments must start on an iframe
+                if ts_0.is_none() && !frame.keyframe {
+                    continue;
+                }
+                let ts_0 = *ts_0.get_or_insert(frame.ts);
+                let time = ClockTime::from_useconds(frame.ts.saturating_sub(ts_0).as_micros() as u64);
+                let mut buf = gstreamer::Buffer::from_slice(frame.data.as_ref().clone());
+                {
+                    let buf = buf.get_mut().unwrap();
+                    buf.set_dts(time);
+                    buf.set_pts(time);
+                }
+                appsrc
+                    .push_buffer(buf)
+                    .map_err(|e| anyhow!("DASH streaming error: {e:?}"))?;
+            }
+            AnyResult::Ok(())
+        } => v,
+    }
+}
+
+fn create_pipeline(format: VidFormat, config: &DashConfig) -> Result<Pipeline> {
+    if ElementFactory::find("dashsink").is_none() {
+        return Err(anyhow!(
+            "Missing dashsink. DASH output requires gst-plugins-bad from GStreamer 1.18 or later"
+        ));
+    }
+    let (caps, parser) = match format {
+        VidFormat::H264 => ("video/x-h264,stream-format=byte-stream", "h264parse"),
+        VidFormat::H265 => ("video/x-h265,stream-format=byte-stream", "h265parse"),
+        VidFormat::Av1 => ("video/x-av1,stream-format=obu-stream", "av1parse"),
+        VidFormat::None => unreachable!(),
+    };
+    let launch_str = format!(
+        "appsrc name=thesource is-live=true format=time caps={caps} \
+        ! {parser} \
+        ! dashsink name=thesink muxer=dash-mp4 dynamic=true"
+    );
+    log::debug!("{}", launch_str);
+
+    let pipeline = launch_full(&launch_str, None, ParseFlags::empty())
+        .context("Unable to load gstreamer pipeline ensure all gstramer plugins are installed")?
+        .dynamic_cast::<Pipeline>()
+        .map_err(|_| {
+            anyhow!("Unable to create gstreamer pipeline ensure all gstramer plugins are installed")
+        })?;
+
+    let sink = pipeline
+        .by_name("thesink")
+        .ok_or_else(|| anyhow!("There shoud be a `thesink`"))?;
+    sink.set_property_from_str("mpd-root-path", &config.output_dir.to_string_lossy());
+    sink.set_property_from_str("mpd-filename", &config.mpd_filename);
+    sink.set_property_from_str("location", "seg_%05d.mp4");
+    sink.set_property_from_str("target-duration", &config.segment_duration_secs.to_string());
+    if let Some(base_url) = config.base_url.as_ref() {
+        sink.set_property_from_str("mpd-baseurl", base_url);
+    }
+
+    Ok(pipeline)
+}
+
+/// Plays the pipeline until it errors or is cancelled
+fn run_pipeline(pipeline: Pipeline, cancel: CancellationToken) -> Result<()> {
+    pipeline.set_state(State::Playing)?;
+    let bus = pipeline
+        .bus()
+        .expect("Pipeline without bus. Shouldn't happen!");
+
+    let mut res = Ok(());
+    while !cancel.is_cancelled() {
+        let Some(msg) = bus.timed_pop(ClockTime::from_mseconds(500)) else {
+            continue;
+        };
+        match msg.view() {
+            MessageView::Eos(..) => break,
+            MessageView::Error(err) => {
+                res = Err(anyhow!("Error from gstreamer in DASH output: {:?}", err));
+                break;
+            }
+            _ => (),
+        }
+    }
+
+    pipeline
+        .set_state(State::Null)
+        .context("Error in gstreamer when setting state to Null")?;
+    res
+}
+
+/// A minimal http server for the DASH files
+///
+/// All responses allow any origin so that browser players do not need a proxy
+async fn serve(root: PathBuf, bind_addr: &str, bind_port: u16) -> Result<()> {
+    let listener = TcpListener::bind((bind_addr, bind_port))
+        .await
+        .with_context(|| format!("Could not bind DASH http server to {bind_addr}:{bind_port}"))?;
+    loop {
+        let (socket, addr) = listener.accept().await?;
+        let root = root.clone();
+        tokio::task::spawn(async move {
+            if let Err(e) =
+                tokio::time::timeout(Duration::from_secs(30), handle_request(socket, &root))
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|v| v)
+            {
+                log::debug!("DASH http request from {addr} failed: {e:?}");
+            }
+        });
+    }
+}
+
+async fn handle_request(mut socket: TcpStream, root: &Path) -> Result<()> {
+    let (read, mut write) = socket.split();
+    let mut reader = BufReader::new(read);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    // Headers are not needed but must be read
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('/');
+
+    let (status, content_type, body) = match method {
+        "OPTIONS" => ("204 No Content", None, vec![]),
+        "GET" | "HEAD" if path.is_empty() || path.split('/').any(|part| part == "..") => {
+            ("404 Not Found", None, vec![])
+        }
+        "GET" | "HEAD" => match tokio::fs::read(root.join(path)).await {
+            Ok(body) => ("200 OK", Some(content_type(path)), body),
+            Err(_) => ("404 Not Found", None, vec![]),
+        },
+        _ => ("405 Method Not Allowed", None, vec![]),
+    };
+
+    let mut header = format!(
+        "HTTP/1.1 {status}\r\n\
+        Access-Control-Allow-Origin: *\r\n\
+        Access-Control-Allow-Methods: GET, HEAD, OPTIONS\r\n\
+        Access-Control-Allow-Headers: *\r\n\
+        Cache-Control: no-cache\r\n\
+        Connection: close\r\n\
+        Content-Length: {}\r\n",
+        body.len()
+    );
+    if let Some(content_type) = content_type {
+        header.push_str(&format!("Content-Type: {content_type}\r\n"));
+    }
+    header.push_str("\r\n");
+    write.write_all(header.as_bytes()).await?;
+    if method != "HEAD" {
+        write.write_all(&body).await?;
+    }
+    write.flush().await?;
+    Ok(())
+}
+
+fn content_type(path: &str) -> &'static str {
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("mpd") => "application/dash+xml",
+        Some("mp4") | Some("m4s") => "video/mp4",
+        Some("ts") => "video/mp2t",
+        _ => "application/octet-stream",
+    }
+}
@@ -3,8 +3,11 @@ use gstreamer::{prelude::*, Bin, Caps, Element, ElementFactory, GhostPad};
 use gstreamer_app::{AppSrc, AppSrcCallbacks, AppStreamType};
 use tokio::sync::mpsc::{channel as mpsc, Receiver as MpscReceiver};
 
+use neolink_core::bcmedia::model::VideoType;
+
 use crate::{
     common::{AudFormat, StreamConfig, VidFormat},
+    config::{Config, ExternalAudioSource, FrameTimestampMode},
     rtsp::gst::NeoMediaFactory,
     AnyResult,
 };
@@ -16,26 +19,93 @@ pub(super) struct ClientSourceData {
 pub(super) struct ClientData {
     pub(super) vid: Option<ClientSourceData>,
     pub(super) aud: Option<ClientSourceData>,
+    /// The bin of the media
+    pub(super) element: Element,
 }
 
+/// A factory that shows the splash pattern
+///
+/// When `stream_config` already knows the stream's resolution and fps from
+/// the camera the splash is made the same size and rate
 pub(super) async fn make_dummy_factory(
     use_splash: bool,
     pattern: String,
+    stream_config: Option<StreamConfig>,
 ) -> AnyResult<NeoMediaFactory> {
+    let size = stream_config.and_then(SplashSize::from_stream_config);
     NeoMediaFactory::new_with_callback(move |element| {
         clear_bin(&element)?;
         if !use_splash {
             Ok(None)
         } else {
-            build_unknown(&element, &pattern)?;
+            build_unknown(&element, &pattern, size.unwrap_or_default())?;
             Ok(Some(element))
         }
     })
     .await
 }
 
+/// A factory that encodes a live test pattern for `neolink simulate-stream`
+pub(super) async fn make_simulated_factory(
+    codec: VideoType,
+    width: u32,
+    height: u32,
+    fps: u32,
+) -> AnyResult<NeoMediaFactory> {
+    NeoMediaFactory::new_with_callback(move |element| {
+        clear_bin(&element)?;
+        build_simulated(&element, &codec, width, height, fps)?;
+        Ok(Some(element))
+    })
+    .await
+}
+
+/// The resolution and frame rate of the splash pattern
+#[derive(Clone, Copy)]
+struct SplashSize {
+    width: i32,
+    height: i32,
+    fps: i32,
+}
+
+impl Default for SplashSize {
+    fn default() -> Self {
+        Self {
+            width: 896,
+            height: 512,
+            fps: 25,
+        }
+    }
+}
+
+impl SplashSize {
+    /// Largest width that is sent. RTP jpeg cannot carry more than 2040
+    const MAX_WIDTH: u32 = 1920;
+
+    fn from_stream_config(stream_config: StreamConfig) -> Option<Self> {
+        let [width, height] = stream_config.resolution;
+        if width == 0 || height == 0 || stream_config.fps == 0 {
+            return None;
+        }
+        // Keep the aspect ratio when scaling down
+        let scale = (Self::MAX_WIDTH as f64 / width as f64).min(1.0);
+        Some(Self {
+            width: ((width as f64 * scale) as i32) & !1,
+            height: ((height as f64 * scale) as i32) & !1,
+            fps: stream_config.fps as i32,
+        })
+    }
+}
+
+///
+/// When `external_audio_url` is given the audio is pulled from that rtsp url
+/// instead of the camera
+///
+/// `timestamps` sets how the appsrcs stamp the frames
 pub(super) async fn make_factory(
     stream_config: &StreamConfig,
+    external_audio_url: Option<String>,
+    timestamps: FrameTimestampMode,
 ) -> AnyResult<(NeoMediaFactory, MpscReceiver<ClientData>)> {
     let (client_tx, client_rx) = mpsc(100);
     let factory = {
@@ -47,11 +117,15 @@ pub(super) async fn make_factory(
                 VidFormat::None => {
                     // This should not be reachable
                     log::debug!("Building unknown during normal make factory");
-                    build_unknown(&element, "black")?;
+                    build_unknown(
+                        &element,
+                        "black",
+                        SplashSize::from_stream_config(stream_config.clone()).unwrap_or_default(),
+                    )?;
                     AnyResult::Ok(None)
                 }
                 VidFormat::H264 => {
-                    let app = build_h264(&element, &stream_config)?;
+                    let app = build_h264(&element, &stream_config, timestamps)?;
                     app.set_callbacks(
                         AppSrcCallbacks::builder()
                             .seek_data(move |_, _seek_pos| true)
@@ -60,7 +134,7 @@ pub(super) async fn make_factory(
                     AnyResult::Ok(Some(app))
                 }
                 VidFormat::H265 => {
-                    let app = build_h265(&element, &stream_config)?;
+                    let app = build_h265(&element, &stream_config, timestamps)?;
 
                     app.set_callbacks(
                         AppSrcCallbacks::builder()
@@ -69,14 +143,30 @@ pub(super) async fn make_factory(
                     );
                     AnyResult::Ok(Some(app))
                 }
+                VidFormat::Av1 => {
+                    let app = build_av1(&element, &stream_config, timestamps)?;
+                    app.set_callbacks(
+                        AppSrcCallbacks::builder()
+                            .seek_data(move |_, _seek_pos| true)
+                            .build(),
+                    );
+                    AnyResult::Ok(Some(app))
+                }
             }?;
-            let aud = if matches!(stream_config.vid_format, VidFormat::None) {
+            let ext_aud = match (&external_audio_url, stream_config.vid_format) {
+                (_, VidFormat::None) | (None, _) => false,
+                (Some(url), _) => {
+                    build_rtsp_audio(&element, url)?;
+                    true
+                }
+            };
+            let aud = if matches!(stream_config.vid_format, VidFormat::None) || ext_aud {
                 None
             } else {
                 match stream_config.aud_format {
                     AudFormat::None => AnyResult::Ok(None),
                     AudFormat::Aac => {
-                        let app = build_aac(&element, &stream_config)?;
+                        let app = build_aac(&element, &stream_config, timestamps)?;
                         app.set_callbacks(
                             AppSrcCallbacks::builder()
                                 .seek_data(move |_, _seek_pos| true)
@@ -85,7 +175,7 @@ pub(super) async fn make_factory(
                         AnyResult::Ok(Some(app))
                     }
                     AudFormat::Adpcm(block_size) => {
-                        let app = build_adpcm(&element, block_size, &stream_config)?;
+                        let app = build_adpcm(&element, block_size, &stream_config, timestamps)?;
                         app.set_callbacks(
                             AppSrcCallbacks::builder()
                                 .seek_data(move |_, _seek_pos| true)
@@ -96,9 +186,12 @@ pub(super) async fn make_factory(
                 }?
             };
 
+            check_pipeline(&element, &stream_config, aud.is_some(), ext_aud)?;
+
             client_tx.blocking_send(ClientData {
                 vid: vid.map(|app| ClientSourceData { app }),
                 aud: aud.map(|app| ClientSourceData { app }),
+                element: element.clone(),
             })?;
             Ok(Some(element))
         })
@@ -108,6 +201,16 @@ pub(super) async fn make_factory(
     Ok((factory, client_rx))
 }
 
+/// Makes a factory that relays the video of another rtsp server
+pub(super) async fn make_rtsp_relay_factory(url: String) -> AnyResult<NeoMediaFactory> {
+    NeoMediaFactory::new_with_callback(move |element| {
+        clear_bin(&element)?;
+        build_rtsp_relay(&element, &url)?;
+        Ok(Some(element))
+    })
+    .await
+}
+
 fn clear_bin(bin: &Element) -> Result<()> {
     let bin = bin
         .clone()
@@ -121,7 +224,73 @@ fn clear_bin(bin: &Element) -> Result<()> {
     Ok(())
 }
 
-fn build_unknown(bin: &Element, pattern: &str) -> Result<()> {
+/// Logs what was built and warns about any expected element that is not in the bin
+///
+/// Optional elements such as the `fallbackswitch` are skipped when their
+/// plugin is missing so this is the only sign that they were left out
+fn check_pipeline(
+    bin: &Element,
+    stream_config: &StreamConfig,
+    has_aud: bool,
+    has_ext_aud: bool,
+) -> Result<()> {
+    let bin = bin
+        .clone()
+        .dynamic_cast::<Bin>()
+        .map_err(|_| anyhow!("Media source's element should be a bin"))?;
+    // The names given to the elements by the build functions with their kind
+    let mut expected = vec![];
+    let vid_pay = match stream_config.vid_format {
+        VidFormat::None => None,
+        VidFormat::H264 => Some("rtph264pay"),
+        VidFormat::H265 => Some("rtph265pay"),
+        VidFormat::Av1 => Some("rtpav1pay"),
+    };
+    if let Some(vid_pay) = vid_pay {
+        expected.extend([("vidsrc", "appsrc"), ("pay0", vid_pay)]);
+    }
+    match stream_config.aud_format {
+        _ if !has_aud => {}
+        AudFormat::None => {}
+        AudFormat::Aac => expected.extend([
+            ("audsrc", "appsrc"),
+            ("audfallbackswitch", "fallbackswitch"),
+            ("pay1", "rtpL16pay"),
+        ]),
+        AudFormat::Adpcm(_) => expected.extend([("audsrc", "appsrc"), ("pay1", "rtpL16pay")]),
+    }
+    if has_ext_aud {
+        expected.push(("extaudio", "rtspsrc"));
+    }
+    let missing = expected
+        .iter()
+        .filter(|(name, _)| bin.by_name(name).is_none())
+        .map(|(name, kind)| format!("`{}` ({} from {})", name, kind, plugin_hint(kind)))
+        .collect::<Vec<_>>();
+    let actual = bin.iterate_elements().into_iter().flatten().count();
+
+    log::info!(
+        "Built pipeline with {} elements: video {:?}, audio {}",
+        actual,
+        stream_config.vid_format,
+        if has_aud {
+            format!("{:?}", stream_config.aud_format)
+        } else if has_ext_aud {
+            "rtsp".to_string()
+        } else {
+            "none".to_string()
+        }
+    );
+    if !missing.is_empty() {
+        log::warn!(
+            "Pipeline was built without {}. You may be missing a gstreamer plugin",
+            missing.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn build_unknown(bin: &Element, pattern: &str, size: SplashSize) -> Result<()> {
     let bin = bin
         .clone()
         .dynamic_cast::<Bin>()
@@ -145,9 +314,9 @@ fn build_unknown(bin: &Element, pattern: &str) -> Result<()> {
         &queue,
         &Caps::builder("video/x-raw")
             .field("format", "YUY2")
-            .field("width", 896i32)
-            .field("height", 512i32)
-            .field("framerate", gstreamer::Fraction::new(25, 1))
+            .field("width", size.width)
+            .field("height", size.height)
+            .field("framerate", gstreamer::Fraction::new(size.fps, 1))
             .build(),
     )?;
     Element::link_many([&queue, &overlay, &encoder, &payload])?;
@@ -155,7 +324,229 @@ fn build_unknown(bin: &Element, pattern: &str) -> Result<()> {
     Ok(())
 }
 
-fn build_h264(bin: &Element, stream_config: &StreamConfig) -> Result<AppSrc> {
+fn build_simulated(
+    bin: &Element,
+    codec: &VideoType,
+    width: u32,
+    height: u32,
+    fps: u32,
+) -> Result<()> {
+    let bin = bin
+        .clone()
+        .dynamic_cast::<Bin>()
+        .map_err(|_| anyhow!("Media source's element should be a bin"))?;
+    log::debug!("Building Simulated Pipeline");
+    let source = make_element("videotestsrc", "testvidsrc")?;
+    source.set_property("is-live", true);
+    source.set_property_from_str("pattern", "smpte");
+    let overlay = make_element("timeoverlay", "overlay")?;
+    overlay.set_property_from_str("valignment", "top");
+    overlay.set_property_from_str("halignment", "left");
+    let convert = make_element("videoconvert", "convert")?;
+    let (encoder, parser, payload) = match codec {
+        VideoType::H264 => (
+            make_element("x264enc", "encoder")?,
+            make_element("h264parse", "parser")?,
+            make_element("rtph264pay", "pay0")?,
+        ),
+        VideoType::H265 => (
+            make_element("x265enc", "encoder")?,
+            make_element("h265parse", "parser")?,
+            make_element("rtph265pay", "pay0")?,
+        ),
+        VideoType::Av1 => return Err(anyhow!("AV1 cannot be simulated")),
+    };
+    encoder.set_property_from_str("tune", "zerolatency");
+    // A keyframe every second so that new clients start quickly
+    encoder.set_property("key-int-max", fps as i32);
+    payload.set_property("config-interval", -1i32);
+
+    bin.add_many([&source, &overlay, &convert, &encoder, &parser, &payload])?;
+    source.link_filtered(
+        &overlay,
+        &Caps::builder("video/x-raw")
+            .field("width", width as i32)
+            .field("height", height as i32)
+            .field("framerate", gstreamer::Fraction::new(fps as i32, 1))
+            .build(),
+    )?;
+    Element::link_many([&overlay, &convert, &encoder, &parser, &payload])?;
+    Ok(())
+}
+
+fn build_rtsp_relay(bin: &Element, url: &str) -> Result<()> {
+    let bin = bin
+        .clone()
+        .dynamic_cast::<Bin>()
+        .map_err(|_| anyhow!("Media source's element should be a bin"))?;
+    log::debug!("Building RTSP Relay Pipeline");
+    // The codec is not known until rtspsrc has connected so this is a
+    // dynamic payloader. Once the video pad appears the depay ! parse ! pay
+    // for its codec is built and exposed as a ghost pad
+    let relay = Bin::builder().name("dynpay0").build();
+    let source = make_element("rtspsrc", "rtspsrc")?;
+    source.set_property("location", url);
+    source.set_property("latency", 0u32);
+    relay.add(&source)?;
+
+    let weak_relay = relay.downgrade();
+    source.connect_pad_added(move |_, pad| {
+        let Some(relay) = weak_relay.upgrade() else {
+            return;
+        };
+        if let Err(e) = link_rtsp_relay(&relay, pad) {
+            log::warn!("Could not relay rtsp pad {}: {:?}", pad.name(), e);
+        }
+    });
+
+    bin.add(&relay)?;
+    Ok(())
+}
+
+fn link_rtsp_relay(relay: &Bin, pad: &gstreamer::Pad) -> Result<()> {
+    if relay.static_pad("src").is_some() {
+        // Already relaying the video
+        return Ok(());
+    }
+    let caps = pad
+        .current_caps()
+        .ok_or_else(|| anyhow!("rtspsrc pad has no caps"))?;
+    let structure = caps
+        .structure(0)
+        .ok_or_else(|| anyhow!("rtspsrc pad has empty caps"))?;
+    if structure.get::<&str>("media").ok() != Some("video") {
+        return Ok(());
+    }
+    let (depay, parse, pay) = match structure.get::<&str>("encoding-name")? {
+        "H264" => ("rtph264depay", "h264parse", "rtph264pay"),
+        "H265" => ("rtph265depay", "h265parse", "rtph265pay"),
+        encoding => return Err(anyhow!("Unsupported rtsp video encoding {}", encoding)),
+    };
+    let depay = make_element(depay, "relay_depay")?;
+    let parse = make_element(parse, "relay_parser")?;
+    let pay = make_element(pay, "relay_pay")?;
+    relay.add_many([&depay, &parse, &pay])?;
+    Element::link_many([&depay, &parse, &pay])?;
+    for element in [&depay, &parse, &pay] {
+        element.sync_state_with_parent()?;
+    }
+    let depay_sink = depay
+        .static_pad("sink")
+        .ok_or_else(|| anyhow!("Depayloader is missing its pad"))?;
+    pad.link(&depay_sink)?;
+
+    let pay_src = pay
+        .static_pad("src")
+        .ok_or_else(|| anyhow!("Payloader is missing its pad"))?;
+    let ghost_pad = GhostPad::builder_with_target(&pay_src)?.name("src").build();
+    ghost_pad.set_active(true)?;
+    relay.add_pad(&ghost_pad)?;
+    relay.no_more_pads();
+    Ok(())
+}
+
+fn build_rtsp_audio(bin: &Element, url: &str) -> Result<()> {
+    let bin = bin
+        .clone()
+        .dynamic_cast::<Bin>()
+        .map_err(|_| anyhow!("Media source's element should be a bin"))?;
+    log::debug!("Building External RTSP Audio Pipeline");
+    // rtspsrc location={url}
+    // ! decodebin
+    // ! audioconvert
+    // ! audioresample
+    // ! rtpL16pay name=pay1
+    let audio = Bin::builder().name("extaudio").build();
+    let source = make_element("rtspsrc", "extaudsrc")?;
+    source.set_property("location", url);
+    source.set_property("latency", 0u32);
+    let decoder = make_element("decodebin", "auddecoder")?;
+    let convert = make_element("audioconvert", "audencoder")?;
+    let resample = make_element("audioresample", "audresample")?;
+    let payload = make_element("rtpL16pay", "pay1")?;
+    audio.add_many([&source, &decoder, &convert, &resample, &payload])?;
+    Element::link_many([&convert, &resample, &payload])?;
+
+    let weak_decoder = decoder.downgrade();
+    source.connect_pad_added(move |_, pad| {
+        let Some(decoder) = weak_decoder.upgrade() else {
+            return;
+        };
+        if let Err(e) = link_rtsp_audio(&decoder, pad) {
+            log::warn!("Could not use rtsp pad {} for audio: {:?}", pad.name(), e);
+        }
+    });
+    let weak_convert = convert.downgrade();
+    decoder.connect_pad_added(move |_, pad| {
+        let Some(sink_pad) = weak_convert
+            .upgrade()
+            .and_then(|convert| convert.static_pad("sink"))
+        else {
+            return;
+        };
+        if sink_pad.is_linked() {
+            return;
+        }
+        if let Err(e) = pad.link(&sink_pad) {
+            log::warn!("Failed to link external audio decoder: {:?}", e);
+        }
+    });
+
+    bin.add(&audio)?;
+    Ok(())
+}
+
+fn link_rtsp_audio(decoder: &Element, pad: &gstreamer::Pad) -> Result<()> {
+    let sink_pad = decoder
+        .static_pad("sink")
+        .ok_or_else(|| anyhow!("Decoder is missing its pad"))?;
+    if sink_pad.is_linked() {
+        // Already have the audio
+        return Ok(());
+    }
+    let caps = pad
+        .current_caps()
+        .ok_or_else(|| anyhow!("rtspsrc pad has no caps"))?;
+    let structure = caps
+        .structure(0)
+        .ok_or_else(|| anyhow!("rtspsrc pad has empty caps"))?;
+    if structure.get::<&str>("media").ok() != Some("audio") {
+        // Any video of the url is not linked and is dropped by rtspsrc
+        return Ok(());
+    }
+    pad.link(&sink_pad)?;
+    Ok(())
+}
+
+/// Sets how the appsrc stamps the frames
+///
+/// - `LocalClock`: The appsrc stamps each frame with the running time when it
+///   is pushed
+/// - `CameraProvided`: The frames are pushed with the camera's time already set
+/// - `ByteStream`: The data is unstamped bytes which the parser splits into
+///   frames
+fn set_timestamp_mode(source: &AppSrc, timestamps: FrameTimestampMode) {
+    match timestamps {
+        FrameTimestampMode::LocalClock => {
+            source.set_do_timestamp(true);
+            source.set_format(gstreamer::Format::Time);
+        }
+        FrameTimestampMode::CameraProvided => {
+            source.set_do_timestamp(false);
+            source.set_format(gstreamer::Format::Time);
+        }
+        FrameTimestampMode::ByteStream => {
+            source.set_do_timestamp(false);
+            source.set_format(gstreamer::Format::Bytes);
+        }
+    }
+}
+
+fn build_h264(
+    bin: &Element,
+    stream_config: &StreamConfig,
+    timestamps: FrameTimestampMode,
+) -> Result<AppSrc> {
     let buffer_size = buffer_size(stream_config.bitrate);
     log::debug!(
         "buffer_size: {buffer_size}, bitrate: {}",
@@ -175,7 +566,7 @@ fn build_h264(bin: &Element, stream_config: &StreamConfig) -> Result<AppSrc> {
     source.set_min_latency(1000 / (stream_config.fps as i64));
     source.set_property("emit-signals", false);
     source.set_max_bytes(buffer_size as u64);
-    source.set_do_timestamp(false);
+    set_timestamp_mode(&source, timestamps);
     source.set_stream_type(AppStreamType::Seekable);
 
     let source = source
@@ -194,7 +585,11 @@ fn build_h264(bin: &Element, stream_config: &StreamConfig) -> Result<AppSrc> {
     Ok(source)
 }
 
-fn build_h265(bin: &Element, stream_config: &StreamConfig) -> Result<AppSrc> {
+fn build_h265(
+    bin: &Element,
+    stream_config: &StreamConfig,
+    timestamps: FrameTimestampMode,
+) -> Result<AppSrc> {
     let buffer_size = buffer_size(stream_config.bitrate);
     let bin = bin
         .clone()
@@ -209,7 +604,7 @@ fn build_h265(bin: &Element, stream_config: &StreamConfig) -> Result<AppSrc> {
     source.set_min_latency(1000 / (stream_config.fps as i64));
     source.set_property("emit-signals", false);
     source.set_max_bytes(buffer_size as u64);
-    source.set_do_timestamp(false);
+    set_timestamp_mode(&source, timestamps);
     source.set_stream_type(AppStreamType::Seekable);
 
     let source = source
@@ -228,7 +623,54 @@ fn build_h265(bin: &Element, stream_config: &StreamConfig) -> Result<AppSrc> {
     Ok(source)
 }
 
-fn build_aac(bin: &Element, stream_config: &StreamConfig) -> Result<AppSrc> {
+fn build_av1(
+    bin: &Element,
+    stream_config: &StreamConfig,
+    timestamps: FrameTimestampMode,
+) -> Result<AppSrc> {
+    let buffer_size = buffer_size(stream_config.bitrate);
+    let bin = bin
+        .clone()
+        .dynamic_cast::<Bin>()
+        .map_err(|_| anyhow!("Media source's element should be a bin"))?;
+    log::debug!("Building AV1 Pipeline");
+    let source = make_element("appsrc", "vidsrc")?
+        .dynamic_cast::<AppSrc>()
+        .map_err(|_| anyhow!("Cannot cast to appsrc."))?;
+    source.set_is_live(false);
+    source.set_block(false);
+    source.set_min_latency(1000 / (stream_config.fps as i64));
+    source.set_property("emit-signals", false);
+    source.set_max_bytes(buffer_size as u64);
+    set_timestamp_mode(&source, timestamps);
+    source.set_stream_type(AppStreamType::Seekable);
+    // Unlike h264 and h265 the parser cannot work out the stream format
+    source.set_caps(Some(
+        &Caps::builder("video/x-av1")
+            .field("stream-format", "obu-stream")
+            .build(),
+    ));
+
+    let source = source
+        .dynamic_cast::<Element>()
+        .map_err(|_| anyhow!("Cannot cast back"))?;
+    let queue = make_queue("source_queue", buffer_size)?;
+    let parser = make_element("av1parse", "parser")?;
+    let payload = make_element("rtpav1pay", "pay0")?;
+    bin.add_many([&source, &queue, &parser, &payload])?;
+    Element::link_many([&source, &queue, &parser, &payload])?;
+
+    let source = source
+        .dynamic_cast::<AppSrc>()
+        .map_err(|_| anyhow!("Cannot convert appsrc"))?;
+    Ok(source)
+}
+
+fn build_aac(
+    bin: &Element,
+    stream_config: &StreamConfig,
+    timestamps: FrameTimestampMode,
+) -> Result<AppSrc> {
     // Audio seems to run at about 800kbs
     let buffer_size = 512 * 1416;
     let bin = bin
@@ -245,7 +687,7 @@ fn build_aac(bin: &Element, stream_config: &StreamConfig) -> Result<AppSrc> {
     source.set_min_latency(1000 / (stream_config.fps as i64));
     source.set_property("emit-signals", false);
     source.set_max_bytes(buffer_size as u64);
-    source.set_do_timestamp(false);
+    set_timestamp_mode(&source, timestamps);
     source.set_stream_type(AppStreamType::Seekable);
 
     let source = source
@@ -294,7 +736,12 @@ fn build_aac(bin: &Element, stream_config: &StreamConfig) -> Result<AppSrc> {
     Ok(source)
 }
 
-fn build_adpcm(bin: &Element, block_size: u32, stream_config: &StreamConfig) -> Result<AppSrc> {
+fn build_adpcm(
+    bin: &Element,
+    block_size: u32,
+    stream_config: &StreamConfig,
+    timestamps: FrameTimestampMode,
+) -> Result<AppSrc> {
     let buffer_size = 512 * 1416;
     let bin = bin
         .clone()
@@ -316,7 +763,7 @@ fn build_adpcm(bin: &Element, block_size: u32, stream_config: &StreamConfig) ->
     source.set_min_latency(1000 / (stream_config.fps as i64));
     source.set_property("emit-signals", false);
     source.set_max_bytes(buffer_size as u64);
-    source.set_do_timestamp(false);
+    set_timestamp_mode(&source, timestamps);
     source.set_stream_type(AppStreamType::Seekable);
 
     source.set_caps(Some(
@@ -354,31 +801,152 @@ fn build_adpcm(bin: &Element, block_size: u32, stream_config: &StreamConfig) ->
     Ok(source)
 }
 
+/// The elements that the configured cameras cannot be served without
+///
+/// The codec of a camera is only known once it sends video so those
+/// elements are not included and are warned about instead
+pub(super) fn required_elements(config: &Config) -> Vec<&'static str> {
+    let mut required = vec![];
+    let mut add = |elements: &[&'static str]| {
+        for element in elements {
+            if !required.contains(element) {
+                required.push(*element);
+            }
+        }
+    };
+    for camera in config.cameras.iter().filter(|camera| camera.enabled) {
+        if camera.is_external_rtsp() {
+            add(&["rtspsrc"]);
+            continue;
+        }
+        add(&["appsrc", "queue"]);
+        // The test pattern is shown while waiting for the first frame
+        if camera.use_splash || camera.format_detect_timeout_secs > 0 {
+            add(&["videotestsrc", "textoverlay", "jpegenc", "rtpjpegpay"]);
+        }
+        if camera.audio {
+            add(&["audioconvert", "rtpL16pay"]);
+        }
+        if let Some(ExternalAudioSource::RtspUrl(_)) = camera.external_audio {
+            add(&[
+                "rtspsrc",
+                "decodebin",
+                "audioconvert",
+                "audioresample",
+                "rtpL16pay",
+            ]);
+        }
+    }
+    required
+}
+
+/// A gstreamer element that could not be found
+pub(super) struct MissingElement {
+    pub(super) name: String,
+    pub(super) package_hint: String,
+}
+
+/// Check that the elements used in the pipelines of the configured cameras are installed
+///
+/// Elements that only some cameras need, such as those of a codec, are only warned about
+pub(super) fn probe_gstreamer_elements(config: &Config) -> Vec<MissingElement> {
+    if ElementFactory::find("fallbackswitch").is_none() {
+        log::warn!(
+            "Missing optional gstreamer element `fallbackswitch` from {}. Audio may stall the stream when the camera stops sending it",
+            plugin_hint("fallbackswitch")
+        );
+    }
+    let audio = config
+        .cameras
+        .iter()
+        .any(|camera| camera.enabled && camera.audio && !camera.is_external_rtsp());
+    if audio {
+        if ElementFactory::find("faad").is_none() && ElementFactory::find("avdec_aac").is_none() {
+            log::warn!(
+                "Missing both `faad` from {} and `avdec_aac` from {}. AAC audio will not work",
+                plugin_hint("faad"),
+                plugin_hint("avdec_aac")
+            );
+        }
+        warn_missing(&["aacparse", "audiotestsrc"], log::Level::Warn, "AAC audio");
+        warn_missing(&["decodebin"], log::Level::Warn, "ADPCM audio");
+    }
+    warn_missing(&["h264parse", "rtph264pay"], log::Level::Warn, "H264 video");
+    warn_missing(&["h265parse", "rtph265pay"], log::Level::Warn, "H265 video");
+    warn_missing(&["av1parse", "rtpav1pay"], log::Level::Debug, "AV1 video");
+
+    required_elements(config)
+        .iter()
+        .filter(|name| ElementFactory::find(name).is_none())
+        .map(|name| MissingElement {
+            name: name.to_string(),
+            package_hint: plugin_hint(name).to_string(),
+        })
+        .collect()
+}
+
+/// Log the elements that are missing for cameras that send `what`
+fn warn_missing(elements: &[&str], level: log::Level, what: &str) {
+    let missing = elements
+        .iter()
+        .filter(|name| ElementFactory::find(name).is_none())
+        .map(|name| format!("`{}` from {}", name, plugin_hint(name)))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        log::log!(
+            level,
+            "Missing gstreamer elements {}. Cameras that send {} will not work",
+            missing.join(" and "),
+            what
+        );
+    }
+}
+
+/// The plugin and package that provides an element
+fn plugin_hint(kind: &str) -> &'static str {
+    match kind {
+        "appsrc" => "app (gst-plugins-base)",
+        "queue" | "queue2" => "coreelements (gstreamer)",
+        "audioconvert" => "audioconvert (gst-plugins-base)",
+        "adpcmdec" => "adpcmdec (gst-plugins-bad)",
+        "h264parse" => "videoparsersbad (gst-plugins-bad)",
+        "h265parse" => "videoparsersbad (gst-plugins-bad)",
+        "av1parse" => "videoparsersbad (gst-plugins-bad 1.20+)",
+        "rtph264pay" => "rtp (gst-plugins-good)",
+        "rtph265pay" => "rtp (gst-plugins-good)",
+        "rtpav1pay" => "rsrtp (gst-plugins-rs)",
+        "rtpjitterbuffer" => "rtp (gst-plugins-good)",
+        "aacparse" => "audioparsers (gst-plugins-good)",
+        "faad" => "faad (gst-plugins-bad)",
+        "avdec_aac" => "libav (gst-libav)",
+        "fallbackswitch" => "fallbackswitch (gst-plugins-rs)",
+        "rtpL16pay" => "rtp (gst-plugins-good)",
+        "x264enc" => "x264 (gst-plugins-ugly)",
+        "x265enc" => "x265 (gst-plugins-bad)",
+        "avdec_h264" => "libav (gst-libav)",
+        "avdec_h265" => "libav (gst-libav)",
+        "videotestsrc" => "videotestsrc (gst-plugins-base)",
+        "textoverlay" => "pango (gst-plugins-base)",
+        "timeoverlay" => "pango (gst-plugins-base)",
+        "videoconvert" => "videoconvertscale (gst-plugins-base)",
+        "audioresample" => "audioresample (gst-plugins-base)",
+        "jpegenc" => "jpeg (gst-plugins-good)",
+        "rtpjpegpay" => "rtp (gst-plugins-good)",
+        "imagefreeze" => "imagefreeze (gst-plugins-good)",
+        "audiotestsrc" => "audiotestsrc (gst-plugins-base)",
+        "decodebin" => "playback (gst-plugins-good)",
+        "rtspsrc" => "rtsp (gst-plugins-good)",
+        "rtph264depay" => "rtp (gst-plugins-good)",
+        "rtph265depay" => "rtp (gst-plugins-good)",
+        _ => "Unknown",
+    }
+}
+
 // Convenice funcion to make an element or provide a message
 // about what plugin is missing
 fn make_element(kind: &str, name: &str) -> AnyResult<Element> {
     ElementFactory::make_with_name(kind, Some(name)).with_context(|| {
-        let plugin = match kind {
-            "appsrc" => "app (gst-plugins-base)",
-            "audioconvert" => "audioconvert (gst-plugins-base)",
-            "adpcmdec" => "Required for audio",
-            "h264parse" => "videoparsersbad (gst-plugins-bad)",
-            "h265parse" => "videoparsersbad (gst-plugins-bad)",
-            "rtph264pay" => "rtp (gst-plugins-good)",
-            "rtph265pay" => "rtp (gst-plugins-good)",
-            "rtpjitterbuffer" => "rtp (gst-plugins-good)",
-            "aacparse" => "audioparsers (gst-plugins-good)",
-            "rtpL16pay" => "rtp (gst-plugins-good)",
-            "x264enc" => "x264 (gst-plugins-ugly)",
-            "x265enc" => "x265 (gst-plugins-bad)",
-            "avdec_h264" => "libav (gst-libav)",
-            "avdec_h265" => "libav (gst-libav)",
-            "videotestsrc" => "videotestsrc (gst-plugins-base)",
-            "imagefreeze" => "imagefreeze (gst-plugins-good)",
-            "audiotestsrc" => "audiotestsrc (gst-plugins-base)",
-            "decodebin" => "playback (gst-plugins-good)",
-            _ => "Unknown",
-        };
+        let plugin = plugin_hint(kind);
         format!(
             "Missing required gstreamer plugin `{}` for `{}` element",
             plugin, kind
@@ -450,3 +1018,55 @@ fn buffer_size(bitrate: u32) -> u32 {
     // 0.1 seconds (according to bitrate) or 4kb what ever is larger
     std::cmp::max(bitrate * 2 / 8u32, 4u32 * 1024u32)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn required_elements_follow_config() {
+        let config = Config::from_toml(
+            r#"
+            [[cameras]]
+            name = "plain"
+            username = "admin"
+            address = "192.168.1.10"
+            audio = false
+            use_splash = false
+            format_detect_timeout_secs = 0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(required_elements(&config), vec!["appsrc", "queue"]);
+
+        let config = Config::from_toml(
+            r#"
+            [[cameras]]
+            name = "full"
+            username = "admin"
+            address = "192.168.1.10"
+
+            [[cameras]]
+            name = "relay"
+            username = "admin"
+            source_type = "rtsp_url"
+            rtsp_url = "rtsp://192.168.1.20/stream"
+            "#,
+        )
+        .unwrap();
+        let required = required_elements(&config);
+        for element in [
+            "appsrc",
+            "videotestsrc",
+            "jpegenc",
+            "audioconvert",
+            "rtpL16pay",
+            "rtspsrc",
+        ] {
+            assert!(required.contains(&element), "{} is required", element);
+        }
+        assert!(!required
+            .iter()
+            .any(|element| element.contains("timestamper")));
+    }
+}
@@ -1,10 +1,13 @@
 //! This module provides an "RtspServer" abstraction that allows consumers of its API to feed it
 //! data using an ordinary std::io::Write interface.
 
+mod client;
 mod factory;
+mod mounts;
 mod server;
 mod shared;
 
+pub(crate) use client::{ParameterError, ParameterRequest};
 pub(crate) use factory::*;
 
 pub(crate) use self::server::NeoRtspServer;
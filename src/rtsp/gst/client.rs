@@ -0,0 +1,261 @@
+//! Attempts to subclass RTSPClient
+//!
+//! This answers the GET_PARAMETER and SET_PARAMETER requests that have
+//! a body. The parameters are passed on to the handler of the path which
+//! applies them to the camera. Requests without a body are keep-alives and
+//! are still answered by gstreamer
+
+use super::AnyResult;
+use gstreamer::glib::{self, object_subclass, translate::ToGlibPtr, Object};
+use gstreamer_rtsp::{rtsp_message::RTSPMessage, RTSPResult, RTSPStatusCode};
+use gstreamer_rtsp_server::{prelude::*, subclass::prelude::*, RTSPClient, RTSPContext};
+use once_cell::sync::OnceCell;
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{mpsc::sync_channel, Arc, RwLock},
+    time::Duration,
+};
+use tokio::sync::mpsc::Sender;
+
+/// The token field that marks a user as allowed to change the parameters
+pub(crate) const NEO_TOKEN_ADMIN: &str = "neolink.admin";
+
+/// How long the handler has to reply before the client is given a 503
+///
+/// The reply is waited for on the thread of the rtsp server's main loop so
+/// no other client is answered in the meantime, not even their keep-alives.
+/// This is kept well below the 30s session timeout so that a camera that
+/// does not answer cannot make the other clients time out. A change that
+/// takes longer than this may still be made after the 503 is sent
+const PARAMETER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The reply to a [`ParameterRequest`]
+pub(crate) type ParameterReply =
+    std::sync::mpsc::SyncSender<Result<Vec<(String, String)>, ParameterError>>;
+
+/// A GET_PARAMETER or SET_PARAMETER for the handler of a path
+pub(crate) enum ParameterRequest {
+    /// Reply with the current value of each name
+    Get {
+        names: Vec<String>,
+        reply: ParameterReply,
+    },
+    /// Change each name to its value and reply with no values
+    Set {
+        values: Vec<(String, String)>,
+        reply: ParameterReply,
+    },
+}
+
+/// Why a parameter could not be got or set
+#[derive(Debug)]
+pub(crate) enum ParameterError {
+    /// The name is not known or the value is invalid
+    BadRequest(String),
+    /// The camera did not take the change
+    Failed(anyhow::Error),
+}
+
+/// The handlers of the parameters by the normalised path
+pub(crate) type ParameterHandlers = Arc<RwLock<HashMap<String, Sender<ParameterRequest>>>>;
+
+glib::wrapper! {
+    /// The wrapped RTSPClient
+    pub(crate) struct NeoRtspClient(ObjectSubclass<NeoRtspClientImpl>) @extends RTSPClient;
+}
+
+impl NeoRtspClient {
+    pub(crate) fn new(handlers: ParameterHandlers) -> Self {
+        let client = Object::new::<NeoRtspClient>();
+        let _ = client.imp().handlers.set(handlers);
+        client
+    }
+}
+
+unsafe impl Send for NeoRtspClient {}
+unsafe impl Sync for NeoRtspClient {}
+
+#[derive(Default)]
+pub(crate) struct NeoRtspClientImpl {
+    // Read from the gstreamer threads so this is not a tokio lock
+    handlers: OnceCell<ParameterHandlers>,
+}
+
+impl ObjectImpl for NeoRtspClientImpl {}
+impl RTSPClientImpl for NeoRtspClientImpl {
+    fn params_set(&self, ctx: &RTSPContext) -> RTSPResult {
+        self.handle_parameters(ctx, |body, reply| {
+            let mut values = vec![];
+            for line in body.lines().filter(|line| !line.trim().is_empty()) {
+                let (name, value) = line.split_once(':').ok_or_else(|| {
+                    ParameterError::BadRequest(format!("Expected name: value but got {line}"))
+                })?;
+                values.push((name.trim().to_string(), value.trim().to_string()));
+            }
+            Ok(ParameterRequest::Set { values, reply })
+        })
+    }
+
+    fn params_get(&self, ctx: &RTSPContext) -> RTSPResult {
+        self.handle_parameters(ctx, |body, reply| {
+            let names = body
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect();
+            Ok(ParameterRequest::Get { names, reply })
+        })
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for NeoRtspClientImpl {
+    const NAME: &'static str = "NeoRtspClient";
+    type Type = NeoRtspClient;
+    type ParentType = RTSPClient;
+}
+
+impl NeoRtspClientImpl {
+    /// Pass the request to the path's handler and answer with its reply
+    fn handle_parameters<F>(&self, ctx: &RTSPContext, make_request: F) -> RTSPResult
+    where
+        F: FnOnce(&str, ParameterReply) -> Result<ParameterRequest, ParameterError>,
+    {
+        let (Some(request), Some(response)) = (ctx.request(), ctx.response()) else {
+            return RTSPResult::Error;
+        };
+        let (code, content_type, body) = match self.run_handler(ctx, request, make_request) {
+            Ok(values) => (
+                RTSPStatusCode::Ok,
+                "text/parameters",
+                values
+                    .iter()
+                    .fold(String::new(), |mut body, (name, value)| {
+                        let _ = write!(body, "{name}: {value}\r\n");
+                        body
+                    }),
+            ),
+            Err((code, reason)) => {
+                log::debug!("Parameter request failed with {code:?}: {reason}");
+                (code, "text/plain", format!("{reason}\r\n"))
+            }
+        };
+        response.init_response(code, Some(request));
+        if !body.is_empty() {
+            set_body(response, content_type, &body);
+        }
+        RTSPResult::Ok
+    }
+
+    fn run_handler<F>(
+        &self,
+        ctx: &RTSPContext,
+        request: &RTSPMessage,
+        make_request: F,
+    ) -> Result<Vec<(String, String)>, (RTSPStatusCode, String)>
+    where
+        F: FnOnce(&str, ParameterReply) -> Result<ParameterRequest, ParameterError>,
+    {
+        if !ctx
+            .token()
+            .map(|token| token.is_allowed(NEO_TOKEN_ADMIN))
+            .unwrap_or(false)
+        {
+            return Err((
+                RTSPStatusCode::Forbidden,
+                "Only admin users may use the parameters".to_string(),
+            ));
+        }
+        let path = self
+            .path(ctx)
+            .map_err(|e| (RTSPStatusCode::BadRequest, format!("{e:#}")))?;
+        let sender = self
+            .handlers
+            .get()
+            .and_then(|handlers| handlers.read().unwrap().get(&path).cloned())
+            .ok_or_else(|| {
+                (
+                    RTSPStatusCode::NotFound,
+                    format!("{path} has no parameters"),
+                )
+            })?;
+
+        let body = get_body(request);
+        let (reply, reply_rx) = sync_channel(1);
+        let parameter_request = make_request(&body, reply).map_err(into_status)?;
+        sender.try_send(parameter_request).map_err(|_| {
+            (
+                RTSPStatusCode::ServiceUnavailable,
+                "The camera is busy".to_string(),
+            )
+        })?;
+        reply_rx
+            .recv_timeout(PARAMETER_TIMEOUT)
+            .map_err(|_| {
+                (
+                    RTSPStatusCode::ServiceUnavailable,
+                    "The camera did not reply".to_string(),
+                )
+            })?
+            .map_err(into_status)
+    }
+
+    /// The normalised path of the request
+    fn path(&self, ctx: &RTSPContext) -> AnyResult<String> {
+        let url = ctx
+            .uri()
+            .ok_or_else(|| anyhow::anyhow!("The request has no url"))?;
+        let mounts = self
+            .obj()
+            .mount_points()
+            .ok_or_else(|| anyhow::anyhow!("The client has no mount points"))?;
+        Ok(mounts.make_path(url)?.to_string())
+    }
+}
+
+fn into_status(error: ParameterError) -> (RTSPStatusCode, String) {
+    match error {
+        ParameterError::BadRequest(reason) => (RTSPStatusCode::BadRequest, reason),
+        ParameterError::Failed(e) => (RTSPStatusCode::InternalServerError, format!("{e:#}")),
+    }
+}
+
+/// The body of the message as text
+fn get_body(message: &RTSPMessage) -> String {
+    let mut data = std::ptr::null_mut();
+    let mut size = 0;
+    let res = unsafe {
+        gstreamer_rtsp::ffi::gst_rtsp_message_get_body(
+            message.to_glib_none().0,
+            &mut data,
+            &mut size,
+        )
+    };
+    if res != gstreamer_rtsp::ffi::GST_RTSP_OK || data.is_null() {
+        return String::new();
+    }
+    // The data is owned by the message
+    let body = unsafe { std::slice::from_raw_parts(data, size as usize) };
+    String::from_utf8_lossy(body)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Set the body of the message and its content type
+fn set_body(message: &RTSPMessage, content_type: &str, body: &str) {
+    unsafe {
+        gstreamer_rtsp::ffi::gst_rtsp_message_add_header_by_name(
+            message.to_glib_none().0,
+            "Content-Type".to_glib_none().0,
+            content_type.to_glib_none().0,
+        );
+        // The body is copied into the message
+        gstreamer_rtsp::ffi::gst_rtsp_message_set_body(
+            message.to_glib_none().0,
+            body.as_ptr(),
+            body.len() as u32,
+        );
+    }
+}
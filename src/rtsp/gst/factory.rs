@@ -8,15 +8,17 @@ use gstreamer::glib::object_subclass;
 use gstreamer::Element;
 use gstreamer::{
     glib::{self, Object},
-    Structure,
+    prelude::*,
+    Message, MessageView, Pipeline, Structure,
 };
 use gstreamer_rtsp::RTSPUrl;
 use gstreamer_rtsp_server::prelude::*;
 use gstreamer_rtsp_server::subclass::prelude::*;
-use gstreamer_rtsp_server::RTSPMediaFactory;
 use gstreamer_rtsp_server::RTSPTransportMode;
+use gstreamer_rtsp_server::{RTSPMedia, RTSPMediaFactory};
 use gstreamer_rtsp_server::{RTSP_PERM_MEDIA_FACTORY_ACCESS, RTSP_PERM_MEDIA_FACTORY_CONSTRUCT};
 use log::*;
+use once_cell::sync::OnceCell;
 use std::collections::HashSet;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -54,6 +56,29 @@ impl NeoMediaFactory {
         Ok(factory)
     }
 
+    /// Call `callback` with every message posted on the bus of the media pipelines
+    ///
+    /// It is called from the thread that posted the message so must be quick
+    pub(crate) async fn set_message_callback<F>(&self, callback: F)
+    where
+        F: Fn(&Message) + Send + Sync + 'static,
+    {
+        self.imp()
+            .on_message
+            .lock()
+            .await
+            .replace(Arc::new(callback));
+    }
+
+    /// Name the camera that the factory streams for the client metrics
+    pub(crate) fn set_camera_name(&self, name: &str) {
+        let _ = self.imp().camera_name.set(name.to_string());
+    }
+
+    pub(crate) fn camera_name(&self) -> Option<&str> {
+        self.imp().camera_name.get().map(String::as_str)
+    }
+
     pub(crate) fn add_permitted_roles<T: AsRef<str>>(&self, permitted_roles: &HashSet<T>) {
         for permitted_role in permitted_roles {
             let s = permitted_role.as_ref();
@@ -100,6 +125,9 @@ unsafe impl Sync for NeoMediaFactory {}
 pub(crate) struct NeoMediaFactoryImpl {
     #[allow(clippy::type_complexity)]
     call_back: Arc<Mutex<Option<Arc<dyn Fn(Element) -> AnyResult<Option<Element>> + Send + Sync>>>>,
+    #[allow(clippy::type_complexity)]
+    on_message: Arc<Mutex<Option<Arc<dyn Fn(&Message) + Send + Sync>>>>,
+    camera_name: OnceCell<String>,
 }
 
 impl Default for NeoMediaFactoryImpl {
@@ -108,6 +136,8 @@ impl Default for NeoMediaFactoryImpl {
         // Prepare thread that sends data into the appsrcs
         Self {
             call_back: Arc::new(Mutex::new(None)),
+            on_message: Arc::new(Mutex::new(None)),
+            camera_name: OnceCell::new(),
         }
     }
 }
@@ -142,6 +172,45 @@ impl RTSPMediaFactoryImpl for NeoMediaFactoryImpl {
         self.parent_create_element(url)
             .and_then(|orig| self.build_pipeline(orig).expect("Could not build pipeline"))
     }
+
+    fn create_pipeline(&self, media: &RTSPMedia) -> Option<Pipeline> {
+        let pipeline = self.parent_create_pipeline(media)?;
+        // The media owns the bus watch so the sync messages are used instead
+        if let Some(bus) = pipeline.bus() {
+            let on_message = self.on_message.blocking_lock().clone();
+            bus.enable_sync_message_emission();
+            bus.connect_sync_message(None, move |_, msg| {
+                if let Some(on_message) = on_message.as_ref() {
+                    on_message(msg);
+                }
+                match msg.view() {
+                    MessageView::StateChanged(state) => {
+                        debug!(
+                            "Pipeline state change of {}: {:?} -> {:?} (pending {:?})",
+                            msg.src()
+                                .map(|src| src.name().to_string())
+                                .unwrap_or_else(|| "unknown".to_string()),
+                            state.old(),
+                            state.current(),
+                            state.pending(),
+                        );
+                    }
+                    MessageView::Error(err) => {
+                        error!(
+                            "Pipeline error from {}: {} ({:?})",
+                            msg.src()
+                                .map(|src| src.name().to_string())
+                                .unwrap_or_else(|| "unknown".to_string()),
+                            err.error(),
+                            err.debug(),
+                        );
+                    }
+                    _ => {}
+                }
+            });
+        }
+        Some(pipeline)
+    }
 }
 
 #[object_subclass]
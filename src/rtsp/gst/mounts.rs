@@ -0,0 +1,72 @@
+//! Attempts to subclass RTSPMountPoints
+//!
+//! This normalises the path of each request before it is
+//! matched against the mounted factories
+
+use gstreamer::glib::{self, object_subclass, GString, Object};
+use gstreamer_rtsp::RTSPUrl;
+use gstreamer_rtsp_server::subclass::prelude::*;
+use gstreamer_rtsp_server::RTSPMountPoints;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+glib::wrapper! {
+    /// The wrapped RTSPMountPoints
+    pub(crate) struct NeoMountPoints(ObjectSubclass<NeoMountPointsImpl>) @extends RTSPMountPoints;
+}
+
+impl Default for NeoMountPoints {
+    fn default() -> Self {
+        Object::new::<NeoMountPoints>()
+    }
+}
+
+impl NeoMountPoints {
+    /// When enabled all paths are lowercased before they are matched
+    pub(crate) fn set_case_insensitive(&self, enabled: bool) {
+        self.imp()
+            .case_insensitive
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    pub(crate) fn case_insensitive(&self) -> bool {
+        self.imp().case_insensitive.load(Ordering::Relaxed)
+    }
+
+    /// The path as it will be matched
+    ///
+    /// Trailing slashes are removed and if case insensitive it is lowercased
+    pub(crate) fn normalize(&self, path: &str) -> String {
+        let path = match path.trim_end_matches('/') {
+            "" => "/",
+            path => path,
+        };
+        if self.case_insensitive() {
+            path.to_lowercase()
+        } else {
+            path.to_string()
+        }
+    }
+}
+
+unsafe impl Send for NeoMountPoints {}
+unsafe impl Sync for NeoMountPoints {}
+
+#[derive(Default)]
+pub(crate) struct NeoMountPointsImpl {
+    case_insensitive: AtomicBool,
+}
+
+impl ObjectImpl for NeoMountPointsImpl {}
+impl RTSPMountPointsImpl for NeoMountPointsImpl {
+    fn make_path(&self, url: &RTSPUrl) -> Option<GString> {
+        let path = self.parent_make_path(url)?;
+        Some(self.obj().normalize(&path).into())
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for NeoMountPointsImpl {
+    const NAME: &'static str = "NeoMountPoints";
+    type Type = NeoMountPoints;
+    type ParentType = RTSPMountPoints;
+}
@@ -3,28 +3,49 @@
 //! We are now messing with gstreamer glib objects
 //! expect issues
 
-use super::AnyResult;
-use crate::config::*;
+use super::{
+    client::{NeoRtspClient, ParameterHandlers, ParameterRequest, NEO_TOKEN_ADMIN},
+    mounts::NeoMountPoints,
+    AnyResult, NeoMediaFactory,
+};
+use crate::{
+    config::*,
+    rtsp::metrics::{ClientStats, FrameType, RtspMetrics},
+};
 
-use anyhow::Context;
-use gstreamer::glib::{self, object_subclass, MainLoop, Object};
-use gstreamer_rtsp::RTSPAuthMethod;
+use anyhow::{anyhow, Context};
+use gstreamer::{
+    glib::{
+        self, object_subclass,
+        translate::{from_glib_full, ToGlibPtr},
+        MainLoop, Object,
+    },
+    Bin, BufferFlags, Clock, ClockTime, PadProbeReturn, PadProbeType, SystemClock,
+};
+use gstreamer_app::AppSrc;
+use gstreamer_net::NtpClock;
+use gstreamer_rtsp::{rtsp_message::RTSPMessage, RTSPAuthMethod};
 use gstreamer_rtsp_server::{
     gio::{TlsAuthenticationMode, TlsCertificate},
     prelude::*,
     subclass::prelude::*,
-    RTSPAuth, RTSPFilterResult, RTSPServer, RTSPToken, RTSP_TOKEN_MEDIA_FACTORY_ROLE,
+    RTSPAuth, RTSPClient, RTSPContext, RTSPFilterResult, RTSPMedia, RTSPMediaFactory,
+    RTSPMountPoints, RTSPServer, RTSPSession, RTSPToken, RTSP_TOKEN_MEDIA_FACTORY_ROLE,
 };
 use log::*;
 use std::{
     collections::{HashMap, HashSet},
     fs,
-    sync::Arc,
+    net::ToSocketAddrs,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
 };
 use tokio::{
-    sync::RwLock,
+    sync::{mpsc::Sender, RwLock},
     task::JoinSet,
-    time::{timeout, Duration},
+    time::{sleep, timeout, Duration, Instant},
 };
 use tokio_util::sync::CancellationToken;
 
@@ -54,7 +75,71 @@ impl NeoRtspServer {
         auth.set_default_token(Some(&mut un_authtoken));
         factory.set_auth(Some(&auth));
 
-        factory.connect_client_connected(|_, client| {
+        // Mount points that can normalise the requested paths
+        factory.set_mount_points(Some(&NeoMountPoints::default()));
+
+        let cors_origins = factory.imp().cors_origins.clone();
+        let metrics = factory.imp().metrics.clone();
+        factory.connect_client_connected(move |_, client| {
+            let cors_origins = cors_origins.clone();
+            let connected = Instant::now();
+            let client_id = metrics.client_id();
+            let ip = client_ip(client);
+            let closed_metrics = metrics.clone();
+            client.connect_closed(move |_| {
+                closed_metrics.session_ended(connected.elapsed());
+                closed_metrics.remove_client(client_id);
+            });
+
+            let play_metrics = metrics.clone();
+            client.connect_play_request(move |client, ctx| {
+                let requested = Instant::now();
+                let path = match request_path(client, ctx) {
+                    Some(path) => path,
+                    None => return,
+                };
+                let bin = match session_media(ctx, &path)
+                    .and_then(|media| media.element().dynamic_cast::<Bin>().ok())
+                {
+                    Some(bin) => bin,
+                    None => return,
+                };
+                if let Some(pad) = bin.by_name("pay0").and_then(|pay| pay.static_pad("src")) {
+                    let metrics = play_metrics.clone();
+                    pad.add_probe(
+                        PadProbeType::BUFFER | PadProbeType::BUFFER_LIST,
+                        move |_, _| {
+                            metrics.first_frame_sent(requested.elapsed());
+                            PadProbeReturn::Remove
+                        },
+                    );
+                }
+                let camera = client
+                    .mount_points()
+                    .and_then(|mounts| camera_of_path(&mounts, &path))
+                    .unwrap_or_else(|| "unknown".to_string());
+                if let Some(stats) =
+                    play_metrics.add_client_stream(client_id, &camera, &path, ip.as_deref())
+                {
+                    count_sent(&bin, stats);
+                }
+            });
+
+            let teardown_metrics = metrics.clone();
+            client.connect_teardown_request(move |client, ctx| {
+                if let Some(path) = request_path(client, ctx) {
+                    teardown_metrics.remove_client_stream(client_id, &path);
+                }
+            });
+
+            // The vfunc for this is not in the bindings so the signal is used
+            client.connect("tunnel-http-response", false, move |values| {
+                let request = values[1].get::<&RTSPMessage>().ok()?;
+                let response = values[2].get::<&RTSPMessage>().ok()?;
+                add_cors_headers(&cors_origins.read().unwrap(), request, response);
+                None
+            });
+
             client.connect_new_session(|_, session| {
                 log::debug!("New Session");
                 // Session timeout too small causes us to drop
@@ -69,10 +154,21 @@ impl NeoRtspServer {
         Ok(factory)
     }
 
-    pub(crate) async fn run(&self, bind_addr: &str, bind_port: u16) -> AnyResult<()> {
+    /// Label the client metrics with the ip address of the client
+    pub(crate) fn set_detailed_metrics(&self, detailed: bool) {
+        self.imp().metrics.set_detailed(detailed);
+    }
+
+    /// The metrics of the clients for the `/metrics` endpoint
+    pub(crate) fn metrics(&self) -> Arc<RtspMetrics> {
+        self.imp().metrics.clone()
+    }
+
+    pub(crate) async fn run(&self, bind_addr: &str, bind_port: u16, backlog: u32) -> AnyResult<()> {
         let server = self;
         server.set_address(bind_addr);
         server.set_service(&format!("{}", bind_port));
+        server.set_backlog(backlog as i32);
         // Attach server to default Glib context
         let _ = server.attach(None);
         let main_loop = Arc::new(MainLoop::new(None, false));
@@ -146,8 +242,287 @@ impl NeoRtspServer {
         self.imp().set_up_tls(config)
     }
 
-    pub(crate) async fn add_user(&self, username: &str, password: &str) -> AnyResult<()> {
-        self.imp().add_user(username, password).await
+    /// Set the browser origins that may use the rtsp over http tunnel
+    ///
+    /// The tunnel responses to these origins get CORS headers so that
+    /// browser players can connect with authentication
+    pub(crate) fn set_cors_origins(&self, origins: &[String]) {
+        let mut cors_origins = self.imp().cors_origins.write().unwrap();
+        if cors_origins.as_slice() != origins {
+            debug!("Setting rtsp cors origins to {:?}", origins);
+            *cors_origins = origins.to_vec();
+        }
+    }
+
+    /// Time all the media with one clock
+    ///
+    /// The clock is given to every mounted factory and to those mounted
+    /// later. Media that are already playing keep their clock. `None` lets
+    /// each media pick its own
+    pub(crate) async fn set_shared_clock(&self, clock: Option<Clock>) {
+        let mut shared_clock = self.imp().clock.write().await;
+        if *shared_clock == clock {
+            return;
+        }
+        *shared_clock = clock;
+        for factory in self.imp().paths.read().await.values() {
+            factory.set_clock(shared_clock.as_ref());
+        }
+    }
+
+    /// Set the shared clock from the config
+    pub(crate) async fn set_up_clock(&self, shared_clock: &SharedClock) -> AnyResult<()> {
+        let clock = match shared_clock {
+            SharedClock::None => None,
+            SharedClock::System => Some(SystemClock::obtain()),
+            SharedClock::Ntp(server) => {
+                // The ntp clock wants an ip address
+                let server = if server.contains(':') {
+                    server.clone()
+                } else {
+                    format!("{server}:123")
+                };
+                let addr = server
+                    .to_socket_addrs()
+                    .with_context(|| format!("Could not resolve the ntp server {server}"))?
+                    .next()
+                    .ok_or_else(|| anyhow!("Could not resolve the ntp server {server}"))?;
+                debug!("Using the ntp clock of {}", addr);
+                Some(
+                    NtpClock::new(
+                        Some("neolink-ntp"),
+                        &addr.ip().to_string(),
+                        addr.port() as i32,
+                        ClockTime::ZERO,
+                    )
+                    .upcast::<Clock>(),
+                )
+            }
+        };
+        self.set_shared_clock(clock).await;
+        Ok(())
+    }
+
+    /// Set the jitter buffer latency in ms that new media will be created with
+    pub(crate) fn set_latency(&self, latency_ms: u32) {
+        self.imp().latency.store(latency_ms, Ordering::Relaxed);
+    }
+
+    pub(crate) fn latency(&self) -> u32 {
+        self.imp().latency.load(Ordering::Relaxed)
+    }
+
+    /// Set the maximum number of threads used to handle the rtsp sessions
+    pub(crate) fn set_thread_pool(&self, n_threads: u32) {
+        let pool = self.thread_pool().unwrap_or_default();
+        if pool.max_threads() != n_threads as i32 {
+            debug!("Setting rtsp thread pool to {} threads", n_threads);
+            pool.set_max_threads(n_threads as i32);
+        }
+        RTSPServerExt::set_thread_pool(self, Some(&pool));
+    }
+
+    fn neo_mount_points(&self) -> AnyResult<NeoMountPoints> {
+        self.mount_points()
+            .and_then(|mounts| mounts.dynamic_cast::<NeoMountPoints>().ok())
+            .ok_or(anyhow!("RTSP server lacks mount point"))
+    }
+
+    /// Mount the factory at the path replacing any factory already there
+    ///
+    /// Any aliases of the path are mounted with the same factory
+    pub(crate) async fn add_stream(&self, path: &str, factory: &NeoMediaFactory) -> AnyResult<()> {
+        let mounts = self.neo_mount_points()?;
+        let aliases = self.aliases_of(path).await;
+        factory.set_clock(self.imp().clock.read().await.as_ref());
+        let mut paths = self.imp().paths.write().await;
+        for path in std::iter::once(path).chain(aliases.iter().map(String::as_str)) {
+            mounts.add_factory(&mounts.normalize(path), factory.clone());
+            paths.insert(path.to_string(), factory.clone());
+        }
+        Ok(())
+    }
+
+    /// Also serve the factory of `existing_path` at `alias_path`
+    ///
+    /// The same factory is mounted so no extra camera stream is used. The
+    /// alias follows the path: it is mounted when a factory is added at
+    /// `existing_path` and unmounted when it is removed
+    pub(crate) async fn add_path_alias(
+        &self,
+        existing_path: &str,
+        alias_path: &str,
+    ) -> AnyResult<()> {
+        self.imp()
+            .aliases
+            .write()
+            .await
+            .insert(alias_path.to_string(), existing_path.to_string());
+        let factory = self.imp().paths.read().await.get(existing_path).cloned();
+        if let Some(factory) = factory {
+            let mounts = self.neo_mount_points()?;
+            mounts.add_factory(&mounts.normalize(alias_path), factory.clone());
+            self.imp()
+                .paths
+                .write()
+                .await
+                .insert(alias_path.to_string(), factory);
+        }
+        let mounts = self.neo_mount_points()?;
+        let mut handlers = self.imp().parameter_handlers.write().unwrap();
+        if let Some(handler) = handlers.get(&mounts.normalize(existing_path)).cloned() {
+            handlers.insert(mounts.normalize(alias_path), handler);
+        }
+        Ok(())
+    }
+
+    /// Send the GET_PARAMETER and SET_PARAMETER requests of the path to `handler`
+    ///
+    /// Aliases of the path use the same handler. It is removed when the
+    /// path is removed
+    pub(crate) async fn add_parameter_handler(
+        &self,
+        path: &str,
+        handler: Sender<ParameterRequest>,
+    ) -> AnyResult<()> {
+        let mounts = self.neo_mount_points()?;
+        let aliases = self.aliases_of(path).await;
+        let mut handlers = self.imp().parameter_handlers.write().unwrap();
+        for path in std::iter::once(path).chain(aliases.iter().map(String::as_str)) {
+            handlers.insert(mounts.normalize(path), handler.clone());
+        }
+        Ok(())
+    }
+
+    /// Stop serving an alias added with [`NeoRtspServer::add_path_alias`]
+    pub(crate) async fn remove_path_alias(&self, alias_path: &str) -> AnyResult<()> {
+        if self
+            .imp()
+            .aliases
+            .write()
+            .await
+            .remove(alias_path)
+            .is_some()
+        {
+            self.remove_stream(alias_path).await?;
+        }
+        Ok(())
+    }
+
+    async fn aliases_of(&self, path: &str) -> Vec<String> {
+        self.imp()
+            .aliases
+            .read()
+            .await
+            .iter()
+            .filter(|(_, existing)| *existing == path)
+            .map(|(alias, _)| alias.clone())
+            .collect()
+    }
+
+    /// Match paths regardless of case
+    ///
+    /// This changes the matching of every path on the server. All the
+    /// mounted factories are remounted under their new paths
+    pub(crate) async fn set_case_insensitive_paths(&self, enabled: bool) -> AnyResult<()> {
+        let mounts = self.neo_mount_points()?;
+        if mounts.case_insensitive() == enabled {
+            return Ok(());
+        }
+        debug!("Setting case insensitive rtsp paths to {}", enabled);
+        let paths = self.imp().paths.read().await;
+        for path in paths.keys() {
+            mounts.remove_factory(&mounts.normalize(path));
+        }
+        mounts.set_case_insensitive(enabled);
+        for (path, factory) in paths.iter() {
+            mounts.add_factory(&mounts.normalize(path), factory.clone());
+        }
+        Ok(())
+    }
+
+    /// Unmount the factory at the path
+    ///
+    /// New clients will get a 404 straight away. Clients already
+    /// watching the path are given [`DRAIN_TIMEOUT`] to disconnect
+    /// after which their appsrcs are sent an EOS and their sessions removed.
+    /// Any aliases of the path are unmounted the same way
+    pub(crate) async fn remove_stream(&self, path: &str) -> AnyResult<()> {
+        let aliases = self.aliases_of(path).await;
+        for alias in std::iter::once(path).chain(aliases.iter().map(String::as_str)) {
+            self.unmount_and_drain(alias).await?;
+        }
+        Ok(())
+    }
+
+    async fn unmount_and_drain(&self, path: &str) -> AnyResult<()> {
+        let mounts = self.neo_mount_points()?;
+        let mut paths = self.imp().paths.write().await;
+        paths.remove(path);
+        let path = &mounts.normalize(path);
+        // Another path may normalise to the same mount
+        if !paths.keys().any(|other| &mounts.normalize(other) == path) {
+            mounts.remove_factory(path);
+            self.imp().parameter_handlers.write().unwrap().remove(path);
+        }
+        drop(paths);
+
+        let Some(sessions) = self.session_pool() else {
+            return Ok(());
+        };
+        let start = Instant::now();
+        loop {
+            sessions.cleanup();
+            let active = sessions
+                .filter(Some(&mut |_, session| {
+                    if session_uses_path(session, path) {
+                        RTSPFilterResult::Ref
+                    } else {
+                        RTSPFilterResult::Keep
+                    }
+                }))
+                .len();
+            if active == 0 {
+                break;
+            }
+            if start.elapsed() >= DRAIN_TIMEOUT {
+                log::debug!("{path}: Closing {active} sessions that did not disconnect");
+                sessions.filter(Some(&mut |_, session| {
+                    if session_uses_path(session, path) {
+                        end_session_media(session, path);
+                        RTSPFilterResult::Remove
+                    } else {
+                        RTSPFilterResult::Keep
+                    }
+                }));
+                break;
+            }
+            sleep(Duration::from_millis(500)).await;
+        }
+        Ok(())
+    }
+
+    /// The paths that currently have a factory mounted
+    pub(crate) async fn list_streams(&self) -> Vec<String> {
+        let mut paths = self
+            .imp()
+            .paths
+            .read()
+            .await
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    pub(crate) async fn add_user(
+        &self,
+        username: &str,
+        password: &str,
+        admin: bool,
+    ) -> AnyResult<()> {
+        self.imp().add_user(username, password, admin).await
     }
 
     pub(crate) async fn remove_user(&self, username: &str) -> AnyResult<()> {
@@ -159,18 +534,221 @@ impl NeoRtspServer {
     }
 }
 
+/// Add the CORS headers to a tunnel response if the request's `Origin` is allowed
+///
+/// The origin is only echoed back when it is in the list so that other
+/// sites cannot use a viewer's credentials
+fn add_cors_headers(origins: &[String], request: &RTSPMessage, response: &RTSPMessage) {
+    let Some(origin) = header_by_name(request, "Origin") else {
+        return;
+    };
+    if !origins.iter().any(|allowed| allowed == &origin) {
+        log::debug!("Not adding CORS headers for origin {origin}");
+        return;
+    }
+    for (name, value) in [
+        ("Access-Control-Allow-Origin", origin.as_str()),
+        (
+            "Access-Control-Allow-Headers",
+            "Authorization, Content-Type",
+        ),
+        ("Access-Control-Allow-Credentials", "true"),
+        ("Vary", "Origin"),
+    ] {
+        unsafe {
+            gstreamer_rtsp::ffi::gst_rtsp_message_add_header_by_name(
+                response.to_glib_none().0,
+                name.to_glib_none().0,
+                value.to_glib_none().0,
+            );
+        }
+    }
+}
+
+/// Read the first header of this name from the message
+fn header_by_name(message: &RTSPMessage, name: &str) -> Option<String> {
+    let mut value = std::ptr::null_mut();
+    let res = unsafe {
+        gstreamer_rtsp::ffi::gst_rtsp_message_get_header_by_name(
+            message.to_glib_none().0,
+            name.to_glib_none().0,
+            &mut value,
+            0,
+        )
+    };
+    if res != gstreamer_rtsp::ffi::GST_RTSP_OK || value.is_null() {
+        return None;
+    }
+    // The value is owned by the message
+    unsafe { std::ffi::CStr::from_ptr(value) }
+        .to_str()
+        .ok()
+        .map(str::to_string)
+}
+
+/// How long clients have to disconnect when their stream is removed
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The normalised path of the request of `ctx`
+fn request_path(client: &RTSPClient, ctx: &RTSPContext) -> Option<String> {
+    Some(
+        client
+            .mount_points()?
+            .make_path(ctx.uri()?)
+            .ok()?
+            .to_string(),
+    )
+}
+
+/// The media of the session of `ctx` at `path`
+fn session_media(ctx: &RTSPContext, path: &str) -> Option<RTSPMedia> {
+    let (session_media, _) = ctx.session()?.media(path);
+    session_media?.media()
+}
+
+/// The camera of the factory mounted at `path`
+fn camera_of_path(mounts: &RTSPMountPoints, path: &str) -> Option<String> {
+    // The binding of this cannot handle no factory being mounted at the path
+    let factory: Option<RTSPMediaFactory> = unsafe {
+        from_glib_full(gstreamer_rtsp_server::ffi::gst_rtsp_mount_points_match(
+            mounts.to_glib_none().0,
+            path.to_glib_none().0,
+            std::ptr::null_mut(),
+        ))
+    };
+    factory?
+        .dynamic_cast::<NeoMediaFactory>()
+        .ok()?
+        .camera_name()
+        .map(str::to_string)
+}
+
+/// The ip address the client connected from
+fn client_ip(client: &RTSPClient) -> Option<String> {
+    // The connection is not in the bindings
+    let ip = unsafe {
+        let connection =
+            gstreamer_rtsp_server::ffi::gst_rtsp_client_get_connection(client.to_glib_none().0);
+        if connection.is_null() {
+            return None;
+        }
+        gstreamer_rtsp::ffi::gst_rtsp_connection_get_ip(connection)
+    };
+    if ip.is_null() {
+        return None;
+    }
+    // The ip is owned by the connection
+    unsafe { std::ffi::CStr::from_ptr(ip) }
+        .to_str()
+        .ok()
+        .map(str::to_string)
+}
+
+/// Count the frames that go into the payloaders of the media and the
+/// bytes that come out
+///
+/// The factory is not shared so every client has its own media and
+/// payloaders
+fn count_sent(bin: &Bin, stats: Arc<ClientStats>) {
+    for (name, audio) in [("pay0", false), ("pay1", true)] {
+        let pay = match bin.by_name(name) {
+            Some(pay) => pay,
+            None => continue,
+        };
+        if let Some(pad) = pay.static_pad("sink") {
+            let stats = stats.clone();
+            pad.add_probe(PadProbeType::BUFFER, move |_, info| {
+                if let Some(buffer) = info.buffer() {
+                    stats.frame_sent(if audio {
+                        FrameType::Audio
+                    } else if buffer.flags().contains(BufferFlags::DELTA_UNIT) {
+                        FrameType::Interframe
+                    } else {
+                        FrameType::Keyframe
+                    });
+                }
+                PadProbeReturn::Ok
+            });
+        }
+        if let Some(pad) = pay.static_pad("src") {
+            let stats = stats.clone();
+            pad.add_probe(
+                PadProbeType::BUFFER | PadProbeType::BUFFER_LIST,
+                move |_, info| {
+                    let size = match (info.buffer(), info.buffer_list()) {
+                        (Some(buffer), _) => buffer.size(),
+                        (_, Some(list)) => list.calculate_size(),
+                        _ => 0,
+                    };
+                    stats.bytes_sent(size as u64);
+                    PadProbeReturn::Ok
+                },
+            );
+        }
+    }
+}
+
+/// If the session is watching exactly this path
+
+fn session_uses_path(session: &RTSPSession, path: &str) -> bool {
+    let (media, matched) = session.media(path);
+    media.is_some() && matched as usize == path.len()
+}
+
+/// Send an EOS into all the appsrcs of the session's media at the path
+fn end_session_media(session: &RTSPSession, path: &str) {
+    let Some(bin) = session
+        .media(path)
+        .0
+        .and_then(|session_media| session_media.media())
+        .and_then(|media| media.element().dynamic_cast::<Bin>().ok())
+    else {
+        return;
+    };
+    for appsrc in bin
+        .iterate_recurse()
+        .into_iter()
+        .flatten()
+        .filter_map(|element| element.dynamic_cast::<AppSrc>().ok())
+    {
+        let _ = appsrc.end_of_stream();
+    }
+}
+
 unsafe impl Send for NeoRtspServer {}
 unsafe impl Sync for NeoRtspServer {}
 
 #[derive(Default)]
 pub(crate) struct NeoRtspServerImpl {
     threads: RwLock<JoinSet<AnyResult<()>>>,
-    users: RwLock<HashMap<String, String>>,
+    // Username to their basic auth and if they are an admin
+    users: RwLock<HashMap<String, (String, bool)>>,
+    paths: RwLock<HashMap<String, NeoMediaFactory>>,
+    // Alias path to the path it serves the factory of
+    aliases: RwLock<HashMap<String, String>>,
     main_loop: RwLock<Option<Arc<MainLoop>>>,
+    clock: RwLock<Option<Clock>>,
+    latency: AtomicU32,
+    // Read from the gstreamer threads so this is not a tokio lock
+    cors_origins: Arc<std::sync::RwLock<Vec<String>>>,
+    parameter_handlers: ParameterHandlers,
+    metrics: Arc<RtspMetrics>,
 }
 
 impl ObjectImpl for NeoRtspServerImpl {}
-impl RTSPServerImpl for NeoRtspServerImpl {}
+impl RTSPServerImpl for NeoRtspServerImpl {
+    /// The same as the default but with our client that handles the parameters
+    fn create_client(&self) -> Option<RTSPClient> {
+        let server = self.obj();
+        let client = NeoRtspClient::new(self.parameter_handlers.clone());
+        client.set_session_pool(server.session_pool().as_ref());
+        client.set_mount_points(server.mount_points().as_ref());
+        client.set_content_length_limit(server.content_length_limit());
+        client.set_auth(server.auth().as_ref());
+        client.set_thread_pool(server.thread_pool().as_ref());
+        Some(client.upcast())
+    }
+}
 
 #[object_subclass]
 impl ObjectSubclass for NeoRtspServerImpl {
@@ -213,26 +791,34 @@ impl NeoRtspServerImpl {
         Ok(())
     }
 
-    pub(crate) async fn add_user(&self, username: &str, password: &str) -> AnyResult<()> {
+    pub(crate) async fn add_user(
+        &self,
+        username: &str,
+        password: &str,
+        admin: bool,
+    ) -> AnyResult<()> {
         let mut locked_users = self.users.write().await;
         let auth = self.obj().auth().unwrap();
 
-        let token = RTSPToken::new(&[(RTSP_TOKEN_MEDIA_FACTORY_ROLE, &username)]);
+        let token = RTSPToken::new(&[
+            (RTSP_TOKEN_MEDIA_FACTORY_ROLE, &username),
+            (NEO_TOKEN_ADMIN, &admin),
+        ]);
         let basic = RTSPAuth::make_basic(username, password);
 
-        if let Some(old_basic) = locked_users.get(username) {
-            if basic.as_str() == old_basic {
-                // Password is the same
+        if let Some((old_basic, old_admin)) = locked_users.get(username) {
+            if basic.as_str() == old_basic && admin == *old_admin {
+                // Password and admin are the same
                 return Ok(());
             } else {
-                // Different password
+                // Different password or admin
                 auth.remove_basic(old_basic);
             }
         }
 
         auth.add_basic(basic.as_str(), &token);
 
-        locked_users.insert(username.to_string(), basic.to_string());
+        locked_users.insert(username.to_string(), (basic.to_string(), admin));
         Ok(())
     }
 
@@ -240,7 +826,7 @@ impl NeoRtspServerImpl {
         let mut locked_users = self.users.write().await;
         let auth = self.obj().auth().unwrap();
 
-        if let Some(old_basic) = locked_users.get(username) {
+        if let Some((old_basic, _)) = locked_users.get(username) {
             auth.remove_basic(old_basic);
         }
 
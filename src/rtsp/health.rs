@@ -0,0 +1,418 @@
+//! Health endpoint of the rtsp server
+//!
+//! A small http server answers `GET /healthz` with `200 OK` when everything
+//! is healthy and `503 Service Unavailable` when it is not. The json body
+//! lists the cameras that are unhealthy and why. It is meant for the
+//! liveness and readiness probes of docker and kubernetes and is what
+//! `neolink health-check` queries
+//!
+//! It is healthy when
+//! - Every camera has at least tried to connect
+//! - The rtsp server is accepting connections
+//! - No stream has been without video for more than twice its
+//!   `format_detect_timeout_secs`
+//!
+//! It also answers `GET /cameras/{name}/pipeline-graph` with the gstreamer
+//! pipelines of the camera's rtsp media as a graphviz DOT graph, which helps
+//! find out why a camera that is connected gives no video
+//!
+//! `GET /metrics` counts the errors and warnings that the rtsp media
+//! pipelines have posted in the Prometheus text format, followed by the
+//! [`RtspMetrics`] of the rtsp clients
+//!
+//! When there are `[[users]]` every endpoint but `/healthz` needs one of them
+//! with Basic or Digest authentication, see [`HttpAuth`]. `/healthz` is
+//! left open so that container probes do not need credentials
+use anyhow::{Context, Result};
+use gstreamer::{
+    glib::{self, translate::IntoGlib, WeakRef},
+    prelude::*,
+    Bin, CoreError, DebugGraphDetails, Element, LibraryError, Message, MessageView, ResourceError,
+    StreamError,
+};
+use neolink_core::bc_protocol::StreamKind;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    time::{timeout, Duration, Instant},
+};
+
+use super::{http_auth::HttpAuth, metrics::RtspMetrics};
+use crate::{common::NeoReactor, config::Config};
+
+/// How long the rtsp server is given to accept the check connection
+const RTSP_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Default)]
+struct CameraHealth {
+    attempted: bool,
+    no_video_since: HashMap<StreamKind, (Instant, Duration)>,
+}
+
+/// Shared record of the cameras for the health endpoint
+#[derive(Debug, Default)]
+pub(super) struct HealthTracker {
+    cameras: Mutex<HashMap<String, CameraHealth>>,
+    /// The bins of the rtsp media of each camera
+    media: Mutex<HashMap<String, Vec<WeakRef<Element>>>>,
+    /// How many errors and warnings the media pipelines have posted
+    pipeline_messages: Arc<Mutex<BTreeMap<PipelineMessageKey, u64>>>,
+}
+
+/// The labels of a pipeline message counter
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct PipelineMessageKey {
+    /// The name of the metric
+    metric: &'static str,
+    camera: String,
+    element: String,
+    domain: String,
+    code: String,
+}
+
+impl HealthTracker {
+    /// The camera has tried to connect at least once
+    pub(super) fn connection_attempted(&self, name: &str) {
+        self.cameras
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .attempted = true;
+    }
+
+    /// The stream is waiting for its video format
+    ///
+    /// `format_detect_timeout` is the camera's `format_detect_timeout_secs`
+    pub(super) fn waiting_for_video(
+        &self,
+        name: &str,
+        stream: StreamKind,
+        format_detect_timeout: Duration,
+    ) {
+        self.cameras
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .no_video_since
+            .insert(stream, (Instant::now(), format_detect_timeout));
+    }
+
+    /// The stream has its video format
+    pub(super) fn video_ready(&self, name: &str, stream: StreamKind) {
+        if let Some(camera) = self.cameras.lock().unwrap().get_mut(name) {
+            camera.no_video_since.remove(&stream);
+        }
+    }
+
+    /// An rtsp media has been built for the camera
+    pub(super) fn media_created(&self, name: &str, element: &Element) {
+        let mut media = self.media.lock().unwrap();
+        let elements = media.entry(name.to_string()).or_default();
+        // Forget the media that has been dropped since
+        elements.retain(|element| element.upgrade().is_some());
+        elements.push(element.downgrade());
+    }
+
+    /// A callback for the media factory that counts the errors and
+    /// warnings of the camera's pipelines
+    pub(super) fn pipeline_message_counter(
+        &self,
+        name: &str,
+    ) -> impl Fn(&Message) + Send + Sync + 'static {
+        let counts = self.pipeline_messages.clone();
+        let name = name.to_string();
+        move |msg| {
+            let (metric, error) = match msg.view() {
+                MessageView::Error(err) => ("neolink_pipeline_errors_total", err.error()),
+                MessageView::Warning(warning) => {
+                    ("neolink_pipeline_warnings_total", warning.error())
+                }
+                _ => return,
+            };
+            let (domain, code) = error_domain(&error);
+            let key = PipelineMessageKey {
+                metric,
+                camera: name.clone(),
+                element: msg
+                    .src()
+                    .map(|src| src.name().to_string())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                domain,
+                code,
+            };
+            *counts.lock().unwrap().entry(key).or_default() += 1;
+        }
+    }
+
+    /// The pipeline message counters in the Prometheus text format
+    fn metrics(&self) -> String {
+        let counts = self.pipeline_messages.lock().unwrap();
+        let mut body = String::new();
+        for (metric, help) in [
+            (
+                "neolink_pipeline_errors_total",
+                "Errors posted by the gstreamer pipelines of the rtsp media",
+            ),
+            (
+                "neolink_pipeline_warnings_total",
+                "Warnings posted by the gstreamer pipelines of the rtsp media",
+            ),
+        ] {
+            body.push_str(&format!(
+                "# HELP {metric} {help}\n# TYPE {metric} counter\n"
+            ));
+            for (key, count) in counts.iter().filter(|(key, _)| key.metric == metric) {
+                body.push_str(&format!(
+                    "{metric}{{camera=\"{}\",element=\"{}\",domain=\"{}\",code=\"{}\"}} {count}\n",
+                    escape_label(&key.camera),
+                    escape_label(&key.element),
+                    escape_label(&key.domain),
+                    escape_label(&key.code),
+                ));
+            }
+        }
+        body
+    }
+
+    /// Forget a camera that is no longer served
+    pub(super) fn remove(&self, name: &str) {
+        self.cameras.lock().unwrap().remove(name);
+        self.media.lock().unwrap().remove(name);
+    }
+
+    /// The DOT graphs of the camera's media that is still alive
+    ///
+    /// The properties of the elements are left out as they can hold
+    /// the credentials of an `external_audio` url
+    fn pipeline_graph(&self, name: &str) -> Option<String> {
+        let media = self.media.lock().unwrap();
+        let graphs = media
+            .get(name)?
+            .iter()
+            .filter_map(|element| element.upgrade()?.downcast::<Bin>().ok())
+            .map(|bin| {
+                bin.debug_to_dot_data(
+                    DebugGraphDetails::MEDIA_TYPE
+                        | DebugGraphDetails::CAPS_DETAILS
+                        | DebugGraphDetails::STATES,
+                )
+                .to_string()
+            })
+            .collect::<Vec<_>>();
+        (!graphs.is_empty()).then(|| graphs.join("\n"))
+    }
+
+    fn status(&self, config: &Config, rtsp: bool) -> HealthStatus {
+        let cameras = self.cameras.lock().unwrap();
+        let now = Instant::now();
+        let mut unhealthy = vec![];
+        for camera_config in config.cameras.iter().filter(|camera| camera.enabled) {
+            let name = &camera_config.name;
+            let health = cameras.get(name);
+            if !camera_config.is_external_rtsp() && !health.map(|h| h.attempted).unwrap_or(false) {
+                unhealthy.push(UnhealthyCamera {
+                    name: name.clone(),
+                    reason: "Has not tried to connect yet".to_string(),
+                });
+                continue;
+            }
+            for (stream, (since, format_detect_timeout)) in
+                health.iter().flat_map(|h| h.no_video_since.iter())
+            {
+                let waited = now.saturating_duration_since(*since);
+                if !format_detect_timeout.is_zero() && waited > *format_detect_timeout * 2 {
+                    unhealthy.push(UnhealthyCamera {
+                        name: name.clone(),
+                        reason: format!("No video on the {stream:?} stream for {waited:?}"),
+                    });
+                }
+            }
+        }
+        HealthStatus {
+            healthy: rtsp && unhealthy.is_empty(),
+            rtsp,
+            unhealthy,
+        }
+    }
+}
+
+/// The name of the gstreamer error domain like `GstStreamError` and the code
+fn error_domain(error: &glib::Error) -> (String, String) {
+    if let Some(code) = error.kind::<CoreError>() {
+        ("GstCoreError".to_string(), code.into_glib().to_string())
+    } else if let Some(code) = error.kind::<LibraryError>() {
+        ("GstLibraryError".to_string(), code.into_glib().to_string())
+    } else if let Some(code) = error.kind::<ResourceError>() {
+        ("GstResourceError".to_string(), code.into_glib().to_string())
+    } else if let Some(code) = error.kind::<StreamError>() {
+        ("GstStreamError".to_string(), code.into_glib().to_string())
+    } else {
+        // The codes of other domains cannot be read without knowing their type
+        (error.domain().as_str().to_string(), "unknown".to_string())
+    }
+}
+
+pub(super) fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The json body of `/healthz`
+#[derive(Debug, Serialize)]
+struct HealthStatus {
+    healthy: bool,
+    rtsp: bool,
+    unhealthy: Vec<UnhealthyCamera>,
+}
+
+#[derive(Debug, Serialize)]
+struct UnhealthyCamera {
+    name: String,
+    reason: String,
+}
+
+/// Serves `/healthz` on `bind_addr:bind_port`
+pub(super) async fn health_main(
+    tracker: Arc<HealthTracker>,
+    metrics: Arc<RtspMetrics>,
+    reactor: NeoReactor,
+    bind_addr: &str,
+    bind_port: u16,
+) -> Result<()> {
+    let listener = TcpListener::bind((bind_addr, bind_port))
+        .await
+        .with_context(|| format!("Could not bind health server to {bind_addr}:{bind_port}"))?;
+    log::info!("Health endpoint at http://{bind_addr}:{bind_port}/healthz");
+    let config = reactor.config().await?;
+    let auth = Arc::new(HttpAuth::new());
+    loop {
+        let (socket, addr) = listener.accept().await?;
+        let tracker = tracker.clone();
+        let metrics = metrics.clone();
+        let auth = auth.clone();
+        let config = config.borrow().clone();
+        tokio::task::spawn(async move {
+            if let Err(e) = timeout(
+                Duration::from_secs(10),
+                handle_request(socket, &tracker, &metrics, &auth, &config),
+            )
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|v| v)
+            {
+                log::debug!("Health request from {addr} failed: {e:?}");
+            }
+        });
+    }
+}
+
+async fn handle_request(
+    mut socket: TcpStream,
+    tracker: &HealthTracker,
+    metrics: &RtspMetrics,
+    auth: &HttpAuth,
+    config: &Config,
+) -> Result<()> {
+    let (read, mut write) = socket.split();
+    let mut reader = BufReader::new(read);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    // Only the authorization header is needed but all must be read
+    let mut authorization = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default();
+
+    let graph_of = path
+        .strip_prefix("/cameras/")
+        .and_then(|path| path.strip_suffix("/pipeline-graph"));
+
+    let authorized = path == "/healthz"
+        || config.users.is_empty()
+        || auth
+            .authenticate(&config.users, method, target, authorization.as_deref())
+            .is_some();
+
+    let (status, content_type, body) = match (method, path, graph_of) {
+        _ if !authorized => ("401 Unauthorized", "application/json", vec![]),
+        ("GET" | "HEAD", "/healthz", _) => {
+            let status = tracker.status(config, rtsp_accepting(config).await);
+            let body = serde_json::to_vec(&status)?;
+            if status.healthy {
+                ("200 OK", "application/json", body)
+            } else {
+                ("503 Service Unavailable", "application/json", body)
+            }
+        }
+        ("GET" | "HEAD", "/metrics", _) => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            (tracker.metrics() + &metrics.render()).into_bytes(),
+        ),
+        ("GET" | "HEAD", _, Some(name)) => match tracker.pipeline_graph(name) {
+            Some(graph) => ("200 OK", "text/vnd.graphviz", graph.into_bytes()),
+            None => ("404 Not Found", "application/json", vec![]),
+        },
+        ("GET" | "HEAD", _, _) => ("404 Not Found", "application/json", vec![]),
+        _ => ("405 Method Not Allowed", "application/json", vec![]),
+    };
+
+    let challenge = if authorized {
+        String::new()
+    } else {
+        auth.challenge()
+    };
+    let header = format!(
+        "HTTP/1.1 {status}\r\n\
+        Cache-Control: no-cache\r\n\
+        Connection: close\r\n\
+        {challenge}\
+        Content-Type: {content_type}\r\n\
+        Content-Length: {}\r\n\r\n",
+        body.len()
+    );
+    write.write_all(header.as_bytes()).await?;
+    if method != "HEAD" {
+        write.write_all(&body).await?;
+    }
+    write.flush().await?;
+    Ok(())
+}
+
+/// Whether a tcp connection to the rtsp server is accepted
+async fn rtsp_accepting(config: &Config) -> bool {
+    // Any address binds are checked over the loopback
+    let host = match config.bind_addr.as_str() {
+        "0.0.0.0" => "127.0.0.1",
+        "::" | "[::]" => "::1",
+        addr => addr,
+    };
+    matches!(
+        timeout(
+            RTSP_CHECK_TIMEOUT,
+            TcpStream::connect((host, config.bind_port))
+        )
+        .await,
+        Ok(Ok(_))
+    )
+}
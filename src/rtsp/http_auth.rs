@@ -0,0 +1,379 @@
+//! Basic and Digest authentication for the http endpoints of the health port
+//!
+//! The users are the same `[[users]]` that the rtsp server uses. When there
+//! are no users the endpoints are open, the same as the rtsp streams.
+//!
+//! Each digest nonce is the time it was issued and a hash of that time with
+//! a random secret made at start up, so any nonce this process issued in the
+//! last [`NONCE_LIFETIME_SECS`] is genuine. Only `qop=auth` digests are
+//! accepted and the `nc` of each must be higher than the last one seen with
+//! its nonce, so a captured request cannot be replayed
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::config::UserConfig;
+
+/// The realm of the `WWW-Authenticate` challenges
+const REALM: &str = "neolink";
+/// How long a digest nonce can be used for
+const NONCE_LIFETIME_SECS: u64 = 300;
+
+pub(super) struct HttpAuth {
+    secret: String,
+    /// The highest `nc` used with each nonce that is still in its lifetime
+    nonce_counts: Mutex<HashMap<String, u64>>,
+}
+
+impl HttpAuth {
+    pub(super) fn new() -> Self {
+        // v4 uuids come from the OS random number generator
+        Self {
+            secret: format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+            nonce_counts: Default::default(),
+        }
+    }
+
+    /// The user that sent the `Authorization` header or None if it is not valid
+    ///
+    /// `method` and `uri` are from the request line and must match what a
+    /// digest was made for
+    pub(super) fn authenticate<'a>(
+        &self,
+        users: &'a [UserConfig],
+        method: &str,
+        uri: &str,
+        authorization: Option<&str>,
+    ) -> Option<&'a UserConfig> {
+        let (scheme, credentials) = authorization?.trim().split_once(' ')?;
+        if scheme.eq_ignore_ascii_case("Basic") {
+            let decoded = BASE64.decode(credentials.trim()).ok()?;
+            let (name, pass) = std::str::from_utf8(&decoded).ok()?.split_once(':')?;
+            users
+                .iter()
+                .find(|user| user.name == name && constant_time_eq(&user.pass, pass))
+        } else if scheme.eq_ignore_ascii_case("Digest") {
+            self.authenticate_digest(users, method, uri, credentials, now_secs())
+        } else {
+            None
+        }
+    }
+
+    fn authenticate_digest<'a>(
+        &self,
+        users: &'a [UserConfig],
+        method: &str,
+        uri: &str,
+        credentials: &str,
+        now: u64,
+    ) -> Option<&'a UserConfig> {
+        let params = parse_params(credentials);
+        let param = |key: &str| {
+            params
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                .map(|(_, value)| value.as_str())
+        };
+        let nonce = param("nonce")?;
+        if param("realm")? != REALM || param("uri")? != uri || !self.nonce_valid(nonce, now) {
+            return None;
+        }
+        // Without the qop there is no nc to stop a replay
+        let (qop, nc, cnonce) = (param("qop")?, param("nc")?, param("cnonce")?);
+        let count = u64::from_str_radix(nc, 16).ok()?;
+        let user = users
+            .iter()
+            .find(|user| Some(user.name.as_str()) == param("username"))?;
+        let expected = digest_response(
+            &user.name,
+            REALM,
+            &user.pass,
+            method,
+            uri,
+            nonce,
+            Some((qop, nc, cnonce)),
+        );
+        if !constant_time_eq(&expected, param("response")?) {
+            return None;
+        }
+        self.use_nonce_count(nonce, count, now).then_some(user)
+    }
+
+    /// Record the `nc` of a valid digest, false if it is not higher than
+    /// the last one used with the nonce
+    fn use_nonce_count(&self, nonce: &str, count: u64, now: u64) -> bool {
+        let mut counts = self.nonce_counts.lock().unwrap();
+        counts.retain(|nonce, _| self.nonce_valid(nonce, now));
+        match counts.get_mut(nonce) {
+            Some(last) if *last >= count => false,
+            Some(last) => {
+                *last = count;
+                true
+            }
+            None => {
+                counts.insert(nonce.to_string(), count);
+                true
+            }
+        }
+    }
+
+    /// The `WWW-Authenticate` headers of a `401 Unauthorized` reply
+    pub(super) fn challenge(&self) -> String {
+        format!(
+            "WWW-Authenticate: Basic realm=\"{REALM}\"\r\n\
+            WWW-Authenticate: Digest realm=\"{REALM}\", qop=\"auth\", algorithm=MD5, nonce=\"{}\"\r\n",
+            self.nonce(now_secs())
+        )
+    }
+
+    fn nonce(&self, issued: u64) -> String {
+        format!(
+            "{issued:x}-{:x}",
+            md5::compute(format!("{issued}:{}", self.secret))
+        )
+    }
+
+    fn nonce_valid(&self, nonce: &str, now: u64) -> bool {
+        let issued = match nonce
+            .split_once('-')
+            .and_then(|(issued, _)| u64::from_str_radix(issued, 16).ok())
+        {
+            Some(issued) => issued,
+            None => return false,
+        };
+        issued <= now
+            && now - issued <= NONCE_LIFETIME_SECS
+            && constant_time_eq(nonce, &self.nonce(issued))
+    }
+}
+
+/// The digest `response` of RFC 2617 with the MD5 algorithm
+///
+/// `qop` is the qop, nc and cnonce when the client sent them
+fn digest_response(
+    name: &str,
+    realm: &str,
+    pass: &str,
+    method: &str,
+    uri: &str,
+    nonce: &str,
+    qop: Option<(&str, &str, &str)>,
+) -> String {
+    let ha1 = md5::compute(format!("{name}:{realm}:{pass}"));
+    let ha2 = md5::compute(format!("{method}:{uri}"));
+    let response = match qop {
+        Some((qop, nc, cnonce)) => format!("{ha1:x}:{nonce}:{nc}:{cnonce}:{qop}:{ha2:x}"),
+        None => format!("{ha1:x}:{nonce}:{ha2:x}"),
+    };
+    format!("{:x}", md5::compute(response))
+}
+
+/// Splits `key=value, key="quoted, value"` into its pairs
+fn parse_params(params: &str) -> Vec<(String, String)> {
+    let mut pairs = vec![];
+    let mut rest = params.trim();
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let after = after.trim_start();
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            },
+            None => match after.find(',') {
+                Some(end) => (after[..end].trim(), &after[end..]),
+                None => (after.trim(), ""),
+            },
+        };
+        pairs.push((key, value.to_string()));
+        rest = remaining.trim_start().trim_start_matches(',');
+    }
+    pairs
+}
+
+/// Compares without returning early so the time taken does not give away
+/// how much of a password was right
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users() -> Vec<UserConfig> {
+        vec![UserConfig {
+            name: "Mufasa".to_string(),
+            pass: "Circle Of Life".to_string(),
+            admin: false,
+        }]
+    }
+
+    #[test]
+    fn digest_response_matches_rfc_2617() {
+        // The example of RFC 2617 section 3.5
+        let response = digest_response(
+            "Mufasa",
+            "testrealm@host.com",
+            "Circle Of Life",
+            "GET",
+            "/dir/index.html",
+            "dcd98b7102dd2f0e8b11d0f600bfb0c093",
+            Some(("auth", "00000001", "0a4f113b")),
+        );
+        assert_eq!(response, "6629fae49393a05397450978507c4ef1");
+    }
+
+    #[test]
+    fn basic() {
+        let auth = HttpAuth::new();
+        let users = users();
+        let header = format!("Basic {}", BASE64.encode("Mufasa:Circle Of Life"));
+        assert!(auth
+            .authenticate(&users, "GET", "/metrics", Some(&header))
+            .is_some());
+        let header = format!("Basic {}", BASE64.encode("Mufasa:circle of life"));
+        assert!(auth
+            .authenticate(&users, "GET", "/metrics", Some(&header))
+            .is_none());
+        assert!(auth
+            .authenticate(&users, "GET", "/metrics", Some("Basic !!!"))
+            .is_none());
+        assert!(auth.authenticate(&users, "GET", "/metrics", None).is_none());
+    }
+
+    #[test]
+    fn digest() {
+        let auth = HttpAuth::new();
+        let users = users();
+        let now = now_secs();
+        let nonce = auth.nonce(now);
+        let response = digest_response(
+            "Mufasa",
+            REALM,
+            "Circle Of Life",
+            "GET",
+            "/metrics",
+            &nonce,
+            Some(("auth", "00000001", "0a4f113b")),
+        );
+        let header = format!(
+            "username=\"Mufasa\", realm=\"{REALM}\", nonce=\"{nonce}\", uri=\"/metrics\", \
+            qop=auth, nc=00000001, cnonce=\"0a4f113b\", response=\"{response}\""
+        );
+        assert!(auth
+            .authenticate_digest(&users, "GET", "/metrics", &header, now)
+            .is_some());
+        // The digest is only for the uri and method it was made for
+        assert!(auth
+            .authenticate_digest(&users, "GET", "/cameras/a/pipeline-graph", &header, now)
+            .is_none());
+        assert!(auth
+            .authenticate_digest(&users, "HEAD", "/metrics", &header, now)
+            .is_none());
+        // Nonces run out
+        assert!(auth
+            .authenticate_digest(
+                &users,
+                "GET",
+                "/metrics",
+                &header,
+                now + NONCE_LIFETIME_SECS + 1
+            )
+            .is_none());
+    }
+
+    /// The `Authorization` digest of the test user for `GET /metrics`
+    fn digest_header(nonce: &str, nc: Option<&str>) -> String {
+        let qop = nc.map(|nc| ("auth", nc, "0a4f113b"));
+        let response = digest_response(
+            "Mufasa",
+            REALM,
+            "Circle Of Life",
+            "GET",
+            "/metrics",
+            nonce,
+            qop,
+        );
+        let qop = qop
+            .map(|(qop, nc, cnonce)| format!("qop={qop}, nc={nc}, cnonce=\"{cnonce}\", "))
+            .unwrap_or_default();
+        format!(
+            "username=\"Mufasa\", realm=\"{REALM}\", nonce=\"{nonce}\", uri=\"/metrics\", \
+            {qop}response=\"{response}\""
+        )
+    }
+
+    #[test]
+    fn digest_replay() {
+        let auth = HttpAuth::new();
+        let users = users();
+        let now = now_secs();
+        let nonce = auth.nonce(now);
+        let first = digest_header(&nonce, Some("00000001"));
+        assert!(auth
+            .authenticate_digest(&users, "GET", "/metrics", &first, now)
+            .is_some());
+        // The same request again is a replay
+        assert!(auth
+            .authenticate_digest(&users, "GET", "/metrics", &first, now)
+            .is_none());
+        let second = digest_header(&nonce, Some("00000002"));
+        assert!(auth
+            .authenticate_digest(&users, "GET", "/metrics", &second, now + 1)
+            .is_some());
+        assert!(auth
+            .authenticate_digest(&users, "GET", "/metrics", &first, now + 1)
+            .is_none());
+        // Without qop there is no nc to check
+        let legacy = digest_header(&auth.nonce(now), None);
+        assert!(auth
+            .authenticate_digest(&users, "GET", "/metrics", &legacy, now)
+            .is_none());
+    }
+
+    #[test]
+    fn nonce_counts_are_forgotten_when_the_nonce_runs_out() {
+        let auth = HttpAuth::new();
+        assert!(auth.use_nonce_count(&auth.nonce(1000), 1, 1000));
+        assert!(auth.use_nonce_count(&auth.nonce(2000), 1, 2000));
+        assert_eq!(auth.nonce_counts.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn nonces_are_signed() {
+        let auth = HttpAuth::new();
+        let nonce = auth.nonce(1000);
+        assert!(auth.nonce_valid(&nonce, 1000));
+        assert!(!auth.nonce_valid(&nonce, 999));
+        assert!(!auth.nonce_valid(&HttpAuth::new().nonce(1000), 1000));
+        assert!(!auth.nonce_valid("3e8-00000000000000000000000000000000", 1000));
+        assert!(!auth.nonce_valid("nonsense", 1000));
+    }
+
+    #[test]
+    fn params() {
+        assert_eq!(
+            parse_params(r#"username="a, b", qop=auth,nc=00000001 , uri="/x""#),
+            vec![
+                ("username".to_string(), "a, b".to_string()),
+                ("qop".to_string(), "auth".to_string()),
+                ("nc".to_string(), "00000001".to_string()),
+                ("uri".to_string(), "/x".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! Prometheus metrics of the rtsp clients
+//!
+//! These are recorded from the signals of the rtsp server's clients and are
+//! served with the pipeline counters on the `/metrics` endpoint of the
+//! health port
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use super::health::escape_label;
+
+/// The upper bounds in seconds of the buckets of the session histograms
+const SESSION_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 5.0, 15.0, 60.0, 300.0, 1800.0, 3600.0];
+
+/// A Prometheus histogram
+///
+/// The `+Inf` bucket is not stored as it is always the count
+#[derive(Debug)]
+struct Histogram {
+    bounds: &'static [f64],
+    /// The observations that fell in each bucket, not cumulative
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: Duration) {
+        let secs = value.as_secs_f64();
+        if let Some(bucket) = self.bounds.iter().position(|bound| secs <= *bound) {
+            self.counts[bucket] += 1;
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+
+    /// Add the histogram to `body` in the Prometheus text format
+    fn render(&self, metric: &str, help: &str, body: &mut String) {
+        body.push_str(&format!(
+            "# HELP {metric} {help}\n# TYPE {metric} histogram\n"
+        ));
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter()) {
+            cumulative += count;
+            body.push_str(&format!("{metric}_bucket{{le=\"{bound}\"}} {cumulative}\n"));
+        }
+        body.push_str(&format!(
+            "{metric}_bucket{{le=\"+Inf\"}} {}\n{metric}_sum {}\n{metric}_count {}\n",
+            self.count, self.sum, self.count
+        ));
+    }
+}
+
+/// The kind of a frame that went into a payloader
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum FrameType {
+    Keyframe,
+    Interframe,
+    Audio,
+}
+
+/// What has been sent to one client on one path
+///
+/// The pad probes of the client's media count into this
+#[derive(Debug)]
+pub(crate) struct ClientStats {
+    camera: String,
+    path: String,
+    client_ip: Option<String>,
+    bytes: AtomicU64,
+    keyframes: AtomicU64,
+    interframes: AtomicU64,
+    audio_frames: AtomicU64,
+}
+
+impl ClientStats {
+    /// A payloader has sent `bytes` of rtp to the client
+    pub(crate) fn bytes_sent(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// A frame has gone into a payloader of the client's media
+    pub(crate) fn frame_sent(&self, frame_type: FrameType) {
+        match frame_type {
+            FrameType::Keyframe => &self.keyframes,
+            FrameType::Interframe => &self.interframes,
+            FrameType::Audio => &self.audio_frames,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The metrics of the clients of the rtsp server
+#[derive(Debug)]
+pub(crate) struct RtspMetrics {
+    /// From the client connecting to it closing
+    session_duration: Mutex<Histogram>,
+    /// From a PLAY request to the first packet of the video payloader
+    first_frame_latency: Mutex<Histogram>,
+    next_client_id: AtomicU64,
+    /// The stats by the client id and the path it plays
+    clients: Mutex<HashMap<(u64, String), Arc<ClientStats>>>,
+    /// If the client stats are labelled with the client's ip address
+    detailed: AtomicBool,
+    /// The video frames that arrived before the format was known by camera
+    discarded_video: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Default for RtspMetrics {
+    fn default() -> Self {
+        Self {
+            session_duration: Mutex::new(Histogram::new(SESSION_BUCKETS)),
+            first_frame_latency: Mutex::new(Histogram::new(SESSION_BUCKETS)),
+            next_client_id: AtomicU64::new(0),
+            clients: Default::default(),
+            detailed: AtomicBool::new(false),
+            discarded_video: Default::default(),
+        }
+    }
+}
+
+impl RtspMetrics {
+    /// A client has closed after being connected for `duration`
+    pub(crate) fn session_ended(&self, duration: Duration) {
+        self.session_duration.lock().unwrap().observe(duration);
+    }
+
+    /// The first video packet was sent `latency` after the PLAY request
+    pub(crate) fn first_frame_sent(&self, latency: Duration) {
+        self.first_frame_latency.lock().unwrap().observe(latency);
+    }
+
+    /// Video frames of the camera have been dropped as its format is not known yet
+    pub(crate) fn video_discarded(&self, camera: &str, frames: u64) {
+        *self
+            .discarded_video
+            .lock()
+            .unwrap()
+            .entry(camera.to_string())
+            .or_default() += frames;
+    }
+
+    /// Label the client stats with the client ip address
+    pub(crate) fn set_detailed(&self, detailed: bool) {
+        self.detailed.store(detailed, Ordering::Relaxed);
+    }
+
+    /// An id for a newly connected client
+    pub(crate) fn client_id(&self) -> u64 {
+        self.next_client_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Start the stats of the client playing `path`
+    ///
+    /// None when they have already been started so that a PLAY after a
+    /// PAUSE does not count the media twice
+    pub(crate) fn add_client_stream(
+        &self,
+        client: u64,
+        camera: &str,
+        path: &str,
+        client_ip: Option<&str>,
+    ) -> Option<Arc<ClientStats>> {
+        let mut clients = self.clients.lock().unwrap();
+        let key = (client, path.to_string());
+        if clients.contains_key(&key) {
+            return None;
+        }
+        let stats = Arc::new(ClientStats {
+            camera: camera.to_string(),
+            path: path.to_string(),
+            client_ip: client_ip.map(str::to_string),
+            bytes: AtomicU64::new(0),
+            keyframes: AtomicU64::new(0),
+            interframes: AtomicU64::new(0),
+            audio_frames: AtomicU64::new(0),
+        });
+        clients.insert(key, stats.clone());
+        Some(stats)
+    }
+
+    /// Forget the stats of the client playing `path`
+    pub(crate) fn remove_client_stream(&self, client: u64, path: &str) {
+        self.clients
+            .lock()
+            .unwrap()
+            .remove(&(client, path.to_string()));
+    }
+
+    /// Forget the stats of a client that has closed
+    pub(crate) fn remove_client(&self, client: u64) {
+        self.clients
+            .lock()
+            .unwrap()
+            .retain(|(id, _), _| *id != client);
+    }
+
+    /// The client counters by their labels
+    ///
+    /// Clients with the same labels are summed as a series can only be
+    /// given once
+    fn client_counters(&self) -> (BTreeMap<String, u64>, BTreeMap<String, u64>) {
+        let detailed = self.detailed.load(Ordering::Relaxed);
+        let mut bytes = BTreeMap::new();
+        let mut frames = BTreeMap::new();
+        for stats in self.clients.lock().unwrap().values() {
+            let mut labels = format!(
+                "camera=\"{}\",path=\"{}\"",
+                escape_label(&stats.camera),
+                escape_label(&stats.path)
+            );
+            if detailed {
+                labels.push_str(&format!(
+                    ",client_ip=\"{}\"",
+                    escape_label(stats.client_ip.as_deref().unwrap_or("unknown"))
+                ));
+            }
+            *bytes.entry(labels.clone()).or_default() += stats.bytes.load(Ordering::Relaxed);
+            for (frame_type, count) in [
+                ("keyframe", &stats.keyframes),
+                ("interframe", &stats.interframes),
+                ("audio", &stats.audio_frames),
+            ] {
+                *frames
+                    .entry(format!("{labels},frame_type=\"{frame_type}\""))
+                    .or_default() += count.load(Ordering::Relaxed);
+            }
+        }
+        (bytes, frames)
+    }
+
+    /// The metrics in the Prometheus text format
+    pub(crate) fn render(&self) -> String {
+        let mut body = String::new();
+        self.session_duration.lock().unwrap().render(
+            "neolink_rtsp_session_duration_seconds",
+            "How long the rtsp clients were connected for",
+            &mut body,
+        );
+        self.first_frame_latency.lock().unwrap().render(
+            "neolink_rtsp_first_frame_latency_seconds",
+            "The time from an rtsp PLAY request to the first video packet sent",
+            &mut body,
+        );
+        let discarded = self
+            .discarded_video
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(camera, count)| (format!("camera=\"{}\"", escape_label(camera)), *count))
+            .collect();
+        let (bytes, frames) = self.client_counters();
+        for (metric, help, counts) in [
+            (
+                "neolink_rtsp_client_bytes_sent_total",
+                "The rtp bytes sent to each connected rtsp client",
+                bytes,
+            ),
+            (
+                "neolink_rtsp_client_frames_sent_total",
+                "The frames sent to each connected rtsp client",
+                frames,
+            ),
+            (
+                "neolink_video_discarded_frames_total",
+                "Video frames dropped as the format of the stream was not yet detected",
+                discarded,
+            ),
+        ] {
+            body.push_str(&format!(
+                "# HELP {metric} {help}\n# TYPE {metric} counter\n"
+            ));
+            for (labels, count) in counts {
+                body.push_str(&format!("{metric}{{{labels}}} {count}\n"));
+            }
+        }
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::new(&[1.0, 5.0]);
+        histogram.observe(Duration::from_millis(500));
+        histogram.observe(Duration::from_secs(1));
+        histogram.observe(Duration::from_secs(3));
+        histogram.observe(Duration::from_secs(10));
+        let mut body = String::new();
+        histogram.render("test_seconds", "A test", &mut body);
+        assert_eq!(
+            body,
+            "# HELP test_seconds A test\n\
+            # TYPE test_seconds histogram\n\
+            test_seconds_bucket{le=\"1\"} 2\n\
+            test_seconds_bucket{le=\"5\"} 3\n\
+            test_seconds_bucket{le=\"+Inf\"} 4\n\
+            test_seconds_sum 14.5\n\
+            test_seconds_count 4\n"
+        );
+    }
+
+    #[test]
+    fn empty_metrics_have_every_bucket() {
+        let body = RtspMetrics::default().render();
+        for metric in [
+            "neolink_rtsp_session_duration_seconds",
+            "neolink_rtsp_first_frame_latency_seconds",
+        ] {
+            assert!(body.contains(&format!("# TYPE {metric} histogram\n")));
+            assert!(body.contains(&format!("{metric}_bucket{{le=\"0.1\"}} 0\n")));
+            assert!(body.contains(&format!("{metric}_bucket{{le=\"3600\"}} 0\n")));
+            assert!(body.contains(&format!("{metric}_bucket{{le=\"+Inf\"}} 0\n")));
+            assert!(body.contains(&format!("{metric}_count 0\n")));
+        }
+    }
+
+    #[test]
+    fn observations_are_recorded() {
+        let metrics = RtspMetrics::default();
+        metrics.session_ended(Duration::from_secs(120));
+        metrics.first_frame_sent(Duration::from_millis(300));
+        let body = metrics.render();
+        assert!(body.contains("neolink_rtsp_session_duration_seconds_bucket{le=\"60\"} 0\n"));
+        assert!(body.contains("neolink_rtsp_session_duration_seconds_bucket{le=\"300\"} 1\n"));
+        assert!(body.contains("neolink_rtsp_first_frame_latency_seconds_bucket{le=\"0.1\"} 0\n"));
+        assert!(body.contains("neolink_rtsp_first_frame_latency_seconds_bucket{le=\"0.5\"} 1\n"));
+    }
+
+    #[test]
+    fn client_stats() {
+        let metrics = RtspMetrics::default();
+        let first = metrics.client_id();
+        let second = metrics.client_id();
+        let stats = metrics
+            .add_client_stream(first, "Cam", "/Cam/main", Some("10.0.0.2"))
+            .unwrap();
+        // A second PLAY of the same path is not counted again
+        assert!(metrics
+            .add_client_stream(first, "Cam", "/Cam/main", Some("10.0.0.2"))
+            .is_none());
+        stats.bytes_sent(1000);
+        stats.frame_sent(FrameType::Keyframe);
+        stats.frame_sent(FrameType::Interframe);
+        stats.frame_sent(FrameType::Interframe);
+        let other = metrics
+            .add_client_stream(second, "Cam", "/Cam/main", Some("10.0.0.3"))
+            .unwrap();
+        other.bytes_sent(500);
+
+        // Without the ip the two clients are one series
+        let body = metrics.render();
+        assert!(body.contains(
+            "neolink_rtsp_client_bytes_sent_total{camera=\"Cam\",path=\"/Cam/main\"} 1500\n"
+        ));
+        assert!(body.contains(
+            "neolink_rtsp_client_frames_sent_total{camera=\"Cam\",path=\"/Cam/main\",frame_type=\"interframe\"} 2\n"
+        ));
+        assert!(!body.contains("client_ip"));
+
+        metrics.set_detailed(true);
+        let body = metrics.render();
+        assert!(body.contains(
+            "neolink_rtsp_client_bytes_sent_total{camera=\"Cam\",path=\"/Cam/main\",client_ip=\"10.0.0.2\"} 1000\n"
+        ));
+        assert!(body.contains(
+            "neolink_rtsp_client_bytes_sent_total{camera=\"Cam\",path=\"/Cam/main\",client_ip=\"10.0.0.3\"} 500\n"
+        ));
+        assert!(body.contains(
+            "neolink_rtsp_client_frames_sent_total{camera=\"Cam\",path=\"/Cam/main\",client_ip=\"10.0.0.2\",frame_type=\"keyframe\"} 1\n"
+        ));
+
+        // The stats go with the client
+        metrics.remove_client(first);
+        let body = metrics.render();
+        assert!(!body.contains("10.0.0.2"));
+        assert!(body.contains("10.0.0.3"));
+        metrics.remove_client_stream(second, "/Cam/main");
+        assert!(!metrics.render().contains("10.0.0.3"));
+    }
+
+    #[test]
+    fn discarded_video() {
+        let metrics = RtspMetrics::default();
+        assert!(metrics
+            .render()
+            .contains("# TYPE neolink_video_discarded_frames_total counter\n"));
+        metrics.video_discarded("Cam", 1);
+        metrics.video_discarded("Cam", 4);
+        metrics.video_discarded("Other", 2);
+        let body = metrics.render();
+        assert!(body.contains("neolink_video_discarded_frames_total{camera=\"Cam\"} 5\n"));
+        assert!(body.contains("neolink_video_discarded_frames_total{camera=\"Other\"} 2\n"));
+    }
+}
@@ -53,7 +53,6 @@
 //   - `"none"`: Resends the last iframe the camera. This does not reencode at all.  **Most use cases should use this one as it has the least effort on the cpu and gives what you would expect**
 //
 use anyhow::{anyhow, Context, Result};
-use gstreamer_rtsp_server::prelude::*;
 use log::*;
 use neolink_core::bc_protocol::StreamKind;
 use std::collections::{HashMap, HashSet};
@@ -68,25 +67,71 @@ use tokio_stream::StreamExt;
 use tokio_util::sync::CancellationToken;
 
 mod cmdline;
+mod dash;
 mod factory;
 mod gst;
+mod health;
+mod http_auth;
+mod metrics;
+mod parameters;
+mod simulate;
 mod stream;
 
-use crate::common::{NeoInstance, NeoReactor};
+use crate::{
+    common::{CameraConnectionState, NeoInstance, NeoReactor},
+    utils::check_fd_limits,
+};
 use factory::*;
+use health::{health_main, HealthTracker};
+use parameters::parameters_main;
 use stream::*;
 
-use super::config::UserConfig;
+use super::config::{Config, UserConfig};
 pub(crate) use cmdline::Opt;
 use gst::NeoRtspServer;
+pub(crate) use simulate::{simulate_main, SimulatedStream};
 
 type AnyResult<T> = anyhow::Result<T, anyhow::Error>;
 
+/// Number of rtsp clients per camera that the open file limit is checked against
+const EXPECTED_CLIENTS_PER_CAMERA: u32 = 5;
+
 /// Entry point for the rtsp subcommand
 ///
 /// Opt is the command line options
 pub(crate) async fn main(_opt: Opt, reactor: NeoReactor) -> Result<()> {
     let rtsp = Arc::new(NeoRtspServer::new()?);
+    let health = Arc::new(HealthTracker::default());
+
+    let missing = probe_gstreamer_elements(&reactor.config().await?.borrow());
+    if !missing.is_empty() {
+        log::error!("The following gstreamer elements are required but could not be found:");
+        log::error!("  {:<20} Plugin (package)", "Element");
+        for element in missing.iter() {
+            log::error!("  {:<20} {}", element.name, element.package_hint);
+        }
+        log::error!("Install the packages and try again");
+        return Err(anyhow!(
+            "Missing required gstreamer elements: {}",
+            missing
+                .iter()
+                .map(|element| element.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    let camera_count = reactor
+        .config()
+        .await?
+        .borrow()
+        .cameras
+        .iter()
+        .filter(|camera| camera.enabled)
+        .count();
+    if let Some(warning) = check_fd_limits(camera_count, EXPECTED_CLIENTS_PER_CAMERA) {
+        log::warn!("{}", warning);
+    }
 
     let global_cancel = CancellationToken::new();
 
@@ -97,6 +142,16 @@ pub(crate) async fn main(_opt: Opt, reactor: NeoReactor) -> Result<()> {
     let thread_cancel = global_cancel.clone();
     let thread_rtsp = rtsp.clone();
     thread_rtsp.set_up_tls(&thread_config.borrow_and_update().clone())?;
+    thread_rtsp.set_latency(thread_config.borrow().rtpjitterbuffer_latency_ms);
+    thread_rtsp.set_thread_pool(thread_config.borrow().rtsp_threads);
+    thread_rtsp.set_cors_origins(&thread_config.borrow().rtsp_cors_origins);
+    thread_rtsp.set_detailed_metrics(thread_config.borrow().detailed_metrics);
+    let mut shared_clock = thread_config.borrow().shared_clock.clone();
+    thread_rtsp.set_up_clock(&shared_clock).await?;
+    let case_insensitive_paths = thread_config.borrow().rtsp_case_insensitive_paths;
+    thread_rtsp
+        .set_case_insensitive_paths(case_insensitive_paths)
+        .await?;
     set.spawn(async move {
         tokio::select! {
             _ = thread_cancel.cancelled() => AnyResult::Ok(()),
@@ -106,6 +161,20 @@ pub(crate) async fn main(_opt: Opt, reactor: NeoReactor) -> Result<()> {
                     if let Err(e) = thread_rtsp.set_up_tls(&thread_config.borrow().clone()) {
                         log::error!("Could not setup TLS: {e}");
                     }
+                    thread_rtsp.set_latency(thread_config.borrow().rtpjitterbuffer_latency_ms);
+                    thread_rtsp.set_thread_pool(thread_config.borrow().rtsp_threads);
+                    thread_rtsp.set_cors_origins(&thread_config.borrow().rtsp_cors_origins);
+                    thread_rtsp.set_detailed_metrics(thread_config.borrow().detailed_metrics);
+                    let new_shared_clock = thread_config.borrow().shared_clock.clone();
+                    if new_shared_clock != shared_clock {
+                        // A new ntp clock has to sync again so only make one on a change
+                        shared_clock = new_shared_clock;
+                        if let Err(e) = thread_rtsp.set_up_clock(&shared_clock).await {
+                            log::error!("Could not setup the shared clock: {e}");
+                        }
+                    }
+                    let case_insensitive_paths = thread_config.borrow().rtsp_case_insensitive_paths;
+                    thread_rtsp.set_case_insensitive_paths(case_insensitive_paths).await?;
                 }
             } => v
         }
@@ -146,6 +215,7 @@ pub(crate) async fn main(_opt: Opt, reactor: NeoReactor) -> Result<()> {
     let thread_cancel = global_cancel.clone();
     let thread_rtsp = rtsp.clone();
     let thread_reactor = reactor.clone();
+    let thread_health = health.clone();
     set.spawn(async move {
         let mut set = JoinSet::<AnyResult<()>>::new();
         let thread_cancel2 = thread_cancel.clone();
@@ -168,7 +238,27 @@ pub(crate) async fn main(_opt: Opt, reactor: NeoReactor) -> Result<()> {
                             let thread_global_cancel = thread_cancel2.clone();
                             let thread_rtsp2 = thread_rtsp.clone();
                             let thread_reactor2 = thread_reactor.clone();
+                            let thread_health2 = thread_health.clone();
                             let name = name.clone();
+                            let external = thread_config.borrow().cameras.iter().any(|cam_config| cam_config.name == name && cam_config.is_external_rtsp());
+                            if external {
+                                // Relaying another rtsp server, no BC connection is made
+                                set.spawn(async move {
+                                    let res = tokio::select!(
+                                        _ = thread_global_cancel.cancelled() => {
+                                            AnyResult::Ok(())
+                                        },
+                                        _ = local_cancel.cancelled() => {
+                                            AnyResult::Ok(())
+                                        },
+                                        v = external_main(name.clone(), &thread_reactor2, &thread_rtsp2) => v,
+                                    );
+                                    // Don't leave the relay mounted once it has stopped
+                                    unmount_camera(&thread_rtsp2, &name).await?;
+                                    res
+                                });
+                                continue;
+                            }
                             set.spawn(async move {
                                 let camera = thread_reactor2.get(&name).await?;
                                 tokio::select!(
@@ -178,17 +268,28 @@ pub(crate) async fn main(_opt: Opt, reactor: NeoReactor) -> Result<()> {
                                     _ = local_cancel.cancelled() => {
                                         AnyResult::Ok(())
                                     },
-                                    v = camera_main(camera, &thread_rtsp2) => v,
+                                    v = camera_main(camera, &thread_reactor2, &thread_rtsp2, &thread_health2) => v,
                                 )
                             }) ;
                         }
                     }
 
-                    for (running_name, token) in cameras.iter() {
-                        if ! config_names.contains(running_name) {
-                            token.cancel();
+                    cameras.retain(|running_name, token| {
+                        if config_names.contains(running_name) {
+                            return true;
                         }
-                    }
+                        token.cancel();
+                        thread_health.remove(running_name);
+                        // Unmount the camera so it is not left serving a dead stream
+                        let thread_rtsp2 = thread_rtsp.clone();
+                        let name = running_name.clone();
+                        set.spawn(async move {
+                            unmount_camera(&thread_rtsp2, &name).await?;
+                            log::info!("{name}: Rtsp Stopped");
+                            AnyResult::Ok(())
+                        });
+                        false
+                    });
                 }
             } => v,
         }
@@ -202,10 +303,27 @@ pub(crate) async fn main(_opt: Opt, reactor: NeoReactor) -> Result<()> {
 
     let bind_addr = rtsp_config.bind_addr.clone();
     let bind_port = rtsp_config.bind_port;
-    rtsp.run(&bind_addr, bind_port).await?;
+    rtsp.run(&bind_addr, bind_port, rtsp_config.rtsp_max_backlog)
+        .await?;
     let thread_rtsp = rtsp.clone();
     set.spawn(async move { thread_rtsp.join().await });
 
+    if let Some(health_port) = rtsp_config.health_bind_port {
+        let thread_health = health.clone();
+        let thread_metrics = rtsp.metrics();
+        let thread_reactor = reactor.clone();
+        set.spawn(async move {
+            health_main(
+                thread_health,
+                thread_metrics,
+                thread_reactor,
+                &bind_addr,
+                health_port,
+            )
+            .await
+        });
+    }
+
     while let Some(joined) = set
         .join_next()
         .await
@@ -233,7 +351,7 @@ async fn apply_users(rtsp: &NeoRtspServer, curr_users: &HashSet<UserConfig>) ->
     // Add those missing
     for user in curr_users.iter() {
         log::debug!("Adding user {} to rtsp server", user.name);
-        rtsp.add_user(&user.name, &user.pass).await?;
+        rtsp.add_user(&user.name, &user.pass, user.admin).await?;
     }
     // Remove unused
     let rtsp_users = rtsp.get_users().await?;
@@ -246,10 +364,112 @@ async fn apply_users(rtsp: &NeoRtspServer, curr_users: &HashSet<UserConfig>) ->
     Ok(())
 }
 
+/// The rtsp users that may view a camera given its `permitted_users`
+async fn get_permitted_users(
+    rtsp: &NeoRtspServer,
+    permitted_users: &Option<Vec<String>>,
+) -> AnyResult<HashSet<String>> {
+    let all_users = rtsp
+        .get_users()
+        .await?
+        .iter()
+        .filter(|a| *a != "anyone" && *a != "anonymous")
+        .cloned()
+        .collect::<HashSet<_>>();
+    Ok(match permitted_users {
+        // If in the camera config there is the user "anyone", or if none is specified but users
+        // are defined at all, then we add all users to the camera's allowed list.
+        Some(p) if p.iter().any(|u| u == "anyone") => all_users,
+        None if !all_users.is_empty() => all_users,
+
+        // The user specified permitted_users
+        Some(p) => p.iter().cloned().collect(),
+
+        // The user didn't specify permitted_users, and there are none defined anyway
+        None => ["anonymous".to_string()].iter().cloned().collect(),
+    })
+}
+
+/// Entry point for cameras that relay another rtsp server
+///
+/// The stream is served at the same paths as a main stream. It is mounted
+/// again whenever the url, users or aliases of the camera change
+async fn external_main(name: String, reactor: &NeoReactor, rtsp: &NeoRtspServer) -> Result<()> {
+    let mut config = reactor.config().await?;
+    let paths = [
+        format!("/{name}"),
+        format!("/{name}/main"),
+        format!("/{name}/Main"),
+        format!("/{name}/mainStream"),
+        format!("/{name}/MainStream"),
+        format!("/{name}/Mainstream"),
+        format!("/{name}/mainstream"),
+    ];
+    let relay_settings = |config: &Config| {
+        config
+            .cameras
+            .iter()
+            .find(|cam_config| cam_config.name == name)
+            .map(|cam_config| {
+                (
+                    cam_config.rtsp_url.clone(),
+                    cam_config.permitted_users.clone(),
+                    cam_config.path_aliases.clone(),
+                )
+            })
+    };
+
+    loop {
+        let settings = relay_settings(&config.borrow_and_update());
+        let (url, permitted_users, path_aliases) = settings
+            .clone()
+            .ok_or_else(|| anyhow!("{name}: Not in the config"))?;
+        let url = url
+            .ok_or_else(|| anyhow!("{name}: rtsp_url is required when source_type is rtsp_url"))?;
+        let permitted_users = get_permitted_users(rtsp, &permitted_users).await?;
+
+        let factory = make_rtsp_relay_factory(url).await?;
+        factory.add_permitted_roles(&permitted_users);
+        for path in paths.iter() {
+            log::debug!("Path: {}", path);
+            rtsp.add_stream(path, &factory).await?;
+        }
+        for alias in path_aliases.iter() {
+            rtsp.add_path_alias(&paths[0], alias).await?;
+        }
+        log::info!("{}: Relaying rtsp at {}", name, paths.join(", "));
+
+        config
+            .wait_for(|config| relay_settings(config) != settings)
+            .await?;
+        log::info!("{}: Relay settings changed, remounting", name);
+        for path in paths.iter() {
+            rtsp.remove_stream(path).await?;
+        }
+    }
+}
+
+/// Remove every path of the camera from the rtsp server, along with their aliases
+async fn unmount_camera(rtsp: &NeoRtspServer, name: &str) -> AnyResult<()> {
+    let prefix = format!("/{name}/");
+    for path in rtsp.list_streams().await {
+        if path == format!("/{name}") || path.starts_with(&prefix) {
+            log::debug!("{name}: Removing {path}");
+            rtsp.remove_stream(&path).await?;
+        }
+    }
+    Ok(())
+}
+
 /// Top level camera entry point
 ///
 /// It checks which streams are supported and then starts them
-async fn camera_main(camera: NeoInstance, rtsp: &NeoRtspServer) -> Result<()> {
+async fn camera_main(
+    camera: NeoInstance,
+    reactor: &NeoReactor,
+    rtsp: &NeoRtspServer,
+    health: &Arc<HealthTracker>,
+) -> Result<()> {
     let name = camera.config().await?.borrow().name.clone();
     log::debug!("{name}: Camera Main");
     let later_camera = camera.clone();
@@ -289,7 +509,33 @@ async fn camera_main(camera: NeoInstance, rtsp: &NeoRtspServer) -> Result<()> {
         AnyResult::Ok(())
     });
 
+    let connection_state = camera.connection_state().await?;
+    let thread_health = health.clone();
+    let thread_name = name.clone();
+    set.spawn(async move {
+        // Wait for the first connection attempt for the health endpoint
+        let mut i = IntervalStream::new(interval(Duration::from_millis(500)));
+        while i.next().await.is_some() {
+            if *connection_state.lock().unwrap() != CameraConnectionState::Disconnected {
+                thread_health.connection_attempted(&thread_name);
+                break;
+            }
+        }
+        AnyResult::Ok(())
+    });
+
+    let dash_camera = camera.clone();
+    let dash_name = name.clone();
+    set.spawn(async move {
+        let res = dash::dash_main(dash_camera).await;
+        if let Err(e) = &res {
+            log::error!("{dash_name}: DASH output stopped: {e:?}");
+        }
+        res
+    });
+
     let mut camera_config = camera.config().await?.clone();
+    let mut prev_path_aliases = vec![];
     loop {
         let prev_stream_config = camera_config.borrow_and_update().stream;
         let prev_stream_users = camera_config.borrow().permitted_users.clone();
@@ -299,10 +545,21 @@ async fn camera_main(camera: NeoInstance, rtsp: &NeoRtspServer) -> Result<()> {
             .collect::<HashSet<_>>();
         let use_splash = camera_config.borrow().use_splash;
         let splash_pattern = camera_config.borrow().splash_pattern.to_string();
+        let path_aliases = camera_config.borrow().path_aliases.clone();
+        for alias in prev_path_aliases
+            .iter()
+            .filter(|alias| !path_aliases.contains(alias))
+        {
+            rtsp.remove_path_alias(alias).await?;
+        }
+        for alias in path_aliases.iter() {
+            rtsp.add_path_alias(&format!("/{name}"), alias).await?;
+        }
+        prev_path_aliases.clone_from(&path_aliases);
 
         // This select is for changes to camera_config.stream
         break tokio::select! {
-            v = camera_config.wait_for(|config| config.stream != prev_stream_config || config.permitted_users != prev_stream_users || config.use_splash != use_splash) => {
+            v = camera_config.wait_for(|config| config.stream != prev_stream_config || config.permitted_users != prev_stream_users || config.use_splash != use_splash || config.path_aliases != path_aliases) => {
                 if let Err(e) = v {
                     AnyResult::Err(e.into())
                 } else {
@@ -313,22 +570,11 @@ async fn camera_main(camera: NeoInstance, rtsp: &NeoRtspServer) -> Result<()> {
             v = async {
                 // This select handles enabling the right stream
                 // and setting up the users
-                let all_users = rtsp.get_users().await?.iter().filter(|a| *a != "anyone" && *a != "anonymous").cloned().collect::<HashSet<_>>();
-                let permitted_users: HashSet<String> = match &prev_stream_users {
-                    // If in the camera config there is the user "anyone", or if none is specified but users
-                    // are defined at all, then we add all users to the camera's allowed list.
-                    Some(p) if p.iter().any(|u| u == "anyone") => all_users,
-                    None if !all_users.is_empty() => all_users,
-
-                    // The user specified permitted_users
-                    Some(p) => p.iter().cloned().collect(),
-
-                    // The user didn't specify permitted_users, and there are none defined anyway
-                    None => ["anonymous".to_string()].iter().cloned().collect(),
-                };
+                let permitted_users = get_permitted_users(rtsp, &prev_stream_users).await?;
 
                 // Create the dummy factory
-                let dummy_factory = make_dummy_factory(use_splash, splash_pattern).await?;
+                let dummy_factory = make_dummy_factory(use_splash, splash_pattern, None).await?;
+                dummy_factory.set_camera_name(&name);
                 dummy_factory.add_permitted_roles(&permitted_users);
                 let mut supported_streams_1 = supported_streams.clone();
                 let mut supported_streams_2 = supported_streams.clone();
@@ -352,17 +598,17 @@ async fn camera_main(camera: NeoInstance, rtsp: &NeoRtspServer) -> Result<()> {
                         //
                         // This is for BI since it will give up forever on a 404 rather then retry
                         //
-                        let mounts = rtsp
-                            .mount_points()
-                            .ok_or(anyhow!("RTSP server lacks mount point"))?;
                         for path in paths.iter() {
                             log::debug!("Path: {}", path);
-                            mounts.add_factory(path, dummy_factory.clone());
+                            rtsp.add_stream(path, &dummy_factory).await?;
                         }
                         log::debug!("{}: Preparing at {}", name, paths.join(", "));
 
                         supported_streams_1.wait_for(|ss| ss.contains(&StreamKind::Main)).await?;
-                        stream_main(camera.stream(StreamKind::Main).await?, camera.clone(), rtsp, &permitted_users, &paths).await
+                        tokio::select! {
+                            v = stream_main(camera.stream(StreamKind::Main).await?, camera.clone(), reactor, rtsp, health, &permitted_users, &paths) => v,
+                            v = parameters_main(camera.clone(), reactor, rtsp, StreamKind::Main, &paths) => v,
+                        }
                     }, if active_streams.contains(&StreamKind::Main) => v,
                     v = async {
                         let name = camera.config().await?.borrow().name.clone();
@@ -385,18 +631,17 @@ async fn camera_main(camera: NeoInstance, rtsp: &NeoRtspServer) -> Result<()> {
                         //
                         // This is for BI since it will give up forever on a 404 rather then retry
                         //
-                        let mounts = rtsp
-                            .mount_points()
-                            .ok_or(anyhow!("RTSP server lacks mount point"))?;
-                        // Create the dummy factory
                         for path in paths.iter() {
                             log::debug!("Path: {}", path);
-                            mounts.add_factory(path, dummy_factory.clone());
+                            rtsp.add_stream(path, &dummy_factory).await?;
                         }
                         log::debug!("{}: Preparing at {}", name, paths.join(", "));
 
                         supported_streams_2.wait_for(|ss| ss.contains(&StreamKind::Sub)).await?;
-                        stream_main(camera.stream(StreamKind::Sub).await?,camera.clone(), rtsp, &permitted_users, &paths).await
+                        tokio::select! {
+                            v = stream_main(camera.stream(StreamKind::Sub).await?,camera.clone(), reactor, rtsp, health, &permitted_users, &paths) => v,
+                            v = parameters_main(camera.clone(), reactor, rtsp, StreamKind::Sub, &paths) => v,
+                        }
                     }, if active_streams.contains(&StreamKind::Sub) => v,
                     v = async {
                         let name = camera.config().await?.borrow().name.clone();
@@ -419,17 +664,17 @@ async fn camera_main(camera: NeoInstance, rtsp: &NeoRtspServer) -> Result<()> {
                         //
                         // This is for BI since it will give up forever on a 404 rather then retry
                         //
-                        let mounts = rtsp
-                            .mount_points()
-                            .ok_or(anyhow!("RTSP server lacks mount point"))?;
                         for path in paths.iter() {
                             log::debug!("Path: {}", path);
-                            mounts.add_factory(path, dummy_factory.clone());
+                            rtsp.add_stream(path, &dummy_factory).await?;
                         }
                         log::debug!("{}: Preparing at {}", name, paths.join(", "));
 
                         supported_streams_3.wait_for(|ss| ss.contains(&StreamKind::Extern)).await?;
-                        stream_main(camera.stream(StreamKind::Extern).await?,camera.clone(), rtsp, &permitted_users, &paths).await
+                        tokio::select! {
+                            v = stream_main(camera.stream(StreamKind::Extern).await?,camera.clone(), reactor, rtsp, health, &permitted_users, &paths) => v,
+                            v = parameters_main(camera.clone(), reactor, rtsp, StreamKind::Extern, &paths) => v,
+                        }
                     }, if active_streams.contains(&StreamKind::Extern) => v,
                     else => {
                         // all disabled just wait here until config is changed
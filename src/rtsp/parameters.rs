@@ -0,0 +1,185 @@
+//! The rtsp GET_PARAMETER and SET_PARAMETER of a stream
+//!
+//! The body of the request is `text/parameters` with one `name: value` per
+//! line. The known names are
+//! - `bitrate`: The bitrate of the stream in kbps
+//! - `resolution`: The resolution of the stream like `1920x1080`. It must
+//!   be one that the camera supports for the stream
+//! - `audio_enabled`: `true` or `false` to serve the stream with or
+//!   without audio. This changes `audio` in the camera's config
+//!
+//! Only users with `admin = true` may use them
+use anyhow::Context;
+use neolink_core::bc_protocol::StreamKind;
+use tokio::sync::mpsc::channel;
+
+use super::gst::{NeoRtspServer, ParameterError, ParameterRequest};
+use crate::{
+    common::{NeoInstance, NeoReactor},
+    AnyResult,
+};
+
+/// Handles the parameter requests of the stream's paths until an error
+pub(super) async fn parameters_main(
+    camera: NeoInstance,
+    reactor: &NeoReactor,
+    rtsp: &NeoRtspServer,
+    stream: StreamKind,
+    paths: &[String],
+) -> AnyResult<()> {
+    let (tx, mut rx) = channel(10);
+    for path in paths.iter() {
+        rtsp.add_parameter_handler(path, tx.clone()).await?;
+    }
+    drop(tx);
+
+    while let Some(request) = rx.recv().await {
+        match request {
+            ParameterRequest::Get { names, reply } => {
+                let _ = reply.send(get_parameters(&camera, stream, &names).await);
+            }
+            ParameterRequest::Set { values, reply } => {
+                let _ = reply.send(
+                    set_parameters(&camera, reactor, stream, &values)
+                        .await
+                        .map(|_| vec![]),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn get_parameters(
+    camera: &NeoInstance,
+    stream: StreamKind,
+    names: &[String],
+) -> Result<Vec<(String, String)>, ParameterError> {
+    check_names(names.iter())?;
+    let encoding = camera
+        .run_task(|cam| Box::pin(async move { Ok(cam.get_encoding_config(stream).await?) }))
+        .await
+        .map_err(ParameterError::Failed)?;
+    let audio = camera
+        .config()
+        .await
+        .map_err(ParameterError::Failed)?
+        .borrow()
+        .audio;
+    Ok(names
+        .iter()
+        .map(|name| {
+            let value = match name.as_str() {
+                "bitrate" => encoding.bitrate_kbps.to_string(),
+                "resolution" => format!("{}x{}", encoding.resolution.0, encoding.resolution.1),
+                "audio_enabled" => audio.to_string(),
+                _ => unreachable!(),
+            };
+            (name.clone(), value)
+        })
+        .collect())
+}
+
+async fn set_parameters(
+    camera: &NeoInstance,
+    reactor: &NeoReactor,
+    stream: StreamKind,
+    values: &[(String, String)],
+) -> Result<(), ParameterError> {
+    check_names(values.iter().map(|(name, _)| name))?;
+    let mut bitrate = None;
+    let mut resolution = None;
+    let mut audio = None;
+    for (name, value) in values.iter() {
+        let invalid = || ParameterError::BadRequest(format!("Invalid {name}: {value}"));
+        match name.as_str() {
+            "bitrate" => bitrate = Some(value.parse::<u32>().map_err(|_| invalid())?),
+            "resolution" => {
+                let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+                resolution = Some((
+                    width.trim().parse::<u32>().map_err(|_| invalid())?,
+                    height.trim().parse::<u32>().map_err(|_| invalid())?,
+                ));
+            }
+            "audio_enabled" => {
+                audio = Some(match value.to_lowercase().as_str() {
+                    "true" | "1" | "on" => true,
+                    "false" | "0" | "off" => false,
+                    _ => return Err(invalid()),
+                })
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if bitrate.is_some() || resolution.is_some() {
+        let supported = camera
+            .run_task(|cam| {
+                Box::pin(async move { Ok(cam.get_supported_resolutions(stream).await?) })
+            })
+            .await
+            .map_err(ParameterError::Failed)?;
+        if let Some(resolution) = resolution {
+            if !supported.contains(&resolution) {
+                return Err(ParameterError::BadRequest(format!(
+                    "Unsupported resolution {}x{}",
+                    resolution.0, resolution.1
+                )));
+            }
+        }
+        camera
+            .run_task(|cam| {
+                Box::pin(async move {
+                    let mut encoding = cam
+                        .get_encoding_config(stream)
+                        .await
+                        .context("Unable to get the stream encoding settings")?;
+                    if let Some(bitrate) = bitrate {
+                        encoding.bitrate_kbps = bitrate;
+                    }
+                    if let Some(resolution) = resolution {
+                        encoding.resolution = resolution;
+                    }
+                    cam.set_encoding_config(stream, &encoding)
+                        .await
+                        .context("Unable to set the stream encoding settings")?;
+                    Ok(())
+                })
+            })
+            .await
+            .map_err(ParameterError::Failed)?;
+    }
+
+    if let Some(audio) = audio {
+        set_audio(camera, reactor, audio)
+            .await
+            .map_err(ParameterError::Failed)?;
+    }
+    Ok(())
+}
+
+fn check_names<'a, I: Iterator<Item = &'a String>>(mut names: I) -> Result<(), ParameterError> {
+    match names.find(|name| !matches!(name.as_str(), "bitrate" | "resolution" | "audio_enabled")) {
+        Some(name) => Err(ParameterError::BadRequest(format!(
+            "Unknown parameter {name}"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Change `audio` of the camera in the config which restarts the stream
+async fn set_audio(camera: &NeoInstance, reactor: &NeoReactor, audio: bool) -> AnyResult<()> {
+    let name = camera.config().await?.borrow().name.clone();
+    let mut config = reactor.config().await?.borrow().clone();
+    let camera_config = config
+        .cameras
+        .iter_mut()
+        .find(|camera_config| camera_config.name == name)
+        .with_context(|| format!("{name} is not in the config"))?;
+    if camera_config.audio == audio {
+        return Ok(());
+    }
+    camera_config.audio = audio;
+    log::info!("{name}: Setting audio to {audio} from rtsp");
+    reactor.update_config(config).await
+}
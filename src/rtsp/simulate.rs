@@ -0,0 +1,69 @@
+//! Serves a test pattern over rtsp without a camera
+//!
+//! This is for `neolink simulate-stream`. The rtsp server is set up from the
+//! config as in `neolink rtsp` so the users, TLS and bind address are the
+//! same but no camera is connected
+use anyhow::Result;
+use neolink_core::bcmedia::model::VideoType;
+use std::collections::HashSet;
+use tokio::time::{sleep, Duration};
+
+use super::{apply_users, factory::make_simulated_factory, get_permitted_users, NeoRtspServer};
+use crate::common::NeoReactor;
+
+/// The stream that is simulated
+pub(crate) struct SimulatedStream {
+    pub(crate) path: String,
+    pub(crate) codec: VideoType,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) fps: u32,
+    /// Stop after this long or run until ctrl-c if `None`
+    pub(crate) duration: Option<Duration>,
+}
+
+/// Serve the simulated stream until the duration is up or ctrl-c
+pub(crate) async fn simulate_main(reactor: NeoReactor, stream: SimulatedStream) -> Result<()> {
+    let config = reactor.config().await?.borrow().clone();
+    let rtsp = NeoRtspServer::new()?;
+    rtsp.set_up_tls(&config)?;
+    rtsp.set_latency(config.rtpjitterbuffer_latency_ms);
+    rtsp.set_thread_pool(config.rtsp_threads);
+    rtsp.set_case_insensitive_paths(config.rtsp_case_insensitive_paths)
+        .await?;
+    apply_users(&rtsp, &config.users.iter().cloned().collect::<HashSet<_>>()).await?;
+    let permitted_users = get_permitted_users(&rtsp, &None).await?;
+
+    let factory = make_simulated_factory(
+        stream.codec.clone(),
+        stream.width,
+        stream.height,
+        stream.fps,
+    )
+    .await?;
+    factory.add_permitted_roles(&permitted_users);
+    rtsp.add_stream(&stream.path, &factory).await?;
+
+    rtsp.run(&config.bind_addr, config.bind_port, config.rtsp_max_backlog)
+        .await?;
+    log::info!(
+        "Simulating {}x{}@{} {:?} at rtsp://{}:{}{}",
+        stream.width,
+        stream.height,
+        stream.fps,
+        stream.codec,
+        config.bind_addr,
+        config.bind_port,
+        stream.path
+    );
+
+    tokio::select! {
+        v = tokio::signal::ctrl_c() => v?,
+        _ = sleep(stream.duration.unwrap_or_default()), if stream.duration.is_some() => {
+            log::info!("Simulated stream finished");
+        }
+    }
+    rtsp.quit().await?;
+    rtsp.join().await?;
+    Ok(())
+}
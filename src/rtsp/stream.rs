@@ -2,23 +2,32 @@ use anyhow::{anyhow, Result};
 use gstreamer::{prelude::*, ClockTime, FlowError};
 use gstreamer_app::AppSrc;
 use gstreamer_rtsp_server::prelude::*;
-use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
 use tokio::{
     sync::{broadcast::channel as broadcast, watch::channel as watch},
     task::JoinSet,
-    time::{sleep, Duration},
+    time::{sleep, sleep_until, Duration, Instant},
+};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt,
 };
-use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
 use tokio_util::sync::CancellationToken;
 
-use crate::common::{Permit, StampedData, UseCounter, VidFormat};
+use crate::common::{AudFormat, Permit, StampedData, UseCounter, VidFormat};
 use crate::{
-    common::{NeoInstance, StreamConfig, StreamInstance},
+    common::{NeoInstance, NeoReactor, StreamConfig, StreamInstance},
+    config::{CameraConfig, ExternalAudioSource, FrameTimestampMode},
     AnyResult,
 };
+use neolink_core::bc_protocol::StreamKind;
+
+use super::{factory::*, gst::NeoRtspServer, health::HealthTracker, metrics::RtspMetrics};
 
-use super::{factory::*, gst::NeoRtspServer};
+/// How often the warning about discarded video is repeated
+const DISCARD_WARN_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Clone)]
 struct PauseAffectors {
@@ -31,14 +40,18 @@ struct PauseAffectors {
 pub(super) async fn stream_main(
     mut stream_instance: StreamInstance,
     camera: NeoInstance,
+    reactor: &NeoReactor,
     rtsp: &NeoRtspServer,
+    health: &HealthTracker,
     users: &HashSet<String>,
     paths: &[String],
 ) -> Result<()> {
     let mut camera_config = camera.config().await?.clone();
     let name = camera_config.borrow().name.clone();
+    let stream_kind = stream_instance.name;
 
     let mut curr_pause;
+    let mut reload_limiter = ReloadLimiter::new();
     loop {
         let this_loop_cancel = CancellationToken::new();
         let _drop_guard = this_loop_cancel.clone().drop_guard();
@@ -46,10 +59,67 @@ pub(super) async fn stream_main(
         stream_instance.activate().await?;
 
         // Wait for a valid stream format to be detected
-        stream_instance
-            .config
-            .wait_for(|config| config.vid_ready())
-            .await?;
+        let detect_start = Instant::now();
+        let detect_timeout =
+            Duration::from_secs(camera_config.borrow().format_detect_timeout_secs as u64);
+        health.waiting_for_video(&name, stream_kind, detect_timeout);
+        let discard_cancel = this_loop_cancel.child_token();
+        let thread_discard_cancel = discard_cancel.clone();
+        let thread_name = name.clone();
+        let discard_vid = BroadcastStream::new(stream_instance.vid.resubscribe());
+        let metrics = rtsp.metrics();
+        tokio::task::spawn(async move {
+            tokio::select! {
+                _ = thread_discard_cancel.cancelled() => {},
+                _ = count_discarded_video(&thread_name, discard_vid, &metrics) => {},
+            }
+        });
+        let detected = if detect_timeout.is_zero() {
+            None
+        } else {
+            tokio::time::timeout(
+                detect_timeout,
+                stream_instance.config.wait_for(|config| config.vid_ready()),
+            )
+            .await
+            .ok()
+            .map(|v| v.map(|_| ()))
+        };
+        match detected {
+            Some(v) => v?,
+            None => {
+                if !detect_timeout.is_zero() {
+                    // Show the test pattern rather than a stale or black stream until the video arrives
+                    log::warn!(
+                        "{}: No video after {:?}. Showing the test pattern until it arrives",
+                        &name,
+                        detect_start.elapsed()
+                    );
+                    let pattern = camera_config.borrow().splash_pattern.to_string();
+                    // The camera reports the resolution before the first frame
+                    let known = stream_instance.config.borrow().clone();
+                    let test_factory = make_dummy_factory(true, pattern, Some(known)).await?;
+                    test_factory.set_camera_name(&name);
+                    test_factory.add_permitted_roles(users);
+                    for path in paths.iter() {
+                        rtsp.add_stream(path, &test_factory).await?;
+                    }
+                }
+                stream_instance
+                    .config
+                    .wait_for(|config| config.vid_ready())
+                    .await?;
+                if !detect_timeout.is_zero() {
+                    log::info!(
+                        "{}: Video arrived after {:?}",
+                        &name,
+                        detect_start.elapsed()
+                    );
+                }
+            }
+        }
+        health.video_ready(&name, stream_kind);
+        discard_cancel.cancel();
         // After vid give it 1s to look for audio
         // Ignore timeout but check err
         if let Ok(v) = tokio::time::timeout(
@@ -62,8 +132,48 @@ pub(super) async fn stream_main(
         }
 
         curr_pause = camera_config.borrow().pause.clone();
+        let curr_audio = camera_config.borrow().audio;
+        let curr_external_audio = camera_config.borrow().external_audio.clone();
+        let curr_frame_buffer_size = camera_config.borrow().frame_buffer_size;
+        let curr_av_sync_threshold = camera_config.borrow().av_sync_threshold_ms;
+        let curr_frame_timestamps = camera_config.borrow().frame_timestamps;
+        let run_camera_config = camera_config.borrow().clone();
 
         let last_stream_config = stream_instance.config.borrow().clone();
+        let mut run_stream_config = last_stream_config.clone();
+        // The audio of another camera replaces this camera's own
+        let mut external_stream = match (&curr_external_audio, curr_audio) {
+            (Some(ExternalAudioSource::BcStream(other)), true) => {
+                // The audio is the same on every stream so use the lightest
+                let mut external_stream = reactor.get(other).await?.stream(StreamKind::Sub).await?;
+                external_stream.activate().await?;
+                if let Ok(v) = tokio::time::timeout(
+                    Duration::from_secs(5),
+                    external_stream.config.wait_for(|config| config.aud_ready()),
+                )
+                .await
+                {
+                    v?;
+                } else {
+                    log::warn!("{name}: No audio found on {other}");
+                }
+                Some(external_stream)
+            }
+            _ => None,
+        };
+        let mut external_config = external_stream
+            .as_ref()
+            .map(|external_stream| external_stream.config.clone());
+        let last_external_config = external_config
+            .as_ref()
+            .map(|external_config| external_config.borrow().clone());
+        if !curr_audio || matches!(curr_external_audio, Some(ExternalAudioSource::RtspUrl(_))) {
+            // Leave the audio branch out of the pipeline entirely
+            // or let the factory pull it from the url
+            run_stream_config.aud_format = AudFormat::None;
+        } else if let Some(external_config) = last_external_config.as_ref() {
+            run_stream_config.aud_format = external_config.aud_format;
+        }
         let mut thread_stream_config = stream_instance.config.clone();
 
         let (pause_affector_tx, pause_affector) = watch(PauseAffectors {
@@ -185,6 +295,15 @@ pub(super) async fn stream_main(
             let mut client_activator = stream_instance.activator_handle().await;
             client_activator.deactivate().await?;
             stream_instance.deactivate().await?;
+            // The other camera's audio is only needed while this stream runs
+            let mut external_activator = match external_stream.as_mut() {
+                Some(external_stream) => {
+                    let external_activator = external_stream.activator_handle().await;
+                    external_stream.deactivate().await?;
+                    Some(external_activator)
+                }
+                None => None,
+            };
             let mut pause_affector = tokio_stream::wrappers::WatchStream::new(pause_affector);
             let thread_curr_pause = curr_pause.clone();
             set.spawn(async move {
@@ -192,26 +311,18 @@ pub(super) async fn stream_main(
                     _ = cancel.cancelled() => AnyResult::Ok(()),
                     v = async {
                         while let Some(state) = pause_affector.next().await {
-                            if thread_curr_pause.on_motion && thread_curr_pause.on_disconnect {
-                                if state.client && (state.motion || state.push) {
-                                    client_activator.activate().await?;
-                                } else {
-                                    client_activator.deactivate().await?;
-                                }
-                            } else if thread_curr_pause.on_motion {
-                                if state.motion || state.push {
-                                    client_activator.activate().await?;
+                            let active = match (thread_curr_pause.on_motion, thread_curr_pause.on_disconnect) {
+                                (true, true) => state.client && (state.motion || state.push),
+                                (true, false) => state.motion || state.push,
+                                (false, true) => state.client,
+                                (false, false) => unreachable!(),
+                            };
+                            for activator in std::iter::once(&mut client_activator).chain(external_activator.as_mut()) {
+                                if active {
+                                    activator.activate().await?;
                                 } else {
-                                    client_activator.deactivate().await?;
+                                    activator.deactivate().await?;
                                 }
-                            } else if thread_curr_pause.on_disconnect {
-                                if state.client {
-                                    client_activator.activate().await?;
-                                } else {
-                                    client_activator.deactivate().await?;
-                                }
-                            } else {
-                                unreachable!()
                             }
                         }
                         AnyResult::Ok(())
@@ -262,62 +373,184 @@ pub(super) async fn stream_main(
         // This runs the actual stream.
         // The select will restart if the stream's config updates
         break tokio::select! {
-            v = thread_stream_config.wait_for(|new_conf| new_conf != &last_stream_config) => {
+            v = async {
+                // While throttled the format changes are ignored and the
+                // current pipeline is kept
+                if let Some(until) = reload_limiter.throttled_until() {
+                    sleep_until(until).await;
+                }
+                thread_stream_config.wait_for(|new_conf| new_conf != &last_stream_config).await.map(|v| v.clone())
+            } => {
                 let v = v?;
                 // If stream config changes we reload the stream
                 log::info!("{}: Stream Configuration Changed. Reloading Streams", &name);
-                log::trace!("    From {:?} to {:?}", last_stream_config, v.clone());
+                log::trace!("    From {:?} to {:?}", last_stream_config, v);
+                if reload_limiter.record(Instant::now()) {
+                    log::warn!(
+                        "{}: The stream format changed more than {} times in {:?}. Ignoring further changes for {:?}",
+                        &name,
+                        ReloadLimiter::MAX_CHANGES,
+                        ReloadLimiter::WINDOW,
+                        ReloadLimiter::THROTTLE
+                    );
+                }
+                continue;
+            },
+            v = async {
+                match (external_config.as_mut(), last_external_config.as_ref()) {
+                    (Some(external_config), Some(last_external_config)) => {
+                        external_config
+                            .wait_for(|new_conf| new_conf.aud_format != last_external_config.aud_format)
+                            .await
+                            .map(|_| ())
+                    }
+                    _ => futures::future::pending().await,
+                }
+            } => {
+                v?;
+                log::info!("{}: External Audio Changed. Reloading Streams", &name);
                 continue;
             },
-            v = camera_config.wait_for(|new_conf| new_conf.pause != curr_pause ) => {
+            v = camera_config.wait_for(|new_conf| new_conf.pause != curr_pause || new_conf.audio != curr_audio || new_conf.external_audio != curr_external_audio || new_conf.frame_buffer_size != curr_frame_buffer_size || new_conf.av_sync_threshold_ms != curr_av_sync_threshold || new_conf.frame_timestamps != curr_frame_timestamps ) => {
                 v?;
-                // If pause config changes restart
-                log::info!("{}: Pause Configuration Changed. Reloading Streams", &name);
+                // If pause, audio or buffer config changes restart
+                log::info!("{}: Pause/Audio/Buffer Configuration Changed. Reloading Streams", &name);
                 continue;
             },
-            v = stream_run(&name, &stream_instance, rtsp, &last_stream_config, users, paths, client_count) => v,
+            v = stream_run(&run_camera_config, &stream_instance, external_stream.as_ref().unwrap_or(&stream_instance), rtsp, health, &run_stream_config, users, paths, client_count) => v,
         };
     }
 }
 
+/// Counts the video frames that arrive while the format is not yet detected
+///
+/// There is no rtsp media for them to go to until it is so they are dropped
+async fn count_discarded_video(
+    name: &str,
+    mut vid: BroadcastStream<StampedData>,
+    metrics: &RtspMetrics,
+) {
+    let mut last_warn: Option<Instant> = None;
+    while let Some(frame) = vid.next().await {
+        let frames = match frame {
+            Ok(_) => 1,
+            Err(BroadcastStreamRecvError::Lagged(missed)) => missed,
+        };
+        metrics.video_discarded(name, frames);
+        if last_warn.map_or(true, |last| last.elapsed() >= DISCARD_WARN_INTERVAL) {
+            log::warn!(
+                "{name}: Video data is being discarded (format not yet detected). Check camera codec configuration."
+            );
+            last_warn = Some(Instant::now());
+        }
+    }
+}
+
+/// Tracks how often the stream format changes so that a camera that keeps
+/// flipping its format cannot rebuild the pipeline over and over
+struct ReloadLimiter {
+    changes: VecDeque<Instant>,
+    throttled_until: Option<Instant>,
+}
+
+impl ReloadLimiter {
+    const WINDOW: Duration = Duration::from_secs(5);
+    const MAX_CHANGES: usize = 3;
+    const THROTTLE: Duration = Duration::from_secs(10);
+
+    fn new() -> Self {
+        Self {
+            changes: VecDeque::with_capacity(Self::MAX_CHANGES + 1),
+            throttled_until: None,
+        }
+    }
+
+    /// Record a format change and return true if it starts a throttle
+    fn record(&mut self, now: Instant) -> bool {
+        self.changes.push_back(now);
+        while self
+            .changes
+            .front()
+            .is_some_and(|first| now.duration_since(*first) > Self::WINDOW)
+        {
+            self.changes.pop_front();
+        }
+        if self.changes.len() > Self::MAX_CHANGES {
+            self.changes.clear();
+            self.throttled_until = Some(now + Self::THROTTLE);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// When format changes should be accepted again if they are being throttled
+    fn throttled_until(&self) -> Option<Instant> {
+        self.throttled_until.filter(|until| *until > Instant::now())
+    }
+}
+
 /// This handles the stream itself by creating the factory and pushing messages into it
+///
+/// The audio is taken from `aud_instance` which is usually the same as `stream_instance`
+#[allow(clippy::too_many_arguments)]
 async fn stream_run(
-    name: &str,
+    camera_config: &CameraConfig,
     stream_instance: &StreamInstance,
+    aud_instance: &StreamInstance,
     rtsp: &NeoRtspServer,
+    health: &HealthTracker,
     stream_config: &StreamConfig,
     users: &HashSet<String>,
     paths: &[String],
     client_count: Permit,
 ) -> AnyResult<()> {
+    let name = camera_config.name.as_str();
+    let frame_buffer_size = camera_config.frame_buffer_size;
+    let timestamps = camera_config.frame_timestamps;
+    let av_sync_threshold = Duration::from_millis(camera_config.av_sync_threshold_ms);
     let vidstream = stream_instance.vid.resubscribe();
-    let audstream = stream_instance.aud.resubscribe();
+    let audstream = aud_instance.aud.resubscribe();
     let vid_history = stream_instance.vid_history.clone();
-    let aud_history = stream_instance.aud_history.clone();
+    let aud_history = aud_instance.aud_history.clone();
+    let external_audio_url = match &camera_config.external_audio {
+        Some(ExternalAudioSource::RtspUrl(url)) if camera_config.audio => Some(url.clone()),
+        _ => None,
+    };
+    // Audio from another camera has its own clock
+    let rebase_audio = matches!(
+        camera_config.external_audio,
+        Some(ExternalAudioSource::BcStream(_))
+    );
 
     // Finally ready to create the factory and connect the stream
-    let mounts = rtsp
-        .mount_points()
-        .ok_or(anyhow!("RTSP server lacks mount point"))?;
-    // Create the factory
-    let (factory, mut client_rx) = make_factory(stream_config).await?;
+    let (factory, mut client_rx) =
+        make_factory(stream_config, external_audio_url, timestamps).await?;
+    // This is the latency of the rtpjitterbuffer of the media's rtpbin
+    factory.set_latency(rtsp.latency());
+    factory
+        .set_message_callback(health.pipeline_message_counter(name))
+        .await;
 
+    factory.set_camera_name(name);
     factory.add_permitted_roles(users);
 
     for path in paths.iter() {
         log::debug!("Path: {}", path);
-        mounts.add_factory(path, factory.clone());
+        rtsp.add_stream(path, &factory).await?;
     }
     log::info!("{}: Available at {}", name, paths.join(", "));
 
     let stream_cancel = CancellationToken::new();
     let drop_guard = stream_cancel.clone().drop_guard();
     let mut set = JoinSet::new();
+    let hevc_param_sets = Arc::new(Mutex::new(HevcParamSets::default()));
     // Wait for new media client data to come in from the factory
     while let Some(mut client_data) = client_rx.recv().await {
         // New media created
         let vid = client_data.vid.take().map(|data| data.app);
         let aud = client_data.aud.take().map(|data| data.app);
+        health.media_created(name, &client_data.element);
 
         // This is the data that gets sent to gstreamer thread
         // It represents the combination of the camera stream and the appsrc seek messages
@@ -367,45 +600,48 @@ async fn stream_run(
         });
 
         // This thread takes the audio data from the cam and passed it into the stream
-        let mut audstream = BroadcastStream::new(audstream.resubscribe());
-        let thread_stream_cancel = stream_cancel.clone();
-        let thread_aud_data_tx = aud_data_tx.clone();
-        let thread_aud_history = aud_history.clone();
-        set.spawn(async move {
-            let r = tokio::select! {
-                _ = thread_stream_cancel.cancelled() => AnyResult::Ok(()),
-                v = async {
-                    // Send Initial
-                    {
-                        let history = thread_aud_history.borrow();
-                        // let last_ts = history.back().map(|s| s.ts);
-                        for data in history.iter() {
-                            thread_aud_data_tx.send(
-                                // StampedData {
-                                //     keyframe: data.keyframe,
-                                //     data: data.data.clone(),
-                                //     ts: last_ts.unwrap()
-                                // }
-                                data.clone()
+        // It is not needed if the audio was left out of the pipeline
+        if !matches!(stream_config.aud_format, AudFormat::None) {
+            let mut audstream = BroadcastStream::new(audstream.resubscribe());
+            let thread_stream_cancel = stream_cancel.clone();
+            let thread_aud_data_tx = aud_data_tx.clone();
+            let thread_aud_history = aud_history.clone();
+            set.spawn(async move {
+                let r = tokio::select! {
+                    _ = thread_stream_cancel.cancelled() => AnyResult::Ok(()),
+                    v = async {
+                        // Send Initial
+                        {
+                            let history = thread_aud_history.borrow();
+                            // let last_ts = history.back().map(|s| s.ts);
+                            for data in history.iter() {
+                                thread_aud_data_tx.send(
+                                    // StampedData {
+                                    //     keyframe: data.keyframe,
+                                    //     data: data.data.clone(),
+                                    //     ts: last_ts.unwrap()
+                                    // }
+                                    data.clone()
 
-                            )?;
+                                )?;
+                            }
                         }
-                    }
 
-                    // Send new
-                    while let Some(frame) = audstream.next().await {
-                        if let Ok(data) = frame {
-                            thread_aud_data_tx.send(
-                                data
-                            )?;
-                        }
-                    };
-                    AnyResult::Ok(())
-                } => v,
-            };
-            log::trace!("Stream Aud Media End: {r:?}");
-            AnyResult::Ok(())
-        });
+                        // Send new
+                        while let Some(frame) = audstream.next().await {
+                            if let Ok(data) = frame {
+                                thread_aud_data_tx.send(
+                                    data
+                                )?;
+                            }
+                        };
+                        AnyResult::Ok(())
+                    } => v,
+                };
+                log::trace!("Stream Aud Media End: {r:?}");
+                AnyResult::Ok(())
+            });
+        }
 
         // Handles sending the video data into gstreamer
         let thread_stream_cancel = stream_cancel.clone();
@@ -413,6 +649,7 @@ async fn stream_run(
         let thread_vid = vid.clone();
         let mut thread_client_count = client_count.subscribe();
         let thread_format = stream_config.vid_format;
+        let thread_hevc_param_sets = hevc_param_sets.clone();
         let (ts_tx, ts_rx) = tokio::sync::watch::channel(Duration::ZERO);
         // let fallback_time = Duration::from_secs(3);
         let framerate =
@@ -429,8 +666,12 @@ async fn stream_run(
                             // insert_filler(
                                 frametime_stream(
                                     sync_stream(
-                                        wait_for_keyframe(
-                                            vid_data_rx,
+                                        hevc_parameter_sets(
+                                            wait_for_keyframe(
+                                                vid_data_rx,
+                                            ),
+                                            thread_format,
+                                            thread_hevc_param_sets,
                                         ),
                                         ts_tx,
                                     ),
@@ -441,7 +682,10 @@ async fn stream_run(
                             // ),
                             thread_format,
                         ),
-                        &thread_vid
+                        &thread_vid,
+                        frame_buffer_size,
+                        true,
+                        timestamps,
                     ) => {
                         v
                     },
@@ -458,6 +702,13 @@ async fn stream_run(
         let thread_aud = aud.clone();
         let aud_framerate =
             Duration::from_millis(1000u64 / std::cmp::max(stream_config.fps as u64, 5u64));
+        let av_sync_ts_rx = ts_rx.clone();
+        let aud_timed: Pin<Box<dyn Stream<Item = AnyResult<StampedData>> + Send>> = if rebase_audio
+        {
+            Box::pin(rebase_stream(wait_for_keyframe(aud_data_rx), ts_rx))
+        } else {
+            Box::pin(hold_stream(wait_for_keyframe(aud_data_rx), ts_rx))
+        };
         if let Some(thread_aud) = thread_aud {
             set.spawn(async move {
                 let r = tokio::select! {
@@ -466,14 +717,16 @@ async fn stream_run(
                     },
                     v = send_to_appsrc(
                         frametime_stream(
-                            hold_stream(
-                                wait_for_keyframe(
-                                    aud_data_rx
-                                ),
-                                ts_rx,
+                            av_sync_stream(
+                                aud_timed,
+                                av_sync_ts_rx,
+                                av_sync_threshold,
                             ),
                             aud_framerate),
-                        &thread_aud) => {
+                        &thread_aud,
+                        frame_buffer_size,
+                        false,
+                        timestamps) => {
                         v
                     },
                 };
@@ -557,6 +810,167 @@ fn hold_stream<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
     })
 }
 
+/// Moves the timestamps of a stream from another camera onto the video's clock
+///
+/// The first frame is stamped with the current video timestamp and the
+/// rest keep their spacing from it. Any drift after that is left to
+/// [`av_sync_stream`]
+fn rebase_stream<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
+    mut stream: T,
+    vid_ts: tokio::sync::watch::Receiver<Duration>,
+) -> impl Stream<Item = AnyResult<StampedData>> + Unpin {
+    Box::pin(async_stream::stream! {
+        let mut base = None;
+        while let Some(frame) = stream.next().await {
+            if let Ok(frame) = frame {
+                let (frame_0, vid_0) = *base.get_or_insert((frame.ts, *vid_ts.borrow()));
+                yield Ok(StampedData {
+                    ts: vid_0 + frame.ts.saturating_sub(frame_0),
+                    ..frame
+                });
+            }
+        }
+    })
+}
+
+/// What [`AvSyncCorrector`] does with an audio frame
+#[derive(Debug, PartialEq, Eq)]
+enum AvSyncAction {
+    /// Send the frame with this timestamp
+    Send(Duration),
+    /// Leave the frame out
+    Drop,
+    /// Send the frame twice with these timestamps
+    Repeat(Duration, Duration),
+}
+
+/// Keeps the audio in line with the video
+///
+/// The offset of each audio timestamp from the latest video timestamp jumps
+/// about by up to a frame depending on which arrived last, so the median over
+/// the last [`AvSyncCorrector::WINDOW`] audio frames is used. The first window
+/// is taken as in sync and the drift is how far the median has moved since.
+///
+/// When the drift goes over the threshold whole audio frames are dropped, if
+/// the audio is ahead, or repeated, if it is behind, until it is back within
+/// half a frame. The timestamps that are sent never go backwards
+struct AvSyncCorrector {
+    offsets_us: VecDeque<i64>,
+    baseline_us: Option<i64>,
+    threshold_us: i64,
+    /// Added to every audio timestamp. Goes down by a frame for each one
+    /// dropped and up by a frame for each one repeated
+    correction_us: i64,
+    correcting: bool,
+    last_audio: Option<Duration>,
+    last_sent: Duration,
+}
+
+impl AvSyncCorrector {
+    const WINDOW: usize = 25;
+
+    fn new(threshold: Duration) -> Self {
+        Self {
+            offsets_us: VecDeque::with_capacity(Self::WINDOW),
+            baseline_us: None,
+            threshold_us: threshold.as_micros() as i64,
+            correction_us: 0,
+            correcting: false,
+            last_audio: None,
+            last_sent: Duration::ZERO,
+        }
+    }
+
+    /// Record the latest video timestamp and decide what to do with the audio frame
+    fn correct(&mut self, video_ts: Duration, audio_ts: Duration) -> AvSyncAction {
+        let frame_us = self
+            .last_audio
+            .replace(audio_ts)
+            .and_then(|last| audio_ts.checked_sub(last))
+            .map(|frame| frame.as_micros() as i64)
+            .filter(|&frame| frame > 0 && frame < 1_000_000);
+        self.offsets_us
+            .push_back(audio_ts.as_micros() as i64 - video_ts.as_micros() as i64);
+        while self.offsets_us.len() > Self::WINDOW {
+            self.offsets_us.pop_front();
+        }
+
+        let action = match (self.drift_us(), frame_us) {
+            (Some(drift_us), Some(frame_us)) if self.threshold_us > 0 => {
+                if drift_us.abs() > self.threshold_us {
+                    if !self.correcting {
+                        log::debug!("A/V drift of {}ms, correcting", drift_us / 1000);
+                    }
+                    self.correcting = true;
+                } else if drift_us.abs() <= frame_us / 2 {
+                    self.correcting = false;
+                }
+                if !self.correcting {
+                    None
+                } else if drift_us > 0 {
+                    self.correction_us -= frame_us;
+                    Some(AvSyncAction::Drop)
+                } else {
+                    let first = self.stamp(audio_ts);
+                    self.correction_us += frame_us;
+                    Some(AvSyncAction::Repeat(first, self.stamp(audio_ts)))
+                }
+            }
+            _ => None,
+        };
+        action.unwrap_or_else(|| AvSyncAction::Send(self.stamp(audio_ts)))
+    }
+
+    /// How far the audio is ahead of where it started, after the corrections so far
+    fn drift_us(&mut self) -> Option<i64> {
+        if self.offsets_us.len() < Self::WINDOW {
+            return None;
+        }
+        let mut offsets = self.offsets_us.iter().copied().collect::<Vec<_>>();
+        offsets.sort_unstable();
+        let median = offsets[offsets.len() / 2];
+        let baseline = *self.baseline_us.get_or_insert(median);
+        Some(median - baseline + self.correction_us)
+    }
+
+    /// The corrected timestamp of the audio, never before the last one sent
+    fn stamp(&mut self, audio_ts: Duration) -> Duration {
+        let ts =
+            Duration::from_micros((audio_ts.as_micros() as i64 + self.correction_us).max(0) as u64);
+        self.last_sent = self.last_sent.max(ts);
+        self.last_sent
+    }
+}
+
+/// Keeps the audio in line with the video with an [`AvSyncCorrector`]
+///
+/// The video timestamps come from the watcher that is updated by [`sync_stream`]
+fn av_sync_stream<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
+    mut stream: T,
+    vid_ts: tokio::sync::watch::Receiver<Duration>,
+    threshold: Duration,
+) -> impl Stream<Item = AnyResult<StampedData>> + Unpin {
+    Box::pin(async_stream::stream! {
+        let mut corrector = AvSyncCorrector::new(threshold);
+        while let Some(frame) = stream.next().await {
+            if let Ok(frame) = frame {
+                let video_ts = *vid_ts.borrow();
+                match corrector.correct(video_ts, frame.ts) {
+                    AvSyncAction::Send(ts) => yield Ok(StampedData { ts, ..frame }),
+                    AvSyncAction::Drop => {}
+                    AvSyncAction::Repeat(first, second) => {
+                        yield Ok(StampedData {
+                            ts: first,
+                            ..frame.clone()
+                        });
+                        yield Ok(StampedData { ts: second, ..frame });
+                    }
+                }
+            }
+        }
+    })
+}
+
 /// This is the counter part to [`hold_stream`]
 ///
 /// It just updates the ts in the watcher
@@ -662,6 +1076,13 @@ fn insert_filler<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
                             ts: last_ts,
                         }))
                     }
+                    VidFormat::Av1 => {
+                        Some(Ok(StampedData {
+                            data: Arc::new(av1_filler(4096)),
+                            keyframe: false,
+                            ts: last_ts,
+                        }))
+                    }
                     VidFormat::None => unreachable!(),
                 }
             }
@@ -682,6 +1103,22 @@ fn h265_filler(size: usize) -> Vec<u8> {
     buf
 }
 
+/// A padding OBU with its size as a four byte leb128 so that it can be any size
+fn av1_filler(size: usize) -> Vec<u8> {
+    assert!(size >= 5);
+    let payload_size = (size - 5) as u32;
+    let mut buf = vec![
+        // OBU_PADDING with the has size flag
+        (15 << 3) | 0b010,
+        (payload_size & 0x7F) as u8 | 0x80,
+        ((payload_size >> 7) & 0x7F) as u8 | 0x80,
+        ((payload_size >> 14) & 0x7F) as u8 | 0x80,
+        ((payload_size >> 21) & 0x7F) as u8,
+    ];
+    buf.resize(size, 0);
+    buf
+}
+
 fn h264_filler(size: usize) -> Vec<u8> {
     assert!(size >= 5);
     let mut buf = vec![0x0, 0x0, 0x1, 0xC];
@@ -690,7 +1127,7 @@ fn h264_filler(size: usize) -> Vec<u8> {
     buf
 }
 
-/// Takes a stream and pads it with filler blocks up to 4kb in h265 or h264 format
+/// Takes a stream and pads it with filler blocks up to 4kb in h265, h264 or av1 format
 fn pad_vid<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
     mut stream: T,
     format: VidFormat,
@@ -698,6 +1135,7 @@ fn pad_vid<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
     let min_pad: usize = match format {
         VidFormat::H264 => 5,
         VidFormat::H265 => 6,
+        VidFormat::Av1 => 5,
         VidFormat::None => unreachable!(),
     };
     Box::pin(async_stream::stream! {
@@ -719,6 +1157,11 @@ fn pad_vid<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
                                             h265_filler(pad_size).iter()
                                         ).copied().collect()
                                     }
+                                    VidFormat::Av1 => {
+                                        frame.data.iter().chain(
+                                            av1_filler(pad_size).iter()
+                                        ).copied().collect()
+                                    }
                                     VidFormat::None => unreachable!(),
                                 }
                             ),
@@ -733,6 +1176,110 @@ fn pad_vid<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
     })
 }
 
+/// The h265 parameter sets last seen in a keyframe
+///
+/// Shared between the clients of a stream so that a client that joins on
+/// a keyframe without them can still be given them
+#[derive(Default)]
+struct HevcParamSets {
+    vps: Option<Vec<u8>>,
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+}
+
+const HEVC_NAL_VPS: u8 = 32;
+const HEVC_NAL_SPS: u8 = 33;
+const HEVC_NAL_PPS: u8 = 34;
+
+/// Split annex b data into its NAL units without the start codes
+fn nal_units(data: &[u8]) -> Vec<&[u8]> {
+    let mut starts = vec![];
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i..i + 3] == [0, 0, 1] {
+            starts.push(i + 3);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, &start)| {
+            let mut end = starts.get(n + 1).map(|next| next - 3).unwrap_or(data.len());
+            // Leave out the leading zero of a four byte start code
+            while end > start && data[end - 1] == 0 && n + 1 < starts.len() {
+                end -= 1;
+            }
+            &data[start..end]
+        })
+        .collect()
+}
+
+/// Takes an h265 stream and makes sure each keyframe carries its VPS, SPS and PPS
+///
+/// `h265parse` cannot produce output until it has seen them. Keyframes
+/// that have them update `param_sets`, those that do not have the last
+/// ones put in front of them. Other formats pass through as they are
+fn hevc_parameter_sets<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
+    mut stream: T,
+    format: VidFormat,
+    param_sets: Arc<Mutex<HevcParamSets>>,
+) -> impl Stream<Item = Result<StampedData, E>> + Unpin {
+    Box::pin(async_stream::stream! {
+        while let Some(frame) = stream.next().await {
+            let frame = match frame {
+                Ok(frame) if frame.keyframe && format == VidFormat::H265 => frame,
+                frame => {
+                    yield frame;
+                    continue;
+                }
+            };
+            let mut found = HevcParamSets::default();
+            for nal in nal_units(frame.data.as_slice()) {
+                let Some(header) = nal.first() else {
+                    continue;
+                };
+                let slot = match (header >> 1) & 0x3f {
+                    HEVC_NAL_VPS => &mut found.vps,
+                    HEVC_NAL_SPS => &mut found.sps,
+                    HEVC_NAL_PPS => &mut found.pps,
+                    _ => continue,
+                };
+                *slot = Some([&[0, 0, 0, 1], nal].concat());
+            }
+
+            let mut missing = vec![];
+            {
+                let mut param_sets = param_sets.lock().unwrap();
+                let param_sets = &mut *param_sets;
+                for (seen, cached) in [
+                    (found.vps, &mut param_sets.vps),
+                    (found.sps, &mut param_sets.sps),
+                    (found.pps, &mut param_sets.pps),
+                ] {
+                    match (seen, cached) {
+                        (Some(nal), cached) => *cached = Some(nal),
+                        (None, Some(nal)) => missing.extend_from_slice(nal),
+                        (None, None) => {}
+                    }
+                }
+            }
+            if missing.is_empty() {
+                yield Ok(frame);
+            } else {
+                log::trace!("Adding the cached h265 parameter sets to a keyframe");
+                yield Ok(StampedData {
+                    keyframe: frame.keyframe,
+                    data: Arc::new([missing.as_slice(), frame.data.as_slice()].concat()),
+                    ts: frame.ts,
+                });
+            }
+        }
+    })
+}
+
 #[allow(dead_code)]
 // This will take a stream and if there is a notibable lack of data
 // then it will repeat the last keyframe (if there have been no
@@ -787,10 +1334,146 @@ fn repeat_keyframe<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
     })
 }
 
+/// Frames waiting to be pushed into an appsrc
+///
+/// Pushing never blocks. When it is full the oldest non keyframe is
+/// dropped (like `leaky=downstream`) so that gstreamer backpressure cannot
+/// hold up the camera
+struct FrameBuffer {
+    // The frames and if the buffer is closed
+    frames: Mutex<(VecDeque<StampedData>, bool)>,
+    ready: Condvar,
+    max_size: usize,
+}
+
+impl FrameBuffer {
+    fn new(max_size: usize) -> Self {
+        Self {
+            frames: Mutex::new((VecDeque::with_capacity(max_size), false)),
+            ready: Condvar::new(),
+            max_size,
+        }
+    }
+
+    /// Returns false if the buffer has been closed
+    fn push(&self, data: StampedData) -> bool {
+        let mut locked = self.frames.lock().unwrap();
+        let (frames, closed) = &mut *locked;
+        if *closed {
+            return false;
+        }
+        frames.push_back(data);
+        if frames.len() > self.max_size {
+            let drop_at = frames.iter().position(|frame| !frame.keyframe).unwrap_or(0);
+            frames.remove(drop_at);
+            log::trace!("Frame buffer full dropping a frame");
+        }
+        self.ready.notify_one();
+        true
+    }
+
+    /// Blocks until there is a frame or the buffer is closed and empty
+    fn pop(&self) -> Option<StampedData> {
+        let mut locked = self.frames.lock().unwrap();
+        loop {
+            let (frames, closed) = &mut *locked;
+            if let Some(frame) = frames.pop_front() {
+                return Some(frame);
+            } else if *closed {
+                return None;
+            }
+            locked = self.ready.wait(locked).unwrap();
+        }
+    }
+
+    fn close(&self) {
+        self.frames.lock().unwrap().1 = true;
+        self.ready.notify_all();
+    }
+}
+
+/// Fill of `max-bytes` in percent above which the non keyframes are dropped
+const PRESSURE_HIGH_PERCENT: u64 = 80;
+/// Fill of `max-bytes` in percent below which dropping stops
+const PRESSURE_LOW_PERCENT: u64 = 60;
+
+/// Drops the non keyframes while the queue of an appsrc is nearly full
+///
+/// This gives the pipeline a chance to catch up before the appsrc starts
+/// refusing buffers. Dropping only stops on a keyframe so that the
+/// decoder is never given a frame that refers to a dropped one
+struct QueuePressure {
+    dropping: bool,
+    dropped: u64,
+    last_report: std::time::Instant,
+}
+
+impl QueuePressure {
+    fn new() -> Self {
+        Self {
+            dropping: false,
+            dropped: 0,
+            last_report: std::time::Instant::now(),
+        }
+    }
+
+    /// Returns true if the frame should be dropped
+    fn should_drop(&mut self, appsrc: &AppSrc, keyframe: bool) -> bool {
+        let was_dropping = self.dropping;
+        let drop = self.update(appsrc.current_level_bytes(), appsrc.max_bytes(), keyframe);
+        if self.dropping && !was_dropping {
+            log::debug!(
+                "Queue over {}% on {}, dropping non keyframes",
+                PRESSURE_HIGH_PERCENT,
+                appsrc.name()
+            );
+        }
+        if self.last_report.elapsed() >= Duration::from_secs(1) {
+            if self.dropped > 0 {
+                log::debug!(
+                    "Dropped {} frames in the last {:?} on {}",
+                    self.dropped,
+                    self.last_report.elapsed(),
+                    appsrc.name()
+                );
+            }
+            self.dropped = 0;
+            self.last_report = std::time::Instant::now();
+        }
+        drop
+    }
+
+    /// Returns true if the frame should be dropped when the queue holds
+    /// `level` of its `max` bytes
+    fn update(&mut self, level: u64, max: u64, keyframe: bool) -> bool {
+        if !self.dropping && max > 0 && level * 100 > max * PRESSURE_HIGH_PERCENT {
+            self.dropping = true;
+        } else if self.dropping && keyframe && level * 100 < max * PRESSURE_LOW_PERCENT {
+            self.dropping = false;
+        }
+
+        let drop = self.dropping && !keyframe;
+        if drop {
+            self.dropped += 1;
+        }
+        drop
+    }
+}
+
 /// Takes a stream and sends it to an appsrc
+///
+/// The frames are buffered by a [`FrameBuffer`] of `frame_buffer_size` frames.
+/// If `drop_under_pressure` is set non keyframes are dropped when the
+/// appsrc's queue is nearly full, see [`QueuePressure`]
+///
+/// The camera's timestamps are only put on the buffers when `timestamps` is
+/// [`FrameTimestampMode::CameraProvided`]
 async fn send_to_appsrc<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
     mut stream: T,
     appsrc: &AppSrc,
+    frame_buffer_size: usize,
+    drop_under_pressure: bool,
+    timestamps: FrameTimestampMode,
 ) -> AnyResult<()> {
     let mut ts_0 = Duration::MAX;
     let mut wait_for_iframe = true;
@@ -798,14 +1481,22 @@ async fn send_to_appsrc<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
     let mut paused = true;
     appsrc.set_state(gstreamer::State::Paused).unwrap();
 
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<StampedData>(2000);
+    let buffer = Arc::new(FrameBuffer::new(frame_buffer_size));
+    let mut pressure = drop_under_pressure.then(QueuePressure::new);
 
     // Run blocking code on a seperate thread
     let appsrc = appsrc.clone();
+    let thread_buffer = buffer.clone();
     std::thread::spawn(move || {
-        let r = (move || {
-            while let Some(data) = rx.blocking_recv() {
+        let r = (|| {
+            while let Some(data) = thread_buffer.pop() {
                 check_live(&appsrc)?; // Stop if appsrc is dropped
+                if pressure
+                    .as_mut()
+                    .is_some_and(|pressure| pressure.should_drop(&appsrc, data.keyframe))
+                {
+                    continue;
+                }
                 if wait_for_iframe && !data.keyframe {
                     continue;
                 } else if wait_for_iframe {
@@ -836,10 +1527,12 @@ async fn send_to_appsrc<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
                     // let mut gst_buf = gstreamer::Buffer::with_size(data.data.len()).unwrap();
                     {
                         let gst_buf_mut = gst_buf.get_mut().unwrap();
-                        let time = ClockTime::from_useconds(rt.as_micros() as u64);
-                        // gst_buf_mut.set_dts(ClockTime::from_useconds(dts));
-                        gst_buf_mut.set_dts(time);
-                        gst_buf_mut.set_pts(time);
+                        if timestamps == FrameTimestampMode::CameraProvided {
+                            let time = ClockTime::from_useconds(rt.as_micros() as u64);
+                            // gst_buf_mut.set_dts(ClockTime::from_useconds(dts));
+                            gst_buf_mut.set_dts(time);
+                            gst_buf_mut.set_pts(time);
+                        }
                         let mut gst_buf_data = gst_buf_mut.map_writable().unwrap();
                         gst_buf_data.copy_from_slice(data.data.as_slice());
                     }
@@ -876,16 +1569,352 @@ async fn send_to_appsrc<E, T: Stream<Item = Result<StampedData, E>> + Unpin>(
             }
             AnyResult::Ok(())
         })();
+        // Stop the sender too
+        thread_buffer.close();
         log::trace!("r: {:?}", r);
         r
     });
 
     // Send to the blocking thread
     while let Some(Ok(data)) = stream.next().await {
-        // Start on iframes
-        if tx.send(data).await.is_err() {
+        if !buffer.push(data) {
             break;
         }
     }
+    buffer.close();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// Feed audio frames every `frame` ms starting from `start` with the
+    /// video at `video(i)` and collect the timestamps that are sent
+    fn av_sync_run(
+        corrector: &mut AvSyncCorrector,
+        frames: u64,
+        frame: u64,
+        video: impl Fn(u64) -> u64,
+    ) -> Vec<Duration> {
+        let mut sent = vec![];
+        for i in 0..frames {
+            match corrector.correct(ms(video(i)), ms(i * frame)) {
+                AvSyncAction::Send(ts) => sent.push(ts),
+                AvSyncAction::Drop => {}
+                AvSyncAction::Repeat(first, second) => sent.extend([first, second]),
+            }
+        }
+        sent
+    }
+
+    #[test]
+    fn av_sync_disabled_by_zero_threshold() {
+        let mut corrector = AvSyncCorrector::new(Duration::ZERO);
+        // Audio running 10% fast
+        let sent = av_sync_run(&mut corrector, 200, 20, |i| i * 18);
+        assert_eq!(sent, (0..200).map(|i| ms(i * 20)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn av_sync_ignores_jitter() {
+        let mut corrector = AvSyncCorrector::new(ms(40));
+        // The video is up to 60ms either side of the audio but does not drift
+        let sent = av_sync_run(&mut corrector, 200, 20, |i| {
+            (i * 20 + 100).saturating_sub([0, 60, 120][i as usize % 3])
+        });
+        assert_eq!(sent, (0..200).map(|i| ms(i * 20)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn av_sync_drops_audio_that_runs_ahead() {
+        let mut corrector = AvSyncCorrector::new(ms(40));
+        // Audio timestamps run 1% faster than the video
+        let frames = 2000;
+        let video = |i| i * 20 - i / 5;
+        let sent = av_sync_run(&mut corrector, frames, 20, video);
+        assert!(sent.len() < frames as usize);
+        assert!(sent.windows(2).all(|pair| pair[0] <= pair[1]));
+        // Without the correction the audio would be 400ms ahead by the end
+        let last = *sent.last().unwrap();
+        let drift = last.as_millis() as i64 - video(frames - 1) as i64;
+        assert!(drift.abs() <= 50, "drift {}ms", drift);
+    }
+
+    #[test]
+    fn av_sync_repeats_audio_that_falls_behind() {
+        let mut corrector = AvSyncCorrector::new(ms(40));
+        // Audio timestamps run 1% slower than the video
+        let frames = 2000;
+        let video = |i| i * 20 + i / 5;
+        let sent = av_sync_run(&mut corrector, frames, 20, video);
+        assert!(sent.len() > frames as usize);
+        assert!(sent.windows(2).all(|pair| pair[0] <= pair[1]));
+        let last = *sent.last().unwrap();
+        let drift = last.as_millis() as i64 - video(frames - 1) as i64;
+        assert!(drift.abs() <= 50, "drift {}ms", drift);
+    }
+
+    #[test]
+    fn av_sync_never_goes_backwards() {
+        let mut corrector = AvSyncCorrector::new(ms(40));
+        let mut sent = av_sync_run(&mut corrector, 50, 20, |i| i * 20);
+        // The camera's audio clock jumps back
+        for i in 0..50 {
+            if let AvSyncAction::Send(ts) = corrector.correct(ms(1000 + i * 20), ms(500 + i * 20)) {
+                sent.push(ts);
+            }
+        }
+        assert!(sent.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    fn frame(ts: u64, keyframe: bool) -> StampedData {
+        StampedData {
+            keyframe,
+            data: Arc::new(vec![]),
+            ts: ms(ts),
+        }
+    }
+
+    fn drain(buffer: &FrameBuffer) -> Vec<u64> {
+        buffer.close();
+        std::iter::from_fn(|| buffer.pop())
+            .map(|frame| frame.ts.as_millis() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn frame_buffer_keeps_up_to_capacity() {
+        let buffer = FrameBuffer::new(3);
+        for ts in 0..3 {
+            assert!(buffer.push(frame(ts, false)));
+        }
+        assert_eq!(drain(&buffer), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn frame_buffer_drops_oldest_non_keyframe() {
+        let buffer = FrameBuffer::new(3);
+        buffer.push(frame(0, true));
+        buffer.push(frame(1, false));
+        buffer.push(frame(2, false));
+        buffer.push(frame(3, false));
+        assert_eq!(drain(&buffer), vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn frame_buffer_drops_oldest_when_all_keyframes() {
+        let buffer = FrameBuffer::new(2);
+        buffer.push(frame(0, true));
+        buffer.push(frame(1, true));
+        buffer.push(frame(2, true));
+        assert_eq!(drain(&buffer), vec![1, 2]);
+    }
+
+    #[test]
+    fn frame_buffer_refuses_push_after_close() {
+        let buffer = FrameBuffer::new(2);
+        buffer.push(frame(0, true));
+        buffer.close();
+        assert!(!buffer.push(frame(1, false)));
+        // What was already buffered can still be read
+        assert_eq!(buffer.pop().map(|frame| frame.ts), Some(ms(0)));
+        assert!(buffer.pop().is_none());
+    }
+
+    #[test]
+    fn frame_buffer_wakes_waiting_pop() {
+        let buffer = Arc::new(FrameBuffer::new(2));
+        let reader = {
+            let buffer = buffer.clone();
+            std::thread::spawn(move || {
+                let first = buffer.pop().map(|frame| frame.ts);
+                let second = buffer.pop().map(|frame| frame.ts);
+                (first, second)
+            })
+        };
+        std::thread::sleep(ms(50));
+        buffer.push(frame(7, true));
+        std::thread::sleep(ms(50));
+        // Close must wake a pop that is waiting on an empty buffer
+        buffer.close();
+        assert_eq!(reader.join().unwrap(), (Some(ms(7)), None));
+    }
+
+    #[test]
+    fn queue_pressure_drops_non_keyframes_over_high_mark() {
+        let mut pressure = QueuePressure::new();
+        assert!(!pressure.update(80, 100, false));
+        assert!(pressure.update(81, 100, false));
+        // Keyframes always go through
+        assert!(!pressure.update(90, 100, true));
+        assert_eq!(pressure.dropped, 1);
+    }
+
+    #[test]
+    fn queue_pressure_stops_on_keyframe_under_low_mark() {
+        let mut pressure = QueuePressure::new();
+        assert!(pressure.update(90, 100, false));
+        // Between the marks it keeps dropping
+        assert!(!pressure.update(70, 100, true));
+        assert!(pressure.update(70, 100, false));
+        // Under the low mark it waits for a keyframe so no frame refers
+        // to a dropped one
+        assert!(pressure.update(10, 100, false));
+        assert!(!pressure.update(10, 100, true));
+        assert!(!pressure.update(10, 100, false));
+    }
+
+    #[test]
+    fn queue_pressure_ignores_unlimited_queue() {
+        let mut pressure = QueuePressure::new();
+        assert!(!pressure.update(1_000_000, 0, false));
+    }
+
+    #[test]
+    fn nal_units_three_byte_start_codes() {
+        let data = [0, 0, 1, 0x40, 0x01, 0, 0, 1, 0x42, 0x01, 0xaa];
+        assert_eq!(
+            nal_units(&data),
+            vec![&[0x40, 0x01][..], &[0x42, 0x01, 0xaa][..]]
+        );
+    }
+
+    #[test]
+    fn nal_units_four_byte_start_codes() {
+        let data = [0, 0, 0, 1, 0x40, 0x01, 0, 0, 0, 1, 0x42, 0x01, 0xaa];
+        assert_eq!(
+            nal_units(&data),
+            vec![&[0x40, 0x01][..], &[0x42, 0x01, 0xaa][..]]
+        );
+    }
+
+    #[test]
+    fn nal_units_mixed_start_codes() {
+        let data = [
+            0, 0, 0, 1, 0x40, 0x01, 0, 0, 1, 0x42, 0x01, 0, 0, 0, 1, 0x44,
+        ];
+        assert_eq!(
+            nal_units(&data),
+            vec![&[0x40, 0x01][..], &[0x42, 0x01][..], &[0x44][..]]
+        );
+        assert!(nal_units(&[0xaa, 0xbb]).is_empty());
+    }
+
+    const VPS: [u8; 3] = [0x40, 0x01, 0x0c];
+    const SPS: [u8; 3] = [0x42, 0x01, 0x01];
+    const PPS: [u8; 3] = [0x44, 0x01, 0xc1];
+    const IDR: [u8; 3] = [0x26, 0x01, 0xaf];
+
+    fn annex_b(nals: &[&[u8]]) -> Vec<u8> {
+        nals.iter()
+            .flat_map(|nal| [&[0, 0, 0, 1][..], nal].concat())
+            .collect()
+    }
+
+    fn stamped(data: Vec<u8>, keyframe: bool) -> Result<StampedData> {
+        Ok(StampedData {
+            keyframe,
+            data: Arc::new(data),
+            ts: Duration::ZERO,
+        })
+    }
+
+    async fn run_hevc(format: VidFormat, frames: Vec<Result<StampedData>>) -> Vec<Vec<u8>> {
+        let param_sets = Arc::new(Mutex::new(HevcParamSets::default()));
+        hevc_parameter_sets(tokio_stream::iter(frames), format, param_sets)
+            .map(|frame| frame.unwrap().data.as_ref().clone())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn hevc_parameter_sets_are_extracted() {
+        let param_sets = Arc::new(Mutex::new(HevcParamSets::default()));
+        let frames = vec![stamped(annex_b(&[&VPS, &SPS, &PPS, &IDR]), true)];
+        let _ = hevc_parameter_sets(
+            tokio_stream::iter(frames),
+            VidFormat::H265,
+            param_sets.clone(),
+        )
+        .collect::<Vec<_>>()
+        .await;
+        let param_sets = param_sets.lock().unwrap();
+        assert_eq!(param_sets.vps, Some(annex_b(&[&VPS])));
+        assert_eq!(param_sets.sps, Some(annex_b(&[&SPS])));
+        assert_eq!(param_sets.pps, Some(annex_b(&[&PPS])));
+    }
+
+    #[tokio::test]
+    async fn hevc_parameter_sets_are_added_to_keyframes() {
+        let full = annex_b(&[&VPS, &SPS, &PPS, &IDR]);
+        let bare = annex_b(&[&IDR]);
+        let p_frame = annex_b(&[&[0x02, 0x01, 0xd0]]);
+        let out = run_hevc(
+            VidFormat::H265,
+            vec![
+                stamped(full.clone(), true),
+                stamped(p_frame.clone(), false),
+                stamped(bare, true),
+            ],
+        )
+        .await;
+        assert_eq!(out, vec![full.clone(), p_frame, full]);
+    }
+
+    #[tokio::test]
+    async fn hevc_parameter_sets_leave_other_formats() {
+        let bare = annex_b(&[&IDR]);
+        let out = run_hevc(
+            VidFormat::H264,
+            vec![
+                stamped(annex_b(&[&VPS, &SPS, &PPS, &IDR]), true),
+                stamped(bare.clone(), true),
+            ],
+        )
+        .await;
+        assert_eq!(out[1], bare);
+    }
+
+    #[test]
+    fn reload_limiter_allows_changes_up_to_limit() {
+        let start = Instant::now();
+        let mut limiter = ReloadLimiter::new();
+        for i in 0..ReloadLimiter::MAX_CHANGES as u64 {
+            assert!(!limiter.record(start + Duration::from_secs(i)));
+        }
+        assert!(limiter.throttled_until().is_none());
+        // One more inside the window starts the throttle
+        let now = start + Duration::from_secs(ReloadLimiter::MAX_CHANGES as u64);
+        assert!(limiter.record(now));
+        assert_eq!(limiter.throttled_until, Some(now + ReloadLimiter::THROTTLE));
+        assert!(limiter.throttled_until().is_some());
+        // The count starts again after a throttle
+        assert!(!limiter.record(now + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn reload_limiter_forgets_changes_outside_window() {
+        let start = Instant::now();
+        let mut limiter = ReloadLimiter::new();
+        let spacing = ReloadLimiter::WINDOW / ReloadLimiter::MAX_CHANGES as u32 + ms(1);
+        for i in 0..(ReloadLimiter::MAX_CHANGES as u32 * 4) {
+            assert!(!limiter.record(start + spacing * i));
+        }
+        assert!(limiter.throttled_until().is_none());
+    }
+
+    #[test]
+    fn reload_limiter_window_is_inclusive() {
+        let start = Instant::now();
+        let mut limiter = ReloadLimiter::new();
+        for _ in 0..ReloadLimiter::MAX_CHANGES {
+            limiter.record(start);
+        }
+        assert!(limiter.record(start + ReloadLimiter::WINDOW));
+    }
+}
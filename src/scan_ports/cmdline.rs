@@ -0,0 +1,19 @@
+use clap::Parser;
+
+/// The scan-ports command looks for the ports of a camera that talk the BC protocol
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The ip address or host name of the camera
+    #[arg(long)]
+    pub camera_address: String,
+    /// How long each port is given to connect and reply in milliseconds
+    #[arg(long, default_value_t = 2000)]
+    pub timeout: u64,
+    /// The ports to try
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "9000,8000,80,443,1935,34567,37777"
+    )]
+    pub ports: Vec<u16>,
+}
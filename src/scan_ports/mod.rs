@@ -0,0 +1,109 @@
+///
+/// # Neolink Scan Ports
+///
+/// This module looks for the port that a camera serves the BC protocol on
+/// for when the usual port 9000 does not connect
+///
+/// Each port is tried at once. The first message of the login is sent and
+/// the port is reported as BC if the camera replies with its login
+/// challenge. No credentials are sent and the login is not completed so a
+/// config is not needed
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink scan-ports --camera-address=192.168.1.187
+/// # Other ports or a longer timeout in ms
+/// neolink scan-ports --camera-address=192.168.1.187 --ports=9000,9001 --timeout=5000
+/// ```
+///
+use anyhow::Result;
+use neolink_core::bc_protocol::{is_login_challenge_reply, login_challenge_request};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    task::JoinSet,
+    time::{timeout, Duration},
+};
+
+mod cmdline;
+
+pub(crate) use cmdline::Opt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PortStatus {
+    /// Replied with a BC login challenge
+    Bc,
+    /// Accepted the connection but did not reply with BC
+    Open,
+    /// Refused or did not accept the connection in time
+    Closed,
+}
+
+impl std::fmt::Display for PortStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            PortStatus::Bc => "BC protocol",
+            PortStatus::Open => "open (not BC)",
+            PortStatus::Closed => "closed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Entry point for the scan-ports subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt) -> Result<()> {
+    let port_timeout = Duration::from_millis(opt.timeout);
+
+    let mut set = JoinSet::new();
+    for port in opt.ports.iter().copied() {
+        let host = opt.camera_address.clone();
+        set.spawn(async move { (port, scan_port(&host, port, port_timeout).await) });
+    }
+    let mut results = vec![];
+    while let Some(result) = set.join_next().await {
+        results.push(result?);
+    }
+    // Keep the order that the ports were given in
+    results.sort_by_key(|(port, _)| opt.ports.iter().position(|p| p == port));
+
+    println!("{:<8} STATUS", "PORT");
+    for (port, status) in results.iter() {
+        println!("{:<8} {}", port, status);
+    }
+    let found = results
+        .iter()
+        .filter(|(_, status)| *status == PortStatus::Bc)
+        .map(|(port, _)| port.to_string())
+        .collect::<Vec<_>>();
+    if found.is_empty() {
+        println!("No port replied with the BC protocol");
+    } else {
+        println!(
+            "Try address = \"{}:{}\" in the config",
+            opt.camera_address, found[0]
+        );
+    }
+    Ok(())
+}
+
+async fn scan_port(host: &str, port: u16, port_timeout: Duration) -> PortStatus {
+    let mut socket = match timeout(port_timeout, TcpStream::connect((host, port))).await {
+        Ok(Ok(socket)) => socket,
+        _ => return PortStatus::Closed,
+    };
+    let reply = timeout(port_timeout, async {
+        socket.write_all(&login_challenge_request()).await?;
+        let mut header = [0u8; 8];
+        socket.read_exact(&mut header).await?;
+        std::io::Result::Ok(header)
+    })
+    .await;
+    match reply {
+        Ok(Ok(header)) if is_login_challenge_reply(&header) => PortStatus::Bc,
+        _ => PortStatus::Open,
+    }
+}
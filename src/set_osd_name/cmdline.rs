@@ -0,0 +1,23 @@
+use clap::{ArgGroup, Parser};
+
+/// The set-osd-name command will change the name that the camera draws on screen
+#[derive(Parser, Debug)]
+#[command(group(
+    ArgGroup::new("change")
+        .required(true)
+        .multiple(true)
+        .args(["display_name", "enable_osd", "disable_osd"])
+))]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The name to draw like "Front Door"
+    #[arg(long)]
+    pub display_name: Option<String>,
+    /// Draw the name and the date/time
+    #[arg(long, conflicts_with = "disable_osd")]
+    pub enable_osd: bool,
+    /// Draw neither the name nor the date/time
+    #[arg(long)]
+    pub disable_osd: bool,
+}
@@ -0,0 +1,69 @@
+///
+/// # Neolink Set OSD Name
+///
+/// This module renames the camera on the on screen display without
+/// changing any of the other OSD settings. It can also turn the whole OSD
+/// on or off
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink set-osd-name --config=config.toml CameraName --display-name "Front Door"
+/// # To hide the name and date/time
+/// neolink set-osd-name --config=config.toml CameraName --disable-osd
+/// ```
+///
+use anyhow::{Context, Result};
+
+mod cmdline;
+
+use crate::common::NeoReactor;
+pub(crate) use cmdline::Opt;
+
+/// Entry point for the set-osd-name subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let enabled = match (opt.enable_osd, opt.disable_osd) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    };
+
+    let osd = camera
+        .run_task(|cam| {
+            let display_name = opt.display_name.clone();
+            Box::pin(async move {
+                let mut osd = cam
+                    .get_osd_config()
+                    .await
+                    .context("Unable to get camera OSD settings")?;
+                if let Some(display_name) = display_name {
+                    osd.camera_name = display_name;
+                }
+                if let Some(enabled) = enabled {
+                    osd.camera_name_enabled = enabled;
+                    osd.timestamp_enabled = enabled;
+                }
+                cam.set_osd_config(&osd)
+                    .await
+                    .context("Unable to set camera OSD settings")?;
+                Ok(osd)
+            })
+        })
+        .await?;
+
+    println!(
+        "{}: OSD name is {:?} and the OSD is {}",
+        opt.camera,
+        osd.camera_name,
+        if osd.camera_name_enabled || osd.timestamp_enabled {
+            "shown"
+        } else {
+            "hidden"
+        }
+    );
+    Ok(())
+}
@@ -0,0 +1,11 @@
+use clap::Parser;
+
+/// The set-password command will change the password that neolink logs into the camera with
+///
+/// The new password is asked for on the terminal. Once the camera has
+/// accepted it the password in the config file is updated
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+}
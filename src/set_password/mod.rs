@@ -0,0 +1,107 @@
+///
+/// # Neolink Set Password
+///
+/// This module changes the password of the user that neolink logs into
+/// the camera with and then writes it into the config file
+///
+/// The password is read from the terminal without echoing it and is
+/// never printed or logged
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink set-password --config=config.toml CameraName
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+
+mod cmdline;
+
+use crate::common::NeoReactor;
+pub(crate) use cmdline::Opt;
+
+/// Entry point for the set-password subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor, config_path: &Path) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let config = camera.config().await?.borrow().clone();
+
+    let password = tokio::task::spawn_blocking(prompt_password).await??;
+
+    let username = config.username.clone();
+    camera
+        .run_task(|cam| {
+            let username = username.clone();
+            let password = password.clone();
+            Box::pin(async move {
+                cam.set_password(&username, &password)
+                    .await
+                    .context("Unable to set the password")?;
+                Ok(())
+            })
+        })
+        .await?;
+
+    // The channels of an NVR are written in the config under the camera
+    // that owns the connection
+    let config_name = config.shared_connection.as_deref().unwrap_or(&config.name);
+    write_password(config_path, config_name, &password).with_context(|| {
+        format!(
+            "The camera's password was changed but the {:?} config file could not be updated",
+            config_path
+        )
+    })?;
+    log::info!("Password updated successfully");
+    Ok(())
+}
+
+/// Ask for the new password twice on the terminal
+fn prompt_password() -> Result<String> {
+    let password = rpassword::prompt_password("New password: ")?;
+    if password.is_empty() {
+        return Err(anyhow!("The password cannot be empty"));
+    }
+    let confirm = rpassword::prompt_password("Confirm password: ")?;
+    if password != confirm {
+        return Err(anyhow!("The passwords do not match"));
+    }
+    Ok(password)
+}
+
+/// Replace the password of a camera in the config file
+///
+/// The rest of the file including its comments is kept as it is. The new
+/// file is written next to the old one and then renamed over it so that the
+/// config is never left half written
+fn write_password(config_path: &Path, camera_name: &str, password: &str) -> Result<()> {
+    let mut doc = std::fs::read_to_string(config_path)?.parse::<toml_edit::DocumentMut>()?;
+    let camera = doc
+        .get_mut("cameras")
+        .and_then(|cameras| cameras.as_array_of_tables_mut())
+        .and_then(|cameras| {
+            cameras.iter_mut().find(|camera| {
+                camera.get("name").and_then(|name| name.as_str()) == Some(camera_name)
+            })
+        })
+        .ok_or_else(|| anyhow!("No [[cameras]] table with the name {camera_name}"))?;
+    let key = if camera.contains_key("pass") {
+        "pass"
+    } else {
+        "password"
+    };
+    camera[key] = toml_edit::value(password);
+
+    let mut tmp_name = config_path
+        .file_name()
+        .ok_or_else(|| anyhow!("The config path is not a file"))?
+        .to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = config_path.with_file_name(tmp_name);
+    std::fs::write(&tmp_path, doc.to_string())?;
+    std::fs::set_permissions(&tmp_path, std::fs::metadata(config_path)?.permissions())?;
+    std::fs::rename(&tmp_path, config_path)?;
+    Ok(())
+}
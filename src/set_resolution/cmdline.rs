@@ -0,0 +1,31 @@
+use crate::common::{codec_parse, stream_parse};
+use clap::Parser;
+use neolink_core::{bc_protocol::StreamKind, bcmedia::model::VideoType};
+
+/// The set-resolution command will change the resolution, frame rate and bitrate of a stream
+///
+/// Values not given are left as they are. When nothing is given the
+/// current settings and supported resolutions are printed
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The stream to change: main, sub or extern
+    #[arg(long, value_parser = stream_parse, default_value = "main")]
+    pub stream: StreamKind,
+    /// Width of the stream in pixels
+    #[arg(long, requires = "height")]
+    pub width: Option<u32>,
+    /// Height of the stream in pixels
+    #[arg(long, requires = "width")]
+    pub height: Option<u32>,
+    /// Frames per second
+    #[arg(long)]
+    pub fps: Option<u8>,
+    /// Bitrate in kbps
+    #[arg(long)]
+    pub bitrate: Option<u32>,
+    /// Video codec: h264 or h265
+    #[arg(long, value_parser = codec_parse)]
+    pub codec: Option<VideoType>,
+}
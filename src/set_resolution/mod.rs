@@ -0,0 +1,99 @@
+///
+/// # Neolink Set Resolution
+///
+/// This module changes the resolution, frame rate, bitrate and codec
+/// of one of the camera's streams
+///
+/// The resolution is checked against those that the camera reports for
+/// the stream before it is sent
+///
+///
+/// # Usage
+///
+/// ```bash
+/// # To print the current settings and supported resolutions
+/// neolink set-resolution --config=config.toml CameraName --stream=main
+/// # To change them
+/// neolink set-resolution --config=config.toml CameraName --stream=main --width=2560 --height=1440 --fps=15 --bitrate=4096
+/// ```
+///
+use anyhow::{Context, Result};
+use neolink_core::bcmedia::model::VideoType;
+
+mod cmdline;
+
+use crate::common::NeoReactor;
+pub(crate) use cmdline::Opt;
+
+/// Entry point for the set-resolution subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let stream = opt.stream;
+    let resolution = opt.width.zip(opt.height);
+    let fps = opt.fps;
+    let bitrate = opt.bitrate;
+    let codec = opt.codec.clone();
+    let changed = resolution.is_some() || fps.is_some() || bitrate.is_some() || codec.is_some();
+
+    let (encoding, supported) = camera
+        .run_task(|cam| {
+            let codec = codec.clone();
+            Box::pin(async move {
+                let mut encoding = cam
+                    .get_encoding_config(stream)
+                    .await
+                    .context("Unable to get the stream encoding settings")?;
+                if changed {
+                    if let Some(resolution) = resolution {
+                        encoding.resolution = resolution;
+                    }
+                    if let Some(fps) = fps {
+                        encoding.frame_rate = fps;
+                    }
+                    if let Some(bitrate) = bitrate {
+                        encoding.bitrate_kbps = bitrate;
+                    }
+                    if let Some(codec) = codec {
+                        encoding.codec = codec;
+                    }
+                    cam.set_encoding_config(stream, &encoding)
+                        .await
+                        .context("Unable to set the stream encoding settings")?;
+                }
+                let supported = cam
+                    .get_supported_resolutions(stream)
+                    .await
+                    .context("Unable to get the supported resolutions")?;
+                Ok((encoding, supported))
+            })
+        })
+        .await?;
+
+    println!(
+        "resolution: {}x{}",
+        encoding.resolution.0, encoding.resolution.1
+    );
+    println!("fps:        {}", encoding.frame_rate);
+    println!("bitrate:    {} kbps", encoding.bitrate_kbps);
+    println!(
+        "codec:      {}",
+        match encoding.codec {
+            VideoType::H264 => "h264",
+            VideoType::H265 => "h265",
+            VideoType::Av1 => "av1",
+        }
+    );
+    if !changed {
+        println!(
+            "supported:  {}",
+            supported
+                .iter()
+                .map(|(width, height)| format!("{width}x{height}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use clap::{ArgGroup, Parser};
+
+/// The set-timezone command will change the time zone of the camera
+#[derive(Parser, Debug)]
+#[command(group(
+    ArgGroup::new("zone")
+        .required(true)
+        .args(["timezone", "use_system"])
+))]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The time zone as an IANA name like "Europe/London" or an offset like "+05:30"
+    #[arg(long)]
+    pub timezone: Option<String>,
+    /// Use the time zone of this computer
+    #[arg(long)]
+    pub use_system: bool,
+}
@@ -0,0 +1,177 @@
+///
+/// # Neolink Set Timezone
+///
+/// This module sets the time zone that the camera shows its time in,
+/// either from an IANA name like `Europe/London`, an offset like `+05:30`
+/// or from the time zone of this computer
+///
+/// The camera only stores an offset from UTC and does not follow daylight
+/// saving by itself. An IANA name is set to its standard offset, with a
+/// warning if the zone has daylight saving, while `--use-system` sets the
+/// offset that this computer is using now, so it should be run again when
+/// the clocks change
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink set-timezone --config=config.toml CameraName --timezone=Europe/Berlin
+/// # Use the time zone of this computer
+/// neolink set-timezone --config=config.toml CameraName --use-system
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+
+mod cmdline;
+mod zones;
+
+use crate::common::NeoReactor;
+pub(crate) use cmdline::Opt;
+
+/// Entry point for the set-timezone subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let (name, offset) = match opt.timezone.as_deref() {
+        Some(timezone) => {
+            let offset = parse_timezone(timezone)?;
+            if zones::has_dst(timezone) {
+                log::warn!(
+                    "{} has daylight saving but the camera only keeps an offset. It is set to the standard offset UTC{} which is wrong while daylight saving is in effect. Use --use-system or give the offset instead then",
+                    timezone,
+                    format_offset(offset)
+                );
+            }
+            (timezone.to_string(), offset)
+        }
+        None => {
+            let name = system_timezone_name().unwrap_or_else(|| "Local".to_string());
+            (name, Local::now().offset().local_minus_utc())
+        }
+    };
+
+    let camera = reactor.get(&opt.camera).await?;
+    let (old, new) = camera
+        .run_task(|cam| {
+            Box::pin(async move {
+                let old = cam
+                    .get_timezone()
+                    .await
+                    .context("Unable to get camera time zone")?;
+                cam.set_timezone(offset)
+                    .await
+                    .context("Unable to set camera time zone")?;
+                let new = cam
+                    .get_timezone()
+                    .await
+                    .context("Unable to get camera time zone")?;
+                Ok((old, new))
+            })
+        })
+        .await?;
+
+    println!(
+        "{}: Time zone changed from UTC{} to UTC{} ({})",
+        opt.camera,
+        format_offset(old),
+        format_offset(new),
+        name
+    );
+    Ok(())
+}
+
+/// The offset in seconds east of UTC of an IANA name or an offset like `+05:30`
+fn parse_timezone(timezone: &str) -> Result<i32> {
+    if let Some(offset) = zones::offset_of(timezone) {
+        return Ok(offset);
+    }
+    let offset = timezone
+        .strip_prefix("UTC")
+        .or_else(|| timezone.strip_prefix("GMT"))
+        .unwrap_or(timezone);
+    let (sign, rest) = match offset.chars().next() {
+        Some('+') => (1, &offset[1..]),
+        Some('-') => (-1, &offset[1..]),
+        _ => {
+            return Err(anyhow!(
+                "Unknown time zone {:?}. Use an offset like +05:30 instead",
+                timezone
+            ))
+        }
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() > 2 => rest.split_at(rest.len() - 2),
+        None => (rest, "0"),
+    };
+    let hours: i32 = hours
+        .parse()
+        .with_context(|| format!("Invalid hours in offset {:?}", timezone))?;
+    let minutes: i32 = minutes
+        .parse()
+        .with_context(|| format!("Invalid minutes in offset {:?}", timezone))?;
+    if hours > 14 || minutes >= 60 {
+        return Err(anyhow!("The offset {:?} is out of range", timezone));
+    }
+    Ok(sign * (hours * 3600 + minutes * 60))
+}
+
+/// The IANA name of the time zone of this computer from `TZ` or `/etc/timezone`
+fn system_timezone_name() -> Option<String> {
+    std::env::var("TZ")
+        .ok()
+        .map(|tz| tz.trim_start_matches(':').to_string())
+        .or_else(|| std::fs::read_to_string("/etc/timezone").ok())
+        .or_else(|| {
+            // Otherwise /etc/localtime links to /usr/share/zoneinfo/<name>
+            let target = std::fs::read_link("/etc/localtime").ok()?;
+            let target = target.to_string_lossy();
+            target
+                .split_once("zoneinfo/")
+                .map(|(_, name)| name.to_string())
+        })
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+fn format_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    format!("{}{:02}:{:02}", sign, offset / 3600, (offset % 3600) / 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_timezone_names() {
+        assert_eq!(parse_timezone("Europe/London").unwrap(), 0);
+        assert_eq!(parse_timezone("Europe/Berlin").unwrap(), 3600);
+        assert!(parse_timezone("Mars/Olympus_Mons").is_err());
+    }
+
+    #[test]
+    fn parse_timezone_offsets() {
+        assert_eq!(parse_timezone("+05:30").unwrap(), 5 * 3600 + 30 * 60);
+        assert_eq!(parse_timezone("-03:30").unwrap(), -(3 * 3600 + 30 * 60));
+        assert_eq!(parse_timezone("+0545").unwrap(), 5 * 3600 + 45 * 60);
+        assert_eq!(parse_timezone("-8").unwrap(), -8 * 3600);
+        assert_eq!(parse_timezone("UTC+8").unwrap(), 8 * 3600);
+        assert_eq!(parse_timezone("GMT-02:00").unwrap(), -2 * 3600);
+
+        assert!(parse_timezone("05:30").is_err());
+        assert!(parse_timezone("+15").is_err());
+        assert!(parse_timezone("+05:60").is_err());
+        assert!(parse_timezone("+aa:00").is_err());
+    }
+
+    #[test]
+    fn format_offsets() {
+        assert_eq!(format_offset(0), "+00:00");
+        assert_eq!(format_offset(5 * 3600 + 30 * 60), "+05:30");
+        assert_eq!(format_offset(-(3 * 3600 + 30 * 60)), "-03:30");
+        assert_eq!(format_offset(parse_timezone("-09:45").unwrap()), "-09:45");
+    }
+}
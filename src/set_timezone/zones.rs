@@ -0,0 +1,164 @@
+//! The UTC offsets of the IANA time zones
+//!
+//! The camera only stores an offset so each zone is mapped to its
+//! standard (winter) offset in minutes east of UTC along with whether it
+//! has daylight saving, in which case that offset is only right in winter
+
+const ZONES: &[(&str, i32, bool)] = &[
+    ("UTC", 0, false),
+    ("Etc/UTC", 0, false),
+    ("GMT", 0, false),
+    ("Etc/GMT", 0, false),
+    // Africa
+    ("Africa/Abidjan", 0, false),
+    ("Africa/Accra", 0, false),
+    ("Africa/Algiers", 60, false),
+    ("Africa/Cairo", 120, true),
+    ("Africa/Casablanca", 60, true),
+    ("Africa/Johannesburg", 120, false),
+    ("Africa/Lagos", 60, false),
+    ("Africa/Nairobi", 180, false),
+    ("Africa/Tunis", 60, false),
+    // America
+    ("America/Anchorage", -540, true),
+    ("America/Argentina/Buenos_Aires", -180, false),
+    ("America/Bogota", -300, false),
+    ("America/Caracas", -240, false),
+    ("America/Chicago", -360, true),
+    ("America/Denver", -420, true),
+    ("America/Edmonton", -420, true),
+    ("America/Halifax", -240, true),
+    ("America/Lima", -300, false),
+    ("America/Los_Angeles", -480, true),
+    ("America/Mexico_City", -360, false),
+    ("America/New_York", -300, true),
+    ("America/Phoenix", -420, false),
+    ("America/Santiago", -240, true),
+    ("America/Sao_Paulo", -180, false),
+    ("America/St_Johns", -210, true),
+    ("America/Toronto", -300, true),
+    ("America/Vancouver", -480, true),
+    ("America/Winnipeg", -360, true),
+    ("Pacific/Honolulu", -600, false),
+    // Asia
+    ("Asia/Bangkok", 420, false),
+    ("Asia/Dhaka", 360, false),
+    ("Asia/Dubai", 240, false),
+    ("Asia/Ho_Chi_Minh", 420, false),
+    ("Asia/Hong_Kong", 480, false),
+    ("Asia/Jakarta", 420, false),
+    ("Asia/Jerusalem", 120, true),
+    ("Asia/Karachi", 300, false),
+    ("Asia/Kathmandu", 345, false),
+    ("Asia/Kolkata", 330, false),
+    ("Asia/Kuala_Lumpur", 480, false),
+    ("Asia/Manila", 480, false),
+    ("Asia/Riyadh", 180, false),
+    ("Asia/Seoul", 540, false),
+    ("Asia/Shanghai", 480, false),
+    ("Asia/Singapore", 480, false),
+    ("Asia/Taipei", 480, false),
+    ("Asia/Tehran", 210, false),
+    ("Asia/Tokyo", 540, false),
+    // Atlantic
+    ("Atlantic/Azores", -60, true),
+    ("Atlantic/Reykjavik", 0, false),
+    // Australia and the Pacific
+    ("Australia/Adelaide", 570, true),
+    ("Australia/Brisbane", 600, false),
+    ("Australia/Darwin", 570, false),
+    ("Australia/Melbourne", 600, true),
+    ("Australia/Perth", 480, false),
+    ("Australia/Sydney", 600, true),
+    ("Pacific/Auckland", 720, true),
+    ("Pacific/Fiji", 720, false),
+    // Europe
+    ("Europe/Amsterdam", 60, true),
+    ("Europe/Athens", 120, true),
+    ("Europe/Berlin", 60, true),
+    ("Europe/Brussels", 60, true),
+    ("Europe/Bucharest", 120, true),
+    ("Europe/Budapest", 60, true),
+    ("Europe/Copenhagen", 60, true),
+    ("Europe/Dublin", 0, true),
+    ("Europe/Helsinki", 120, true),
+    ("Europe/Istanbul", 180, false),
+    ("Europe/Kyiv", 120, true),
+    ("Europe/Lisbon", 0, true),
+    ("Europe/London", 0, true),
+    ("Europe/Madrid", 60, true),
+    ("Europe/Moscow", 180, false),
+    ("Europe/Oslo", 60, true),
+    ("Europe/Paris", 60, true),
+    ("Europe/Prague", 60, true),
+    ("Europe/Rome", 60, true),
+    ("Europe/Stockholm", 60, true),
+    ("Europe/Vienna", 60, true),
+    ("Europe/Warsaw", 60, true),
+    ("Europe/Zurich", 60, true),
+];
+
+/// The standard offset in seconds east of UTC of an IANA time zone
+pub(super) fn offset_of(name: &str) -> Option<i32> {
+    ZONES
+        .iter()
+        .find(|(zone, _, _)| zone.eq_ignore_ascii_case(name))
+        .map(|(_, minutes, _)| minutes * 60)
+}
+
+/// If the IANA time zone moves its clocks for daylight saving
+pub(super) fn has_dst(name: &str) -> bool {
+    ZONES
+        .iter()
+        .find(|(zone, _, _)| zone.eq_ignore_ascii_case(name))
+        .map(|(_, _, dst)| *dst)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn zone_names_are_unique() {
+        let mut seen = HashSet::new();
+        for (name, _, _) in ZONES {
+            assert!(
+                seen.insert(name.to_ascii_lowercase()),
+                "{} is listed twice",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn zone_offsets_are_in_range() {
+        for (name, minutes, _) in ZONES {
+            assert!(
+                (-12 * 60..=14 * 60).contains(minutes),
+                "{} is out of range",
+                name
+            );
+            assert_eq!(minutes % 15, 0, "{} is not a whole quarter hour", name);
+        }
+    }
+
+    #[test]
+    fn offset_of_names() {
+        assert_eq!(offset_of("UTC"), Some(0));
+        assert_eq!(offset_of("Europe/Paris"), Some(3600));
+        assert_eq!(offset_of("america/new_york"), Some(-5 * 3600));
+        assert_eq!(offset_of("America/St_Johns"), Some(-(3 * 3600 + 30 * 60)));
+        assert_eq!(offset_of("Mars/Olympus_Mons"), None);
+    }
+
+    #[test]
+    fn dst_zones() {
+        assert!(has_dst("Europe/Berlin"));
+        assert!(has_dst("america/new_york"));
+        assert!(!has_dst("Asia/Tokyo"));
+        assert!(!has_dst("UTC"));
+        assert!(!has_dst("Mars/Olympus_Mons"));
+    }
+}
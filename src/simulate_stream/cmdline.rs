@@ -0,0 +1,36 @@
+use crate::common::codec_parse;
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use neolink_core::bcmedia::model::VideoType;
+
+fn resolution_parse(src: &str) -> Result<(u32, u32)> {
+    src.split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .filter(|(width, height)| *width > 0 && *height > 0)
+        .ok_or_else(|| {
+            anyhow!(
+                "Could not understand {}, check your input, should be like 1280x720",
+                src
+            )
+        })
+}
+
+/// The simulate-stream command serves a test pattern over rtsp without a camera
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The rtsp path to serve the stream at
+    #[arg(long, default_value = "/test")]
+    pub path: String,
+    /// Video codec: h264 or h265
+    #[arg(long, value_parser = codec_parse, default_value = "h264")]
+    pub codec: VideoType,
+    /// Resolution of the stream like 1280x720
+    #[arg(long, value_parser = resolution_parse, default_value = "1280x720")]
+    pub resolution: (u32, u32),
+    /// Frames per second
+    #[arg(long, default_value_t = 25, value_parser = clap::value_parser!(u32).range(1..=120))]
+    pub fps: u32,
+    /// Stop after this many seconds, 0 runs until stopped with ctrl-c
+    #[arg(long, default_value_t = 0)]
+    pub duration: u64,
+}
@@ -0,0 +1,53 @@
+///
+/// # Neolink Simulate Stream
+///
+/// This module serves a test pattern over rtsp without connecting to a
+/// camera. It is for testing rtsp clients, the users and TLS of the config
+/// and anything else downstream without a real camera, for example in CI
+///
+/// The rtsp server is set up from the config the same as `neolink rtsp`
+/// but the cameras in it are not connected to
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink simulate-stream --config=config.toml
+/// # A 1080p h265 stream at /sim that stops after a minute
+/// neolink simulate-stream --config=config.toml --path=/sim --codec=h265 --resolution=1920x1080 --fps=15 --duration=60
+/// ```
+///
+use anyhow::Result;
+use tokio::time::Duration;
+
+mod cmdline;
+
+use crate::{
+    common::NeoReactor,
+    rtsp::{simulate_main, SimulatedStream},
+};
+pub(crate) use cmdline::Opt;
+
+/// Entry point for the simulate-stream subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let duration = (opt.duration > 0).then(|| Duration::from_secs(opt.duration));
+    let path = if opt.path.starts_with('/') {
+        opt.path
+    } else {
+        format!("/{}", opt.path)
+    };
+    simulate_main(
+        reactor,
+        SimulatedStream {
+            path,
+            codec: opt.codec,
+            width: opt.resolution.0,
+            height: opt.resolution.1,
+            fps: opt.fps,
+            duration,
+        },
+    )
+    .await
+}
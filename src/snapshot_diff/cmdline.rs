@@ -0,0 +1,31 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use std::path::PathBuf;
+
+fn threshold_parse(src: &str) -> Result<f64> {
+    let threshold: f64 = src.parse()?;
+    if (0.0..=100.0).contains(&threshold) {
+        Ok(threshold)
+    } else {
+        Err(anyhow!("The threshold should be from 0 to 100"))
+    }
+}
+
+/// The snapshot-diff command saves snapshots that changed from the one before
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// Seconds between snapshots
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    pub interval: u64,
+    /// The percentage difference from 0 to 100 above which the snapshots are saved
+    #[arg(long, default_value_t = 5.0, value_parser = threshold_parse)]
+    pub threshold: f64,
+    /// The directory to save the snapshots in
+    #[arg(long, default_value = ".")]
+    pub output_dir: PathBuf,
+    /// Also save a png of the difference between the snapshots
+    #[arg(long)]
+    pub save_diff: bool,
+}
@@ -0,0 +1,90 @@
+use anyhow::{anyhow, Context, Result};
+use gstreamer::{parse::launch_full, prelude::*, ParseFlags, Pipeline, State};
+use gstreamer_app::{AppSink, AppSrc};
+
+/// A decoded image with one brightness byte per pixel
+pub(super) struct Luma {
+    pub(super) width: usize,
+    pub(super) height: usize,
+    pub(super) pixels: Vec<u8>,
+}
+
+/// Decode a jpeg into its brightness
+///
+/// This blocks until the jpeg is decoded so should be run with `spawn_blocking`
+pub(super) fn decode_jpeg(jpeg: &[u8]) -> Result<Luma> {
+    let pipeline = create_pipeline()?;
+    let source = pipeline
+        .by_name("thesource")
+        .expect("There shoud be a `thesource`")
+        .dynamic_cast::<AppSrc>()
+        .map_err(|_| anyhow!("Cannot find appsource in gstreamer, check your gstreamer plugins"))?;
+    let sink = pipeline
+        .by_name("thesink")
+        .expect("There shoud be a `thesink`")
+        .dynamic_cast::<AppSink>()
+        .map_err(|_| anyhow!("Cannot find appsink in gstreamer, check your gstreamer plugins"))?;
+
+    pipeline.set_state(State::Playing)?;
+    let result = (|| {
+        source
+            .push_buffer(gstreamer::Buffer::from_slice(jpeg.to_vec()))
+            .map_err(|e| anyhow!("Streaming error: {e:?}"))?;
+        source
+            .end_of_stream()
+            .map_err(|e| anyhow!("Streaming error: {e:?}"))?;
+        let sample = sink
+            .pull_sample()
+            .map_err(|_| anyhow!("Could not decode the snapshot"))?;
+        let structure = sample
+            .caps()
+            .and_then(|caps| caps.structure(0))
+            .ok_or_else(|| anyhow!("The decoded snapshot has no size"))?;
+        let width = structure.get::<i32>("width")? as usize;
+        let height = structure.get::<i32>("height")? as usize;
+        let buffer = sample
+            .buffer()
+            .ok_or_else(|| anyhow!("The decoded snapshot is empty"))?;
+        let map = buffer.map_readable()?;
+        // Each row of raw video is padded to four bytes
+        let stride = (width + 3) & !3;
+        if map.len() < stride * height {
+            return Err(anyhow!("The decoded snapshot is too small"));
+        }
+        let pixels = map
+            .as_slice()
+            .chunks(stride)
+            .take(height)
+            .flat_map(|row| &row[..width])
+            .copied()
+            .collect();
+        Ok(Luma {
+            width,
+            height,
+            pixels,
+        })
+    })();
+    pipeline
+        .set_state(State::Null)
+        .context("Error in gstreamer when setting state to Null")?;
+    result
+}
+
+fn create_pipeline() -> Result<Pipeline> {
+    gstreamer::init()
+        .context("Unable to start gstreamer ensure it and all plugins are installed")?;
+
+    let launch_str = "appsrc name=thesource caps=image/jpeg \
+        ! jpegdec \
+        ! videoconvert \
+        ! video/x-raw,format=GRAY8 \
+        ! appsink name=thesink sync=false";
+    log::debug!("{}", launch_str);
+
+    launch_full(launch_str, None, ParseFlags::empty())
+        .context("Unable to load gstreamer pipeline ensure all gstramer plugins are installed")?
+        .dynamic_cast::<Pipeline>()
+        .map_err(|_| {
+            anyhow!("Unable to create gstreamer pipeline ensure all gstramer plugins are installed")
+        })
+}
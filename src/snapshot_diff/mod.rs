@@ -0,0 +1,118 @@
+///
+/// # Neolink Snapshot Diff
+///
+/// This module takes a snapshot from the camera every `--interval` seconds
+/// and compares it with the one before. When they differ by more than
+/// `--threshold` percent both snapshots are saved in `--output-dir`
+///
+/// The difference is the mean absolute difference of the brightness of
+/// each pixel, so it works as a simple motion detection in software for
+/// cameras whose own motion detection triggers too often or not at all
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink snapshot-diff --config=config.toml CameraName --interval=5 --threshold=10 --output-dir=changes
+/// # Also save an image of what changed
+/// neolink snapshot-diff --config=config.toml CameraName --output-dir=changes --save-diff
+/// ```
+///
+use anyhow::{Context, Result};
+use std::{fs::File, io::BufWriter, path::Path};
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+mod cmdline;
+mod gst;
+
+use crate::common::NeoReactor;
+pub(crate) use cmdline::Opt;
+use gst::Luma;
+
+/// Entry point for the snapshot-diff subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    tokio::fs::create_dir_all(&opt.output_dir)
+        .await
+        .with_context(|| format!("Unable to create {:?}", opt.output_dir))?;
+
+    let mut ticker = interval(Duration::from_secs(opt.interval));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut previous: Option<(Vec<u8>, Luma)> = None;
+    loop {
+        tokio::select! {
+            v = tokio::signal::ctrl_c() => return v.map_err(Into::into),
+            _ = ticker.tick() => {}
+        }
+
+        let jpeg = camera
+            .run_task(|cam| Box::pin(async move { Ok(cam.get_snapshot().await?) }))
+            .await
+            .context("Unable to get a snapshot")?;
+        let decode_jpeg = jpeg.clone();
+        let luma = tokio::task::spawn_blocking(move || gst::decode_jpeg(&decode_jpeg)).await??;
+
+        if let Some((previous_jpeg, previous_luma)) = previous.as_ref() {
+            match difference(previous_luma, &luma) {
+                Some(score) if score > opt.threshold => {
+                    let stem = format!(
+                        "{}_{}_{:.1}",
+                        opt.camera,
+                        chrono::Local::now().format("%Y%m%d_%H%M%S"),
+                        score
+                    );
+                    let dir = &opt.output_dir;
+                    tokio::fs::write(dir.join(format!("{}_before.jpeg", stem)), previous_jpeg)
+                        .await?;
+                    tokio::fs::write(dir.join(format!("{}_after.jpeg", stem)), &jpeg).await?;
+                    if opt.save_diff {
+                        write_diff(
+                            previous_luma,
+                            &luma,
+                            &dir.join(format!("{}_diff.png", stem)),
+                        )?;
+                    }
+                    println!("{}: Changed by {:.1}%, saved {}", opt.camera, score, stem);
+                }
+                Some(score) => log::debug!("{}: Changed by {:.1}%", opt.camera, score),
+                None => log::info!("{}: The snapshot size changed, not compared", opt.camera),
+            }
+        }
+        previous = Some((jpeg, luma));
+    }
+}
+
+/// The mean absolute difference of two images as a percentage
+///
+/// Returns `None` if they are not the same size
+fn difference(a: &Luma, b: &Luma) -> Option<f64> {
+    if a.width != b.width || a.height != b.height || a.pixels.is_empty() {
+        return None;
+    }
+    let total: u64 = a
+        .pixels
+        .iter()
+        .zip(b.pixels.iter())
+        .map(|(a, b)| a.abs_diff(*b) as u64)
+        .sum();
+    Some(total as f64 / a.pixels.len() as f64 / 255.0 * 100.0)
+}
+
+/// Save the difference of two images as a greyscale png where white is
+/// the most change
+fn write_diff(a: &Luma, b: &Luma, path: &Path) -> Result<()> {
+    let pixels = a
+        .pixels
+        .iter()
+        .zip(b.pixels.iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .collect::<Vec<_>>();
+    let file = File::create(path).with_context(|| format!("Unable to create {:?}", path))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), a.width as u32, a.height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+    Ok(())
+}
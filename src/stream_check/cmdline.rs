@@ -0,0 +1,15 @@
+use clap::Parser;
+
+/// The stream-check command checks that an rtsp stream is delivering video
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The rtsp url of the stream like rtsp://localhost:8554/CameraName
+    #[arg(long)]
+    pub url: String,
+    /// How many seconds to receive the stream for
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    pub duration: u64,
+    /// The lowest frames per second that passes
+    #[arg(long, default_value_t = 1.0)]
+    pub min_fps: f64,
+}
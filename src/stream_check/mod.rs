@@ -0,0 +1,225 @@
+///
+/// # Neolink Stream Check
+///
+/// This module checks that an rtsp stream is delivering video. It connects
+/// to the url as a client, receives the stream for a while and counts the
+/// video frames
+///
+/// The frames, their rate, the codec and resolution and any errors are
+/// printed. The command fails if there were errors or the frame rate is
+/// below `--min-fps` so it can be used in CI or as a readiness probe
+///
+/// It does not need a config and works with any rtsp server
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink stream-check --url=rtsp://localhost:8554/CameraName
+/// # Receive for 30 seconds and fail below 10 fps
+/// neolink stream-check --url=rtsp://localhost:8554/CameraName --duration=30 --min-fps=10
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use gstreamer::{
+    prelude::*, ClockTime, Element, ElementFactory, MessageView, Pad, PadProbeReturn, PadProbeType,
+    Pipeline, State,
+};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+mod cmdline;
+
+pub(crate) use cmdline::Opt;
+
+/// What was seen of the video
+#[derive(Default)]
+struct Report {
+    /// The encoding name of the rtp like H264
+    codec: Option<String>,
+    width: Option<i32>,
+    height: Option<i32>,
+    frames: u64,
+    first_frame: Option<Instant>,
+    last_frame: Option<Instant>,
+    errors: Vec<String>,
+}
+
+/// Entry point for the stream-check subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt) -> Result<()> {
+    gstreamer::init()
+        .context("Unable to start gstreamer ensure it and all plugins are installed")?;
+    let report = Arc::new(Mutex::new(Report::default()));
+    let pipeline = create_pipeline(&opt.url, report.clone())?;
+    let duration = Duration::from_secs(opt.duration);
+
+    let thread_report = report.clone();
+    tokio::task::spawn_blocking(move || run_pipeline(pipeline, duration, thread_report)).await??;
+
+    let report = report.lock().unwrap();
+    // Counted between the first and last frames so the time to connect is not included
+    let fps = match (report.first_frame, report.last_frame) {
+        (Some(first_frame), Some(last_frame)) if report.frames > 1 => {
+            let elapsed = last_frame.duration_since(first_frame).as_secs_f64();
+            (report.frames - 1) as f64 / elapsed.max(f64::EPSILON)
+        }
+        _ => 0.0,
+    };
+    let passed = report.errors.is_empty() && report.frames > 0 && fps >= opt.min_fps;
+
+    println!("URL:        {}", opt.url);
+    println!(
+        "Codec:      {}",
+        report.codec.as_deref().unwrap_or("unknown")
+    );
+    match (report.width, report.height) {
+        (Some(width), Some(height)) => println!("Resolution: {}x{}", width, height),
+        _ => println!("Resolution: unknown"),
+    }
+    println!("Frames:     {} in {}s", report.frames, opt.duration);
+    println!("FPS:        {:.1} (minimum {})", fps, opt.min_fps);
+    if report.errors.is_empty() {
+        println!("Errors:     none");
+    } else {
+        for error in report.errors.iter() {
+            println!("Error:      {}", error);
+        }
+    }
+    println!("Result:     {}", if passed { "OK" } else { "FAILED" });
+
+    if passed {
+        Ok(())
+    } else {
+        Err(anyhow!("The stream at {} failed the check", opt.url))
+    }
+}
+
+/// Builds `rtspsrc` with a `parsebin ! fakesink` for the video and a
+/// `fakesink` for anything else
+fn create_pipeline(url: &str, report: Arc<Mutex<Report>>) -> Result<Pipeline> {
+    let pipeline = Pipeline::new();
+    let source = make_element("rtspsrc")?;
+    source.set_property("location", url);
+    source.set_property("latency", 200u32);
+    pipeline.add(&source)?;
+
+    let weak_pipeline = pipeline.downgrade();
+    source.connect_pad_added(move |_, pad| {
+        let Some(pipeline) = weak_pipeline.upgrade() else {
+            return;
+        };
+        if let Err(e) = link_source_pad(&pipeline, pad, &report) {
+            report.lock().unwrap().errors.push(format!("{:#}", e));
+        }
+    });
+    Ok(pipeline)
+}
+
+fn link_source_pad(pipeline: &Pipeline, pad: &Pad, report: &Arc<Mutex<Report>>) -> Result<()> {
+    let caps = pad
+        .current_caps()
+        .ok_or_else(|| anyhow!("The rtsp stream has no caps"))?;
+    let structure = caps
+        .structure(0)
+        .ok_or_else(|| anyhow!("The rtsp stream has empty caps"))?;
+    let is_video = structure.get::<&str>("media").ok() == Some("video");
+    let sink = make_element("fakesink")?;
+    sink.set_property("sync", false);
+
+    if !is_video {
+        pipeline.add(&sink)?;
+        sink.sync_state_with_parent()?;
+        pad.link(&sink.static_pad("sink").expect("fakesink has a sink"))?;
+        return Ok(());
+    }
+
+    {
+        let mut report = report.lock().unwrap();
+        if report.codec.is_some() {
+            return Err(anyhow!("The rtsp stream has more than one video"));
+        }
+        report.codec = structure
+            .get::<&str>("encoding-name")
+            .ok()
+            .map(str::to_string);
+    }
+    let parser = make_element("parsebin")?;
+    pipeline.add_many([&parser, &sink])?;
+    let thread_report = report.clone();
+    let thread_sink = sink.clone();
+    parser.connect_pad_added(move |_, parsed_pad| {
+        let report = thread_report.clone();
+        parsed_pad.add_probe(PadProbeType::BUFFER, move |parsed_pad, _| {
+            let mut report = report.lock().unwrap();
+            let now = Instant::now();
+            report.last_frame = Some(now);
+            if report.first_frame.is_none() {
+                report.first_frame = Some(now);
+                if let Some(structure) = parsed_pad
+                    .current_caps()
+                    .as_ref()
+                    .and_then(|caps| caps.structure(0))
+                {
+                    report.width = structure.get::<i32>("width").ok();
+                    report.height = structure.get::<i32>("height").ok();
+                }
+            }
+            report.frames += 1;
+            PadProbeReturn::Ok
+        });
+        if let Some(sink_pad) = thread_sink.static_pad("sink") {
+            let _ = parsed_pad.link(&sink_pad);
+        }
+    });
+    parser.sync_state_with_parent()?;
+    sink.sync_state_with_parent()?;
+    pad.link(&parser.static_pad("sink").expect("parsebin has a sink"))?;
+    Ok(())
+}
+
+/// Receives the stream for `duration` or until an error
+fn run_pipeline(pipeline: Pipeline, duration: Duration, report: Arc<Mutex<Report>>) -> Result<()> {
+    let bus = pipeline
+        .bus()
+        .expect("Pipeline without bus. Shouldn't happen!");
+    pipeline.set_state(State::Playing)?;
+
+    let end = Instant::now() + duration;
+    while let Some(remaining) = end.checked_duration_since(Instant::now()) {
+        let Some(msg) = bus.timed_pop(ClockTime::from_nseconds(remaining.as_nanos() as u64)) else {
+            break;
+        };
+        match msg.view() {
+            MessageView::Eos(..) => {
+                report
+                    .lock()
+                    .unwrap()
+                    .errors
+                    .push("The stream ended".to_string());
+                break;
+            }
+            MessageView::Error(err) => {
+                report
+                    .lock()
+                    .unwrap()
+                    .errors
+                    .push(format!("{} ({:?})", err.error(), err.debug()));
+                break;
+            }
+            _ => (),
+        }
+    }
+
+    pipeline
+        .set_state(State::Null)
+        .context("Error in gstreamer when setting state to Null")?;
+    Ok(())
+}
+
+fn make_element(kind: &str) -> Result<Element> {
+    ElementFactory::make(kind)
+        .build()
+        .with_context(|| format!("Missing required gstreamer element `{}`", kind))
+}
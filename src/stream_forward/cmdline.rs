@@ -0,0 +1,19 @@
+use crate::common::stream_parse;
+use clap::Parser;
+use neolink_core::bc_protocol::StreamKind;
+
+/// The stream-forward command publishes a camera to one or more rtsp servers
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The rtsp urls to publish to separated by commas
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub destinations: Vec<String>,
+    /// The stream to forward: main, sub or extern
+    #[arg(long, value_parser = stream_parse, default_value = "main")]
+    pub stream: StreamKind,
+    /// How many seconds to wait before connecting to a destination again after it fails
+    #[arg(long, default_value_t = 5)]
+    pub retry_secs: u64,
+}
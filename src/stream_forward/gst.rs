@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Context, Result};
+use gstreamer::{
+    parse::launch_full, prelude::*, ClockTime, MessageView, ParseFlags, Pipeline, State,
+};
+use gstreamer_app::AppSrc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::common::{StampedData, VidFormat};
+
+/// The pipeline that publishes to one destination
+#[derive(Clone)]
+pub(super) struct Forward {
+    pipeline: Pipeline,
+    vid_src: AppSrc,
+    aud_src: Option<AppSrc>,
+}
+
+impl Forward {
+    pub(super) fn new(format: VidFormat, with_audio: bool, url: &str) -> Result<Self> {
+        gstreamer::init()
+            .context("Unable to start gstreamer ensure it and all plugins are installed")?;
+        let pipeline = create_pipeline(format, with_audio)?;
+        pipeline
+            .by_name("thesink")
+            .ok_or_else(|| anyhow!("There shoud be a `thesink`"))?
+            .set_property("location", url);
+        let vid_src = get_source(&pipeline, "vidsrc")?;
+        let aud_src = if with_audio {
+            Some(get_source(&pipeline, "audsrc")?)
+        } else {
+            None
+        };
+        Ok(Self {
+            pipeline,
+            vid_src,
+            aud_src,
+        })
+    }
+
+    pub(super) fn push_video(&self, frame: &StampedData, ts_0: Duration) -> Result<()> {
+        push(&self.vid_src, frame, ts_0)
+    }
+
+    pub(super) fn push_audio(&self, frame: &StampedData, ts_0: Duration) -> Result<()> {
+        match self.aud_src.as_ref() {
+            Some(aud_src) => push(aud_src, frame, ts_0),
+            None => Ok(()),
+        }
+    }
+
+    /// Plays the pipeline until it errors or is cancelled
+    ///
+    /// This blocks so should be run with `spawn_blocking`
+    pub(super) fn run(&self, url: &str, cancel: CancellationToken) -> Result<()> {
+        let sink = self.pipeline.by_name("thesink");
+        self.pipeline.set_state(State::Playing)?;
+        let bus = self
+            .pipeline
+            .bus()
+            .expect("Pipeline without bus. Shouldn't happen!");
+
+        let mut res = Ok(());
+        while !cancel.is_cancelled() {
+            let Some(msg) = bus.timed_pop(ClockTime::from_mseconds(500)) else {
+                continue;
+            };
+            match msg.view() {
+                MessageView::Eos(..) => break,
+                MessageView::Error(err) => {
+                    res = Err(anyhow!("{} ({:?})", err.error(), err.debug()));
+                    break;
+                }
+                MessageView::StateChanged(state)
+                    if msg.src() == sink.as_ref().map(|sink| sink.upcast_ref())
+                        && state.current() == State::Playing =>
+                {
+                    log::info!("Publishing to {}", url);
+                }
+                _ => (),
+            }
+        }
+
+        self.pipeline
+            .set_state(State::Null)
+            .context("Error in gstreamer when setting state to Null")?;
+        res
+    }
+}
+
+fn push(source: &AppSrc, frame: &StampedData, ts_0: Duration) -> Result<()> {
+    let time = ClockTime::from_useconds(frame.ts.saturating_sub(ts_0).as_micros() as u64);
+    let mut buf = gstreamer::Buffer::from_slice(frame.data.as_ref().clone());
+    {
+        let buf = buf.get_mut().unwrap();
+        buf.set_dts(time);
+        buf.set_pts(time);
+    }
+    source
+        .push_buffer(buf)
+        .map_err(|e| anyhow!("Streaming error: {e:?}"))?;
+    Ok(())
+}
+
+fn get_source(pipeline: &Pipeline, name: &str) -> Result<AppSrc> {
+    pipeline
+        .by_name(name)
+        .ok_or_else(|| anyhow!("There shoud be a `{}`", name))?
+        .dynamic_cast::<AppSrc>()
+        .map_err(|_| anyhow!("Cannot find appsource in gstreamer, check your gstreamer plugins"))
+}
+
+fn create_pipeline(format: VidFormat, with_audio: bool) -> Result<Pipeline> {
+    let (caps, parser) = match format {
+        // The parameter sets are repeated so the destination can start at any keyframe
+        VidFormat::H264 => (
+            "video/x-h264,stream-format=byte-stream",
+            "h264parse config-interval=-1",
+        ),
+        VidFormat::H265 => (
+            "video/x-h265,stream-format=byte-stream",
+            "h265parse config-interval=-1",
+        ),
+        VidFormat::Av1 => ("video/x-av1,stream-format=obu-stream", "av1parse"),
+        VidFormat::None => unreachable!(),
+    };
+    // A slow destination drops its oldest frames rather than holding up the rest
+    let mut launch_str = format!(
+        "rtspclientsink name=thesink \
+        appsrc name=vidsrc is-live=true format=time caps={caps} \
+        ! {parser} \
+        ! queue leaky=downstream max-size-buffers=0 max-size-bytes=0 max-size-time=2000000000 \
+        ! thesink."
+    );
+    if with_audio {
+        launch_str.push_str(
+            " appsrc name=audsrc is-live=true format=time caps=audio/mpeg,mpegversion=4,stream-format=adts \
+            ! aacparse \
+            ! queue leaky=downstream max-size-buffers=0 max-size-bytes=0 max-size-time=2000000000 \
+            ! thesink.",
+        );
+    }
+    log::debug!("{}", launch_str);
+
+    launch_full(&launch_str, None, ParseFlags::empty())
+        .context("Unable to load gstreamer pipeline ensure all gstramer plugins are installed")?
+        .dynamic_cast::<Pipeline>()
+        .map_err(|_| {
+            anyhow!("Unable to create gstreamer pipeline ensure all gstramer plugins are installed")
+        })
+}
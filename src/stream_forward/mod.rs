@@ -0,0 +1,164 @@
+///
+/// # Neolink Stream Forward
+///
+/// This module publishes the stream of a camera to one or more rtsp
+/// servers, such as mediamtx or a cloud ingest, with `rtspclientsink`
+///
+/// The camera is only streamed once. Each destination has a pipeline of
+/// its own fed from that stream so that a destination that fails, or is
+/// too slow, does not stop the others. A failed destination is connected
+/// again after `--retry-secs`
+///
+/// The video is forwarded as it comes from the camera without being
+/// encoded again. AAC audio is forwarded too, adpcm audio is not
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink stream-forward --config=config.toml CameraName --destinations=rtsp://host1:8554/cam,rtsp://host2:8554/cam
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use futures::future::try_join_all;
+use tokio::{
+    sync::broadcast::error::RecvError,
+    time::{sleep, timeout, Duration},
+};
+use tokio_util::sync::CancellationToken;
+
+mod cmdline;
+mod gst;
+
+use crate::{
+    common::{AudFormat, NeoReactor, StreamInstance, VidFormat},
+    utils::redact_url,
+};
+pub(crate) use cmdline::Opt;
+
+/// How long to wait for the camera to send any audio
+const AUDIO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Entry point for the stream-forward subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let stream_instance = camera
+        .stream(opt.stream)
+        .await
+        .context("Failed to start video")?;
+    let vid_format = stream_instance
+        .config
+        .clone()
+        .wait_for(|config| config.vid_ready())
+        .await?
+        .vid_format;
+    let with_audio = match timeout(
+        AUDIO_TIMEOUT,
+        stream_instance.config.clone().wait_for(|c| c.aud_ready()),
+    )
+    .await
+    {
+        Ok(Ok(config)) => match config.aud_format {
+            AudFormat::Aac => true,
+            AudFormat::Adpcm(_) => {
+                log::warn!("The camera's adpcm audio cannot be forwarded, only the video is sent");
+                false
+            }
+            AudFormat::None => false,
+        },
+        _ => false,
+    };
+
+    let forwards = opt.destinations.iter().map(|url| {
+        forward_main(
+            &opt.camera,
+            &stream_instance,
+            vid_format,
+            with_audio,
+            url,
+            Duration::from_secs(opt.retry_secs),
+        )
+    });
+    tokio::select! {
+        v = try_join_all(forwards) => v.map(|_| ()),
+        v = tokio::signal::ctrl_c() => v.map_err(Into::into),
+    }
+}
+
+/// Publishes to one destination connecting again whenever it fails
+async fn forward_main(
+    name: &str,
+    stream_instance: &StreamInstance,
+    vid_format: VidFormat,
+    with_audio: bool,
+    url: &str,
+    retry: Duration,
+) -> Result<()> {
+    // Credentials in the url are not logged
+    let display_url = redact_url(url);
+    loop {
+        log::info!("{}: Connecting to {}", name, display_url);
+        match forward_run(stream_instance, vid_format, with_audio, url, &display_url).await {
+            Ok(()) => log::warn!("{}: {} ended the stream", name, display_url),
+            Err(e) => log::warn!("{}: Forwarding to {} failed: {:?}", name, display_url, e),
+        }
+        log::info!(
+            "{}: Connecting to {} again in {}s",
+            name,
+            display_url,
+            retry.as_secs()
+        );
+        sleep(retry).await;
+    }
+}
+
+async fn forward_run(
+    stream_instance: &StreamInstance,
+    vid_format: VidFormat,
+    with_audio: bool,
+    url: &str,
+    display_url: &str,
+) -> Result<()> {
+    let forward = gst::Forward::new(vid_format, with_audio, url)?;
+    let cancel = CancellationToken::new();
+    let _drop_guard = cancel.clone().drop_guard();
+    let thread_cancel = cancel.clone();
+    let thread_forward = forward.clone();
+    let thread_url = display_url.to_string();
+
+    let mut vid = stream_instance.vid.resubscribe();
+    let mut aud = stream_instance.aud.resubscribe();
+    tokio::select! {
+        v = tokio::task::spawn_blocking(move || thread_forward.run(&thread_url, thread_cancel)) => v?,
+        v = async {
+            let mut ts_0 = None;
+            loop {
+                tokio::select! {
+                    frame = vid.recv() => match frame {
+                        Ok(frame) => {
+                            // The destination must start on an iframe
+                            if ts_0.is_none() && !frame.keyframe {
+                                continue;
+                            }
+                            let ts_0 = *ts_0.get_or_insert(frame.ts);
+                            forward.push_video(&frame, ts_0)?;
+                        }
+                        Err(RecvError::Lagged(n)) => log::debug!("Dropped {} video frames", n),
+                        Err(RecvError::Closed) => return Result::<()>::Err(anyhow!("The stream has closed")),
+                    },
+                    frame = aud.recv(), if with_audio => match frame {
+                        Ok(frame) => {
+                            if let Some(ts_0) = ts_0 {
+                                forward.push_audio(&frame, ts_0)?;
+                            }
+                        }
+                        Err(RecvError::Lagged(n)) => log::debug!("Dropped {} audio frames", n),
+                        Err(RecvError::Closed) => return Result::<()>::Err(anyhow!("The stream has closed")),
+                    },
+                }
+            }
+        } => v,
+    }
+}
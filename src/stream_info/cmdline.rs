@@ -0,0 +1,22 @@
+use crate::common::stream_parse;
+use clap::{Parser, ValueEnum};
+use neolink_core::bc_protocol::StreamKind;
+
+/// The stream-info command will print what the camera actually sends in its stream
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// The stream to inspect: main, sub or extern
+    #[arg(long, value_parser = stream_parse, default_value = "main")]
+    pub stream: StreamKind,
+    /// How to print the results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser, Debug, Clone, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
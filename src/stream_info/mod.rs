@@ -0,0 +1,498 @@
+///
+/// # Neolink Stream Info
+///
+/// This module prints the parameters of the stream that the camera
+/// actually sends without needing an rtsp client
+///
+/// It connects, reads about two seconds of the stream and then prints the
+/// resolution, frame rate, codecs and an estimate of the bitrate
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink stream-info --config=config.toml CameraName
+/// # For the sub stream as json
+/// neolink stream-info --config=config.toml CameraName --stream=sub --format=json
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use neolink_core::bcmedia::model::*;
+use serde::Serialize;
+use tokio::time::{timeout, Duration, Instant};
+
+mod cmdline;
+
+use crate::common::NeoReactor;
+pub(crate) use cmdline::{Opt, OutputFormat};
+
+/// How long the stream is read for when estimating the bitrate
+const SAMPLE_DURATION: Duration = Duration::from_secs(2);
+
+/// The parameters of a stream as seen on the wire
+#[derive(Debug, Default, Serialize)]
+struct StreamInfo {
+    width: Option<u32>,
+    height: Option<u32>,
+    fps: Option<u32>,
+    video_codec: Option<String>,
+    audio_codec: Option<String>,
+    audio_sample_rate: Option<u32>,
+    audio_channels: Option<u8>,
+    stream_bitrate_estimate_kbps: u32,
+}
+
+/// Entry point for the stream-info subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let camera = reactor.get(&opt.camera).await?;
+    let strict = camera.config().await?.borrow().strict;
+    let stream = opt.stream;
+
+    let info = camera
+        .run_task(|cam| {
+            Box::pin(async move {
+                // Older cameras send the fps as an index into this table
+                let framerate_table = cam
+                    .get_stream_info()
+                    .await?
+                    .stream_infos
+                    .iter()
+                    .flat_map(|info| info.encode_tables.clone())
+                    .find(|encode| encode.name == stream.to_string())
+                    .map(|encode| {
+                        encode
+                            .framerate_table
+                            .split(',')
+                            .filter_map(|c| c.parse::<u32>().ok())
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+
+                let mut stream_data = cam.start_video(stream, 0, strict).await?;
+                let mut info = StreamInfo::default();
+                let mut first_iframe = None;
+                let mut start = None;
+                let mut bytes = 0usize;
+                loop {
+                    let data = timeout(Duration::from_secs(10), stream_data.get_data())
+                        .await
+                        .context("Timed out waiting for the camera to send the stream")???;
+                    let start = *start.get_or_insert_with(Instant::now);
+                    match data {
+                        BcMedia::InfoV1(BcMediaInfoV1 {
+                            video_width,
+                            video_height,
+                            fps,
+                            ..
+                        })
+                        | BcMedia::InfoV2(BcMediaInfoV2 {
+                            video_width,
+                            video_height,
+                            fps,
+                            ..
+                        }) => {
+                            info.width = Some(video_width);
+                            info.height = Some(video_height);
+                            info.fps = Some(
+                                framerate_table
+                                    .get(fps as usize)
+                                    .copied()
+                                    .unwrap_or(fps as u32),
+                            );
+                        }
+                        BcMedia::Iframe(BcMediaIframe {
+                            video_type, data, ..
+                        }) => {
+                            info.video_codec = Some(codec_name(&video_type).to_string());
+                            bytes += data.len();
+                            if first_iframe.is_none() {
+                                first_iframe = Some((video_type, data));
+                            }
+                        }
+                        BcMedia::Pframe(BcMediaPframe {
+                            video_type, data, ..
+                        }) => {
+                            info.video_codec = Some(codec_name(&video_type).to_string());
+                            bytes += data.len();
+                        }
+                        BcMedia::Aac(BcMediaAac { data }) => {
+                            info.audio_codec = Some("aac".to_string());
+                            if let Some((sample_rate, channels)) = parse_adts(&data) {
+                                info.audio_sample_rate = Some(sample_rate);
+                                info.audio_channels = Some(channels);
+                            }
+                            bytes += data.len();
+                        }
+                        BcMedia::Adpcm(BcMediaAdpcm { data }) => {
+                            // Reolink only sends 8kHz mono ADPCM
+                            info.audio_codec = Some("adpcm".to_string());
+                            info.audio_sample_rate = Some(8000);
+                            info.audio_channels = Some(1);
+                            bytes += data.len();
+                        }
+                    }
+
+                    let elapsed = start.elapsed();
+                    if elapsed >= SAMPLE_DURATION && info.video_codec.is_some() {
+                        info.stream_bitrate_estimate_kbps =
+                            (bytes as f64 * 8.0 / 1000.0 / elapsed.as_secs_f64()) as u32;
+                        break;
+                    }
+                }
+
+                if info.width.is_none() {
+                    // No info packet, fall back to the SPS of the first iframe
+                    if let Some((VideoType::H264, data)) = first_iframe.as_ref() {
+                        if let Some((width, height)) = h264_sps_resolution(data) {
+                            info.width = Some(width);
+                            info.height = Some(height);
+                        }
+                    }
+                }
+                Ok(info)
+            })
+        })
+        .await?;
+
+    match opt.format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&info)?),
+        OutputFormat::Table => {
+            fn or_unknown<T: ToString>(value: &Option<T>) -> String {
+                value
+                    .as_ref()
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "unknown".to_string())
+            }
+            println!("width:                        {}", or_unknown(&info.width));
+            println!("height:                       {}", or_unknown(&info.height));
+            println!("fps:                          {}", or_unknown(&info.fps));
+            println!(
+                "video_codec:                  {}",
+                or_unknown(&info.video_codec)
+            );
+            println!(
+                "audio_codec:                  {}",
+                info.audio_codec.as_deref().unwrap_or("none")
+            );
+            println!(
+                "audio_sample_rate:            {}",
+                or_unknown(&info.audio_sample_rate)
+            );
+            println!(
+                "audio_channels:               {}",
+                or_unknown(&info.audio_channels)
+            );
+            println!(
+                "stream_bitrate_estimate_kbps: {}",
+                info.stream_bitrate_estimate_kbps
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn codec_name(video_type: &VideoType) -> &'static str {
+    match video_type {
+        VideoType::H264 => "h264",
+        VideoType::H265 => "h265",
+        VideoType::Av1 => "av1",
+    }
+}
+
+/// Read the sample rate and channel count from an ADTS header
+fn parse_adts(data: &[u8]) -> Option<(u32, u8)> {
+    const SAMPLE_RATES: [u32; 13] = [
+        96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+    ];
+    if data.len() < 7 || data[0] != 0xFF || data[1] & 0xF0 != 0xF0 {
+        return None;
+    }
+    let sample_rate = *SAMPLE_RATES.get(((data[2] >> 2) & 0x0F) as usize)?;
+    let channels = ((data[2] & 0x01) << 2) | (data[3] >> 6);
+    Some((sample_rate, channels))
+}
+
+/// Reads the exp-golomb coded fields of an SPS
+struct BitReader {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl BitReader {
+    fn bit(&mut self) -> Result<u32> {
+        let byte = self
+            .data
+            .get(self.pos / 8)
+            .ok_or_else(|| anyhow!("SPS is truncated"))?;
+        let bit = (byte >> (7 - self.pos % 8)) & 1;
+        self.pos += 1;
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, n: usize) -> Result<u32> {
+        (0..n).try_fold(0, |acc, _| Ok((acc << 1) | self.bit()?))
+    }
+
+    fn ue(&mut self) -> Result<u32> {
+        let mut zeros = 0;
+        while self.bit()? == 0 {
+            zeros += 1;
+            if zeros > 31 {
+                return Err(anyhow!("Invalid exp-golomb code in SPS"));
+            }
+        }
+        Ok((1u32 << zeros) - 1 + self.bits(zeros)?)
+    }
+
+    fn se(&mut self) -> Result<i32> {
+        let v = self.ue()?;
+        Ok(if v % 2 == 1 {
+            ((v + 1) / 2) as i32
+        } else {
+            -((v / 2) as i32)
+        })
+    }
+}
+
+/// Find the SPS in an H264 iframe and compute the picture size from it
+fn h264_sps_resolution(data: &[u8]) -> Option<(u32, u32)> {
+    // Find a start code followed by a NAL of type 7 (SPS)
+    let start = data
+        .windows(4)
+        .position(|w| w[0..3] == [0, 0, 1] && w[3] & 0x1F == 7)?
+        + 4;
+    // Remove the emulation prevention bytes
+    let mut rbsp = Vec::with_capacity(data.len() - start);
+    let mut zeros = 0;
+    for &byte in &data[start..] {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        if zeros >= 2 && byte == 1 {
+            // Next start code
+            break;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        rbsp.push(byte);
+    }
+    parse_h264_sps(BitReader { data: rbsp, pos: 0 }).ok()
+}
+
+fn parse_h264_sps(mut r: BitReader) -> Result<(u32, u32)> {
+    let profile_idc = r.bits(8)?;
+    r.bits(16)?; // Constraint flags and level
+    r.ue()?; // seq_parameter_set_id
+    let mut chroma_format_idc = 1;
+    if matches!(
+        profile_idc,
+        100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+    ) {
+        chroma_format_idc = r.ue()?;
+        if chroma_format_idc == 3 {
+            r.bit()?; // separate_colour_plane_flag
+        }
+        r.ue()?; // bit_depth_luma_minus8
+        r.ue()?; // bit_depth_chroma_minus8
+        r.bit()?; // qpprime_y_zero_transform_bypass_flag
+        if r.bit()? == 1 {
+            // Skip the scaling matrices
+            let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+            for i in 0..lists {
+                if r.bit()? == 1 {
+                    let size = if i < 6 { 16 } else { 64 };
+                    let (mut last, mut next) = (8i32, 8i32);
+                    for _ in 0..size {
+                        if next != 0 {
+                            // Only the low byte of the delta counts so that
+                            // garbage cannot overflow
+                            next = (last + r.se()? % 256 + 256) % 256;
+                        }
+                        if next != 0 {
+                            last = next;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    r.ue()?; // log2_max_frame_num_minus4
+    match r.ue()? {
+        0 => {
+            r.ue()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            r.bit()?; // delta_pic_order_always_zero_flag
+            r.se()?; // offset_for_non_ref_pic
+            r.se()?; // offset_for_top_to_bottom_field
+            for _ in 0..r.ue()? {
+                r.se()?; // offset_for_ref_frame
+            }
+        }
+        _ => {}
+    }
+    r.ue()?; // max_num_ref_frames
+    r.bit()?; // gaps_in_frame_num_value_allowed_flag
+    let width_in_mbs = r.ue()? + 1;
+    let height_in_map_units = r.ue()? + 1;
+    let frame_mbs_only = r.bit()?;
+    if frame_mbs_only == 0 {
+        r.bit()?; // mb_adaptive_frame_field_flag
+    }
+    r.bit()?; // direct_8x8_inference_flag
+
+    let too_big = || anyhow!("Invalid picture size in SPS");
+    let mut width = width_in_mbs.checked_mul(16).ok_or_else(too_big)?;
+    let mut height = height_in_map_units
+        .checked_mul((2 - frame_mbs_only) * 16)
+        .ok_or_else(too_big)?;
+    if r.bit()? == 1 {
+        let (left, right, top, bottom) = (r.ue()?, r.ue()?, r.ue()?, r.ue()?);
+        let (crop_x, crop_y) = match chroma_format_idc {
+            0 => (1, 2 - frame_mbs_only),
+            1 => (2, 2 * (2 - frame_mbs_only)),
+            2 => (2, 2 - frame_mbs_only),
+            _ => (1, 2 - frame_mbs_only),
+        };
+        width = width.saturating_sub(left.saturating_add(right).saturating_mul(crop_x));
+        height = height.saturating_sub(top.saturating_add(bottom).saturating_mul(crop_y));
+    }
+    Ok((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SPS that x264 writes for 1080p High profile, 1920x1088 cropped to 1080
+    const SPS_1080P_HIGH: [u8; 31] = [
+        0x00, 0x00, 0x00, 0x01, 0x67, 0x64, 0x00, 0x28, 0xac, 0xd9, 0x40, 0x78, 0x02, 0x27, 0xe5,
+        0xc0, 0x44, 0x00, 0x00, 0x03, 0x00, 0x04, 0x00, 0x00, 0x03, 0x00, 0xf0, 0x3c, 0x60, 0xc6,
+        0x58,
+    ];
+
+    /// A 720p Baseline profile SPS without cropping or VUI
+    const SPS_720P_BASELINE: [u8; 13] = [
+        0x00, 0x00, 0x00, 0x01, 0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0x40, 0x16, 0xe4,
+    ];
+
+    fn reader(data: &[u8]) -> BitReader {
+        BitReader {
+            data: data.to_vec(),
+            pos: 0,
+        }
+    }
+
+    #[test]
+    fn bit_reader_exp_golomb() {
+        // 1 010 011 00100 00101 then se 010 011 00100
+        let mut r = reader(&[0b1010_0110, 0b0100_0010, 0b1010_0110, 0b0100_0000]);
+        assert_eq!(r.ue().unwrap(), 0);
+        assert_eq!(r.ue().unwrap(), 1);
+        assert_eq!(r.ue().unwrap(), 2);
+        assert_eq!(r.ue().unwrap(), 3);
+        assert_eq!(r.ue().unwrap(), 4);
+        assert_eq!(r.se().unwrap(), 1);
+        assert_eq!(r.se().unwrap(), -1);
+        assert_eq!(r.se().unwrap(), 2);
+    }
+
+    #[test]
+    fn bit_reader_errors() {
+        let mut r = reader(&[0b1010_0000]);
+        assert_eq!(r.bits(3).unwrap(), 0b101);
+        assert!(r.bits(6).is_err());
+
+        // The code runs past the end of the data
+        assert!(reader(&[0b0000_0001]).ue().is_err());
+        // More leading zeros than fit in a u32
+        assert!(reader(&[0, 0, 0, 0, 0xff]).ue().is_err());
+    }
+
+    #[test]
+    fn sps_1080p_high_with_cropping() {
+        assert_eq!(h264_sps_resolution(&SPS_1080P_HIGH), Some((1920, 1080)));
+    }
+
+    #[test]
+    fn sps_720p_baseline() {
+        assert_eq!(h264_sps_resolution(&SPS_720P_BASELINE), Some((1280, 720)));
+    }
+
+    #[test]
+    fn sps_after_other_nals() {
+        // An AUD and an SEI come before the SPS in some iframes
+        let mut frame = vec![0x00, 0x00, 0x00, 0x01, 0x09, 0xf0];
+        frame.extend_from_slice(&[0x00, 0x00, 0x01, 0x06, 0x05, 0x01, 0xff, 0x80]);
+        frame.extend_from_slice(&SPS_1080P_HIGH);
+        frame.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x68, 0xeb, 0xe3, 0xcb, 0x22, 0xc0]);
+        assert_eq!(h264_sps_resolution(&frame), Some((1920, 1080)));
+    }
+
+    #[test]
+    fn sps_truncated() {
+        for len in 0..SPS_720P_BASELINE.len() {
+            assert_eq!(h264_sps_resolution(&SPS_720P_BASELINE[..len]), None);
+        }
+        // Cut off in the middle of the scaling and size fields
+        assert_eq!(h264_sps_resolution(&SPS_1080P_HIGH[..10]), None);
+    }
+
+    #[test]
+    fn sps_garbage() {
+        assert_eq!(h264_sps_resolution(&[]), None);
+        assert_eq!(
+            h264_sps_resolution(&[0xde, 0xad, 0xbe, 0xef, 0x00, 0x01]),
+            None
+        );
+        // A start code with an SPS type but nothing that parses
+        assert_eq!(
+            h264_sps_resolution(&[0x00, 0x00, 0x01, 0x67, 0x00, 0x00, 0x00]),
+            None
+        );
+
+        // Random bytes after an SPS header must not panic
+        let mut seed: u32 = 1;
+        for len in 0..2000 {
+            let mut data = vec![0x00, 0x00, 0x00, 0x01, 0x67];
+            for _ in 0..(len % 48) {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                data.push((seed >> 16) as u8);
+            }
+            let _ = h264_sps_resolution(&data);
+        }
+    }
+
+    #[test]
+    fn adts_headers() {
+        // AAC LC 16kHz mono as sent by the cameras
+        assert_eq!(
+            parse_adts(&[0xff, 0xf1, 0x60, 0x40, 0x1c, 0xff, 0xfc]),
+            Some((16000, 1))
+        );
+        // AAC LC 44.1kHz stereo
+        assert_eq!(
+            parse_adts(&[0xff, 0xf1, 0x50, 0x80, 0x2e, 0x7f, 0xfc]),
+            Some((44100, 2))
+        );
+    }
+
+    #[test]
+    fn adts_truncated_and_garbage() {
+        assert_eq!(parse_adts(&[]), None);
+        assert_eq!(parse_adts(&[0xff, 0xf1, 0x60, 0x40, 0x1c, 0xff]), None);
+        // No sync word
+        assert_eq!(
+            parse_adts(&[0x12, 0x34, 0x60, 0x40, 0x1c, 0xff, 0xfc]),
+            None
+        );
+        // Sample rate index 15 is reserved
+        assert_eq!(
+            parse_adts(&[0xff, 0xf1, 0x7c, 0x40, 0x1c, 0xff, 0xfc]),
+            None
+        );
+    }
+}
@@ -0,0 +1,22 @@
+use clap::Parser;
+
+/// The stress-test command connects many rtsp clients to a stream at once
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The rtsp url of the stream like rtsp://localhost:8554/CameraName
+    #[arg(long)]
+    pub url: String,
+    /// How many clients to connect
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub clients: u32,
+    /// How many seconds to keep the clients connected once they have all started
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    pub duration: u64,
+    /// Seconds to wait between starting each client
+    #[arg(long, default_value_t = 0.0)]
+    pub ramp_up: f64,
+    /// The process id of the neolink serving the stream to report the memory and cpu of.
+    /// Defaults to this process
+    #[arg(long)]
+    pub pid: Option<u32>,
+}
@@ -0,0 +1,193 @@
+use anyhow::{anyhow, Context, Result};
+use gstreamer::{
+    prelude::*, ClockTime, Element, ElementFactory, MessageView, Pad, PadProbeReturn, PadProbeType,
+    Pipeline, State,
+};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// How long a failed client waits before it connects again
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// What one client has received
+#[derive(Default)]
+pub(super) struct ClientReport {
+    pub(super) frames: u64,
+    pub(super) first_frame: Option<Instant>,
+    pub(super) last_frame: Option<Instant>,
+    pub(super) errors: Vec<String>,
+}
+
+/// How many clients are receiving video
+#[derive(Default)]
+pub(super) struct Concurrency {
+    current: AtomicUsize,
+    pub(super) peak: AtomicUsize,
+}
+
+impl Concurrency {
+    fn start(&self) {
+        let current = self.current.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak.fetch_max(current, Ordering::Relaxed);
+    }
+
+    fn stop(&self) {
+        self.current.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Receive the stream until cancelled, connecting again after any error
+///
+/// This blocks so should be run with `spawn_blocking`
+pub(super) fn run_client(
+    url: &str,
+    report: Arc<Mutex<ClientReport>>,
+    concurrency: Arc<Concurrency>,
+    cancel: CancellationToken,
+) {
+    while !cancel.is_cancelled() {
+        // Set once this connection has had video
+        let receiving = Arc::new(AtomicBool::new(false));
+        if let Err(e) = run_connection(url, &report, &concurrency, &receiving, &cancel) {
+            report.lock().unwrap().errors.push(format!("{:#}", e));
+        }
+        if receiving.load(Ordering::Relaxed) {
+            concurrency.stop();
+        }
+        let retry = Instant::now() + RECONNECT_DELAY;
+        while !cancel.is_cancelled() && Instant::now() < retry {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+fn run_connection(
+    url: &str,
+    report: &Arc<Mutex<ClientReport>>,
+    concurrency: &Arc<Concurrency>,
+    receiving: &Arc<AtomicBool>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let pipeline = create_pipeline(url, report, concurrency, receiving)?;
+    let bus = pipeline
+        .bus()
+        .expect("Pipeline without bus. Shouldn't happen!");
+    pipeline.set_state(State::Playing)?;
+
+    let mut result = Ok(());
+    while !cancel.is_cancelled() {
+        let Some(msg) = bus.timed_pop(ClockTime::from_mseconds(200)) else {
+            continue;
+        };
+        match msg.view() {
+            MessageView::Eos(..) => {
+                result = Err(anyhow!("The stream ended"));
+                break;
+            }
+            MessageView::Error(err) => {
+                result = Err(anyhow!("{} ({:?})", err.error(), err.debug()));
+                break;
+            }
+            _ => (),
+        }
+    }
+
+    pipeline
+        .set_state(State::Null)
+        .context("Error in gstreamer when setting state to Null")?;
+    result
+}
+
+/// Builds `rtspsrc` with a `parsebin ! fakesink` for the video and a
+/// `fakesink` for anything else
+fn create_pipeline(
+    url: &str,
+    report: &Arc<Mutex<ClientReport>>,
+    concurrency: &Arc<Concurrency>,
+    receiving: &Arc<AtomicBool>,
+) -> Result<Pipeline> {
+    let pipeline = Pipeline::new();
+    let source = make_element("rtspsrc")?;
+    source.set_property("location", url);
+    source.set_property("latency", 200u32);
+    pipeline.add(&source)?;
+
+    let weak_pipeline = pipeline.downgrade();
+    let report = report.clone();
+    let concurrency = concurrency.clone();
+    let receiving = receiving.clone();
+    source.connect_pad_added(move |_, pad| {
+        let Some(pipeline) = weak_pipeline.upgrade() else {
+            return;
+        };
+        if let Err(e) = link_source_pad(&pipeline, pad, &report, &concurrency, &receiving) {
+            report.lock().unwrap().errors.push(format!("{:#}", e));
+        }
+    });
+    Ok(pipeline)
+}
+
+fn link_source_pad(
+    pipeline: &Pipeline,
+    pad: &Pad,
+    report: &Arc<Mutex<ClientReport>>,
+    concurrency: &Arc<Concurrency>,
+    receiving: &Arc<AtomicBool>,
+) -> Result<()> {
+    let caps = pad
+        .current_caps()
+        .ok_or_else(|| anyhow!("The rtsp stream has no caps"))?;
+    let is_video = caps
+        .structure(0)
+        .and_then(|structure| structure.get::<&str>("media").ok())
+        == Some("video");
+    let sink = make_element("fakesink")?;
+    sink.set_property("sync", false);
+
+    if !is_video {
+        pipeline.add(&sink)?;
+        sink.sync_state_with_parent()?;
+        pad.link(&sink.static_pad("sink").expect("fakesink has a sink"))?;
+        return Ok(());
+    }
+
+    let parser = make_element("parsebin")?;
+    pipeline.add_many([&parser, &sink])?;
+    let report = report.clone();
+    let concurrency = concurrency.clone();
+    let receiving = receiving.clone();
+    let thread_sink = sink.clone();
+    parser.connect_pad_added(move |_, parsed_pad| {
+        let report = report.clone();
+        let concurrency = concurrency.clone();
+        let receiving = receiving.clone();
+        parsed_pad.add_probe(PadProbeType::BUFFER, move |_, _| {
+            if !receiving.swap(true, Ordering::Relaxed) {
+                concurrency.start();
+            }
+            let mut report = report.lock().unwrap();
+            let now = Instant::now();
+            report.last_frame = Some(now);
+            report.first_frame.get_or_insert(now);
+            report.frames += 1;
+            PadProbeReturn::Ok
+        });
+        if let Some(sink_pad) = thread_sink.static_pad("sink") {
+            let _ = parsed_pad.link(&sink_pad);
+        }
+    });
+    parser.sync_state_with_parent()?;
+    sink.sync_state_with_parent()?;
+    pad.link(&parser.static_pad("sink").expect("parsebin has a sink"))?;
+    Ok(())
+}
+
+fn make_element(kind: &str) -> Result<Element> {
+    ElementFactory::make(kind)
+        .build()
+        .with_context(|| format!("Missing required gstreamer element `{}`", kind))
+}
@@ -0,0 +1,222 @@
+///
+/// # Neolink Stress Test
+///
+/// This module load tests an rtsp server by connecting `--clients` rtsp
+/// clients to one stream. The clients are started `--ramp-up` seconds apart
+/// and once they have all started they are kept connected for `--duration`
+/// seconds. A client that fails connects again after a second
+///
+/// The peak number of clients receiving video at once, the frames, frame
+/// rate and errors of each client and the memory and cpu use of the server
+/// are printed. The server is `--pid` or, if that is not given, this process
+///
+/// It does not need a config and works with any rtsp server
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink stress-test --url=rtsp://localhost:8554/CameraName --clients=20 --duration=60 --ramp-up=0.5 --pid=$(pidof neolink)
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use std::sync::{atomic::Ordering, Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+mod cmdline;
+mod gst;
+
+pub(crate) use cmdline::Opt;
+use gst::{ClientReport, Concurrency};
+
+/// Entry point for the stress-test subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt) -> Result<()> {
+    gstreamer::init()
+        .context("Unable to start gstreamer ensure it and all plugins are installed")?;
+    let ramp_up = Duration::try_from_secs_f64(opt.ramp_up)
+        .context("--ramp-up must be a positive number of seconds")?;
+    let pid = opt
+        .pid
+        .map(|pid| pid.to_string())
+        .unwrap_or_else(|| "self".to_string());
+
+    let before = ProcStats::read(&pid);
+    let start = Instant::now();
+    let cancel = CancellationToken::new();
+    let concurrency = Arc::new(Concurrency::default());
+    let mut clients = vec![];
+    // Ctrl-C stops the test early but the clients so far are still reported
+    let result: std::io::Result<()> = tokio::select! {
+        v = tokio::signal::ctrl_c() => v,
+        _ = async {
+            for i in 0..opt.clients {
+                if i > 0 {
+                    sleep(ramp_up).await;
+                }
+                log::info!("Starting client {}", i + 1);
+                let report = Arc::new(Mutex::new(ClientReport::default()));
+                let url = opt.url.clone();
+                let thread_report = report.clone();
+                let thread_concurrency = concurrency.clone();
+                let thread_cancel = cancel.clone();
+                let handle = tokio::task::spawn_blocking(move || {
+                    gst::run_client(&url, thread_report, thread_concurrency, thread_cancel)
+                });
+                clients.push((report, handle));
+            }
+            sleep(Duration::from_secs(opt.duration)).await;
+        } => Ok(()),
+    };
+    cancel.cancel();
+    for (_, handle) in clients.iter_mut() {
+        handle.await?;
+    }
+    let elapsed = start.elapsed();
+    let after = ProcStats::read(&pid);
+    result?;
+
+    println!("URL:              {}", opt.url);
+    println!(
+        "Peak clients:     {} of {}",
+        concurrency.peak.load(Ordering::Relaxed),
+        clients.len()
+    );
+    let mut total_fps = 0.0;
+    let mut failed = 0;
+    for (i, (report, _)) in clients.iter().enumerate() {
+        let report = report.lock().unwrap();
+        let fps = fps(&report);
+        total_fps += fps;
+        if report.frames == 0 || !report.errors.is_empty() {
+            failed += 1;
+        }
+        println!(
+            "Client {:<4}      {} frames, {:.1} fps, {} errors",
+            i + 1,
+            report.frames,
+            fps,
+            report.errors.len()
+        );
+        for error in report.errors.iter() {
+            log::debug!("Client {}: {}", i + 1, error);
+        }
+    }
+    if !clients.is_empty() {
+        println!("Average FPS:      {:.1}", total_fps / clients.len() as f64);
+    }
+    print_proc_stats(&pid, before, after, elapsed);
+
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} of {} clients had errors or no video",
+            failed,
+            clients.len()
+        ))
+    }
+}
+
+/// Counted between the first and last frames so the time to connect is not included
+fn fps(report: &ClientReport) -> f64 {
+    match (report.first_frame, report.last_frame) {
+        (Some(first_frame), Some(last_frame)) if report.frames > 1 => {
+            let elapsed = last_frame.duration_since(first_frame).as_secs_f64();
+            (report.frames - 1) as f64 / elapsed.max(f64::EPSILON)
+        }
+        _ => 0.0,
+    }
+}
+
+/// The memory and cpu of a process from `/proc`
+struct ProcStats {
+    /// Resident memory in kB
+    rss_kb: Option<u64>,
+    /// Peak resident memory in kB
+    peak_rss_kb: Option<u64>,
+    threads: Option<u64>,
+    /// User and system cpu time in clock ticks
+    cpu_ticks: Option<u64>,
+}
+
+impl ProcStats {
+    fn read(pid: &str) -> Self {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).unwrap_or_default();
+        let field = |name: &str| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .and_then(|value| value.split_whitespace().next()?.parse().ok())
+        };
+        // The fields after the name, which is in brackets, start at the state
+        // so utime and stime are the 12th and 13th
+        let cpu_ticks = std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .ok()
+            .and_then(|stat| {
+                let fields = stat
+                    .rsplit_once(')')?
+                    .1
+                    .split_whitespace()
+                    .collect::<Vec<_>>();
+                let utime: u64 = fields.get(11)?.parse().ok()?;
+                let stime: u64 = fields.get(12)?.parse().ok()?;
+                Some(utime + stime)
+            });
+        Self {
+            rss_kb: field("VmRSS"),
+            peak_rss_kb: field("VmHWM"),
+            threads: field("Threads"),
+            cpu_ticks,
+        }
+    }
+}
+
+fn print_proc_stats(pid: &str, before: ProcStats, after: ProcStats, elapsed: Duration) {
+    let show = |value: Option<u64>| {
+        value
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    };
+    println!("Process:          {}", pid);
+    println!(
+        "Memory:           {} kB before, {} kB after, {} kB peak",
+        show(before.rss_kb),
+        show(after.rss_kb),
+        show(after.peak_rss_kb)
+    );
+    println!(
+        "Threads:          {} before, {} after",
+        show(before.threads),
+        show(after.threads)
+    );
+    match (before.cpu_ticks, after.cpu_ticks) {
+        (Some(before), Some(after)) => {
+            let cpu_secs = after.saturating_sub(before) as f64 / clock_ticks() as f64;
+            println!(
+                "CPU:              {:.1}% of one core",
+                cpu_secs / elapsed.as_secs_f64().max(f64::EPSILON) * 100.0
+            );
+        }
+        _ => println!("CPU:              unknown"),
+    }
+}
+
+#[cfg(unix)]
+fn clock_ticks() -> u64 {
+    // SAFETY: sysconf only reads a system setting
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as u64
+    } else {
+        100
+    }
+}
+
+#[cfg(not(unix))]
+fn clock_ticks() -> u64 {
+    100
+}
@@ -5,7 +5,7 @@ use gstreamer::{
     element_error, parse::launch_full, prelude::*, Caps, ClockTime, FlowError, FlowSuccess,
     MessageView, ParseFlags, Pipeline, ResourceError, State,
 };
-use gstreamer_app::{AppSink, AppSinkCallbacks};
+use gstreamer_app::{AppSink, AppSinkCallbacks, AppSrc};
 use tokio::task::JoinSet;
 
 use byte_slice_cast::*;
@@ -21,6 +21,31 @@ pub(super) fn from_input(
     input(pipeline)
 }
 
+/// Like [`from_input`] but the audio is pushed into the returned appsrc
+///
+/// `caps` are the caps of the audio that will be pushed
+#[allow(clippy::type_complexity)]
+pub(crate) fn from_appsrc(
+    caps: &str,
+    volume: f32,
+    block_align: u16,
+    sample_rate: u16,
+) -> Result<(JoinSet<AnyResult<()>>, Receiver<Vec<u8>>, AppSrc)> {
+    let pipeline = create_pipeline(
+        &format!("appsrc name=thesource is-live=true do-timestamp=true format=time caps={caps}"),
+        volume,
+        block_align,
+        sample_rate,
+    )?;
+    let appsrc = pipeline
+        .by_name("thesource")
+        .expect("There shoud be a `thesource`")
+        .dynamic_cast::<AppSrc>()
+        .map_err(|_| anyhow!("Cannot find appsource in gstreamer, check your gstreamer plugins"))?;
+    let (set, rx) = input(pipeline)?;
+    Ok((set, rx, appsrc))
+}
+
 #[allow(clippy::type_complexity)]
 fn input(pipeline: Pipeline) -> Result<(JoinSet<AnyResult<()>>, Receiver<Vec<u8>>)> {
     let appsink = get_sink(&pipeline)?;
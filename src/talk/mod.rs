@@ -17,7 +17,9 @@ use neolink_core::bc::xml::TalkConfig;
 mod cmdline;
 mod gst;
 
-use crate::common::NeoReactor;
+pub(crate) use gst::from_appsrc;
+
+use crate::common::{NeoInstance, NeoReactor};
 pub(crate) use cmdline::Opt;
 
 /// Entry point for the talk subcommand
@@ -25,6 +27,45 @@ pub(crate) use cmdline::Opt;
 /// Opt is the command line options
 pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
     let camera = reactor.get(&opt.camera).await?;
+    let (talk_config, block_size, sample_rate) = talk_config(&camera).await?;
+
+    let (mut set, rx) = match (&opt.file_path, &opt.microphone) {
+        (Some(path), false) => gst::from_input(
+            &format!(
+                "filesrc location={}",
+                path.to_str().expect("File path not UTF8 complient")
+            ),
+            opt.volume,
+            block_size,
+            sample_rate,
+        )
+        .with_context(|| format!("Failed to setup gst with the file: {:?}", path))?,
+        (None, true) => gst::from_input(&opt.input_src, opt.volume, block_size, sample_rate)
+            .context("Failed to setup gst using the microphone")?,
+        _ => unreachable!(),
+    };
+
+    camera
+        .run_task(|cam| {
+            let rx = rx.clone();
+            let talk_config = talk_config.clone();
+            Box::pin(async move {
+                cam.talk_stream(rx, talk_config).await?;
+                Ok(())
+            })
+        })
+        .await
+        .context("Talk stream ended early")?;
+
+    drop(rx);
+    while set.join_next().await.is_some() {}
+
+    Ok(())
+}
+
+/// The talk config of the camera with the block size and sample rate of
+/// the adpcm that it takes
+pub(crate) async fn talk_config(camera: &NeoInstance) -> Result<(TalkConfig, u16, u16)> {
     let config = camera.config().await?.borrow().clone();
     let name = config.name.clone();
 
@@ -69,37 +110,5 @@ pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
             name
         ));
     }
-
-    let (mut set, rx) = match (&opt.file_path, &opt.microphone) {
-        (Some(path), false) => gst::from_input(
-            &format!(
-                "filesrc location={}",
-                path.to_str().expect("File path not UTF8 complient")
-            ),
-            opt.volume,
-            block_size,
-            sample_rate,
-        )
-        .with_context(|| format!("Failed to setup gst with the file: {:?}", path))?,
-        (None, true) => gst::from_input(&opt.input_src, opt.volume, block_size, sample_rate)
-            .context("Failed to setup gst using the microphone")?,
-        _ => unreachable!(),
-    };
-
-    camera
-        .run_task(|cam| {
-            let rx = rx.clone();
-            let talk_config = talk_config.clone();
-            Box::pin(async move {
-                cam.talk_stream(rx, talk_config).await?;
-                Ok(())
-            })
-        })
-        .await
-        .context("Talk stream ended early")?;
-
-    drop(rx);
-    while set.join_next().await.is_some() {}
-
-    Ok(())
+    Ok((talk_config, block_size, sample_rate))
 }
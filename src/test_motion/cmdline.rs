@@ -0,0 +1,11 @@
+use clap::Parser;
+
+/// The test-motion command simulates a motion event of a camera for `neolink mqtt`
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// The name of the camera. Must be a name in the config
+    pub camera: String,
+    /// How long the motion lasts in seconds before the motion stop is sent
+    #[arg(long, default_value_t = 10)]
+    pub duration: u64,
+}
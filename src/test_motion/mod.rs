@@ -0,0 +1,64 @@
+///
+/// # Neolink Test Motion
+///
+/// This module asks a running `neolink mqtt` to simulate a motion event
+/// of a camera
+///
+/// It publishes `neolink/{CAMERANAME}/control/test_motion` to the mqtt
+/// server of the config. The camera's motion then goes through the same
+/// path as motion from the camera so `status/motion` is set `on` and then
+/// `off` once the duration is over
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink test-motion --config=config.toml CameraName --duration=10
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use rumqttc::{AsyncClient, Event, Incoming, QoS};
+use tokio::time::{timeout, Duration};
+
+mod cmdline;
+
+use crate::{common::NeoReactor, mqtt::mqtt_options, AnyResult};
+pub(crate) use cmdline::Opt;
+
+/// Entry point for the test-motion subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt, reactor: NeoReactor) -> Result<()> {
+    let config = reactor.config().await?.borrow().clone();
+    if !config.cameras.iter().any(|cam| cam.name == opt.camera) {
+        return Err(anyhow!("Camera {} not found in the config", opt.camera));
+    }
+    let server = config
+        .mqtt
+        .as_ref()
+        .context("A [mqtt] server must be in the config to send a test motion")?;
+
+    let (client, mut connection) = AsyncClient::new(mqtt_options(server), 10);
+    client
+        .publish(
+            format!("neolink/{}/control/test_motion", opt.camera),
+            QoS::AtLeastOnce,
+            false,
+            opt.duration.to_string(),
+        )
+        .await?;
+
+    timeout(Duration::from_secs(10), async {
+        loop {
+            if let Event::Incoming(Incoming::PubAck(_)) = connection.poll().await? {
+                return AnyResult::Ok(());
+            }
+        }
+    })
+    .await
+    .context("Timed out sending the test motion to the mqtt server")??;
+    let _ = client.disconnect().await;
+
+    log::info!("{}: Sent a test motion of {}s", opt.camera, opt.duration);
+    Ok(())
+}
@@ -5,7 +5,8 @@ use log::*;
 use super::config::CameraConfig;
 use anyhow::{anyhow, Context, Error, Result};
 use neolink_core::bc_protocol::{
-    BcCamera, BcCameraOpt, ConnectionProtocol, Credentials, DiscoveryMethods, MaxEncryption,
+    BcCamera, BcCameraOpt, ConnectionProtocol, Credentials, DiscoveryMethods, HttpProxy,
+    MaxEncryption, PacketTap,
 };
 use std::{
     fmt::{Display, Error as FmtError, Formatter},
@@ -13,13 +14,6 @@ use std::{
     str::FromStr,
 };
 
-pub(crate) fn timeout<F>(future: F) -> tokio::time::Timeout<F>
-where
-    F: std::future::Future,
-{
-    tokio::time::timeout(tokio::time::Duration::from_secs(15), future)
-}
-
 pub(crate) enum AddressOrUid {
     Address(String),
     #[allow(dead_code)]
@@ -64,6 +58,7 @@ impl AddressOrUid {
     pub(crate) async fn connect_camera(
         &self,
         camera_config: &CameraConfig,
+        packet_tap: Option<PacketTap>,
     ) -> Result<BcCamera, Error> {
         let (port, addrs) = {
             if let Some(addr_str) = camera_config.camera_addr.as_ref() {
@@ -87,6 +82,18 @@ impl AddressOrUid {
             }
         }?;
 
+        let proxy = camera_config
+            .proxy
+            .as_ref()
+            .map(|proxy_config| -> Result<HttpProxy> {
+                let mut proxy = HttpProxy::from_url(&proxy_config.proxy_url)?;
+                proxy.username.clone_from(&proxy_config.username);
+                proxy.password.clone_from(&proxy_config.password);
+                proxy.target_host = camera_config.camera_addr.as_deref().and_then(hostname);
+                Ok(proxy)
+            })
+            .transpose()?;
+
         let options = BcCameraOpt {
             name: camera_config.name.clone(),
             channel_id: camera_config.channel_id,
@@ -101,6 +108,8 @@ impl AddressOrUid {
             },
             debug: camera_config.debug,
             max_discovery_retries: camera_config.max_discovery_retries,
+            proxy,
+            packet_tap,
         };
 
         trace!("Camera Info: {:?}", options);
@@ -109,7 +118,32 @@ impl AddressOrUid {
     }
 }
 
-pub(crate) async fn connect_and_login(camera_config: &CameraConfig) -> Result<BcCamera> {
+/// The hostname part of a camera address, `None` if it is an IP
+///
+/// This lets the proxy resolve the name itself rather than being sent the IP
+/// that it resolved to here
+fn hostname(addr: &str) -> Option<String> {
+    let host = match addr.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() => host,
+        _ => addr,
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() || IpAddr::from_str(host).is_ok() || IpAddr::from_str(addr).is_ok() {
+        None
+    } else {
+        Some(host.to_string())
+    }
+}
+
+pub(crate) async fn connect(camera_config: &CameraConfig) -> Result<BcCamera> {
+    connect_with_tap(camera_config, None).await
+}
+
+/// Connect to the camera passing a copy of every packet to `packet_tap`
+pub(crate) async fn connect_with_tap(
+    camera_config: &CameraConfig,
+    packet_tap: Option<PacketTap>,
+) -> Result<BcCamera> {
     let camera_addr = AddressOrUid::new(
         &camera_config.camera_addr,
         &camera_config.camera_uid,
@@ -121,16 +155,23 @@ pub(crate) async fn connect_and_login(camera_config: &CameraConfig) -> Result<Bc
         camera_config.name, camera_addr
     );
 
-    let camera = camera_addr
-        .connect_camera(camera_config)
-        .await
-        .with_context(|| {
-            format!(
-                "Failed to connect to camera {} at {} on channel {}",
-                camera_config.name, camera_addr, camera_config.channel_id
-            )
-        })?;
+    let connecting = camera_addr.connect_camera(camera_config, packet_tap);
+    let camera = if let AddressOrUid::Address(_) = camera_addr {
+        // UID discovery has its own retries and may take longer
+        connect_timeout(camera_config, connecting).await?
+    } else {
+        connecting.await
+    }
+    .with_context(|| {
+        format!(
+            "Failed to connect to camera {} at {} on channel {}",
+            camera_config.name, camera_addr, camera_config.channel_id
+        )
+    })?;
+    Ok(camera)
+}
 
+pub(crate) async fn login(camera_config: &CameraConfig, camera: &BcCamera) -> Result<()> {
     let max_encryption = match camera_config.max_encryption.to_lowercase().as_str() {
         "none" => MaxEncryption::None,
         "bcencrypt" => MaxEncryption::BcEncrypt,
@@ -138,11 +179,110 @@ pub(crate) async fn connect_and_login(camera_config: &CameraConfig) -> Result<Bc
         _ => MaxEncryption::Aes,
     };
     info!("{}: Logging in", camera_config.name);
-    timeout(camera.login_with_maxenc(max_encryption))
-        .await
-        .with_context(|| format!("Failed to login to {}", camera_config.name))??;
+    connect_timeout(camera_config, camera.login_with_maxenc(max_encryption))
+        .await?
+        .with_context(|| format!("Failed to login to {}", camera_config.name))?;
 
     info!("{}: Connected and logged in", camera_config.name);
 
-    Ok(camera)
+    Ok(())
+}
+
+/// Limit the future to the `connect_timeout_secs` of the camera
+///
+/// A camera that is unreachable is reported quickly so that the
+/// reconnect can be tried rather than waiting on the tcp timeout
+async fn connect_timeout<F: std::future::Future>(
+    camera_config: &CameraConfig,
+    future: F,
+) -> Result<F::Output> {
+    let secs = camera_config.connect_timeout_secs;
+    tokio::time::timeout(tokio::time::Duration::from_secs(secs as u64), future)
+        .await
+        .map_err(|_| {
+            warn!(
+                "Camera {}: connection timed out after {}s",
+                camera_config.name, secs
+            );
+            anyhow!(
+                "Camera {}: connection timed out after {}s",
+                camera_config.name,
+                secs
+            )
+        })
+}
+
+/// Returned by [`check_fd_limits`] when there may not be enough file descriptors
+pub(crate) struct FdLimitWarning {
+    /// The current soft limit of open files
+    pub(crate) limit: u64,
+    /// An estimate of how many files could be open at once
+    pub(crate) required: u64,
+}
+
+impl Display for FdLimitWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        // Suggest a round number above the estimate
+        let suggested = self.required.div_ceil(1024) * 1024;
+        write!(
+            f,
+            "The open file limit is {} but about {} may be needed for this many cameras and clients. \
+            Raise it with `ulimit -n {}` before starting neolink",
+            self.limit, self.required, suggested
+        )
+    }
+}
+
+/// Check that the open file limit is high enough for the cameras
+///
+/// Each camera needs its socket plus the gstreamer pipeline and about five
+/// more files for every rtsp client. Returns `None` if the limit is fine or
+/// cannot be read
+pub(crate) fn check_fd_limits(
+    camera_count: usize,
+    max_clients_per_camera: u32,
+) -> Option<FdLimitWarning> {
+    const FDS_PER_CLIENT: u64 = 5;
+    const FDS_PER_PIPELINE: u64 = 10;
+    const FDS_BASE: u64 = 64;
+
+    let limit = open_file_limit()?;
+    let required = FDS_BASE
+        + camera_count as u64
+            * (1 + FDS_PER_PIPELINE + max_clients_per_camera as u64 * FDS_PER_CLIENT);
+    (required > limit).then_some(FdLimitWarning { limit, required })
+}
+
+#[cfg(unix)]
+fn open_file_limit() -> Option<u64> {
+    let mut rlim = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: rlim is a valid rlimit for getrlimit to write into
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut rlim) } != 0 {
+        return None;
+    }
+    if rlim.rlim_cur == libc::RLIM_INFINITY {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)] // rlim_t is not u64 on every unix
+    Some(rlim.rlim_cur as u64)
+}
+
+#[cfg(not(unix))]
+fn open_file_limit() -> Option<u64> {
+    None
+}
+
+/// The url without any user and password so that it can be logged or shown
+pub(crate) fn redact_url(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    match rest[..authority_end].rfind('@') {
+        Some(at) => format!("{}://***@{}", scheme, &rest[at + 1..]),
+        None => url.to_string(),
+    }
 }
@@ -0,0 +1,15 @@
+use clap::Parser;
+
+/// The watchdog command runs another neolink command and restarts it when it fails
+#[derive(Parser, Debug)]
+pub struct Opt {
+    /// How many times to restart after failing without a break. Unlimited if not given
+    #[arg(long)]
+    pub max_restarts: Option<u32>,
+    /// How many seconds to wait before restarting
+    #[arg(long, default_value_t = 5)]
+    pub restart_delay: u64,
+    /// The neolink command and its arguments like `rtsp --config=neolink.toml`
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
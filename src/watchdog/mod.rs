@@ -0,0 +1,135 @@
+///
+/// # Neolink Watchdog
+///
+/// This module runs another neolink command as a child process and starts
+/// it again when it exits with an error or is killed. It is a simple
+/// alternative to systemd for docker, NAS devices and other systems that
+/// have nothing to restart neolink
+///
+/// The output of the child is printed with a `[child]` prefix. A child
+/// that exits successfully is not restarted
+///
+/// A child that stays up for a minute resets the count of failures. When
+/// it fails more than `--max-restarts` times in a row the watchdog gives up
+/// and fails itself
+///
+///
+/// # Usage
+///
+/// ```bash
+/// neolink watchdog -- rtsp --config=config.toml
+/// neolink watchdog --max-restarts=10 --restart-delay=30 -- rtsp --config=config.toml
+/// ```
+///
+use anyhow::{anyhow, Context, Result};
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use tokio::time::{sleep, Duration, Instant};
+
+mod cmdline;
+
+pub(crate) use cmdline::Opt;
+
+/// A child that runs for this long is counted as a success
+const HEALTHY_UPTIME: Duration = Duration::from_secs(60);
+/// How often the child is checked for an exit
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Entry point for the watchdog subcommand
+///
+/// Opt is the command line options
+pub(crate) async fn main(opt: Opt) -> Result<()> {
+    let exe = std::env::current_exe().context("Could not find the neolink executable")?;
+    let mut failures = 0u32;
+    loop {
+        let started = Instant::now();
+        let mut child = spawn(&exe, &opt.command)?;
+        log::info!(
+            "Watchdog: Started `neolink {}` (pid {})",
+            opt.command.join(" "),
+            child.id()
+        );
+
+        let status = tokio::select! {
+            v = wait(&mut child) => v?,
+            v = tokio::signal::ctrl_c() => {
+                log::info!("Watchdog: Stopping the child");
+                let _ = child.kill();
+                let _ = child.wait();
+                return v.map_err(Into::into);
+            }
+        };
+        let uptime = started.elapsed();
+        if status.success() {
+            log::info!("Watchdog: The child finished successfully, not restarting");
+            return Ok(());
+        }
+
+        if uptime >= HEALTHY_UPTIME {
+            failures = 0;
+        }
+        failures += 1;
+        log::warn!(
+            "Watchdog: The child stopped with {} after {}s ({} failure(s) in a row)",
+            status,
+            uptime.as_secs(),
+            failures
+        );
+        if let Some(max_restarts) = opt.max_restarts {
+            if failures > max_restarts {
+                return Err(anyhow!(
+                    "The child failed {} times in a row without running for {}s, giving up",
+                    failures,
+                    HEALTHY_UPTIME.as_secs()
+                ));
+            }
+        }
+
+        log::info!("Watchdog: Restarting in {}s", opt.restart_delay);
+        tokio::select! {
+            _ = sleep(Duration::from_secs(opt.restart_delay)) => {}
+            v = tokio::signal::ctrl_c() => return v.map_err(Into::into),
+        }
+    }
+}
+
+/// Start the child with its output forwarded to ours
+fn spawn(exe: &std::path::Path, args: &[String]) -> Result<Child> {
+    let mut child = Command::new(exe)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start {:?}", exe))?;
+    if let Some(stdout) = child.stdout.take() {
+        forward(stdout, |line| println!("[child] {}", line));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward(stderr, |line| eprintln!("[child] {}", line));
+    }
+    Ok(child)
+}
+
+/// Print every line of the output until the child closes it
+fn forward<R: Read + Send + 'static>(output: R, print: fn(&str)) {
+    thread::spawn(move || {
+        for line in BufReader::new(output).lines() {
+            match line {
+                Ok(line) => print(&line),
+                Err(_) => break,
+            }
+        }
+    });
+}
+
+/// Wait for the child without blocking so that ctrl-c can still stop it
+async fn wait(child: &mut Child) -> Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        sleep(POLL_INTERVAL).await;
+    }
+}